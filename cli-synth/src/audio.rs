@@ -7,10 +7,51 @@ use cpal::{
 use scale::Scale;
 use std::sync::mpsc::Receiver;
 use synth_engine::event::ControllerEvent;
+use synth_engine::modules::poly_keys::MpeConfig;
 use synth_engine::simulator::rungekutta::RungeKutta;
+use synth_engine::Float;
 
 pub struct AudioStream(Box<dyn StreamTrait>);
 
+/// Maps MIDI events to the events of the synth: notes get their pitch from the
+/// scale, and the pitch wheel is scaled to octaves. Pitch bends on the member
+/// channels of an MPE zone are left as they are, the keys module scales them
+/// by the bend range of the zone.
+pub struct EventMapping {
+    pub scale: Scale,
+    pub pitch_wheel_range: Float,
+    pub mpe_zones: Vec<MpeConfig>,
+}
+
+impl EventMapping {
+    pub fn map(&self, event: ControllerEvent) -> ControllerEvent {
+        use ControllerEvent::*;
+
+        match event {
+            NoteOn {
+                channel,
+                pitch,
+                velocity,
+                ..
+            } => NoteOn {
+                channel,
+                pitch,
+                velocity,
+                pitch_value: self.scale.pitch_value(pitch as usize).unwrap_or(0.) as Float,
+            },
+            PitchWheel { channel, amount }
+                if !self.mpe_zones.iter().any(|mpe| mpe.is_member(channel)) =>
+            {
+                PitchWheel {
+                    channel,
+                    amount: amount * self.pitch_wheel_range,
+                }
+            }
+            e => e,
+        }
+    }
+}
+
 impl AudioStream {
     pub fn play(&self) -> Result<(), PlayStreamError> {
         self.0.play()
@@ -55,8 +96,7 @@ pub fn sound_simulation(
     buffer_size: u32,
     mut simulation: Box<RungeKutta>,
     receiver: Receiver<ControllerEvent>,
-    scale: Scale,
-    pitch_wheel_range: f32,
+    mapping: EventMapping,
     debug_events: bool,
 ) -> Result<AudioStream, BuildStreamError> {
     let host = cpal::default_host();
@@ -70,7 +110,7 @@ pub fn sound_simulation(
     println!("sample rate: {}", stream_config.sample_rate().0);
     println!("buffer size: {:?}", stream_config.buffer_size());
 
-    let dt = 1.0 / (stream_config.sample_rate().0 as f32);
+    let dt = 1.0 / (stream_config.sample_rate().0 as Float);
 
     let num_channels = stream_config.channels() as usize;
 
@@ -83,7 +123,12 @@ pub fn sound_simulation(
             for frame in data.chunks_mut(num_channels) {
                 simulation.step(dt);
 
-                let (left, right) = simulation.get_stereo_output();
+                // Float is f64 with the f64 feature of the engine
+                #[allow(clippy::unnecessary_cast)]
+                let (left, right) = {
+                    let (left, right) = simulation.get_stereo_output();
+                    (left as f32, right as f32)
+                };
 
                 let frame_size = frame.len();
 
@@ -96,21 +141,7 @@ pub fn sound_simulation(
 
             loop {
                 if let Some(event) = receiver.try_recv().ok() {
-                    use ControllerEvent::*;
-
-                    let event = match event {
-                        NoteOn {
-                            pitch, velocity, ..
-                        } => NoteOn {
-                            pitch,
-                            velocity,
-                            pitch_value: scale.pitch_value(pitch as usize).unwrap_or(0.),
-                        },
-                        PitchWheel { amount } => PitchWheel {
-                            amount: amount * pitch_wheel_range,
-                        },
-                        e => e,
-                    };
+                    let event = mapping.map(event);
 
                     if debug_events {
                         println!("controller event: {:?}", event);
@@ -130,3 +161,36 @@ pub fn sound_simulation(
 
     stream.map(|s| AudioStream(Box::new(s)))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use synth_engine::modules::poly_keys::MpeZone;
+
+    #[test]
+    fn pitch_wheel_range() {
+        let mapping = EventMapping {
+            scale: Scale::equal_temperament(),
+            pitch_wheel_range: 0.25,
+            mpe_zones: vec![MpeConfig {
+                zone: MpeZone::Lower,
+                member_channels: 7,
+                bend_range: 4.,
+            }],
+        };
+        let bend = |channel| match mapping.map(ControllerEvent::PitchWheel {
+            channel,
+            amount: 0.5,
+        }) {
+            ControllerEvent::PitchWheel { amount, .. } => amount,
+            _ => panic!("not a pitch wheel event"),
+        };
+
+        // The master channel and channels outside the zone
+        assert_eq!(bend(0), 0.125);
+        assert_eq!(bend(8), 0.125);
+        // Member channels, scaled by the bend range in the keys module only
+        assert_eq!(bend(1), 0.5);
+        assert_eq!(bend(7), 0.5);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::audio::sound_simulation;
+use crate::audio::{sound_simulation, EventMapping};
 use crate::midi::{Midi, MidiError};
 use clap::Parser;
 use cpal::{BuildStreamError, PlayStreamError};
@@ -7,8 +7,10 @@ use scale::Scale;
 use std::io;
 use std::io::prelude::*;
 use std::sync::mpsc::channel;
+use synth_designer::lint::lint_file;
 use synth_designer::synth_spec::SynthSpec;
 use synth_engine::simulator::rungekutta::RungeKutta;
+use synth_engine::Float;
 use thiserror::Error;
 
 mod audio;
@@ -19,8 +21,9 @@ const DEFAULT_SAMPLE_RATE: u32 = 44100;
 const DEFAULT_BUFFER_SIZE: u32 = 2048;
 const DEFAULT_SIMULATOR: &str = "rk4";
 const DEFAULT_BASE_PITCH: usize = 0;
-const DEFAULT_PITCH_WHEEL_RANGE: f32 = 1.;
+const DEFAULT_PITCH_WHEEL_RANGE: Float = 1.;
 const DEFAULT_DEBUG_EVENTS: bool = false;
+const DEFAULT_CHECK: bool = false;
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -38,13 +41,15 @@ struct CliArgs {
     #[arg(short, long, default_value_t = DEFAULT_BUFFER_SIZE)]
     buffer_size: u32,
     #[arg(short, long, default_value_t = DEFAULT_PITCH_WHEEL_RANGE)]
-    pitch_wheel_range: f32,
+    pitch_wheel_range: Float,
     #[arg(long)]
     scale: Option<String>,
     #[arg(long, default_value_t = DEFAULT_BASE_PITCH)]
     base_pitch: usize,
     #[arg(long, default_value_t = DEFAULT_DEBUG_EVENTS)]
     debug_events: bool,
+    #[arg(long, default_value_t = DEFAULT_CHECK)]
+    check: bool,
 }
 
 #[derive(Error, Debug)]
@@ -88,9 +93,35 @@ fn pause() {
     let _ = stdin.read(&mut [0u8]).unwrap();
 }
 
+fn check_model(model: &str) -> ! {
+    println!("Checking model definition in {}", model);
+
+    match lint_file(model) {
+        Ok(lints) if lints.is_empty() => {
+            println!("No problems found");
+            std::process::exit(0);
+        }
+        Ok(lints) => {
+            for lint in &lints {
+                println!("warning: {}", lint);
+            }
+            println!("{} problem(s) found", lints.len());
+            std::process::exit(1);
+        }
+        Err(err) => {
+            println!("error: {:?}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
 fn main() -> Result<(), RuntimeError> {
     let args = CliArgs::parse();
 
+    if args.check {
+        check_model(args.model.as_str());
+    }
+
     println!("Reading model definition from {}", args.model.as_str());
     let mut spec = match SynthSpec::from_ini_file(args.model.as_str()) {
         Ok(s) => s,
@@ -107,6 +138,11 @@ fn main() -> Result<(), RuntimeError> {
         Ok(()) => {}
         Err(err) => panic!("Error creating synth modules: {:?}", err),
     }
+
+    let loops = match spec.make_loops() {
+        Ok(loops) => loops,
+        Err(err) => panic!("Error resolving delay-free loops: {}", err),
+    };
     println!("done");
 
     println!("Getting scale...");
@@ -114,8 +150,11 @@ fn main() -> Result<(), RuntimeError> {
     println!("done");
 
     print!("Creating simulator...");
-    let simulator =
-        Box::new(make_simulator(args.simulator.as_str(), state_size).with_modules(model));
+    let simulator = Box::new(
+        make_simulator(args.simulator.as_str(), state_size)
+            .with_modules(model)
+            .with_loops(loops),
+    );
     println!("done");
 
     print!("Creating communication channel...");
@@ -128,8 +167,11 @@ fn main() -> Result<(), RuntimeError> {
         args.buffer_size,
         simulator,
         receive,
-        scale,
-        args.pitch_wheel_range,
+        EventMapping {
+            scale,
+            pitch_wheel_range: args.pitch_wheel_range,
+            mpe_zones: spec.mpe_zones(),
+        },
         args.debug_events,
     )?;
     println!("done");
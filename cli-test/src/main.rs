@@ -1,6 +1,7 @@
 use clap::Parser;
-use std::f32::consts::PI;
+use synth_engine::math::consts::PI;
 use synth_engine::stack_program::*;
+use synth_engine::Float;
 use synth_engine::{modules::*, simulator::module::Module, simulator::rungekutta::RungeKutta};
 
 #[derive(Parser, Debug, Clone)]
@@ -11,7 +12,7 @@ struct CliArgs {
     #[arg(short, long)]
     count: usize,
     #[arg(short, long)]
-    sample_rate: f32,
+    sample_rate: Float,
     #[arg(long)]
     simulator: String,
 }
@@ -68,13 +69,13 @@ fn test_modules(test: usize) -> Vec<Box<dyn Module>> {
             result.push(Box::new(MonoOutput::new(0, StackProgram::from_index(2))));
         }
         3 => {
-            let mut wavetable1: Vec<f32> = Vec::new();
-            let mut wavetable2: Vec<f32> = Vec::new();
+            let mut wavetable1: Vec<Float> = Vec::new();
+            let mut wavetable2: Vec<Float> = Vec::new();
             for i in 0..256 {
-                let x = (i as f32) / 256.;
-                let v = 0.5 * ((i as f32) * 2. * PI / 256.).sin() + x - 0.5;
+                let x = (i as Float) / 256.;
+                let v = 0.5 * ((i as Float) * 2. * PI / 256.).sin() + x - 0.5;
                 wavetable1.push(v);
-                let v2 = ((i as f32) * 2. * PI / 256.).sin();
+                let v2 = ((i as Float) * 2. * PI / 256.).sin();
                 wavetable2.push(v2);
             }
             result.push(Box::new(Wavetable::new(
@@ -0,0 +1,27 @@
+use std::path::Path;
+use synth_designer::synth_spec::{CodegenMode, SynthSpec};
+
+const SYNTH_SPEC_FILE: &str = "synth_spec.ini";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SYNTH_SPEC_FILE);
+
+    let mut synth_spec = match SynthSpec::from_ini_file(SYNTH_SPEC_FILE) {
+        Ok(s) => s,
+        Err(err) => panic!("Error reading synth spec: {:?}", err),
+    };
+
+    let out = std::env::var("OUT_DIR").unwrap();
+    let out = Path::new(&out);
+
+    synth_spec.allocate_state();
+    synth_spec.set_data_dir(out);
+    synth_spec.set_codegen_mode(CodegenMode::Static);
+
+    let code = match synth_spec.codegen() {
+        Ok(code) => code.to_string(),
+        Err(err) => panic!("Error generating synth: {}", err),
+    };
+
+    std::fs::write(out.join("synth.rs"), code.as_bytes()).unwrap();
+}
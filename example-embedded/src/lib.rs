@@ -0,0 +1,46 @@
+//! A synth for microcontrollers, generated at build time from
+//! `synth_spec.ini`. Neither the generated code nor the engine use the heap,
+//! so the crate builds for bare-metal targets like the Cortex-M7 of the Daisy
+//! Seed, `thumbv7em-none-eabihf`.
+//!
+//! The delay line buffers are kept apart from the synth, so they can be placed
+//! in static memory:
+//!
+//! ```ignore
+//! static mut BUFFERS: Buffers = Buffers::new();
+//!
+//! let mut synth = Synth::new(unsafe { &mut *core::ptr::addr_of_mut!(BUFFERS) });
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod synth {
+    include!(concat!(env!("OUT_DIR"), "/synth.rs"));
+}
+
+#[cfg(test)]
+mod test {
+    use super::synth::*;
+    use synth_engine::event::ControllerEvent;
+
+    #[test]
+    fn plays_a_note() {
+        let mut buffers = Buffers::new();
+        let mut synth = Synth::new(&mut buffers);
+        let mut peak: f32 = 0.;
+
+        synth.process_event(ControllerEvent::NoteOn {
+            channel: 0,
+            pitch: 60,
+            velocity: 1.,
+            pitch_value: 5.,
+        });
+
+        for _ in 0..4800 {
+            synth.step(1. / 48000.);
+            peak = peak.max(synth.get_stereo_output().0.abs());
+        }
+
+        assert!(peak > 0.);
+    }
+}
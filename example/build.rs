@@ -1,19 +1,41 @@
 use std::path::Path;
-use synth_designer::synth_spec::SynthSpec;
+use synth_designer::synth_spec::{CodegenMode, SynthSpec};
 
 const SYNTH_SPEC_FILE: &str = "synth_spec.ini";
+const FIXED_SYNTH_SPEC_FILE: &str = "../synths/fixed_point.ini";
 
 fn main() {
-    let mut synth_spec = match SynthSpec::from_ini_file(SYNTH_SPEC_FILE) {
+    let out = std::env::var("OUT_DIR").unwrap();
+    let out = Path::new(&out);
+
+    generate(
+        SYNTH_SPEC_FILE,
+        CodegenMode::Alloc,
+        &out.join("synth.rs"),
+        out,
+    );
+    generate(
+        FIXED_SYNTH_SPEC_FILE,
+        CodegenMode::Fixed,
+        &out.join("fixed_synth.rs"),
+        out,
+    );
+}
+
+fn generate(spec_file: &str, mode: CodegenMode, path: &Path, data_dir: &Path) {
+    let mut synth_spec = match SynthSpec::from_ini_file(spec_file) {
         Ok(s) => s,
         Err(err) => panic!("Error reading synth spec: {:?}", err),
     };
 
     synth_spec.allocate_state();
+    synth_spec.set_data_dir(data_dir);
+    synth_spec.set_codegen_mode(mode);
 
-    let code = synth_spec.codegen().to_string();
+    let code = match synth_spec.codegen() {
+        Ok(code) => code.to_string(),
+        Err(err) => panic!("Error generating synth: {}", err),
+    };
 
-    let out = std::env::var("OUT_DIR").unwrap();
-    let out = Path::new(&out).join("synth_modules.rs");
-    std::fs::write(out, code.as_bytes()).unwrap();
+    std::fs::write(path, code.as_bytes()).unwrap();
 }
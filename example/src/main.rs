@@ -1,18 +1,30 @@
-#![no_std]
-
 extern crate alloc;
 
-use alloc::vec;
+use synth_engine::event::ControllerEvent;
+use synth_engine::Float;
+
+mod synth {
+    include!(concat!(env!("OUT_DIR"), "/synth.rs"));
+}
 
-use synth_engine::modules::SynthModule;
-use synth_engine::modules::*;
-use synth_engine::stack_program::Function::*;
-use synth_engine::stack_program::Instr::*;
-use synth_engine::stack_program::StackProgram;
+const SAMPLE_RATE: Float = 44100.;
 
 fn main() {
-    let (modules, state_size) = include!(concat!(env!("OUT_DIR"), "/synth_modules.rs"));
+    debug_assert_eq!(synth::MODULE_COUNT, 20);
+    debug_assert_eq!(synth::STATE_SIZE, 33);
+
+    let mut synth = synth::Synth::new();
+
+    synth.process_event(ControllerEvent::NoteOn {
+        channel: 0,
+        pitch: 60,
+        velocity: 1.,
+        pitch_value: 5.,
+    });
+
+    for _ in 0..SAMPLE_RATE as usize {
+        synth.step(1. / SAMPLE_RATE);
+    }
 
-    debug_assert_eq!(modules.len(), 19);
-    debug_assert_eq!(state_size, 27);
+    let _ = synth.get_stereo_output();
 }
@@ -0,0 +1,172 @@
+//! Check the generated synths against the interpreted synths, built at
+//! runtime from the same INI files.
+
+extern crate alloc;
+
+use synth_designer::synth_spec::SynthSpec;
+use synth_engine::event::ControllerEvent;
+use synth_engine::fixed::TimeStep;
+use synth_engine::simulator::rungekutta::RungeKutta;
+use synth_engine::Float;
+
+mod synth {
+    include!(concat!(env!("OUT_DIR"), "/synth.rs"));
+}
+
+mod fixed_synth {
+    include!(concat!(env!("OUT_DIR"), "/fixed_synth.rs"));
+}
+
+const SAMPLE_RATE: Float = 44100.;
+const SAMPLES: usize = 4410;
+
+#[test]
+fn generated_matches_interpreted() {
+    let mut synth_spec = SynthSpec::from_ini_file("synth_spec.ini").unwrap();
+    let state_size = synth_spec.allocate_state();
+
+    let mut modules = Vec::new();
+    synth_spec.make_modules(&mut modules).unwrap();
+    let loops = synth_spec.make_loops().unwrap();
+    let mut interpreted = RungeKutta::rk4(state_size)
+        .with_modules(modules)
+        .with_loops(loops);
+    let mut generated = synth::Synth::new();
+    let mut peak: Float = 0.;
+
+    // Open the exciter and waveguide filters, then play a note
+    let events = [
+        (
+            0,
+            ControllerEvent::ContinuousControl {
+                channel: 0,
+                control: 22,
+                value: 0.5,
+            },
+        ),
+        (
+            0,
+            ControllerEvent::ContinuousControl {
+                channel: 0,
+                control: 25,
+                value: 0.8,
+            },
+        ),
+        (
+            0,
+            ControllerEvent::NoteOn {
+                channel: 0,
+                pitch: 60,
+                velocity: 0.8,
+                pitch_value: 5.,
+            },
+        ),
+        (
+            SAMPLES / 2,
+            ControllerEvent::NoteOff {
+                channel: 0,
+                pitch: 60,
+                velocity: 0.5,
+            },
+        ),
+    ];
+
+    for sample in 0..SAMPLES {
+        for (_, event) in events.iter().filter(|(at, _)| *at == sample) {
+            interpreted.process_event(*event);
+            generated.process_event(*event);
+        }
+
+        interpreted.step(1. / SAMPLE_RATE);
+        generated.step(1. / SAMPLE_RATE);
+
+        assert_eq!(
+            interpreted.get_stereo_output(),
+            generated.get_stereo_output(),
+            "sample {}",
+            sample
+        );
+
+        peak = peak.max(generated.get_stereo_output().0.abs());
+    }
+
+    assert!(peak > 0.);
+}
+
+#[test]
+fn generated_fixed_point_follows_interpreted() {
+    let mut synth_spec = SynthSpec::from_ini_file("../synths/fixed_point.ini").unwrap();
+    let state_size = synth_spec.allocate_state();
+
+    let mut modules = Vec::new();
+    synth_spec.make_modules(&mut modules).unwrap();
+    let mut interpreted = RungeKutta::rk4(state_size).with_modules(modules);
+    let mut generated = fixed_synth::Synth::new();
+    let dt = TimeStep::from_sample_rate(SAMPLE_RATE as u32);
+    let mut peak: Float = 0.;
+    let mut max_error: Float = 0.;
+
+    // Open the filter, then play a note and glide to another
+    let events = [
+        (
+            0,
+            ControllerEvent::ContinuousControl {
+                channel: 0,
+                control: 74,
+                value: 0.5,
+            },
+        ),
+        (
+            0,
+            ControllerEvent::NoteOn {
+                channel: 0,
+                pitch: 60,
+                velocity: 0.8,
+                pitch_value: 5.,
+            },
+        ),
+        (
+            SAMPLES / 4,
+            ControllerEvent::NoteOn {
+                channel: 0,
+                pitch: 67,
+                velocity: 0.8,
+                pitch_value: 5. + 7. / 12.,
+            },
+        ),
+        (
+            SAMPLES / 2,
+            ControllerEvent::NoteOff {
+                channel: 0,
+                pitch: 67,
+                velocity: 0.5,
+            },
+        ),
+    ];
+
+    for sample in 0..SAMPLES {
+        for (_, event) in events.iter().filter(|(at, _)| *at == sample) {
+            interpreted.process_event(*event);
+            generated.process_event(*event);
+        }
+
+        interpreted.step(1. / SAMPLE_RATE);
+        generated.step(dt);
+
+        let (left, right) = generated.get_stereo_output();
+        let expected = interpreted.get_stereo_output();
+
+        max_error = max_error
+            .max((left.to_float() - expected.0).abs())
+            .max((right.to_float() - expected.1).abs());
+        peak = peak.max(expected.0.abs());
+    }
+
+    assert!(peak > 0.1, "peak {}", peak);
+    assert!(
+        max_error < 0.05 * peak,
+        "error {} for peak {}",
+        max_error,
+        peak
+    );
+}
@@ -6,7 +6,10 @@ use std::path::Path;
 
 const PITCH_VALUES_SIZE: usize = 128;
 
-pub struct Scale([f32; PITCH_VALUES_SIZE]);
+pub struct Scale {
+    pitch_values: [f32; PITCH_VALUES_SIZE],
+    notes_per_period: usize,
+}
 
 impl Scale {
     pub fn equal_temperament() -> Self {
@@ -16,7 +19,10 @@ impl Scale {
             pitch_values[i] = (i as f32) / 12.;
         }
 
-        Self(pitch_values)
+        Self {
+            pitch_values,
+            notes_per_period: 12,
+        }
     }
 
     pub fn from_file(filename: &str, base_pitch: usize) -> Result<Self, SclError> {
@@ -27,12 +33,20 @@ impl Scale {
 
         pitch_values.copy_from_slice(&pitches);
 
-        Ok(Scale(pitch_values))
+        Ok(Scale {
+            pitch_values,
+            notes_per_period: scl_file.note_count(),
+        })
+    }
+
+    /// The number of notes before the scale repeats, 12 for equal temperament.
+    pub fn notes_per_period(&self) -> usize {
+        self.notes_per_period
     }
 
     pub fn pitch_value(&self, pitch_index: usize) -> Option<f32> {
-        if pitch_index < self.0.len() {
-            Some(self.0[pitch_index])
+        if pitch_index < self.pitch_values.len() {
+            Some(self.pitch_values[pitch_index])
         } else {
             None
         }
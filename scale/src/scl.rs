@@ -102,6 +102,11 @@ impl SclFile {
         }
     }
 
+    /// The number of notes before the scale repeats.
+    pub fn note_count(&self) -> usize {
+        self.pitches.len()
+    }
+
     pub fn to_pitch_vec(&self, root_note: usize, offset: f32, len: usize) -> Vec<f32> {
         let mut result = Vec::new();
         let l = self.pitches.len() as u32;
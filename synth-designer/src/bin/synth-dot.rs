@@ -0,0 +1,67 @@
+//! Write the dataflow graph of a synth patch in the Graphviz DOT format.
+//!
+//! ```bash
+//! cargo run --bin synth-dot -- synths/steampipe.ini | dot -Tsvg > steampipe.svg
+//! ```
+
+use ini::Ini;
+use std::process::exit;
+use synth_designer::dot::{synth_to_dot, DotOptions};
+use synth_designer::sub_patch::expand_sub_patches;
+use synth_designer::synth_spec::SynthSpec;
+use synth_designer::wavetable_file::{patch_dir, resolve_file_names};
+
+const USAGE: &str = "usage: synth-dot [--constants] <patch.ini>";
+
+fn main() {
+    let mut options = DotOptions::default();
+    let mut filename: Option<String> = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--constants" => options.show_constants = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if filename.is_none() => filename = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    let Some(filename) = filename else {
+        eprintln!("{}", USAGE);
+        exit(1);
+    };
+
+    let mut spec_file = match Ini::load_from_file(&filename) {
+        Ok(spec_file) => spec_file,
+        Err(err) => {
+            eprintln!("Error reading {}: {}", filename, err);
+            exit(1);
+        }
+    };
+
+    resolve_file_names(&mut spec_file, patch_dir(&filename));
+
+    options.title = spec_file
+        .section(None::<String>)
+        .and_then(|props| props.get("name"))
+        .map(|name| name.to_string());
+
+    let synth_spec = match expand_sub_patches(spec_file)
+        .map_err(|err| err.into())
+        .and_then(SynthSpec::from_sections)
+    {
+        Ok(synth_spec) => synth_spec,
+        Err(err) => {
+            eprintln!("Error reading synth spec: {:?}", err);
+            exit(1);
+        }
+    };
+
+    print!("{}", synth_to_dot(&synth_spec, &options));
+}
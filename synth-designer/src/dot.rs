@@ -0,0 +1,147 @@
+//! Export of the dataflow graph of a synth to the Graphviz DOT format.
+//!
+//! Modules are nodes and every `module.field` reference in an input expression
+//! is an edge, labelled with the output field it reads. Edges that are part of
+//! a feedback loop are drawn in red. Render the output with eg
+//! `dot -Tsvg patch.dot > patch.svg`.
+
+use crate::graph::DataflowGraph;
+use crate::input_expr::Expr;
+use crate::synth_spec::SynthSpec;
+use std::fmt::Write;
+
+const FEEDBACK_COLOR: &str = "red";
+
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Graph title, eg the `name` from the general section of the patch.
+    pub title: Option<String>,
+    /// Also draw inputs that are constant expressions, as plain text nodes.
+    /// Inputs that are left at their default of zero are never drawn.
+    pub show_constants: bool,
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn synth_to_dot(synth_spec: &SynthSpec, options: &DotOptions) -> String {
+    let graph = DataflowGraph::from_references(synth_spec);
+    let cycles = graph.cycles(|_| true);
+    let in_cycle =
+        |from: usize, to: usize| cycles.iter().any(|c| c.contains(&from) && c.contains(&to));
+
+    let mut dot = String::new();
+
+    writeln!(dot, "digraph synth {{").unwrap();
+    writeln!(dot, "    rankdir=LR;").unwrap();
+    writeln!(dot, "    node [shape=box];").unwrap();
+
+    if let Some(title) = &options.title {
+        writeln!(dot, "    label={};", quote(title)).unwrap();
+    }
+
+    for module in &graph.modules {
+        writeln!(dot, "    {};", quote(module)).unwrap();
+    }
+
+    for edge in &graph.edges {
+        let from = quote(&graph.modules[edge.from]);
+        let to = quote(&graph.modules[edge.to]);
+        let label = quote(edge.output);
+
+        if in_cycle(edge.from, edge.to) {
+            writeln!(
+                dot,
+                "    {} -> {} [label={}, color={}, fontcolor={}, penwidth=2];",
+                from, to, label, FEEDBACK_COLOR, FEEDBACK_COLOR
+            )
+            .unwrap();
+        } else {
+            writeln!(dot, "    {} -> {} [label={}];", from, to, label).unwrap();
+        }
+    }
+
+    for (module, input, _) in &graph.errors {
+        let missing = quote(&format!("{}.{} (missing)", graph.modules[*module], input));
+
+        writeln!(
+            dot,
+            "    {} [shape=plaintext, fontcolor={}];",
+            missing, FEEDBACK_COLOR
+        )
+        .unwrap();
+        writeln!(
+            dot,
+            "    {} -> {} [style=dotted];",
+            missing,
+            quote(&graph.modules[*module])
+        )
+        .unwrap();
+    }
+
+    if options.show_constants {
+        for module_spec in synth_spec.module_specs() {
+            for (input, expr) in module_spec.inputs() {
+                if expr.output_refs().is_empty() && *expr != Expr::zero() {
+                    let node = quote(&format!("{}.{}", module_spec.get_name(), input));
+
+                    writeln!(
+                        dot,
+                        "    {} [shape=plaintext, label={}];",
+                        node,
+                        quote(&expr.to_string())
+                    )
+                    .unwrap();
+                    writeln!(
+                        dot,
+                        "    {} -> {} [label={}, style=dashed];",
+                        node,
+                        quote(module_spec.get_name()),
+                        quote(input)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    writeln!(dot, "}}").unwrap();
+
+    dot
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATCH: &str = "
+[amplifier]
+name=amp
+signal_input=feedback.signal_output
+linear_control=0.5
+
+[amplifier]
+name=feedback
+signal_input=amp.signal_output
+
+[mono_out]
+name=out
+signal_input=amp.signal_output
+";
+
+    #[test]
+    fn feedback_and_constants() {
+        let synth_spec = SynthSpec::from_ini_str(PATCH).unwrap();
+        let options = DotOptions {
+            title: None,
+            show_constants: true,
+        };
+        let dot = synth_to_dot(&synth_spec, &options);
+
+        assert!(dot.contains("\"amp\" -> \"out\" [label=\"signal_output\"];"));
+        assert!(dot.contains("\"feedback\" -> \"amp\" [label=\"signal_output\", color=red"));
+        assert!(dot.contains("\"amp.linear_control\" [shape=plaintext, label=\"0.5\"];"));
+        assert!(!dot.contains("\"amp.exp_control\""));
+    }
+}
@@ -0,0 +1,267 @@
+//! Analog filter prototypes, and their state-space form.
+//!
+//! Filters are designed for a cutoff of 1 rad/s as transfer functions: ratios
+//! of polynomials in `s`, with their coefficients from the constant term up.
+
+use std::f64::consts::PI;
+
+/// A complex number as `(re, im)`.
+pub type Complex = (f64, f64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    pub numerator: Vec<f64>,
+    /// Has a leading coefficient of 1 and a degree of at least that of the
+    /// numerator
+    pub denominator: Vec<f64>,
+}
+
+/// The matrices of a system, row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateSpaceMatrices {
+    pub a: Vec<Vec<f64>>,
+    pub b: Vec<Vec<f64>>,
+    pub c: Vec<Vec<f64>>,
+    pub d: Vec<Vec<f64>>,
+}
+
+fn complex_mul(a: Complex, b: Complex) -> Complex {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+/// The polynomial with the given roots and a leading coefficient of 1. Complex
+/// roots should come with their conjugates, the imaginary parts of the
+/// coefficients are dropped.
+fn poly_from_roots(roots: &[Complex]) -> Vec<f64> {
+    let mut poly: Vec<Complex> = vec![(1., 0.)];
+
+    for root in roots {
+        // Multiply by (s - root)
+        let mut next = vec![(0., 0.); poly.len() + 1];
+
+        for (i, c) in poly.iter().enumerate() {
+            let (re, im) = complex_mul(*c, *root);
+
+            next[i].0 -= re;
+            next[i].1 -= im;
+            next[i + 1].0 += c.0;
+            next[i + 1].1 += c.1;
+        }
+
+        poly = next;
+    }
+
+    poly.into_iter().map(|(re, _)| re).collect()
+}
+
+fn eval(poly: &[f64], s: Complex) -> Complex {
+    poly.iter().rev().fold((0., 0.), |acc, c| {
+        let (re, im) = complex_mul(acc, s);
+
+        (re + c, im)
+    })
+}
+
+impl TransferFunction {
+    /// The transfer function `gain (s - z1)(s - z2)... / (s - p1)(s - p2)...`.
+    pub fn from_poles_zeros(poles: &[Complex], zeros: &[Complex], gain: f64) -> Self {
+        Self {
+            numerator: poly_from_roots(zeros).iter().map(|c| c * gain).collect(),
+            denominator: poly_from_roots(poles),
+        }
+    }
+
+    /// An all-pole lowpass with the given poles, and its gain at DC scaled to
+    /// `gain`.
+    fn all_pole(poles: &[Complex], gain: f64) -> Self {
+        let denominator = poly_from_roots(poles);
+
+        Self {
+            numerator: vec![denominator[0] * gain],
+            denominator,
+        }
+    }
+
+    /// The maximally flat lowpass, down 3 dB at the cutoff.
+    pub fn butterworth(order: usize) -> Self {
+        let n = order as f64;
+        let poles: Vec<Complex> = (1..=order)
+            .map(|k| {
+                let theta = PI * (2. * k as f64 + n - 1.) / (2. * n);
+
+                (theta.cos(), theta.sin())
+            })
+            .collect();
+
+        Self::all_pole(&poles, 1.)
+    }
+
+    /// The lowpass with `ripple` dB of equal ripple in the passband, which
+    /// ends at the cutoff. The peaks of the ripple are at unity gain.
+    pub fn chebyshev(order: usize, ripple: f64) -> Self {
+        let n = order as f64;
+        let epsilon = (10f64.powf(ripple / 10.) - 1.).sqrt();
+        let mu = (1. / epsilon).asinh() / n;
+        let poles: Vec<Complex> = (1..=order)
+            .map(|k| {
+                let theta = PI * (2. * k as f64 - 1.) / (2. * n);
+
+                (-mu.sinh() * theta.sin(), mu.cosh() * theta.cos())
+            })
+            .collect();
+        let gain = if order % 2 == 0 {
+            1. / (1. + epsilon * epsilon).sqrt()
+        } else {
+            1.
+        };
+
+        Self::all_pole(&poles, gain)
+    }
+
+    /// The lowpass with maximally flat group delay. Its response at high
+    /// frequencies matches the Butterworth filter of the same order.
+    pub fn bessel(order: usize) -> Self {
+        let factorial = |n: usize| (1..=n).map(|i| i as f64).product::<f64>();
+        // The reverse Bessel polynomial
+        let poly: Vec<f64> = (0..=order)
+            .map(|k| {
+                factorial(2 * order - k)
+                    / (2f64.powi((order - k) as i32) * factorial(k) * factorial(order - k))
+            })
+            .collect();
+        // Substituting a0^(1/n) s for s makes both the constant and the
+        // leading coefficient 1
+        let scale = poly[0].powf(1. / order as f64);
+        let denominator: Vec<f64> = poly
+            .iter()
+            .enumerate()
+            .map(|(k, c)| c / poly[0] * scale.powi(k as i32))
+            .collect();
+
+        Self {
+            numerator: vec![1.],
+            denominator,
+        }
+    }
+
+    /// The highpass with the same response above the cutoff as this lowpass
+    /// below it, by substituting `1 / s` for `s`. None if the system has a pole
+    /// at 0, which would move to infinity.
+    pub fn highpass(&self) -> Option<Self> {
+        let n = self.denominator.len() - 1;
+
+        if self.denominator[0] == 0. {
+            return None;
+        }
+
+        let mut numerator = self.numerator.clone();
+
+        numerator.resize(n + 1, 0.);
+        numerator.reverse();
+
+        let mut denominator = self.denominator.clone();
+        denominator.reverse();
+
+        let lead = denominator[n];
+
+        Some(Self {
+            numerator: numerator.iter().map(|c| c / lead).collect(),
+            denominator: denominator.iter().map(|c| c / lead).collect(),
+        })
+    }
+
+    pub fn order(&self) -> usize {
+        self.denominator.len() - 1
+    }
+
+    /// The response at the angular frequency `omega`.
+    pub fn response(&self, omega: f64) -> Complex {
+        let (nr, ni) = eval(&self.numerator, (0., omega));
+        let (dr, di) = eval(&self.denominator, (0., omega));
+        let d2 = dr * dr + di * di;
+
+        ((nr * dr + ni * di) / d2, (ni * dr - nr * di) / d2)
+    }
+
+    /// The system in controllable canonical form, with one input and one
+    /// output.
+    pub fn state_space(&self) -> StateSpaceMatrices {
+        let n = self.order();
+        let mut numerator = self.numerator.clone();
+
+        numerator.resize(n + 1, 0.);
+
+        let feedthrough = numerator[n];
+        let mut a = vec![vec![0.; n]; n];
+
+        for (i, row) in a.iter_mut().enumerate().take(n.saturating_sub(1)) {
+            row[i + 1] = 1.;
+        }
+
+        if n > 0 {
+            a[n - 1] = self.denominator[..n].iter().map(|c| -c).collect();
+        }
+
+        StateSpaceMatrices {
+            a,
+            b: (0..n)
+                .map(|i| vec![if i + 1 == n { 1. } else { 0. }])
+                .collect(),
+            c: vec![(0..n)
+                .map(|i| numerator[i] - feedthrough * self.denominator[i])
+                .collect()],
+            d: vec![vec![feedthrough]],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn magnitude(tf: &TransferFunction, omega: f64) -> f64 {
+        let (re, im) = tf.response(omega);
+
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn prototypes() {
+        let butterworth = TransferFunction::butterworth(2);
+        assert!((butterworth.denominator[1] - 2f64.sqrt()).abs() < 1e-9);
+        assert!((magnitude(&butterworth, 1.) - 0.5f64.sqrt()).abs() < 1e-9);
+
+        let chebyshev = TransferFunction::chebyshev(4, 1.);
+        assert!((magnitude(&chebyshev, 1.) - 10f64.powf(-0.05)).abs() < 1e-9);
+        assert!(magnitude(&chebyshev, 0.5) > 10f64.powf(-0.05) - 1e-9);
+
+        let bessel = TransferFunction::bessel(3);
+        assert!((magnitude(&bessel, 0.) - 1.).abs() < 1e-9);
+        assert!((bessel.denominator[3] - 1.).abs() < 1e-9);
+
+        let highpass = TransferFunction::butterworth(3).highpass().unwrap();
+        assert!((magnitude(&highpass, 1e3) - 1.).abs() < 1e-6);
+        assert!((magnitude(&highpass, 1.) - 0.5f64.sqrt()).abs() < 1e-9);
+
+        let notch = TransferFunction::from_poles_zeros(
+            &[(-0.5, 0.8), (-0.5, -0.8)],
+            &[(0., 1.), (0., -1.)],
+            1.,
+        );
+        assert!(magnitude(&notch, 1.) < 1e-9);
+    }
+
+    #[test]
+    fn canonical_form() {
+        let tf = TransferFunction {
+            numerator: vec![1., 0., 2.],
+            denominator: vec![3., 4., 1.],
+        };
+        let ss = tf.state_space();
+
+        assert_eq!(ss.a, vec![vec![0., 1.], vec![-3., -4.]]);
+        assert_eq!(ss.b, vec![vec![0.], vec![1.]]);
+        assert_eq!(ss.c, vec![vec![-5., -8.]]);
+        assert_eq!(ss.d, vec![vec![2.]]);
+    }
+}
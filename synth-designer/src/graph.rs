@@ -0,0 +1,387 @@
+//! The dataflow graph of a synth: which module outputs feed which module
+//! inputs. The graph is built either from the compiled input expressions, so it
+//! reflects exactly the state the simulation reads, or directly from the output
+//! references in the expressions, which works before state allocation.
+
+use crate::input_expr::ExprError;
+use crate::synth_spec::SynthSpec;
+use std::collections::BTreeMap;
+use synth_engine::stack_program::Instr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge<'a> {
+    pub from: usize,
+    pub output: &'a str,
+    pub to: usize,
+    pub input: &'static str,
+}
+
+pub struct DataflowGraph<'a> {
+    pub modules: Vec<String>,
+    pub edges: Vec<Edge<'a>>,
+    pub errors: Vec<(usize, &'static str, ExprError)>,
+}
+
+impl<'a> DataflowGraph<'a> {
+    /// Build the graph for a synth. The state of the synth must be allocated
+    /// before calling this, as edges are found through state indices.
+    pub fn from_spec(synth_spec: &'a SynthSpec) -> Self {
+        let mut modules = Vec::new();
+        let mut outputs: BTreeMap<usize, (usize, &str)> = BTreeMap::new();
+
+        for (i, module_spec) in synth_spec.module_specs().enumerate() {
+            modules.push(module_spec.get_name().to_string());
+
+            for field in module_spec.outputs() {
+                if let Ok(index) = module_spec.state_index(field) {
+                    outputs.insert(index, (i, field));
+                }
+            }
+        }
+
+        let mut edges = Vec::new();
+        let mut errors = Vec::new();
+
+        for (to, module_spec) in synth_spec.module_specs().enumerate() {
+            for (input, expr) in module_spec.inputs() {
+                let program = match expr.compile(synth_spec) {
+                    Ok(program) => program,
+                    Err(err) => {
+                        errors.push((to, input, err));
+                        continue;
+                    }
+                };
+
+                for instr in &program.code {
+                    if let Instr::State(index) = instr {
+                        if let Some((from, output)) = outputs.get(index) {
+                            let edge = Edge {
+                                from: *from,
+                                output,
+                                to,
+                                input,
+                            };
+
+                            if !edges.contains(&edge) {
+                                edges.push(edge);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            modules,
+            edges,
+            errors,
+        }
+    }
+
+    /// Build the graph for a synth from the `module.field` references in the
+    /// input expressions. Unlike `from_spec`, this doesn't need allocated state.
+    pub fn from_references(synth_spec: &'a SynthSpec) -> Self {
+        let modules: Vec<String> = synth_spec
+            .module_specs()
+            .map(|m| m.get_name().to_string())
+            .collect();
+        let outputs: Vec<Vec<&str>> = synth_spec.module_specs().map(|m| m.outputs()).collect();
+
+        let mut edges = Vec::new();
+        let mut errors = Vec::new();
+
+        for (to, module_spec) in synth_spec.module_specs().enumerate() {
+            for (input, expr) in module_spec.inputs() {
+                for (module, field) in expr.output_refs() {
+                    let from = modules.iter().position(|m| m == module);
+                    let output = from.and_then(|i| outputs[i].iter().find(|o| **o == field));
+
+                    match (from, output) {
+                        (Some(from), Some(output)) => {
+                            let edge = Edge {
+                                from,
+                                output,
+                                to,
+                                input,
+                            };
+
+                            if !edges.contains(&edge) {
+                                edges.push(edge);
+                            }
+                        }
+                        _ => errors.push((
+                            to,
+                            input,
+                            ExprError::MissingField(module.to_string(), field.to_string()),
+                        )),
+                    }
+                }
+            }
+        }
+
+        Self {
+            modules,
+            edges,
+            errors,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn index_of(&self, module_name: &str) -> Option<usize> {
+        self.modules.iter().position(|m| m == module_name)
+    }
+
+    pub fn edges_from(&self, module: usize) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |e| e.from == module)
+    }
+
+    pub fn edges_to(&self, module: usize) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |e| e.to == module)
+    }
+
+    /// For each module, whether any of the `sinks` can be reached from it by
+    /// following edges. The sinks reach themselves.
+    pub fn reaches(&self, sinks: &[usize]) -> Vec<bool> {
+        let mut reached = vec![false; self.len()];
+        let mut queue: Vec<usize> = sinks.to_vec();
+
+        while let Some(module) = queue.pop() {
+            if !reached[module] {
+                reached[module] = true;
+                queue.extend(self.edges_to(module).map(|e| e.from));
+            }
+        }
+
+        reached
+    }
+
+    /// The strongly connected components of the graph, restricted to the
+    /// edges accepted by `filter`. Components are listed in reverse
+    /// topological order, each with its modules in ascending order.
+    pub fn components<F>(&self, filter: F) -> Vec<Vec<usize>>
+    where
+        F: Fn(&Edge) -> bool,
+    {
+        struct Tarjan<'a> {
+            successors: Vec<Vec<usize>>,
+            index: Vec<Option<usize>>,
+            low_link: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next_index: usize,
+            components: &'a mut Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, v: usize) {
+                self.index[v] = Some(self.next_index);
+                self.low_link[v] = self.next_index;
+                self.next_index += 1;
+                self.stack.push(v);
+                self.on_stack[v] = true;
+
+                for w in self.successors[v].clone() {
+                    match self.index[w] {
+                        None => {
+                            self.visit(w);
+                            self.low_link[v] = self.low_link[v].min(self.low_link[w]);
+                        }
+                        Some(index) if self.on_stack[w] => {
+                            self.low_link[v] = self.low_link[v].min(index);
+                        }
+                        _ => {}
+                    }
+                }
+
+                if Some(self.low_link[v]) == self.index[v] {
+                    let mut component = Vec::new();
+
+                    while let Some(w) = self.stack.pop() {
+                        self.on_stack[w] = false;
+                        component.push(w);
+
+                        if w == v {
+                            break;
+                        }
+                    }
+
+                    component.sort();
+                    self.components.push(component);
+                }
+            }
+        }
+
+        let mut successors = vec![Vec::new(); self.len()];
+
+        for edge in self.edges.iter().filter(|e| filter(e)) {
+            successors[edge.from].push(edge.to);
+        }
+
+        let mut components = Vec::new();
+        let mut tarjan = Tarjan {
+            successors,
+            index: vec![None; self.len()],
+            low_link: vec![0; self.len()],
+            on_stack: vec![false; self.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: &mut components,
+        };
+
+        for v in 0..self.len() {
+            if tarjan.index[v].is_none() {
+                tarjan.visit(v);
+            }
+        }
+
+        components
+    }
+
+    /// Order the modules so that every module comes after the modules it reads
+    /// from, following only the edges accepted by `filter`. Ties are broken by
+    /// module index, so modules that don't depend on each other keep their
+    /// relative order. When the remaining modules form a cycle, the one with
+    /// the lowest index in a cycle that depends on nothing else is taken first,
+    /// and its incoming edges from the rest of the cycle are returned as
+    /// feedback edges.
+    pub fn topological_order<F>(&self, filter: F) -> (Vec<usize>, Vec<&Edge<'a>>)
+    where
+        F: Fn(&Edge) -> bool,
+    {
+        let edges: Vec<&Edge> = self
+            .edges
+            .iter()
+            .filter(|e| e.from != e.to && filter(e))
+            .collect();
+        let mut component = vec![0; self.len()];
+
+        for (i, c) in self.components(|e| filter(e)).iter().enumerate() {
+            for m in c {
+                component[*m] = i;
+            }
+        }
+
+        let mut placed = vec![false; self.len()];
+        let mut order = Vec::with_capacity(self.len());
+        let mut feedback = Vec::new();
+
+        while order.len() < self.len() {
+            let unplaced = |m: &usize| !placed[*m];
+            let ready = (0..self.len())
+                .filter(unplaced)
+                .find(|m| !edges.iter().any(|e| e.to == *m && !placed[e.from]));
+
+            let module = match ready {
+                Some(module) => module,
+                None => {
+                    let module = (0..self.len())
+                        .filter(unplaced)
+                        .find(|m| {
+                            edges.iter().all(|e| {
+                                e.to != *m || placed[e.from] || component[e.from] == component[*m]
+                            })
+                        })
+                        .unwrap();
+
+                    feedback.extend(
+                        edges
+                            .iter()
+                            .filter(|e| e.to == module && !placed[e.from])
+                            .copied(),
+                    );
+
+                    module
+                }
+            };
+
+            placed[module] = true;
+            order.push(module);
+        }
+
+        (order, feedback)
+    }
+
+    /// The modules that are part of a cycle through the edges accepted by
+    /// `filter`, grouped by cycle.
+    pub fn cycles<F>(&self, filter: F) -> Vec<Vec<usize>>
+    where
+        F: Fn(&Edge) -> bool,
+    {
+        self.components(&filter)
+            .into_iter()
+            .filter(|c| {
+                c.len() > 1
+                    || self
+                        .edges
+                        .iter()
+                        .any(|e| e.from == c[0] && e.to == c[0] && filter(e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATCH: &str = "
+[noise]
+name=noise
+
+[amplifier]
+name=amp
+signal_input=noise.signal_output + feedback.signal_output
+
+[amplifier]
+name=feedback
+signal_input=amp.signal_output * 0.5
+
+[control]
+name=unused
+control=20
+
+[mono_out]
+name=out
+output_index=0
+signal_input=amp.signal_output
+";
+
+    #[test]
+    fn edges_and_reachability() {
+        let mut synth_spec = SynthSpec::from_ini_str(PATCH).unwrap();
+        synth_spec.allocate_state();
+
+        let graph = DataflowGraph::from_spec(&synth_spec);
+        let amp = graph.index_of("amp").unwrap();
+        let feedback = graph.index_of("feedback").unwrap();
+        let noise = graph.index_of("noise").unwrap();
+        let out = graph.index_of("out").unwrap();
+        let unused = graph.index_of("unused").unwrap();
+
+        assert_eq!(graph.edges.len(), 4);
+        assert!(graph.edges.contains(&Edge {
+            from: noise,
+            output: "signal_output",
+            to: amp,
+            input: "signal_input",
+        }));
+
+        let reached = graph.reaches(&[out]);
+        assert!(reached[noise] && reached[feedback] && !reached[unused]);
+
+        let mut cycle = vec![amp, feedback];
+        cycle.sort();
+        assert_eq!(graph.cycles(|_| true), vec![cycle]);
+
+        let references = DataflowGraph::from_references(&synth_spec);
+        assert_eq!(references.edges.len(), graph.edges.len());
+        assert!(references.edges.iter().all(|e| graph.edges.contains(e)));
+    }
+}
@@ -2,7 +2,9 @@ extern crate peg;
 use crate::synth_spec::SynthSpec;
 use peg::parser;
 use peg::str::LineCol;
+use std::fmt;
 use synth_engine::stack_program::*;
+use synth_engine::Float;
 use thiserror::Error;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -16,12 +18,17 @@ pub enum ExprError {
     ParseError(String, peg::error::ParseError<LineCol>),
     #[error("Missing module field. Module: {0}, field: {1}")]
     MissingField(String, String),
+    #[error("Unknown variable: {0}")]
+    UnknownVariable(String),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
-    Number(f32),
+    Number(Float),
     OutputState(String, String),
+    /// A name without module, only defined where the expression is evaluated
+    /// with `compile_with_variables`.
+    Variable(String),
     BinOp(BinaryOperator, Box<Expr>, Box<Expr>),
     FunCall(String, Vec<Expr>),
 }
@@ -50,9 +57,127 @@ impl Expr {
         Expr::Number(0.)
     }
 
-    pub fn constant(v: f32) -> Self {
+    pub fn constant(v: Float) -> Self {
         Expr::Number(v)
     }
+
+    /// Rebuild the expression, replacing every module output reference for
+    /// which `f` returns a new expression.
+    pub fn map_outputs<F>(&self, f: &F) -> Self
+    where
+        F: Fn(&str, &str) -> Option<Expr>,
+    {
+        use Expr::*;
+
+        match self {
+            Number(n) => Number(*n),
+            OutputState(m, n) => f(m, n).unwrap_or_else(|| OutputState(m.clone(), n.clone())),
+            Variable(name) => Variable(name.clone()),
+            BinOp(op, e1, e2) => BinOp(
+                op.clone(),
+                Box::new(e1.map_outputs(f)),
+                Box::new(e2.map_outputs(f)),
+            ),
+            FunCall(name, args) => FunCall(
+                name.clone(),
+                args.iter().map(|e| e.map_outputs(f)).collect(),
+            ),
+        }
+    }
+
+    /// Rebuild the expression, replacing every variable for which `f` returns
+    /// a new expression.
+    pub fn map_variables<F>(&self, f: &F) -> Self
+    where
+        F: Fn(&str) -> Option<Expr>,
+    {
+        use Expr::*;
+
+        match self {
+            Number(n) => Number(*n),
+            OutputState(m, n) => OutputState(m.clone(), n.clone()),
+            Variable(name) => f(name).unwrap_or_else(|| Variable(name.clone())),
+            BinOp(op, e1, e2) => BinOp(
+                op.clone(),
+                Box::new(e1.map_variables(f)),
+                Box::new(e2.map_variables(f)),
+            ),
+            FunCall(name, args) => FunCall(
+                name.clone(),
+                args.iter().map(|e| e.map_variables(f)).collect(),
+            ),
+        }
+    }
+
+    /// All module output references in the expression, as `(module, field)`.
+    pub fn output_refs(&self) -> Vec<(&str, &str)> {
+        let mut refs = Vec::new();
+
+        self.output_refs_helper(&mut refs);
+
+        refs
+    }
+
+    fn output_refs_helper<'a>(&'a self, refs: &mut Vec<(&'a str, &'a str)>) {
+        use Expr::*;
+
+        match self {
+            Number(_) | Variable(_) => {}
+            OutputState(m, n) => refs.push((m.as_str(), n.as_str())),
+            BinOp(_, e1, e2) => {
+                e1.output_refs_helper(refs);
+                e2.output_refs_helper(refs);
+            }
+            FunCall(_, args) => {
+                for expr in args {
+                    expr.output_refs_helper(refs);
+                }
+            }
+        }
+    }
+}
+
+/// Prints the expression in a form that `Expr::parse` reads back.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Expr::*;
+
+        match self {
+            Number(n) => {
+                let s = n.to_string();
+
+                if s.contains('.') {
+                    write!(f, "{}", s)
+                } else {
+                    write!(f, "{}.", s)
+                }
+            }
+            OutputState(m, n) => write!(f, "{}.{}", m, n),
+            Variable(name) => write!(f, "{}", name),
+            BinOp(op, e1, e2) => {
+                let op = match op {
+                    BinaryOperator::Add => "+",
+                    BinaryOperator::Subtract => "-",
+                    BinaryOperator::Multiply => "*",
+                    BinaryOperator::Divide => "/",
+                };
+
+                write!(f, "({} {} {})", e1, op, e2)
+            }
+            FunCall(name, args) => {
+                write!(f, "{}(", name)?;
+
+                for (i, expr) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", expr)?;
+                }
+
+                write!(f, ")")
+            }
+        }
+    }
 }
 
 parser! {
@@ -67,19 +192,26 @@ parser! {
             _ n:number() _ { n }
             _ f:function() _ { f }
             _ o:output() _ { o }
+            _ v:variable() _ { v }
             _ "(" e:expression() ")" _ { e }
         }
 
         rule _ = [' ' | '\n']*
 
         rule number() -> Expr
-            = n:$("-"? ['0'..='9']+ "." ['0'..='9']*) { Expr::Number(n.parse::<f32>().unwrap()) }
+            = n:$("-"? ['0'..='9']+ "." ['0'..='9']*) { Expr::Number(n.parse::<Float>().unwrap()) }
+
+        rule identifier() -> &'input str
+            = $(['a'..='z'|'A'..='Z'|'_'] ['a'..='z'|'A'..='Z'|'0'..='9'|'_']*)
 
         rule function() -> Expr
-            = f:$(['a'..='z'|'A'..='Z'|'_']+) "(" args:(expression() ** ",") ")" { Expr::FunCall(f.to_string(), args) }
+            = f:identifier() "(" args:(expression() ** ",") ")" { Expr::FunCall(f.to_string(), args) }
 
         rule output() -> Expr
-            = a:$(['a'..='z'|'A'..='Z'|'_']+) "." b:$(['a'..='z'|'A'..='Z'|'_']+) { Expr::OutputState(a.to_string(), b.to_string()) }
+            = a:identifier() "." b:identifier() { Expr::OutputState(a.to_string(), b.to_string()) }
+
+        rule variable() -> Expr
+            = v:identifier() { Expr::Variable(v.to_string()) }
     }
 }
 
@@ -87,7 +219,19 @@ impl Expr {
     pub fn compile(&self, synth_spec: &SynthSpec) -> Result<StackProgram, ExprError> {
         let mut program: Vec<Instr> = Vec::new();
 
-        self.compile_helper(synth_spec, &mut program)?;
+        self.compile_helper(Some(synth_spec), &[], &mut program)?;
+
+        let stack_size = compute_stack_size(&program);
+
+        Ok(StackProgram::new(program, stack_size))
+    }
+
+    /// Compile an expression that refers to no modules, only to `variables`.
+    /// The program reads variable `i` from state index `i`.
+    pub fn compile_with_variables(&self, variables: &[&str]) -> Result<StackProgram, ExprError> {
+        let mut program: Vec<Instr> = Vec::new();
+
+        self.compile_helper(None, variables, &mut program)?;
 
         let stack_size = compute_stack_size(&program);
 
@@ -96,15 +240,16 @@ impl Expr {
 
     fn compile_helper(
         &self,
-        synth_spec: &SynthSpec,
+        synth_spec: Option<&SynthSpec>,
+        variables: &[&str],
         program: &mut Vec<Instr>,
     ) -> Result<(), ExprError> {
         use Expr::*;
 
         match self {
             BinOp(op, e1, e2) => {
-                e2.compile_helper(synth_spec, program)?;
-                e1.compile_helper(synth_spec, program)?;
+                e2.compile_helper(synth_spec, variables, program)?;
+                e1.compile_helper(synth_spec, variables, program)?;
                 let op_instr = match op {
                     BinaryOperator::Add => Instr::Add,
                     BinaryOperator::Subtract => Instr::Subtract,
@@ -114,14 +259,20 @@ impl Expr {
                 program.push(op_instr);
             }
             Number(n) => program.push(Instr::Const(*n)),
-            OutputState(m, n) => match synth_spec.input_state_index(m.as_str(), n.as_str()) {
-                Ok(index) => program.push(Instr::State(index)),
-                Err(_) => return Err(ExprError::MissingField(m.to_string(), n.to_string())),
+            OutputState(m, n) => {
+                match synth_spec.map(|spec| spec.input_state_index(m.as_str(), n.as_str())) {
+                    Some(Ok(index)) => program.push(Instr::State(index)),
+                    _ => return Err(ExprError::MissingField(m.to_string(), n.to_string())),
+                }
+            }
+            Variable(name) => match variables.iter().position(|v| v == name) {
+                Some(index) => program.push(Instr::State(index)),
+                None => return Err(ExprError::UnknownVariable(name.to_string())),
             },
             FunCall(f, args) => {
                 // TODO check len() of args matches what's required by function
                 for expr in args {
-                    expr.compile_helper(synth_spec, program)?;
+                    expr.compile_helper(synth_spec, variables, program)?;
                 }
 
                 let fun = match f.as_str() {
@@ -193,6 +344,14 @@ mod test {
         assert_eq!(Expr::parse(input), Ok(expected));
     }
 
+    #[test]
+    fn digits_in_names() {
+        let expected = OutputState("voice_2_osc".to_string(), "pitch_10".to_string());
+
+        assert_eq!(Expr::parse("voice_2_osc.pitch_10"), Ok(expected));
+        assert!(Expr::parse("2voice.pitch").is_err());
+    }
+
     #[test]
     fn negative_constant() {
         let input = "-2. * a.b";
@@ -205,6 +364,29 @@ mod test {
         assert_eq!(Expr::parse(input), Ok(expected));
     }
 
+    #[test]
+    fn display_roundtrip() {
+        let input = "lerp(a.b, 2. * -1.5, 3.) - x.y / (4.25 + tanh(z.w))";
+        let expr = Expr::parse(input).unwrap();
+
+        assert_eq!(Expr::parse(&expr.to_string()), Ok(expr));
+    }
+
+    #[test]
+    fn map_outputs() {
+        let expr = Expr::parse("a.b * c.d").unwrap();
+        let expected = Expr::parse("a.b * (1. + e.f)").unwrap();
+        let mapped = expr.map_outputs(&|m, _| {
+            if m == "c" {
+                Some(Expr::parse("1. + e.f").unwrap())
+            } else {
+                None
+            }
+        });
+
+        assert_eq!(mapped, expected);
+    }
+
     #[test]
     fn compile() {
         let mut synth_spec = SynthSpec::new();
@@ -248,6 +430,27 @@ mod test {
         );
     }
 
+    #[test]
+    fn compile_variables() {
+        let expr = Expr::parse("sin(x) * a").unwrap();
+        let expected = StackProgram::new(
+            vec![
+                Instr::State(1),
+                Instr::State(0),
+                Instr::Call(Function::Sin),
+                Instr::Multiply,
+            ],
+            2,
+        );
+
+        assert_eq!(expr.compile_with_variables(&["x", "a"]), Ok(expected));
+        assert_eq!(
+            expr.compile(&SynthSpec::new()),
+            Err(ExprError::UnknownVariable("a".to_string()))
+        );
+        assert_eq!(Expr::parse(&expr.to_string()), Ok(expr));
+    }
+
     #[test]
     fn test_functions() {
         let synth_spec = SynthSpec::new();
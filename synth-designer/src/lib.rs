@@ -1,19 +1,31 @@
+pub mod dot;
+pub mod filter_design;
+pub mod graph;
 pub mod input_expr;
+pub mod lint;
 pub mod modules;
 pub mod state_allocator;
+pub mod sub_patch;
 pub mod synth_spec;
+pub mod wavetable_file;
+pub mod wavetable_gen;
 
 use crate::modules::ModuleError;
+use crate::sub_patch::SubPatchError;
+use synth_engine::Float;
 
 /// This matches the frequency of note zero for the default MIDI spec.
-pub const DEFAULT_FREQUENCY_ZERO: f32 = 8.18;
+pub const DEFAULT_FREQUENCY_ZERO: Float = 8.18;
 
 // TODO use thiserror::Error here
 #[derive(Debug)]
 pub enum SynthError {
     FileError(ini::Error),
+    ParseError(ini::ParseError),
     ModuleError(ModuleError),
+    SubPatchError(SubPatchError),
     UnknownModule(String),
+    InvalidSetting(String, String),
 }
 
 impl From<ini::Error> for SynthError {
@@ -27,3 +39,9 @@ impl From<ModuleError> for SynthError {
         Self::ModuleError(err)
     }
 }
+
+impl From<SubPatchError> for SynthError {
+    fn from(err: SubPatchError) -> Self {
+        Self::SubPatchError(err)
+    }
+}
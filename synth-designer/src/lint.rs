@@ -0,0 +1,365 @@
+//! Static checks on a synth patch. The linter finds patches that load fine but
+//! most likely don't do what was intended: modules that feed nothing, inputs
+//! that refer to missing outputs, single valued keys that are given twice in a
+//! section and MIDI controls or audio outputs claimed by more than one module.
+//! Template inputs that instances leave unbound and delay-free loops that don't
+//! converge are reported as well.
+
+use crate::graph::DataflowGraph;
+use crate::modules::Resource;
+use crate::sub_patch::expand_patch;
+use crate::synth_spec::SynthSpec;
+use crate::wavetable_file::{patch_dir, resolve_file_names};
+use crate::SynthError;
+use ini::{Ini, Properties};
+use std::collections::BTreeMap;
+use std::fmt;
+use synth_engine::Float;
+
+const NAME_FIELD: &str = "name";
+const STEREO_OUTPUTS: [usize; 2] = [0, 1];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// The module doesn't feed any audio output, directly or indirectly.
+    UnusedModule(String),
+    /// An input expression refers to a module or output that doesn't exist.
+    UnresolvedInput {
+        module: String,
+        input: String,
+        message: String,
+    },
+    /// No module writes to the audio output channel.
+    MissingOutput(usize),
+    /// The audio output channel doesn't exist.
+    InvalidOutput { module: String, index: usize },
+    /// A key that takes a single value is given more than once in a section.
+    /// Only the last value is used.
+    DuplicateKey {
+        section: String,
+        name: String,
+        key: String,
+    },
+    /// Several modules claim the same resource.
+    DuplicateResource(Resource, Vec<String>),
+    /// A template input that an instance doesn't bind, so it is zero.
+    UnboundInput { instance: String, input: String },
+    /// A delay-free loop that the loop solver can't resolve from some start
+    /// values of its outputs.
+    UnresolvedLoop {
+        modules: Vec<String>,
+        residual: Float,
+    },
+    /// A delay-free loop that the loop solver can't be set up for.
+    InvalidLoop(String),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Lint::*;
+
+        match self {
+            UnusedModule(module) => write!(f, "module {} doesn't feed any output", module),
+            UnresolvedInput {
+                module,
+                input,
+                message,
+            } => write!(f, "input {} on module {}: {}", input, module, message),
+            MissingOutput(index) => write!(f, "nothing is written to output {}", index),
+            InvalidOutput { module, index } => {
+                write!(
+                    f,
+                    "module {} writes to non-existing output {}",
+                    module, index
+                )
+            }
+            DuplicateKey { section, name, key } => write!(
+                f,
+                "key {} is given more than once in [{}] {}, the last value wins",
+                key, section, name
+            ),
+            DuplicateResource(Resource::MidiControl(control), modules) => write!(
+                f,
+                "MIDI control {} is used by several modules: {}",
+                control,
+                modules.join(", ")
+            ),
+            DuplicateResource(Resource::AudioOutput(index), modules) => write!(
+                f,
+                "output {} is written by several modules: {}",
+                index,
+                modules.join(", ")
+            ),
+            UnboundInput { instance, input } => write!(
+                f,
+                "input {} of instance {} isn't bound, it is zero",
+                input, instance
+            ),
+            UnresolvedLoop { modules, residual } => write!(
+                f,
+                "delay-free loop through {} doesn't converge, residual {}",
+                modules.join(", "),
+                residual
+            ),
+            InvalidLoop(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+fn lint_sections(sections: &[(String, Properties)], synth_spec: &SynthSpec, lints: &mut Vec<Lint>) {
+    for (section, props) in sections {
+        let name = props.get(NAME_FIELD).unwrap_or(section);
+        let repeated = synth_spec
+            .module_specs()
+            .find(|m| m.get_name() == name)
+            .map(|m| m.repeated_fields())
+            .unwrap_or_default();
+        let mut seen: Vec<&str> = Vec::new();
+        let mut reported: Vec<&str> = Vec::new();
+
+        for (k, _) in props.iter() {
+            if seen.contains(&k) && !reported.contains(&k) && !repeated.contains(&k) {
+                lints.push(Lint::DuplicateKey {
+                    section: section.clone(),
+                    name: name.to_string(),
+                    key: k.to_string(),
+                });
+                reported.push(k);
+            }
+
+            seen.push(k);
+        }
+    }
+}
+
+/// Check a synth spec. The state of the synth must be allocated.
+pub fn lint_spec(synth_spec: &SynthSpec) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let graph = DataflowGraph::from_spec(synth_spec);
+
+    for (module, input, err) in &graph.errors {
+        lints.push(Lint::UnresolvedInput {
+            module: graph.modules[*module].clone(),
+            input: input.to_string(),
+            message: err.to_string(),
+        });
+    }
+
+    let mut claims: BTreeMap<Resource, Vec<usize>> = BTreeMap::new();
+
+    for (i, module_spec) in synth_spec.module_specs().enumerate() {
+        for resource in module_spec.resources() {
+            claims.entry(resource).or_default().push(i);
+        }
+    }
+
+    let mut sinks = Vec::new();
+
+    for (resource, modules) in &claims {
+        if let Resource::AudioOutput(index) = resource {
+            sinks.extend(modules);
+
+            if !STEREO_OUTPUTS.contains(index) {
+                for module in modules {
+                    lints.push(Lint::InvalidOutput {
+                        module: graph.modules[*module].clone(),
+                        index: *index,
+                    });
+                }
+            }
+        }
+
+        if modules.len() > 1 {
+            lints.push(Lint::DuplicateResource(
+                *resource,
+                modules.iter().map(|m| graph.modules[*m].clone()).collect(),
+            ));
+        }
+    }
+
+    for index in STEREO_OUTPUTS {
+        if !claims.contains_key(&Resource::AudioOutput(index)) {
+            lints.push(Lint::MissingOutput(index));
+        }
+    }
+
+    for (module, reached) in graph.reaches(&sinks).into_iter().enumerate() {
+        if !reached {
+            lints.push(Lint::UnusedModule(graph.modules[module].clone()));
+        }
+    }
+
+    match synth_spec.probe_loops() {
+        Ok(unresolved) => {
+            for (modules, residual) in unresolved {
+                lints.push(Lint::UnresolvedLoop { modules, residual });
+            }
+        }
+        Err(err) => lints.push(Lint::InvalidLoop(err.to_string())),
+    }
+
+    lints
+}
+
+/// Check a patch. Besides the checks of `lint_spec`, this finds problems that
+/// are lost when the patch is loaded, like keys given more than once.
+pub fn lint_ini(spec_file: Ini) -> Result<Vec<Lint>, SynthError> {
+    let settings = spec_file
+        .section(None::<String>)
+        .cloned()
+        .unwrap_or_default();
+    let patch = expand_patch(spec_file)?;
+    let mut lints = Vec::new();
+    let mut synth_spec = SynthSpec::from_sections(patch.sections.clone())?;
+
+    synth_spec.apply_settings(&settings)?;
+    synth_spec.allocate_state();
+    lint_sections(&patch.sections, &synth_spec, &mut lints);
+
+    for (instance, input) in patch.unbound_inputs {
+        lints.push(Lint::UnboundInput { instance, input });
+    }
+
+    lints.extend(lint_spec(&synth_spec));
+
+    Ok(lints)
+}
+
+pub fn lint_file(filename: &str) -> Result<Vec<Lint>, SynthError> {
+    let mut spec_file = Ini::load_from_file(filename).map_err(SynthError::FileError)?;
+
+    resolve_file_names(&mut spec_file, patch_dir(filename));
+    lint_ini(spec_file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATCH: &str = "
+[noise]
+name=noise
+
+[control]
+name=cutoff
+control=20
+
+[control]
+name=dead
+control=20
+
+[filter_6db]
+name=filter
+signal_input=noise.signal_output
+signal_input=noise.signal_output * 0.5
+cutoff_frequency=cutoff.signal_output + missing.signal_output
+
+[mono_out]
+name=left
+output_index=0
+signal_input=filter.lowpass_output
+
+[mono_out]
+name=also_left
+output_index=0
+signal_input=filter.lowpass_output
+
+[template]
+name=gain
+input=level
+output=signal:amp.signal_output
+
+[amplifier]
+template=gain
+name=amp
+signal_input=input.level
+
+[instance]
+template=gain
+name=quiet
+";
+
+    #[test]
+    fn lint_patch() {
+        let lints = lint_ini(Ini::load_from_str(PATCH).unwrap()).unwrap();
+
+        assert!(lints.contains(&Lint::DuplicateKey {
+            section: "filter_6db".to_string(),
+            name: "filter".to_string(),
+            key: "signal_input".to_string(),
+        }));
+        assert!(lints.contains(&Lint::DuplicateResource(
+            Resource::MidiControl(20),
+            vec!["cutoff".to_string(), "dead".to_string()]
+        )));
+        assert!(lints.contains(&Lint::DuplicateResource(
+            Resource::AudioOutput(0),
+            vec!["left".to_string(), "also_left".to_string()]
+        )));
+        assert!(lints.contains(&Lint::MissingOutput(1)));
+        assert!(lints.contains(&Lint::UnusedModule("dead".to_string())));
+        assert!(!lints.contains(&Lint::UnusedModule("noise".to_string())));
+        assert!(lints.iter().any(|l| matches!(
+            l,
+            Lint::UnresolvedInput { module, .. } if module == "filter"
+        )));
+        assert!(lints.contains(&Lint::UnboundInput {
+            instance: "quiet".to_string(),
+            input: "level".to_string(),
+        }));
+    }
+
+    fn amp_ring(solver: &str, gain: &str, size: usize) -> String {
+        let mut patch = format!("loop_solver={}\n", solver);
+
+        for i in 0..size {
+            patch += &format!(
+                "[amplifier]\nname=a{}\nsignal_input=a{}.signal_output + 1.0\nlinear_control={}\n",
+                i,
+                (i + 1) % size,
+                gain
+            );
+        }
+
+        patch + "[mono_out]\noutput_index=0\nsignal_input=a0.signal_output\n"
+    }
+
+    #[test]
+    fn lint_loops() {
+        let lint_loop = |solver, size| {
+            let patch = amp_ring(solver, "2.0", size);
+            let lints = lint_ini(Ini::load_from_str(&patch).unwrap()).unwrap();
+
+            lints
+                .into_iter()
+                .filter(|l| matches!(l, Lint::UnresolvedLoop { .. } | Lint::InvalidLoop(_)))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matches!(
+            lint_loop("fixed_point", 2)[..],
+            [Lint::UnresolvedLoop { .. }]
+        ));
+        assert!(lint_loop("newton", 2).is_empty());
+        assert!(matches!(lint_loop("newton", 9)[..], [Lint::InvalidLoop(_)]));
+    }
+
+    #[test]
+    fn bundled_patches() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../synths");
+        let mut problems = Vec::new();
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.extension().is_some_and(|ext| ext == "ini") {
+                match lint_file(path.to_str().unwrap()) {
+                    Ok(lints) if lints.is_empty() => {}
+                    result => problems.push((path, result)),
+                }
+            }
+        }
+
+        assert!(problems.is_empty(), "{:#?}", problems);
+    }
+}
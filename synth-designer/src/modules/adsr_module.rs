@@ -0,0 +1,207 @@
+use crate::input_expr::Expr;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::adsr::{AdsrInputs, AdsrShape};
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+
+const MODULE_TYPE: &str = "adsr";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const SIGNAL_OUTPUT: &str = "signal_output";
+const DELAY_CONTROL: &str = "delay_control";
+const ATTACK_CONTROL: &str = "attack_control";
+const HOLD_CONTROL: &str = "hold_control";
+const DECAY_CONTROL: &str = "decay_control";
+const SUSTAIN_CONTROL: &str = "sustain_control";
+const RELEASE_CONTROL: &str = "release_control";
+const VELOCITY_INPUT: &str = "velocity_input";
+const VELOCITY_SENSITIVITY_FIELD: &str = "velocity_sensitivity";
+const ATTACK_CURVE_FIELD: &str = "attack_curve";
+const DECAY_CURVE_FIELD: &str = "decay_curve";
+const RELEASE_CURVE_FIELD: &str = "release_curve";
+const RETRIGGER_FIELD: &str = "retrigger";
+const LOOP_FIELD: &str = "loop";
+const INPUT_SIZE: usize = 8;
+const STATE_SIZE: usize = 2;
+
+pub struct AdsrModuleSpec {
+    name: String,
+    inputs: [Expr; INPUT_SIZE],
+    shape: AdsrShape,
+    state: [usize; STATE_SIZE],
+}
+
+impl AdsrModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut delay_control: Expr = Expr::zero();
+        let mut attack_control: Expr = Expr::zero();
+        let mut hold_control: Expr = Expr::zero();
+        let mut decay_control: Expr = Expr::zero();
+        let mut sustain_control: Expr = Expr::constant(1.);
+        let mut release_control: Expr = Expr::zero();
+        let mut velocity_in: Expr = Expr::constant(1.);
+        let mut shape = AdsrShape::default();
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                DELAY_CONTROL => delay_control = Expr::parse(&v)?,
+                ATTACK_CONTROL => attack_control = Expr::parse(&v)?,
+                HOLD_CONTROL => hold_control = Expr::parse(&v)?,
+                DECAY_CONTROL => decay_control = Expr::parse(&v)?,
+                SUSTAIN_CONTROL => sustain_control = Expr::parse(&v)?,
+                RELEASE_CONTROL => release_control = Expr::parse(&v)?,
+                VELOCITY_INPUT => velocity_in = Expr::parse(&v)?,
+                VELOCITY_SENSITIVITY_FIELD => shape.velocity_sensitivity = v.trim().parse()?,
+                ATTACK_CURVE_FIELD => shape.attack_curve = v.trim().parse()?,
+                DECAY_CURVE_FIELD => shape.decay_curve = v.trim().parse()?,
+                RELEASE_CURVE_FIELD => shape.release_curve = v.trim().parse()?,
+                RETRIGGER_FIELD => {
+                    shape.retrigger_from_zero = match v.trim() {
+                        "current" => false,
+                        "zero" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                LOOP_FIELD => {
+                    shape.looping = match v.trim() {
+                        "off" => false,
+                        "on" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            inputs: [
+                signal_in,
+                delay_control,
+                attack_control,
+                hold_control,
+                decay_control,
+                sustain_control,
+                release_control,
+                velocity_in,
+            ],
+            shape,
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for AdsrModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let inputs = AdsrInputs {
+            gate: self.inputs[0].compile(synth_spec)?,
+            delay: self.inputs[1].compile(synth_spec)?,
+            attack: self.inputs[2].compile(synth_spec)?,
+            hold: self.inputs[3].compile(synth_spec)?,
+            decay: self.inputs[4].compile(synth_spec)?,
+            sustain: self.inputs[5].compile(synth_spec)?,
+            release: self.inputs[6].compile(synth_spec)?,
+            velocity: self.inputs[7].compile(synth_spec)?,
+        };
+        let adsr = Adsr::new(inputs, self.state[0], self.state[1]).with_shape(self.shape);
+
+        Ok(Box::new(adsr))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let [gate, delay, attack, hold, decay, sustain, release, velocity] = self
+            .inputs
+            .each_ref()
+            .map(|input| gen_program(&input.compile(synth_spec).unwrap()));
+        let s0 = self.state[0];
+        let s1 = self.state[1];
+        let AdsrShape {
+            attack_curve,
+            decay_curve,
+            release_curve,
+            velocity_sensitivity,
+            retrigger_from_zero,
+            looping,
+        } = self.shape;
+
+        Ok(quote! { SynthModule::Adsr(Adsr::new(
+            synth_engine::modules::adsr::AdsrInputs {
+                gate: #gate,
+                delay: #delay,
+                attack: #attack,
+                hold: #hold,
+                decay: #decay,
+                sustain: #sustain,
+                release: #release,
+                velocity: #velocity,
+            },
+            #s0,
+            #s1,
+        )
+        .with_shape(synth_engine::modules::adsr::AdsrShape {
+            attack_curve: #attack_curve,
+            decay_curve: #decay_curve,
+            release_curve: #release_curve,
+            velocity_sensitivity: #velocity_sensitivity,
+            retrigger_from_zero: #retrigger_from_zero,
+            looping: #looping,
+        })) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (DELAY_CONTROL, &self.inputs[1]),
+            (ATTACK_CONTROL, &self.inputs[2]),
+            (HOLD_CONTROL, &self.inputs[3]),
+            (DECAY_CONTROL, &self.inputs[4]),
+            (SUSTAIN_CONTROL, &self.inputs[5]),
+            (RELEASE_CONTROL, &self.inputs[6]),
+            (VELOCITY_INPUT, &self.inputs[7]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_INPUT, VELOCITY_INPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
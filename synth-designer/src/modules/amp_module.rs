@@ -1,6 +1,5 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
@@ -64,13 +63,13 @@ impl ModuleSpec for AmpModuleSpec {
         Ok(Box::new(amplifier))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let s0 = self.state[0];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i0 = synth_spec.codegen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = synth_spec.codegen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = synth_spec.codegen_program(&self.inputs[2].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::Amp(Amplifier::new(#i0, #s0, #i1, #i2)) }
+        Ok(quote! { SynthModule::Amp(Amplifier::new(#i0, #s0, #i1, #i2)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -91,4 +90,24 @@ impl ModuleSpec for AmpModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (LINEAR_CONTROL, &self.inputs[1]),
+            (EXP_CONTROL, &self.inputs[2]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
 }
@@ -1,6 +1,6 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
+use crate::synth_spec::gen_program;
 use crate::synth_spec::SynthSpec;
 use crate::DEFAULT_FREQUENCY_ZERO;
 use ini::Properties;
@@ -8,6 +8,7 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "bowed_oscillator";
 const MODULE_NAME: &str = "name";
@@ -26,8 +27,8 @@ pub struct BowedOscillatorModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
-    a: f32,
+    f0: Float,
+    a: Float,
 }
 
 impl BowedOscillatorModuleSpec {
@@ -37,8 +38,8 @@ impl BowedOscillatorModuleSpec {
         let mut lc: Expr = Expr::zero();
         let mut pc: Expr = Expr::zero();
         let mut vc: Expr = Expr::zero();
-        let mut f0: f32 = DEFAULT_FREQUENCY_ZERO;
-        let mut a: f32 = 0.;
+        let mut f0: Float = DEFAULT_FREQUENCY_ZERO;
+        let mut a: Float = 0.;
 
         for (k, v) in props {
             match k.as_str() {
@@ -47,8 +48,8 @@ impl BowedOscillatorModuleSpec {
                 LINEAR_FREQUENCY_CONTROL => lc = Expr::parse(&v)?,
                 PRESSURE_CONTROL => pc = Expr::parse(&v)?,
                 VELOCITY_CONTROL => vc = Expr::parse(&v)?,
-                FREQ0 => f0 = v.parse::<f32>()?,
-                PARAM_A => a = v.parse::<f32>()?,
+                FREQ0 => f0 = v.parse::<Float>()?,
+                PARAM_A => a = v.parse::<Float>()?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -83,17 +84,19 @@ impl ModuleSpec for BowedOscillatorModuleSpec {
         Ok(Box::new(osc))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let a = self.a;
         let s0 = self.state[0];
         let s1 = self.state[1];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i3 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i3 = gen_program(&self.inputs[3].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::Bowed(BowedOscillator::new( #f0, #a, #s0, #s1, #i0, #i1, #i2, #i3 )) }
+        Ok(
+            quote! { SynthModule::Bowed(BowedOscillator::new( #f0, #a, #s0, #s1, #i0, #i1, #i2, #i3 )) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -115,4 +118,17 @@ impl ModuleSpec for BowedOscillatorModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (FREQUENCY_CONTROL, &self.inputs[0]),
+            (LINEAR_FREQUENCY_CONTROL, &self.inputs[1]),
+            (PRESSURE_CONTROL, &self.inputs[2]),
+            (VELOCITY_CONTROL, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_1_OUTPUT, SIGNAL_2_OUTPUT]
+    }
 }
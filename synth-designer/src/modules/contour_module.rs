@@ -1,10 +1,10 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
+use synth_engine::modules::envelope::EnvType;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
 
@@ -15,12 +15,14 @@ const SIGNAL_OUTPUT: &str = "signal_output";
 const RISE_CONTROL: &str = "rise_control";
 const DECAY_CONTROL: &str = "decay_control";
 const SHAPE_CONTROL: &str = "shape_control";
+const TYPE_FIELD: &str = "type";
 const INPUT_SIZE: usize = 4;
 const STATE_SIZE: usize = 2;
 
 pub struct ContourModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
+    env_type: EnvType,
     state: [usize; STATE_SIZE],
 }
 
@@ -31,6 +33,7 @@ impl ContourModuleSpec {
         let mut rise_control: Expr = Expr::zero();
         let mut decay_control: Expr = Expr::zero();
         let mut shape_control: Expr = Expr::zero();
+        let mut env_type = EnvType::AttackRelease;
 
         for (k, v) in props {
             match k.as_str() {
@@ -39,6 +42,14 @@ impl ContourModuleSpec {
                 RISE_CONTROL => rise_control = Expr::parse(&v)?,
                 DECAY_CONTROL => decay_control = Expr::parse(&v)?,
                 SHAPE_CONTROL => shape_control = Expr::parse(&v)?,
+                TYPE_FIELD => {
+                    env_type = match v.trim() {
+                        "attack_release" => EnvType::AttackRelease,
+                        "attack_decay" => EnvType::AttackDecay,
+                        "cyclic" => EnvType::Cyclic,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -46,6 +57,7 @@ impl ContourModuleSpec {
         Ok(Self {
             name,
             inputs: [signal_in, rise_control, decay_control, shape_control],
+            env_type,
             state: [0; STATE_SIZE],
         })
     }
@@ -64,20 +76,29 @@ impl ModuleSpec for ContourModuleSpec {
             self.inputs[3].compile(&synth_spec)?,
             self.state[0],
             self.state[1],
-        );
+        )
+        .with_type(self.env_type);
 
         Ok(Box::new(env))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
-        let i3 = gen_stack_program(&self.inputs[3].compile(&synth_spec).unwrap());
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let i0 = synth_spec.codegen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = synth_spec.codegen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = synth_spec.codegen_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i3 = synth_spec.codegen_program(&self.inputs[3].compile(&synth_spec).unwrap());
         let s0 = self.state[0];
         let s1 = self.state[1];
+        let env_type = match self.env_type {
+            EnvType::AttackDecay => quote! { AttackDecay },
+            EnvType::AttackRelease => quote! { AttackRelease },
+            EnvType::Cyclic => quote! { Cyclic },
+        };
 
-        quote! { SynthModule::Contour(Envelope::new(#i0, #i1, #i2, #i3, #s0, #s1)) }
+        Ok(
+            quote! { SynthModule::Contour(Envelope::new(#i0, #i1, #i2, #i3, #s0, #s1)
+            .with_type(synth_engine::modules::envelope::EnvType::#env_type)) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -98,4 +119,29 @@ impl ModuleSpec for ContourModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (RISE_CONTROL, &self.inputs[1]),
+            (DECAY_CONTROL, &self.inputs[2]),
+            (SHAPE_CONTROL, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_INPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
 }
@@ -1,3 +1,4 @@
+use crate::input_expr::Expr;
 use crate::modules::*;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
@@ -5,6 +6,7 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "midi_cc";
 const MODULE_NAME: &str = "name";
@@ -18,23 +20,23 @@ pub struct ControlModuleSpec {
     name: String,
     control: usize,
     state: [usize; STATE_SIZE],
-    min_value: f32,
-    max_value: f32,
+    min_value: Float,
+    max_value: Float,
 }
 
 impl ControlModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
         let mut control: usize = 0;
-        let mut min_value: f32 = 0.;
-        let mut max_value: f32 = 1.;
+        let mut min_value: Float = 0.;
+        let mut max_value: Float = 1.;
 
         for (k, v) in props {
             match k.as_str() {
                 MODULE_NAME => name = v.to_string(),
                 CONTROL => control = v.parse::<usize>()?,
-                MIN_VALUE => min_value = v.parse::<f32>()?,
-                MAX_VALUE => max_value = v.parse::<f32>()?,
+                MIN_VALUE => min_value = v.parse::<Float>()?,
+                MAX_VALUE => max_value = v.parse::<Float>()?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -61,13 +63,13 @@ impl ModuleSpec for ControlModuleSpec {
         Ok(Box::new(midi_cc))
     }
 
-    fn codegen(&self, _synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, _synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let s0 = self.state[0];
         let c = self.control;
         let min = self.min_value;
         let max = self.max_value;
 
-        quote! { SynthModule::ContinuousControl(ContinuousControl::new(#s0, #c, #min, #max)) }
+        Ok(quote! { SynthModule::ContinuousControl(ContinuousControl::new(#s0, #c, #min, #max)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -88,4 +90,20 @@ impl ModuleSpec for ControlModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::MidiControl(self.control)]
+    }
 }
@@ -1,12 +1,13 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
-use crate::synth_spec::SynthSpec;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::{buffer_ident, CodegenMode, SynthSpec};
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "delay_line";
 const MODULE_NAME: &str = "name";
@@ -24,7 +25,7 @@ const DATA_SIZE_VALUE: usize = 1024;
 pub struct DelayLineModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
-    f0: f32,
+    f0: Float,
     state: [usize; STATE_SIZE],
     data_size: usize,
 }
@@ -32,7 +33,7 @@ pub struct DelayLineModuleSpec {
 impl DelayLineModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
-        let mut f0: f32 = 1.;
+        let mut f0: Float = 1.;
         let mut fc: Expr = Expr::zero();
         let mut lc: Expr = Expr::zero();
         let mut input: Expr = Expr::zero();
@@ -42,7 +43,7 @@ impl DelayLineModuleSpec {
             match k.as_str() {
                 MODULE_NAME => name = v.to_string(),
                 SIGNAL_INPUT => input = Expr::parse(&v)?,
-                FREQUENCY_ZERO => f0 = v.parse::<f32>()?,
+                FREQUENCY_ZERO => f0 = v.parse::<Float>()?,
                 FREQUENCY_CONTROL => fc = Expr::parse(&v)?,
                 LINEAR_CONTROL => lc = Expr::parse(&v)?,
                 DATA_SIZE_FIELD => data_size = v.parse::<usize>()?,
@@ -78,15 +79,27 @@ impl ModuleSpec for DelayLineModuleSpec {
         Ok(Box::new(delay_line))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(&synth_spec).unwrap());
         let ds = self.data_size;
 
-        quote! { SynthModule::Delay(DelayLine::new(#f0, #s0, #i0, #i1, #i2, #ds)) }
+        match synth_spec.codegen_mode() {
+            CodegenMode::Alloc => {
+                Ok(quote! { SynthModule::Delay(DelayLine::new(#f0, #s0, #i0, #i1, #i2, #ds)) })
+            }
+            CodegenMode::Static => {
+                let buffer = buffer_ident(&self.name);
+
+                Ok(quote! {
+                    SynthModule::Delay(DelayLine::with_buffer(#f0, #s0, #i0, #i1, #i2, &mut #buffer[..]))
+                })
+            }
+            CodegenMode::Fixed => Err(ModuleError::NotFixedPoint(self.name.clone())),
+        }
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -107,4 +120,28 @@ impl ModuleSpec for DelayLineModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (FREQUENCY_CONTROL, &self.inputs[1]),
+            (LINEAR_CONTROL, &self.inputs[2]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_INPUT]
+    }
+
+    fn buffer_size(&self) -> Option<usize> {
+        Some(self.data_size)
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
 }
@@ -0,0 +1,108 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "differentiator";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const BANDWIDTH_FIELD: &str = "bandwidth";
+const SIGNAL_OUTPUT: &str = "signal_output";
+/// The default bandwidth in Hz
+const DEFAULT_BANDWIDTH: Float = 1000.;
+
+const STATE_SIZE: usize = 2;
+
+pub struct DifferentiatorModuleSpec {
+    name: String,
+    signal_input: Expr,
+    bandwidth: Float,
+    state: [usize; STATE_SIZE],
+}
+
+impl DifferentiatorModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut bandwidth = DEFAULT_BANDWIDTH;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                BANDWIDTH_FIELD => bandwidth = v.trim().parse()?,
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            signal_input: signal_in,
+            bandwidth,
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for DifferentiatorModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let differentiator = Differentiator::new(
+            self.signal_input.compile(synth_spec)?,
+            self.bandwidth,
+            self.state[0],
+            self.state[1],
+        );
+
+        Ok(Box::new(differentiator))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let i0 = gen_program(&self.signal_input.compile(synth_spec).unwrap());
+        let bandwidth = self.bandwidth;
+        let s0 = self.state[0];
+        let s1 = self.state[1];
+
+        Ok(quote! { SynthModule::Differentiator(Differentiator::new(#i0, #bandwidth, #s0, #s1)) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![(SIGNAL_INPUT, &self.signal_input)]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
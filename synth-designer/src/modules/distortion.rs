@@ -0,0 +1,99 @@
+//! The `distortion` field of the filter modules, which selects the
+//! nonlinearity of a filter and its parameters.
+
+use crate::modules::ModuleError;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::distortion::{make_diodelike, DistortionType};
+use synth_engine::Float;
+
+pub const DISTORTION_FIELD: &str = "distortion";
+
+/// The resistance and ideality factor of the diode by default.
+const DEFAULT_DIODE: (Float, Float) = (1000., 2.);
+/// The logistic function with a slope of 1 at 0 by default.
+const DEFAULT_LOGISTIC: Float = 2.;
+
+/// Parse a distortion like `none`, `tanh`, `smoothstep`, `logistic(4.0)` or
+/// `diode(1000.0, 2.0)`. Parameters that are left out have default values.
+pub fn parse_distortion(v: &str) -> Result<Option<DistortionType>, ModuleError> {
+    let malformed = || ModuleError::MalformedInputSpec(v.to_string());
+    let (name, params) = match v.trim().split_once('(') {
+        Some((name, params)) => (
+            name.trim(),
+            params.trim().strip_suffix(')').ok_or_else(malformed)?,
+        ),
+        None => (v.trim(), ""),
+    };
+    let params = params
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse::<Float>())
+        .collect::<Result<Vec<Float>, _>>()?;
+
+    match (name, &params[..]) {
+        ("none", []) => Ok(None),
+        ("tanh", []) => Ok(Some(DistortionType::Tanh)),
+        ("smoothstep", []) => Ok(Some(DistortionType::Smoothstep)),
+        ("logistic", []) => Ok(Some(DistortionType::Logistic(DEFAULT_LOGISTIC))),
+        ("logistic", &[amount]) => Ok(Some(DistortionType::Logistic(amount))),
+        ("diode", []) => Ok(Some(make_diodelike(DEFAULT_DIODE.0, DEFAULT_DIODE.1))),
+        ("diode", &[r]) if r > 0. => Ok(Some(make_diodelike(r, DEFAULT_DIODE.1))),
+        ("diode", &[r, n]) if r > 0. && n > 0. => Ok(Some(make_diodelike(r, n))),
+        _ => Err(malformed()),
+    }
+}
+
+/// Generate the distortion, as an `Option<DistortionType>`.
+pub fn codegen_distortion(distortion: &Option<DistortionType>) -> TokenStream {
+    let Some(distortion) = distortion else {
+        return quote! { None };
+    };
+    let distortion = match *distortion {
+        DistortionType::Tanh => quote! { Tanh },
+        DistortionType::Smoothstep => quote! { Smoothstep },
+        DistortionType::Diodelike {
+            a,
+            b,
+            c,
+            ln_R: ln_r,
+            R: r,
+        } => quote! {
+            Diodelike { a: #a, b: #b, c: #c, ln_R: #ln_r, R: #r }
+        },
+        DistortionType::Logistic(amount) => quote! { Logistic(#amount) },
+    };
+
+    quote! { Some(synth_engine::distortion::DistortionType::#distortion) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distortions() {
+        assert_eq!(parse_distortion(" none ").unwrap(), None);
+        assert_eq!(
+            parse_distortion("tanh").unwrap(),
+            Some(DistortionType::Tanh)
+        );
+        assert_eq!(
+            parse_distortion("logistic( 4.0 )").unwrap(),
+            Some(DistortionType::Logistic(4.))
+        );
+        assert_eq!(
+            parse_distortion("diode").unwrap(),
+            Some(make_diodelike(1000., 2.))
+        );
+        assert_eq!(
+            parse_distortion("diode(500.0, 1.5)").unwrap(),
+            Some(make_diodelike(500., 1.5))
+        );
+        assert!(parse_distortion("diode(500.0, 1.5, 2.0)").is_err());
+        assert!(parse_distortion("tanh(1.0)").is_err());
+        assert!(parse_distortion("logistic(4.0").is_err());
+        assert!(parse_distortion("clip").is_err());
+    }
+}
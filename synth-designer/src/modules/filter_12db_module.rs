@@ -1,12 +1,14 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
+use crate::synth_spec::gen_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
+use synth_engine::distortion::DistortionType;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "filter_12db";
 const MODULE_NAME: &str = "name";
@@ -26,17 +28,19 @@ pub struct Filter12dbModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
+    f0: Float,
+    distortion: Option<DistortionType>,
 }
 
 impl Filter12dbModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
-        let mut f0: f32 = 1.;
+        let mut f0: Float = 1.;
         let mut fc: Option<Expr> = None;
         let mut lc: Option<Expr> = None;
         let mut rc: Option<Expr> = None;
         let mut input: Option<Expr> = None;
+        let mut distortion: Option<DistortionType> = None;
 
         for (k, v) in props {
             match k.as_str() {
@@ -45,7 +49,8 @@ impl Filter12dbModuleSpec {
                 CUTOFF_CONTROL => fc = Some(Expr::parse(&v)?),
                 RESONANCE_CONTROL => rc = Some(Expr::parse(&v)?),
                 LINEAR_CONTROL => lc = Some(Expr::parse(&v)?),
-                FREQUENCY_ZERO => f0 = v.parse::<f32>()?,
+                FREQUENCY_ZERO => f0 = v.parse::<Float>()?,
+                DISTORTION_FIELD => distortion = parse_distortion(&v)?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -60,6 +65,7 @@ impl Filter12dbModuleSpec {
                 rc.unwrap_or(Expr::zero()),
             ],
             state: [0; STATE_SIZE],
+            distortion,
         })
     }
 }
@@ -79,22 +85,26 @@ impl ModuleSpec for Filter12dbModuleSpec {
             self.inputs[2].compile(&synth_spec)?,
             self.inputs[3].compile(&synth_spec)?,
             self.inputs[0].compile(&synth_spec)?,
-        );
+        )
+        .with_distortion(self.distortion);
 
         Ok(Box::new(filter))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
         let s1 = self.state[1];
         let s2 = self.state[2];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
-        let i3 = gen_stack_program(&self.inputs[3].compile(&synth_spec).unwrap());
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i3 = gen_program(&self.inputs[3].compile(&synth_spec).unwrap());
+        let distortion = codegen_distortion(&self.distortion);
 
-        quote! { SynthModule::Filter2Pole(Filter12db::new(#f0, #s0, #s1, #s2, #i1, #i2, #i3, #i0)) }
+        Ok(
+            quote! { SynthModule::Filter2Pole(Filter12db::new(#f0, #s0, #s1, #s2, #i1, #i2, #i3, #i0).with_distortion(#distortion)) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -117,4 +127,21 @@ impl ModuleSpec for Filter12dbModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (CUTOFF_CONTROL, &self.inputs[1]),
+            (LINEAR_CONTROL, &self.inputs[2]),
+            (RESONANCE_CONTROL, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![HP_OUTPUT, BP_OUTPUT, LP_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![HP_OUTPUT]
+    }
 }
@@ -1,12 +1,14 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
+use crate::synth_spec::gen_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
+use synth_engine::distortion::DistortionType;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "lowpass_filter_24db";
 const MODULE_NAME: &str = "name";
@@ -24,7 +26,8 @@ pub struct Filter24dbModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
+    f0: Float,
+    distortion: Option<DistortionType>,
 }
 
 impl Filter24dbModuleSpec {
@@ -34,7 +37,8 @@ impl Filter24dbModuleSpec {
         let mut fc: Expr = Expr::zero();
         let mut lc: Expr = Expr::zero();
         let mut rc: Expr = Expr::zero();
-        let mut f0: f32 = 1.0_f32;
+        let mut f0: Float = 1.0;
+        let mut distortion = Some(DistortionType::Smoothstep);
 
         for (k, v) in props {
             match k.as_str() {
@@ -43,7 +47,8 @@ impl Filter24dbModuleSpec {
                 CUTOFF_CONTROL => fc = Expr::parse(&v)?,
                 LINEAR_CONTROL => lc = Expr::parse(&v)?,
                 RESONANCE_CONTROL => rc = Expr::parse(&v)?,
-                FREQ0 => f0 = v.parse::<f32>()?,
+                FREQ0 => f0 = v.parse::<Float>()?,
+                DISTORTION_FIELD => distortion = parse_distortion(&v)?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -53,6 +58,7 @@ impl Filter24dbModuleSpec {
             inputs: [signal_in, fc, rc, lc],
             state: [0; 4],
             f0,
+            distortion,
         })
     }
 }
@@ -73,23 +79,27 @@ impl ModuleSpec for Filter24dbModuleSpec {
             self.inputs[3].compile(&synth_spec)?,
             self.inputs[2].compile(&synth_spec)?,
             self.inputs[0].compile(&synth_spec)?,
-        );
+        )
+        .with_distortion(self.distortion);
 
         Ok(Box::new(filter))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
         let s1 = self.state[1];
         let s2 = self.state[2];
         let s3 = self.state[3];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
-        let i3 = gen_stack_program(&self.inputs[3].compile(&synth_spec).unwrap());
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i3 = gen_program(&self.inputs[3].compile(&synth_spec).unwrap());
+        let distortion = codegen_distortion(&self.distortion);
 
-        quote! { SynthModule::Filter4Pole(Filter24db::new( #f0, #s0, #s1, #s2, #s3, #i1, #i3, #i2, #i0 )) }
+        Ok(
+            quote! { SynthModule::Filter4Pole(Filter24db::new( #f0, #s0, #s1, #s2, #s3, #i1, #i3, #i2, #i0 ).with_distortion(#distortion)) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -111,4 +121,17 @@ impl ModuleSpec for Filter24dbModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (CUTOFF_CONTROL, &self.inputs[1]),
+            (RESONANCE_CONTROL, &self.inputs[2]),
+            (LINEAR_CONTROL, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![LOWPASS_OUTPUT, HIGHPASS_OUTPUT]
+    }
 }
@@ -1,12 +1,12 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "lowpass_filter_6db";
 const MODULE_NAME: &str = "name";
@@ -23,7 +23,7 @@ pub struct Filter6dbModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
+    f0: Float,
 }
 
 impl Filter6dbModuleSpec {
@@ -32,7 +32,7 @@ impl Filter6dbModuleSpec {
         let mut signal_in: Expr = Expr::zero();
         let mut fc: Expr = Expr::zero();
         let mut lc: Expr = Expr::zero();
-        let mut f0: f32 = 1.0;
+        let mut f0: Float = 1.0;
 
         for (k, v) in props {
             match k.as_str() {
@@ -40,7 +40,7 @@ impl Filter6dbModuleSpec {
                 SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
                 CUTOFF_CONTROL => fc = Expr::parse(&v)?,
                 LINEAR_CONTROL => lc = Expr::parse(&v)?,
-                FREQ0 => f0 = v.parse::<f32>()?,
+                FREQ0 => f0 = v.parse::<Float>()?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -73,16 +73,16 @@ impl ModuleSpec for Filter6dbModuleSpec {
         Ok(Box::new(filter))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
         let s1 = self.state[1];
         let s2 = self.state[2];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i0 = synth_spec.codegen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = synth_spec.codegen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = synth_spec.codegen_program(&self.inputs[2].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::Filter1Pole(Filter6db::new(#f0, #s2, #s0, #s1, #i0, #i1, #i2)) }
+        Ok(quote! { SynthModule::Filter1Pole(Filter6db::new(#f0, #s2, #s0, #s1, #i0, #i1, #i2)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -104,4 +104,24 @@ impl ModuleSpec for Filter6dbModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (CUTOFF_CONTROL, &self.inputs[0]),
+            (LINEAR_CONTROL, &self.inputs[1]),
+            (SIGNAL_INPUT, &self.inputs[2]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![LOWPASS_OUTPUT, HIGHPASS_OUTPUT]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![HIGHPASS_OUTPUT]
+    }
 }
@@ -1,6 +1,6 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
+use crate::synth_spec::gen_program;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
@@ -61,12 +61,12 @@ impl ModuleSpec for FolderModuleSpec {
         Ok(Box::new(folder))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let s0 = self.state[0];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::Wavefolder(Folder::new(#i0, #i1, #s0)) }
+        Ok(quote! { SynthModule::Wavefolder(Folder::new(#i0, #i1, #s0)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -87,4 +87,16 @@ impl ModuleSpec for FolderModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![(SIGNAL_INPUT, &self.inputs[0]), (CONTROL, &self.inputs[1])]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
 }
@@ -0,0 +1,122 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "integrator";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const LEAK_CONTROL: &str = "leak_control";
+const MIN_FIELD: &str = "min_value";
+const MAX_FIELD: &str = "max_value";
+const SIGNAL_OUTPUT: &str = "signal_output";
+
+const STATE_SIZE: usize = 1;
+const INPUT_SIZE: usize = 2;
+
+pub struct IntegratorModuleSpec {
+    name: String,
+    inputs: [Expr; INPUT_SIZE],
+    limits: Option<(Float, Float)>,
+    state: [usize; STATE_SIZE],
+}
+
+impl IntegratorModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut leak_control: Expr = Expr::zero();
+        let mut min: Option<Float> = None;
+        let mut max: Option<Float> = None;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                LEAK_CONTROL => leak_control = Expr::parse(&v)?,
+                MIN_FIELD => min = Some(v.trim().parse()?),
+                MAX_FIELD => max = Some(v.trim().parse()?),
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        let limits = match (min, max) {
+            (None, None) => None,
+            (min, max) => Some((min.unwrap_or(-Float::MAX), max.unwrap_or(Float::MAX))),
+        };
+
+        Ok(Self {
+            name,
+            inputs: [signal_in, leak_control],
+            limits,
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for IntegratorModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let mut integrator = Integrator::new(
+            self.inputs[0].compile(synth_spec)?,
+            self.inputs[1].compile(synth_spec)?,
+            self.state[0],
+        );
+
+        if let Some((min, max)) = self.limits {
+            integrator = integrator.with_limits(min, max);
+        }
+
+        Ok(Box::new(integrator))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let s0 = self.state[0];
+        let i0 = gen_program(&self.inputs[0].compile(synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(synth_spec).unwrap());
+        let with_limits = self
+            .limits
+            .map(|(min, max)| quote! { .with_limits(#min, #max) });
+
+        Ok(quote! { SynthModule::Integrator(Integrator::new(#i0, #i1, #s0)#with_limits) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (LEAK_CONTROL, &self.inputs[1]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
@@ -0,0 +1,239 @@
+use crate::input_expr::Expr;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::lfo::{LfoInputs, LfoShape, TempoSync};
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "lfo";
+const MODULE_NAME: &str = "name";
+const SIGNAL_OUTPUT: &str = "signal_output";
+const RATE_CONTROL: &str = "rate_control";
+const TRIGGER_INPUT: &str = "trigger_input";
+const FADE_CONTROL: &str = "fade_control";
+const CLOCK_INPUT: &str = "clock_input";
+const SHAPE_FIELD: &str = "shape";
+const POLARITY_FIELD: &str = "polarity";
+const SYNC_FIELD: &str = "sync";
+const CLOCK_PPQ_FIELD: &str = "clock_ppq";
+const SEED_FIELD: &str = "seed";
+const INPUT_SIZE: usize = 4;
+const STATE_SIZE: usize = 2;
+
+/// The beats of a note value like `1/4`, `1/8t` for a triplet or `3/16.` for a
+/// dotted note. A beat is a quarter note.
+fn note_beats(v: &str) -> Result<Float, ModuleError> {
+    let (value, factor) = if let Some(value) = v.trim().strip_suffix('t') {
+        (value, 2. / 3.)
+    } else if let Some(value) = v.trim().strip_suffix('.') {
+        (value, 1.5)
+    } else {
+        (v.trim(), 1.)
+    };
+    let (n, d) = value.split_once('/').unwrap_or((value, "1"));
+    let beats = 4. * n.trim().parse::<Float>()? / d.trim().parse::<Float>()? * factor;
+
+    if beats.is_finite() && beats > 0. {
+        Ok(beats)
+    } else {
+        Err(ModuleError::MalformedInputSpec(v.to_string()))
+    }
+}
+
+pub struct LfoModuleSpec {
+    name: String,
+    inputs: [Expr; INPUT_SIZE],
+    shape: LfoShape,
+    unipolar: bool,
+    sync: Option<TempoSync>,
+    seed: u32,
+    state: [usize; STATE_SIZE],
+}
+
+impl LfoModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut rate_control: Expr = Expr::constant(1.);
+        let mut trigger_in: Expr = Expr::zero();
+        let mut fade_control: Expr = Expr::zero();
+        let mut clock_in: Expr = Expr::zero();
+        let mut shape = LfoShape::Sine;
+        let mut unipolar = false;
+        let mut beats: Option<Float> = None;
+        let mut pulses_per_beat: Float = 1.;
+        let mut seed: u32 = 1;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                RATE_CONTROL => rate_control = Expr::parse(&v)?,
+                TRIGGER_INPUT => trigger_in = Expr::parse(&v)?,
+                FADE_CONTROL => fade_control = Expr::parse(&v)?,
+                CLOCK_INPUT => clock_in = Expr::parse(&v)?,
+                SHAPE_FIELD => {
+                    shape = match v.trim() {
+                        "sine" => LfoShape::Sine,
+                        "triangle" => LfoShape::Triangle,
+                        "saw" => LfoShape::Saw,
+                        "square" => LfoShape::Square,
+                        "random" => LfoShape::Random,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                POLARITY_FIELD => {
+                    unipolar = match v.trim() {
+                        "bipolar" => false,
+                        "unipolar" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                SYNC_FIELD => beats = Some(note_beats(&v)?),
+                CLOCK_PPQ_FIELD => pulses_per_beat = v.trim().parse()?,
+                SEED_FIELD => seed = v.trim().parse()?,
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            inputs: [rate_control, trigger_in, fade_control, clock_in],
+            shape,
+            unipolar,
+            sync: beats.map(|beats| TempoSync {
+                beats,
+                pulses_per_beat,
+            }),
+            seed,
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for LfoModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let inputs = LfoInputs {
+            rate: self.inputs[0].compile(synth_spec)?,
+            trigger: self.inputs[1].compile(synth_spec)?,
+            fade: self.inputs[2].compile(synth_spec)?,
+            clock: self.inputs[3].compile(synth_spec)?,
+        };
+        let mut lfo = Lfo::new(inputs, self.state[0], self.state[1])
+            .with_shape(self.shape)
+            .with_unipolar(self.unipolar)
+            .with_seed(self.seed);
+
+        if let Some(sync) = self.sync {
+            lfo = lfo.with_sync(sync);
+        }
+
+        Ok(Box::new(lfo))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let i0 = gen_program(&self.inputs[0].compile(synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(synth_spec).unwrap());
+        let i3 = gen_program(&self.inputs[3].compile(synth_spec).unwrap());
+        let s0 = self.state[0];
+        let s1 = self.state[1];
+        let unipolar = self.unipolar;
+        let seed = self.seed;
+        let shape = match self.shape {
+            LfoShape::Sine => quote! { Sine },
+            LfoShape::Triangle => quote! { Triangle },
+            LfoShape::Saw => quote! { Saw },
+            LfoShape::Square => quote! { Square },
+            LfoShape::Random => quote! { Random },
+        };
+        let with_sync = self.sync.map(|sync| {
+            let TempoSync {
+                beats,
+                pulses_per_beat,
+            } = sync;
+
+            quote! { .with_sync(synth_engine::modules::lfo::TempoSync {
+                beats: #beats,
+                pulses_per_beat: #pulses_per_beat,
+            }) }
+        });
+
+        Ok(quote! { SynthModule::Lfo(Lfo::new(
+            synth_engine::modules::lfo::LfoInputs {
+                rate: #i0,
+                trigger: #i1,
+                fade: #i2,
+                clock: #i3,
+            },
+            #s0,
+            #s1,
+        )
+        .with_shape(synth_engine::modules::lfo::LfoShape::#shape)
+        .with_unipolar(#unipolar)
+        .with_seed(#seed)
+        #with_sync) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (RATE_CONTROL, &self.inputs[0]),
+            (TRIGGER_INPUT, &self.inputs[1]),
+            (FADE_CONTROL, &self.inputs[2]),
+            (CLOCK_INPUT, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![TRIGGER_INPUT, CLOCK_INPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn note_values() {
+        assert_eq!(note_beats("1/4").unwrap(), 1.);
+        assert_eq!(note_beats("2").unwrap(), 8.);
+        assert_eq!(note_beats(" 3/16. ").unwrap(), 1.125);
+        assert!((note_beats("1/8t").unwrap() - 1. / 3.).abs() < 1e-6);
+        assert!(note_beats("0/4").is_err());
+        assert!(note_beats("1/x").is_err());
+    }
+}
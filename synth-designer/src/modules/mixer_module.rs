@@ -0,0 +1,159 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+
+const MODULE_TYPE: &str = "mixer";
+const MODULE_NAME: &str = "name";
+const INPUT: &str = "input";
+const GAIN: &str = "gain";
+const SIGNAL_OUTPUT: &str = "signal_output";
+
+const STATE_SIZE: usize = 1;
+
+pub struct MixerModuleSpec {
+    name: String,
+    /// The inputs, each with the gain given after it, if any
+    inputs: Vec<(Expr, Option<Expr>)>,
+    state: [usize; STATE_SIZE],
+}
+
+impl MixerModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut inputs: Vec<(Expr, Option<Expr>)> = Vec::new();
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                INPUT => inputs.push((Expr::parse(&v)?, None)),
+                GAIN => match inputs.last_mut() {
+                    Some((_, gain @ None)) => *gain = Some(Expr::parse(&v)?),
+                    _ => {
+                        return Err(ModuleError::MalformedInputSpec(format!(
+                            "gain {} doesn't follow an input without a gain",
+                            v
+                        )))
+                    }
+                },
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            inputs,
+            state: [0; STATE_SIZE],
+        })
+    }
+
+    /// The sum of the inputs times their gains, as one expression.
+    fn mix(&self) -> Expr {
+        self.inputs
+            .iter()
+            .map(|(input, gain)| match gain {
+                Some(gain) => Expr::BinOp(
+                    BinaryOperator::Multiply,
+                    Box::new(input.clone()),
+                    Box::new(gain.clone()),
+                ),
+                None => input.clone(),
+            })
+            .reduce(|sum, term| Expr::BinOp(BinaryOperator::Add, Box::new(sum), Box::new(term)))
+            .unwrap_or(Expr::zero())
+    }
+}
+
+impl ModuleSpec for MixerModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let mixer = Mixer::new(self.mix().compile(synth_spec)?, self.state[0]);
+
+        Ok(Box::new(mixer))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let s0 = self.state[0];
+        let i0 = gen_program(&self.mix().compile(synth_spec).unwrap());
+
+        Ok(quote! { SynthModule::Mixer(Mixer::new(#i0, #s0)) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        self.inputs
+            .iter()
+            .flat_map(|(input, gain)| {
+                std::iter::once((INPUT, input)).chain(gain.iter().map(|gain| (GAIN, gain)))
+            })
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        vec![INPUT, GAIN]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ini::Ini;
+
+    fn mixer(source: &str) -> Result<MixerModuleSpec, ModuleError> {
+        let ini = Ini::load_from_str(source).unwrap();
+
+        MixerModuleSpec::from_ini_properties(ini.section(Some(MODULE_TYPE)).unwrap().clone())
+    }
+
+    #[test]
+    fn gains_follow_inputs() {
+        let spec = mixer("[mixer]\ninput=a.out\ninput=b.out\ngain=0.5\ninput=c.out").unwrap();
+        assert_eq!(
+            spec.mix(),
+            Expr::parse("a.out + b.out * 0.5 + c.out").unwrap()
+        );
+
+        let spec = mixer("[mixer]\ninput=a.out\ngain=2.0\ninput=b.out\ngain=0.5").unwrap();
+        assert_eq!(
+            spec.mix(),
+            Expr::parse("a.out * 2.0 + b.out * 0.5").unwrap()
+        );
+
+        assert!(mixer("[mixer]\ngain=0.5\ninput=a.out").is_err());
+        assert!(mixer("[mixer]\ninput=a.out\ngain=0.5\ngain=2.0").is_err());
+    }
+}
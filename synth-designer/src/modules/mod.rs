@@ -1,42 +1,74 @@
+mod adsr_module;
 mod amp_module;
 mod bowed_osc;
 mod contour_module;
 mod control_module;
 mod delay_line;
+mod differentiator_module;
+mod diode_ladder_module;
+mod distortion;
 mod filter_12db_module;
 mod filter_24db;
 mod filter_6db;
 mod folder_module;
+mod integrator_module;
+mod lfo_module;
+mod mixer_module;
 mod mono_keys_module;
 mod mono_out_module;
 mod noise;
+mod ode_module;
+pub mod poly_keys_module;
 mod quad;
+mod quantizer_module;
+mod sallen_key_module;
+mod sample_hold_module;
+mod sem_filter_module;
+mod slew_module;
+mod state_space_module;
 mod vosim;
 mod wavetable;
 
+pub use adsr_module::AdsrModuleSpec;
 pub use amp_module::AmpModuleSpec;
 pub use bowed_osc::BowedOscillatorModuleSpec;
 pub use contour_module::ContourModuleSpec;
 pub use control_module::ControlModuleSpec;
 pub use delay_line::DelayLineModuleSpec;
+pub use differentiator_module::DifferentiatorModuleSpec;
+pub use diode_ladder_module::DiodeLadderModuleSpec;
+pub use distortion::{codegen_distortion, parse_distortion, DISTORTION_FIELD};
 pub use filter_12db_module::Filter12dbModuleSpec;
 pub use filter_24db::Filter24dbModuleSpec;
 pub use filter_6db::Filter6dbModuleSpec;
 pub use folder_module::FolderModuleSpec;
+pub use integrator_module::IntegratorModuleSpec;
+pub use lfo_module::LfoModuleSpec;
+pub use mixer_module::MixerModuleSpec;
 pub use mono_keys_module::MonoKeysModuleSpec;
 pub use mono_out_module::MonoOutputModuleSpec;
 pub use noise::NoiseGeneratorModuleSpec;
+pub use ode_module::OdeModuleSpec;
+pub use poly_keys_module::PolyKeysModuleSpec;
 pub use quad::QuadOscillatorModuleSpec;
+pub use quantizer_module::QuantizerModuleSpec;
+pub use sallen_key_module::SallenKeyModuleSpec;
+pub use sample_hold_module::SampleHoldModuleSpec;
+pub use sem_filter_module::SemFilterModuleSpec;
+pub use slew_module::SlewModuleSpec;
+pub use state_space_module::StateSpaceModuleSpec;
 pub use vosim::VosimOscillatorModuleSpec;
 pub use wavetable::WavetableOscillatorModuleSpec;
 
-use crate::input_expr::ExprError;
+use crate::input_expr::{Expr, ExprError};
 use crate::state_allocator::StateAllocator;
 use crate::synth_spec::SynthSpec;
 use proc_macro2::TokenStream;
 use std::num::ParseFloatError;
 use std::num::ParseIntError;
+use synth_engine::modules::poly_keys::MpeConfig;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -55,19 +87,87 @@ pub enum ModuleError {
     ParseFloatError(#[from] ParseFloatError),
     #[error("Error parsing integer: {0}")]
     ParseIntError(#[from] ParseIntError),
-    #[error("Error parsing INI file: {0}")]
+    #[error("Error reading WAV file: {0}")]
     HoundError(#[from] hound::Error),
+    #[error("Error loading wavetable {0}: {1}")]
+    WavetableFile(String, String),
+    #[error("Error loading scale {0}: {1}")]
+    ScaleFile(String, String),
+    #[error("Error writing {0}: {1}")]
+    DataFile(String, String),
+    #[error("Invalid wavetable definition: {0}")]
+    InvalidWavetable(String),
     #[error("Error in arithmetic expression: {0}")]
     ExprError(#[from] ExprError),
     #[error("Module with name {0} already in spec")]
     ModuleNameClash(String),
+    #[error("Delay-free loop through {modules} doesn't converge, residual {1}", modules = .0.join(", "))]
+    UnresolvedLoop(Vec<String>, Float),
+    #[error("Delay-free loop through {modules} has too many outputs for Newton's method", modules = .0.join(", "))]
+    LargeLoop(Vec<String>),
+    #[error("Delay-free loop through {modules} can't be generated without allocation", modules = .0.join(", "))]
+    StaticLoop(Vec<String>),
+    #[error("Module {0} has no fixed-point version")]
+    NotFixedPoint(String),
+    #[error("Delay-free loop through {modules} can't be generated in fixed point", modules = .0.join(", "))]
+    FixedPointLoop(Vec<String>),
+}
+
+/// Resources that at most one module in a synth should claim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    MidiControl(usize),
+    AudioOutput(usize),
 }
 
 pub trait ModuleSpec {
     fn allocate_state(&mut self, alloc: &mut StateAllocator);
     fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError>;
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream;
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError>;
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError>;
     fn get_name(&self) -> &str;
     fn state_size(&self) -> usize;
+    /// The input expressions of the module, by field name.
+    fn inputs(&self) -> Vec<(&'static str, &Expr)>;
+    /// The field names that other modules can refer to in their inputs.
+    fn outputs(&self) -> Vec<&str>;
+    /// The outputs that are set as absolute values on every update instead of
+    /// being integrated. Modules that read them should be evaluated after this
+    /// module.
+    fn absolute_outputs(&self) -> Vec<&str> {
+        vec![]
+    }
+    /// The inputs that are only read when a time step is finalized. They don't
+    /// take part in delay-free loops.
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![]
+    }
+    /// The size of the sample buffer the module needs, if any. In the static
+    /// code generation mode the buffer is a field of the generated `Buffers`
+    /// struct, named by `synth_spec::buffer_ident`.
+    fn buffer_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn resources(&self) -> Vec<Resource> {
+        vec![]
+    }
+
+    /// Whether the engine module implements `FixedModule`, so that the module
+    /// can be generated in the `Fixed` code generation mode.
+    fn fixed_point(&self) -> bool {
+        false
+    }
+
+    /// The fields that may be given more than once, each value adding to the
+    /// ones before it.
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// The MPE zone the module takes notes from, if any. Pitch bends on the
+    /// member channels of the zone are scaled by the module itself.
+    fn mpe(&self) -> Option<MpeConfig> {
+        None
+    }
 }
@@ -1,8 +1,10 @@
+use crate::input_expr::Expr;
 use crate::modules::*;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
+use synth_engine::modules::mono_keys::{GlideMode, KeyOutputs, NotePriority};
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
 
@@ -13,29 +15,98 @@ const GATE_OUTPUT: &str = "gate";
 const PRESSURE_OUTPUT: &str = "aftertouch";
 const VELOCITY_OUTPUT: &str = "velocity";
 const PITCHWHEEL_OUTPUT: &str = "pitchwheel";
-const STATE_SIZE: usize = 5;
+const TRIGGER_OUTPUT: &str = "trigger";
+const SUSTAIN_OUTPUT: &str = "sustain";
+const PRIORITY_FIELD: &str = "priority";
+const MODE_FIELD: &str = "mode";
+const GLIDE_TIME_INPUT: &str = "glide_time";
+const GLIDE_MODE_FIELD: &str = "glide_mode";
+const GLIDE_SHAPE_FIELD: &str = "glide_shape";
+const GLIDE_WHEN_FIELD: &str = "glide_when";
+const STATE_SIZE: usize = 7;
 
 pub struct MonoKeysModuleSpec {
     name: String,
+    priority: NotePriority,
+    legato: bool,
+    glide_time: Expr,
+    glide_mode: GlideMode,
     state: [usize; STATE_SIZE],
 }
 
 impl MonoKeysModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
+        let mut priority = NotePriority::Last;
+        let mut legato = true;
+        let mut glide_time = Expr::zero();
+        let mut glide_mode = GlideMode::default();
 
         for (k, v) in props {
             match k.as_str() {
                 MODULE_NAME => name = v.to_string(),
+                PRIORITY_FIELD => {
+                    priority = match v.trim() {
+                        "last" => NotePriority::Last,
+                        "low" => NotePriority::Low,
+                        "high" => NotePriority::High,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                MODE_FIELD => {
+                    legato = match v.trim() {
+                        "legato" => true,
+                        "retrigger" => false,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                GLIDE_TIME_INPUT => glide_time = Expr::parse(&v)?,
+                GLIDE_MODE_FIELD => {
+                    glide_mode.constant_rate = match v.trim() {
+                        "time" => false,
+                        "rate" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                GLIDE_SHAPE_FIELD => {
+                    glide_mode.linear = match v.trim() {
+                        "exponential" => false,
+                        "linear" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                GLIDE_WHEN_FIELD => {
+                    glide_mode.legato_only = match v.trim() {
+                        "always" => false,
+                        "legato" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
 
         Ok(Self {
             name,
+            priority,
+            legato,
+            glide_time,
+            glide_mode,
             state: [0; STATE_SIZE],
         })
     }
+
+    fn key_outputs(&self) -> KeyOutputs {
+        KeyOutputs {
+            pitch: self.state[0],
+            gate: self.state[1],
+            pressure: self.state[2],
+            velocity: self.state[3],
+            pitchwheel: self.state[4],
+            trigger: self.state[5],
+            sustain: self.state[6],
+        }
+    }
 }
 
 impl ModuleSpec for MonoKeysModuleSpec {
@@ -43,26 +114,58 @@ impl ModuleSpec for MonoKeysModuleSpec {
         alloc.allocate(&mut self.state);
     }
 
-    fn create_module(&self, _synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
-        let midi_mono = MonoKeys::new(
-            self.state[0],
-            self.state[1],
-            self.state[2],
-            self.state[3],
-            self.state[4],
-        );
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let midi_mono = MonoKeys::new(self.key_outputs(), self.glide_time.compile(synth_spec)?)
+            .with_priority(self.priority)
+            .with_legato(self.legato)
+            .with_glide(self.glide_mode);
 
         Ok(Box::new(midi_mono))
     }
 
-    fn codegen(&self, _synth_spec: &SynthSpec) -> TokenStream {
-        let s0 = self.state[0];
-        let s1 = self.state[1];
-        let s2 = self.state[2];
-        let s3 = self.state[3];
-        let s4 = self.state[4];
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let i0 = synth_spec.codegen_program(&self.glide_time.compile(synth_spec).unwrap());
+        let KeyOutputs {
+            pitch,
+            gate,
+            pressure,
+            velocity,
+            pitchwheel,
+            trigger,
+            sustain,
+        } = self.key_outputs();
+        let legato = self.legato;
+        let priority = match self.priority {
+            NotePriority::Last => quote! { Last },
+            NotePriority::Low => quote! { Low },
+            NotePriority::High => quote! { High },
+        };
+
+        let GlideMode {
+            constant_rate,
+            linear,
+            legato_only,
+        } = self.glide_mode;
 
-        quote! { SynthModule::MonoKeys(MonoKeys::new(#s0, #s1, #s2, #s3, #s4)) }
+        Ok(quote! { SynthModule::MonoKeys(MonoKeys::new(
+            synth_engine::modules::mono_keys::KeyOutputs {
+                pitch: #pitch,
+                gate: #gate,
+                pressure: #pressure,
+                velocity: #velocity,
+                pitchwheel: #pitchwheel,
+                trigger: #trigger,
+                sustain: #sustain,
+            },
+            #i0,
+        )
+        .with_priority(synth_engine::modules::mono_keys::NotePriority::#priority)
+        .with_legato(#legato)
+        .with_glide(synth_engine::modules::mono_keys::GlideMode {
+            constant_rate: #constant_rate,
+            linear: #linear,
+            legato_only: #legato_only,
+        })) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -72,6 +175,8 @@ impl ModuleSpec for MonoKeysModuleSpec {
             PRESSURE_OUTPUT => Ok(self.state[2]),
             VELOCITY_OUTPUT => Ok(self.state[3]),
             PITCHWHEEL_OUTPUT => Ok(self.state[4]),
+            TRIGGER_OUTPUT => Ok(self.state[5]),
+            SUSTAIN_OUTPUT => Ok(self.state[6]),
             _ => Err(ModuleError::MissingStateName(
                 MODULE_TYPE.to_string(),
                 self.name.clone(),
@@ -87,4 +192,32 @@ impl ModuleSpec for MonoKeysModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![(GLIDE_TIME_INPUT, &self.glide_time)]
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![GLIDE_TIME_INPUT]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![
+            PITCH_OUTPUT,
+            GATE_OUTPUT,
+            PRESSURE_OUTPUT,
+            VELOCITY_OUTPUT,
+            PITCHWHEEL_OUTPUT,
+            TRIGGER_OUTPUT,
+            SUSTAIN_OUTPUT,
+        ]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![PITCH_OUTPUT, GATE_OUTPUT, VELOCITY_OUTPUT, TRIGGER_OUTPUT]
+    }
 }
@@ -1,6 +1,5 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
@@ -53,11 +52,11 @@ impl ModuleSpec for MonoOutputModuleSpec {
         Ok(Box::new(mono_output))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let oi = self.output_index;
-        let i = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i = synth_spec.codegen_program(&self.inputs[0].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::Output(MonoOutput::new(#oi, #i)) }
+        Ok(quote! { SynthModule::Output(MonoOutput::new(#oi, #i)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -78,4 +77,24 @@ impl ModuleSpec for MonoOutputModuleSpec {
     fn state_size(&self) -> usize {
         0
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![(SIGNAL_INPUT, &self.inputs[0])]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_INPUT]
+    }
+
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::AudioOutput(self.output_index)]
+    }
 }
@@ -1,3 +1,4 @@
+use crate::input_expr::Expr;
 use crate::modules::*;
 use crate::synth_spec::SynthSpec;
 use ini::Properties;
@@ -73,13 +74,13 @@ impl ModuleSpec for NoiseGeneratorModuleSpec {
         Ok(Box::new(noise))
     }
 
-    fn codegen(&self, _synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, _synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let a = self.a;
         let b = self.b;
         let s = self.seed;
         let s0 = self.state[0];
 
-        quote! { SynthModule::Noise(NoiseGenerator::new(#a, #b, #s, #s0)) }
+        Ok(quote! { SynthModule::Noise(NoiseGenerator::new(#a, #b, #s, #s0)) })
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -100,4 +101,16 @@ impl ModuleSpec for NoiseGeneratorModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
 }
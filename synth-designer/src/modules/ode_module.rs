@@ -0,0 +1,216 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::ode::{Equation, MAX_EQUATIONS};
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "ode";
+const MODULE_NAME: &str = "name";
+const STATE_FIELD: &str = "state";
+const DERIVATIVE_INPUT: &str = "derivative";
+const INITIAL_FIELD: &str = "initial";
+const TIME_OUTPUT: &str = "time";
+const TIME_STEP_OUTPUT: &str = "dt";
+
+pub struct OdeModuleSpec {
+    name: String,
+    /// The names of the states, in the order of the equations
+    states: Vec<String>,
+    /// The derivatives of the states, with references to the states, the time
+    /// and the time step as outputs of this module
+    derivatives: Vec<Expr>,
+    initial: Vec<Float>,
+    /// The states of the equations, then the time and the time step
+    state: Vec<usize>,
+}
+
+impl OdeModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut states: Vec<String> = Vec::new();
+        let mut derivatives: Vec<Expr> = Vec::new();
+        let mut initial: Vec<Float> = Vec::new();
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                STATE_FIELD => {
+                    let state = v.trim();
+                    let valid = matches!(Expr::parse(state), Ok(Expr::Variable(_)))
+                        && ![TIME_OUTPUT, TIME_STEP_OUTPUT].contains(&state)
+                        && !states.iter().any(|s| s == state);
+
+                    if !valid {
+                        return Err(ModuleError::MalformedInputSpec(v));
+                    }
+
+                    states.push(state.to_string());
+                }
+                DERIVATIVE_INPUT => derivatives.push(Expr::parse(&v)?),
+                INITIAL_FIELD => initial.push(v.trim().parse()?),
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        if states.len() > MAX_EQUATIONS
+            || derivatives.len() != states.len()
+            || initial.len() > states.len()
+        {
+            return Err(ModuleError::MalformedInputSpec(format!(
+                "{} states, {} derivatives and {} initial values",
+                states.len(),
+                derivatives.len(),
+                initial.len()
+            )));
+        }
+
+        // The states, the time and the time step are referred to by their bare
+        // names in the derivatives
+        let derivatives = derivatives
+            .iter()
+            .map(|expr| {
+                expr.map_variables(&|variable| {
+                    (states.iter().any(|s| s == variable)
+                        || [TIME_OUTPUT, TIME_STEP_OUTPUT].contains(&variable))
+                    .then(|| Expr::OutputState(name.clone(), variable.to_string()))
+                })
+            })
+            .collect();
+
+        initial.resize(states.len(), 0.);
+
+        Ok(Self {
+            name,
+            state: vec![0; states.len() + 2],
+            states,
+            derivatives,
+            initial,
+        })
+    }
+
+    fn time_indices(&self) -> (usize, usize) {
+        let n = self.states.len();
+
+        (self.state[n], self.state[n + 1])
+    }
+}
+
+impl ModuleSpec for OdeModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let mut equations = Vec::new();
+
+        for (i, derivative) in self.derivatives.iter().enumerate() {
+            equations.push(Equation {
+                state_index: self.state[i],
+                derivative: derivative.compile(synth_spec)?,
+                initial: self.initial[i],
+            });
+        }
+
+        let (time_index, time_step_index) = self.time_indices();
+
+        Ok(Box::new(Ode::new(equations, time_index, time_step_index)))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let equations = self.derivatives.iter().enumerate().map(|(i, derivative)| {
+            let state_index = self.state[i];
+            let derivative = gen_program(&derivative.compile(synth_spec).unwrap());
+            let initial = self.initial[i];
+
+            quote! { synth_engine::modules::ode::Equation {
+                state_index: #state_index,
+                derivative: #derivative,
+                initial: #initial,
+            } }
+        });
+        let (time_index, time_step_index) = self.time_indices();
+
+        Ok(quote! { SynthModule::Ode(Ode::new(
+            [#(#equations),*],
+            #time_index,
+            #time_step_index,
+        )) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match self.outputs().iter().position(|o| *o == state_field) {
+            Some(index) => Ok(self.state[index]),
+            None => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        self.derivatives
+            .iter()
+            .map(|expr| (DERIVATIVE_INPUT, expr))
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<&str> {
+        let mut outputs: Vec<&str> = self.states.iter().map(|s| s.as_str()).collect();
+
+        outputs.push(TIME_OUTPUT);
+        outputs.push(TIME_STEP_OUTPUT);
+        outputs
+    }
+
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        vec![STATE_FIELD, DERIVATIVE_INPUT, INITIAL_FIELD]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn states_and_derivatives() {
+        let spec = SynthSpec::from_ini_str(
+            "
+[ode]
+name=osc
+state=x
+state=y
+derivative=y
+derivative=0. - x * cos(time)
+initial=1
+",
+        )
+        .unwrap();
+        let ode = spec.module_specs().next().unwrap();
+
+        assert_eq!(ode.outputs(), vec!["x", "y", "time", "dt"]);
+        assert_eq!(
+            ode.inputs()[1].1.output_refs(),
+            vec![("osc", "x"), ("osc", "time")]
+        );
+
+        let reserved = SynthSpec::from_ini_str("[ode]\nstate=time\nderivative=1.");
+        assert!(reserved.is_err());
+        let unpaired = SynthSpec::from_ini_str("[ode]\nstate=x\nstate=y\nderivative=1.");
+        assert!(unpaired.is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use crate::input_expr::Expr;
+use crate::modules::*;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::sync::LazyLock;
+use synth_engine::modules::poly_keys::{
+    MpeConfig, MpeZone, VoiceOutputs, VoiceStealing, MAX_VOICES,
+};
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::stack_program::Instr;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "poly_keys";
+const MODULE_NAME: &str = "name";
+const VOICES_FIELD: &str = "voices";
+const STEALING_FIELD: &str = "stealing";
+const MPE_FIELD: &str = "mpe";
+const MEMBER_CHANNELS_FIELD: &str = "member_channels";
+const BEND_RANGE_FIELD: &str = "bend_range";
+const LEVEL_INPUT: &str = "level";
+const PITCHWHEEL_OUTPUT: &str = "pitchwheel";
+const SUSTAIN_OUTPUT: &str = "sustain";
+const DEFAULT_VOICES: usize = 8;
+const DEFAULT_MEMBER_CHANNELS: u8 = 15;
+/// The default per-note pitch bend range of MPE, in semitones
+const DEFAULT_BEND_RANGE: Float = 48.;
+
+/// The outputs of every voice, named `<field>_<voice>`, like `gate_0`.
+pub const VOICE_FIELDS: [&str; 5] = ["pitch", "gate", "velocity", "aftertouch", "timbre"];
+const ABSOLUTE_VOICE_FIELDS: [&str; 3] = ["pitch", "gate", "velocity"];
+
+/// The output names of all voices, made once so that they are `&'static str`
+/// like the output names of other modules.
+static VOICE_OUTPUTS: LazyLock<Vec<String>> = LazyLock::new(|| {
+    (0..MAX_VOICES)
+        .flat_map(|voice| VOICE_FIELDS.iter().map(move |f| voice_output(f, voice)))
+        .collect()
+});
+
+pub fn voice_output(field: &str, voice: usize) -> String {
+    format!("{}_{}", field, voice)
+}
+
+/// Whether the output name is an output of a single voice, like `gate_0`.
+pub fn is_voice_output(name: &str) -> bool {
+    name.rsplit_once('_').is_some_and(|(field, voice)| {
+        VOICE_FIELDS.contains(&field) && voice.parse::<usize>().is_ok()
+    })
+}
+
+/// The number of voices of a `poly_keys` section.
+pub fn voice_count(props: &Properties) -> Result<usize, ModuleError> {
+    let voices = match props.get(VOICES_FIELD) {
+        Some(v) => v.trim().parse::<usize>()?,
+        None => DEFAULT_VOICES,
+    };
+
+    if (1..=MAX_VOICES).contains(&voices) {
+        Ok(voices)
+    } else {
+        Err(ModuleError::MalformedInputSpec(format!(
+            "{} voices, poly keys have 1 to {}",
+            voices, MAX_VOICES
+        )))
+    }
+}
+
+pub struct PolyKeysModuleSpec {
+    name: String,
+    voices: usize,
+    stealing: VoiceStealing,
+    mpe: Option<MpeConfig>,
+    levels: Vec<Expr>,
+    /// Voice outputs in the order of `VOICE_OUTPUTS`, then the pitch wheel and
+    /// the sustain pedal
+    state: Vec<usize>,
+}
+
+impl PolyKeysModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut stealing = VoiceStealing::Oldest;
+        let mut levels: Vec<Expr> = Vec::new();
+        let mut zone: Option<MpeZone> = None;
+        let mut member_channels = DEFAULT_MEMBER_CHANNELS;
+        let mut bend_range = DEFAULT_BEND_RANGE;
+        let voices = voice_count(&props)?;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                VOICES_FIELD => {}
+                STEALING_FIELD => {
+                    stealing = match v.trim() {
+                        "oldest" => VoiceStealing::Oldest,
+                        "quietest" => VoiceStealing::Quietest,
+                        "same_note" => VoiceStealing::SameNote,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                MPE_FIELD => {
+                    zone = match v.trim() {
+                        "lower" => Some(MpeZone::Lower),
+                        "upper" => Some(MpeZone::Upper),
+                        "off" => None,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                MEMBER_CHANNELS_FIELD => {
+                    member_channels = v.trim().parse()?;
+
+                    if !(1..=15).contains(&member_channels) {
+                        return Err(ModuleError::MalformedInputSpec(format!(
+                            "{} member channels, MPE zones have 1 to 15",
+                            member_channels
+                        )));
+                    }
+                }
+                BEND_RANGE_FIELD => bend_range = v.trim().parse()?,
+                LEVEL_INPUT => levels.push(Expr::parse(&v)?),
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        if !levels.is_empty() && levels.len() != voices {
+            return Err(ModuleError::MalformedInputSpec(format!(
+                "{} levels for {} voices",
+                levels.len(),
+                voices
+            )));
+        }
+
+        Ok(Self {
+            name,
+            voices,
+            stealing,
+            mpe: zone.map(|zone| MpeConfig {
+                zone,
+                member_channels,
+                bend_range: bend_range / 12.,
+            }),
+            levels,
+            state: vec![0; voices * VOICE_FIELDS.len() + 2],
+        })
+    }
+
+    /// The outputs of the voices, with the state indices of the levels. A level
+    /// has to be a plain module output.
+    fn voice_outputs(&self, synth_spec: &SynthSpec) -> Result<Vec<VoiceOutputs>, ModuleError> {
+        (0..self.voices)
+            .map(|voice| {
+                let level = match self.levels.get(voice) {
+                    Some(expr) => match expr.compile(synth_spec)?.code[..] {
+                        [Instr::State(index)] => Some(index),
+                        _ => return Err(ModuleError::MalformedInputSpec(expr.to_string())),
+                    },
+                    None => None,
+                };
+                let state = &self.state[voice * VOICE_FIELDS.len()..];
+
+                Ok(VoiceOutputs {
+                    pitch: state[0],
+                    gate: state[1],
+                    velocity: state[2],
+                    pressure: state[3],
+                    timbre: state[4],
+                    level,
+                })
+            })
+            .collect()
+    }
+
+    fn pitchwheel_index(&self) -> usize {
+        self.state[self.state.len() - 2]
+    }
+
+    fn sustain_index(&self) -> usize {
+        self.state[self.state.len() - 1]
+    }
+}
+
+impl ModuleSpec for PolyKeysModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let mut poly_keys = PolyKeys::new(
+            &self.voice_outputs(synth_spec)?,
+            self.pitchwheel_index(),
+            self.sustain_index(),
+            self.stealing,
+        );
+
+        if let Some(mpe) = self.mpe {
+            poly_keys = poly_keys.with_mpe(mpe);
+        }
+
+        Ok(Box::new(poly_keys))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let voices: Vec<TokenStream> = self
+            .voice_outputs(synth_spec)
+            .unwrap()
+            .iter()
+            .map(|outputs| {
+                let VoiceOutputs {
+                    pitch,
+                    gate,
+                    velocity,
+                    pressure,
+                    timbre,
+                    level,
+                } = outputs;
+                let level = match level {
+                    Some(level) => quote! { Some(#level) },
+                    None => quote! { None },
+                };
+
+                quote! {
+                    synth_engine::modules::poly_keys::VoiceOutputs {
+                        pitch: #pitch,
+                        gate: #gate,
+                        velocity: #velocity,
+                        pressure: #pressure,
+                        timbre: #timbre,
+                        level: #level,
+                    }
+                }
+            })
+            .collect();
+        let pw = self.pitchwheel_index();
+        let sustain = self.sustain_index();
+        let stealing = match self.stealing {
+            VoiceStealing::Oldest => quote! { Oldest },
+            VoiceStealing::Quietest => quote! { Quietest },
+            VoiceStealing::SameNote => quote! { SameNote },
+        };
+
+        let with_mpe = self.mpe.map(|mpe| {
+            let zone = match mpe.zone {
+                MpeZone::Lower => quote! { Lower },
+                MpeZone::Upper => quote! { Upper },
+            };
+            let member_channels = mpe.member_channels;
+            let bend_range = mpe.bend_range;
+
+            quote! { .with_mpe(synth_engine::modules::poly_keys::MpeConfig {
+                zone: synth_engine::modules::poly_keys::MpeZone::#zone,
+                member_channels: #member_channels,
+                bend_range: #bend_range,
+            }) }
+        });
+
+        Ok(quote! { SynthModule::PolyKeys(PolyKeys::new(
+            &[#(#voices),*],
+            #pw,
+            #sustain,
+            synth_engine::modules::poly_keys::VoiceStealing::#stealing,
+        )#with_mpe) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match self.outputs().iter().position(|o| *o == state_field) {
+            Some(index) => Ok(self.state[index]),
+            None => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        self.levels.iter().map(|expr| (LEVEL_INPUT, expr)).collect()
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        let mut outputs: Vec<&'static str> = VOICE_OUTPUTS[..self.voices * VOICE_FIELDS.len()]
+            .iter()
+            .map(|o| o.as_str())
+            .collect();
+
+        outputs.push(PITCHWHEEL_OUTPUT);
+        outputs.push(SUSTAIN_OUTPUT);
+        outputs
+    }
+
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        vec![LEVEL_INPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&str> {
+        self.outputs()
+            .into_iter()
+            .filter(|o| {
+                ABSOLUTE_VOICE_FIELDS
+                    .iter()
+                    .any(|f| o.rsplit_once('_').map(|(field, _)| field) == Some(*f))
+            })
+            .collect()
+    }
+
+    fn delayed_inputs(&self) -> Vec<&'static str> {
+        vec![LEVEL_INPUT]
+    }
+
+    fn mpe(&self) -> Option<MpeConfig> {
+        self.mpe
+    }
+}
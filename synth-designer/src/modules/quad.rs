@@ -1,6 +1,5 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
 use crate::synth_spec::SynthSpec;
 use crate::DEFAULT_FREQUENCY_ZERO;
 use ini::Properties;
@@ -8,8 +7,9 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
-const MODULE_TYPE: &str = "bowed_oscillator";
+const MODULE_TYPE: &str = "quadrature_oscillator";
 const MODULE_NAME: &str = "name";
 const FREQUENCY_CONTROL: &str = "frequency_control";
 const LINEAR_FREQUENCY_CONTROL: &str = "linear_frequency_control";
@@ -23,13 +23,13 @@ pub struct QuadOscillatorModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
+    f0: Float,
 }
 
 impl QuadOscillatorModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
-        let mut f0: f32 = DEFAULT_FREQUENCY_ZERO;
+        let mut f0: Float = DEFAULT_FREQUENCY_ZERO;
         let mut fc: Expr = Expr::zero();
         let mut lc: Expr = Expr::zero();
 
@@ -38,7 +38,7 @@ impl QuadOscillatorModuleSpec {
                 MODULE_NAME => name = v.to_string(),
                 FREQUENCY_CONTROL => fc = Expr::parse(&v)?,
                 LINEAR_FREQUENCY_CONTROL => lc = Expr::parse(&v)?,
-                FREQ0 => f0 = v.parse::<f32>()?,
+                FREQ0 => f0 = v.parse::<Float>()?,
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
@@ -69,14 +69,16 @@ impl ModuleSpec for QuadOscillatorModuleSpec {
         Ok(Box::new(osc))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
         let s1 = self.state[1];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i0 = synth_spec.codegen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = synth_spec.codegen_program(&self.inputs[1].compile(&synth_spec).unwrap());
 
-        quote! { SynthModule::QuadOscillator(QuadratureOscillator::new(#f0, #s0, #s1, #i0, #i1)) }
+        Ok(
+            quote! { SynthModule::QuadOscillator(QuadratureOscillator::new(#f0, #s0, #s1, #i0, #i1)) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -98,4 +100,19 @@ impl ModuleSpec for QuadOscillatorModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (FREQUENCY_CONTROL, &self.inputs[0]),
+            (LINEAR_FREQUENCY_CONTROL, &self.inputs[1]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_1_OUTPUT, SIGNAL_2_OUTPUT]
+    }
+
+    fn fixed_point(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,187 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use scale::Scale;
+use synth_engine::modules::quantizer::MAX_PITCHES;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "quantizer";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const SIGNAL_OUTPUT: &str = "signal_output";
+const SCALE_FIELD: &str = "scale";
+const BASE_PITCH_FIELD: &str = "base_pitch";
+const NOTES_FIELD: &str = "notes";
+
+const STATE_SIZE: usize = 1;
+
+pub struct QuantizerModuleSpec {
+    name: String,
+    signal_input: Expr,
+    /// The pitches to snap to, in rising order
+    pitches: Vec<Float>,
+    state: [usize; STATE_SIZE],
+}
+
+/// Parse a list of notes of the period of the scale, like `0, 2, 4, 5, 7, 9, 11`
+/// for the octave of equal temperament.
+fn parse_notes(v: &str, notes_per_period: usize) -> Result<Vec<usize>, ModuleError> {
+    v.split(',')
+        .map(|note| {
+            let note = note.trim().parse::<usize>()?;
+
+            if note < notes_per_period {
+                Ok(note)
+            } else {
+                Err(ModuleError::MalformedInputSpec(v.to_string()))
+            }
+        })
+        .collect()
+}
+
+impl QuantizerModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut scale_file: Option<String> = None;
+        let mut base_pitch: usize = 0;
+        let mut notes: Option<String> = None;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                SCALE_FIELD => scale_file = Some(v.trim().to_string()),
+                BASE_PITCH_FIELD => base_pitch = v.trim().parse()?,
+                NOTES_FIELD => notes = Some(v),
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        let scale = match scale_file {
+            Some(filename) => Scale::from_file(&filename, base_pitch)
+                .map_err(|err| ModuleError::ScaleFile(filename, err.to_string()))?,
+            None => Scale::equal_temperament(),
+        };
+        let period = scale.notes_per_period();
+        let notes = notes.map(|v| parse_notes(&v, period)).transpose()?;
+
+        // Notes count from the base pitch
+        let mut pitches: Vec<Float> = (0..MAX_PITCHES)
+            .filter(|i| match &notes {
+                Some(notes) => notes.contains(&((i + period - base_pitch % period) % period)),
+                None => true,
+            })
+            .filter_map(|i| scale.pitch_value(i))
+            .map(|p| p as Float)
+            .collect();
+
+        pitches.sort_by(|a, b| a.total_cmp(b));
+
+        Ok(Self {
+            name,
+            signal_input: signal_in,
+            pitches,
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for QuantizerModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let quantizer = Quantizer::new(
+            self.signal_input.compile(synth_spec)?,
+            self.state[0],
+            &self.pitches,
+        );
+
+        Ok(Box::new(quantizer))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let s0 = self.state[0];
+        let i0 = gen_program(&self.signal_input.compile(synth_spec).unwrap());
+        let pitches = &self.pitches;
+
+        Ok(quote! { SynthModule::Quantizer(Quantizer::new(#i0, #s0, &[#(#pitches),*])) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![(SIGNAL_INPUT, &self.signal_input)]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ini::Ini;
+
+    #[test]
+    fn notes_of_scale_period() {
+        let filename = std::env::temp_dir().join(format!("quantizer_{}.scl", std::process::id()));
+        std::fs::write(
+            &filename,
+            "! Five equal steps to the octave\n5 notes\n5\n240.0\n480.0\n720.0\n960.0\n1200.0\n",
+        )
+        .unwrap();
+
+        let quantizer = |notes: &str| {
+            let source = format!("[quantizer]\nscale={}\nnotes={notes}", filename.display());
+            let props = Ini::load_from_str(&source)
+                .unwrap()
+                .section(Some(MODULE_TYPE))
+                .unwrap()
+                .clone();
+
+            QuantizerModuleSpec::from_ini_properties(props)
+        };
+
+        // Two of the five notes of every octave, of 128 pitches
+        let pitches = quantizer("0, 2").unwrap().pitches;
+        assert_eq!(pitches.len(), 52);
+        assert!(pitches
+            .iter()
+            .all(|p| [0., 0.4].iter().any(|f| (p.fract() - f).abs() < 1e-5)));
+
+        assert!(quantizer("0, 7").is_err());
+
+        std::fs::remove_file(filename).unwrap();
+    }
+}
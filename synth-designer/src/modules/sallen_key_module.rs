@@ -0,0 +1,135 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::distortion::DistortionType;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "sallen_key";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const FREQUENCY_ZERO: &str = "frequency_zero";
+const CUTOFF_CONTROL: &str = "cutoff_frequency";
+const LINEAR_CONTROL: &str = "linear_frequency";
+const RESONANCE_CONTROL: &str = "resonance";
+const LOWPASS_OUTPUT: &str = "lowpass_output";
+const INPUT_SIZE: usize = 4;
+const STATE_SIZE: usize = 2;
+
+pub struct SallenKeyModuleSpec {
+    name: String,
+    inputs: [Expr; INPUT_SIZE],
+    state: [usize; STATE_SIZE],
+    f0: Float,
+    distortion: Option<DistortionType>,
+}
+
+impl SallenKeyModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut fc: Expr = Expr::zero();
+        let mut lc: Expr = Expr::zero();
+        let mut rc: Expr = Expr::zero();
+        let mut f0: Float = 1.0;
+        let mut distortion = Some(DistortionType::Tanh);
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                CUTOFF_CONTROL => fc = Expr::parse(&v)?,
+                LINEAR_CONTROL => lc = Expr::parse(&v)?,
+                RESONANCE_CONTROL => rc = Expr::parse(&v)?,
+                FREQUENCY_ZERO => f0 = v.trim().parse()?,
+                DISTORTION_FIELD => distortion = parse_distortion(&v)?,
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            inputs: [signal_in, fc, lc, rc],
+            state: [0; STATE_SIZE],
+            f0,
+            distortion,
+        })
+    }
+}
+
+impl ModuleSpec for SallenKeyModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let inputs = FilterInputs {
+            signal: self.inputs[0].compile(synth_spec)?,
+            cutoff: self.inputs[1].compile(synth_spec)?,
+            linear: self.inputs[2].compile(synth_spec)?,
+            resonance: self.inputs[3].compile(synth_spec)?,
+        };
+        let filter = SallenKey::new(self.f0, inputs, self.state).with_distortion(self.distortion);
+
+        Ok(Box::new(filter))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let [signal, cutoff, linear, resonance] = self
+            .inputs
+            .each_ref()
+            .map(|input| gen_program(&input.compile(synth_spec).unwrap()));
+        let f0 = self.f0;
+        let state = self.state;
+        let distortion = codegen_distortion(&self.distortion);
+
+        Ok(quote! { SynthModule::SallenKey(SallenKey::new(
+            #f0,
+            synth_engine::modules::FilterInputs {
+                signal: #signal,
+                cutoff: #cutoff,
+                linear: #linear,
+                resonance: #resonance,
+            },
+            [#(#state),*],
+        )
+        .with_distortion(#distortion)) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            LOWPASS_OUTPUT => Ok(self.state[1]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (CUTOFF_CONTROL, &self.inputs[1]),
+            (LINEAR_CONTROL, &self.inputs[2]),
+            (RESONANCE_CONTROL, &self.inputs[3]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![LOWPASS_OUTPUT]
+    }
+}
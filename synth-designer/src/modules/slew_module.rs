@@ -0,0 +1,111 @@
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+
+const MODULE_TYPE: &str = "slew";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const RISE_CONTROL: &str = "rise_control";
+const FALL_CONTROL: &str = "fall_control";
+const SIGNAL_OUTPUT: &str = "signal_output";
+
+const STATE_SIZE: usize = 1;
+const INPUT_SIZE: usize = 3;
+
+pub struct SlewModuleSpec {
+    name: String,
+    inputs: [Expr; INPUT_SIZE],
+    state: [usize; STATE_SIZE],
+}
+
+impl SlewModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_in: Expr = Expr::zero();
+        let mut rise_control: Expr = Expr::zero();
+        let mut fall_control: Expr = Expr::zero();
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_in = Expr::parse(&v)?,
+                RISE_CONTROL => rise_control = Expr::parse(&v)?,
+                FALL_CONTROL => fall_control = Expr::parse(&v)?,
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        Ok(Self {
+            name,
+            inputs: [signal_in, rise_control, fall_control],
+            state: [0; STATE_SIZE],
+        })
+    }
+}
+
+impl ModuleSpec for SlewModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let slew = SlewLimiter::new(
+            self.inputs[0].compile(synth_spec)?,
+            self.inputs[1].compile(synth_spec)?,
+            self.inputs[2].compile(synth_spec)?,
+            self.state[0],
+        );
+
+        Ok(Box::new(slew))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let s0 = self.state[0];
+        let i0 = gen_program(&self.inputs[0].compile(synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(synth_spec).unwrap());
+
+        Ok(quote! { SynthModule::Slew(SlewLimiter::new(#i0, #i1, #i2, #s0)) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match state_field {
+            SIGNAL_OUTPUT => Ok(self.state[0]),
+            _ => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (SIGNAL_INPUT, &self.inputs[0]),
+            (RISE_CONTROL, &self.inputs[1]),
+            (FALL_CONTROL, &self.inputs[2]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+}
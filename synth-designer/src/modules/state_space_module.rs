@@ -0,0 +1,400 @@
+use crate::filter_design::{Complex, StateSpaceMatrices, TransferFunction};
+use crate::input_expr::*;
+use crate::modules::*;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::SynthSpec;
+use ini::Properties;
+use proc_macro2::TokenStream;
+use quote::quote;
+use synth_engine::modules::state_space::{LinearSystem, MAX_INPUTS, MAX_ORDER, MAX_OUTPUTS};
+use synth_engine::modules::*;
+use synth_engine::simulator::module::Module;
+use synth_engine::Float;
+
+const MODULE_TYPE: &str = "state_space";
+const MODULE_NAME: &str = "name";
+const SIGNAL_INPUT: &str = "signal_input";
+const CUTOFF_CONTROL: &str = "cutoff_control";
+const A_FIELD: &str = "a";
+const B_FIELD: &str = "b";
+const C_FIELD: &str = "c";
+const D_FIELD: &str = "d";
+const POLE_FIELD: &str = "pole";
+const ZERO_FIELD: &str = "zero";
+const GAIN_FIELD: &str = "gain";
+const DESIGN_FIELD: &str = "design";
+const ORDER_FIELD: &str = "order";
+const RIPPLE_FIELD: &str = "ripple";
+const RESPONSE_FIELD: &str = "response";
+const DEFAULT_RIPPLE: f64 = 1.; // dB
+
+const STATE_OUTPUTS: [&str; MAX_ORDER] = [
+    "state_0", "state_1", "state_2", "state_3", "state_4", "state_5", "state_6", "state_7",
+];
+const SIGNAL_OUTPUTS: [&str; MAX_OUTPUTS] = ["output_0", "output_1", "output_2", "output_3"];
+
+pub struct StateSpaceModuleSpec {
+    name: String,
+    signal_inputs: Vec<Expr>,
+    /// The frequency that scales the system, in rad/s
+    frequency: Expr,
+    matrices: StateSpaceMatrices,
+    /// The states of the system, then its outputs
+    state: Vec<usize>,
+}
+
+/// Parse a row of numbers, like `0.0, 1.0`.
+fn parse_row(v: &str) -> Result<Vec<f64>, ModuleError> {
+    v.split(',').map(|x| Ok(x.trim().parse::<f64>()?)).collect()
+}
+
+/// Parse a root of a polynomial, `re` or `re, im`.
+fn parse_root(v: &str) -> Result<Complex, ModuleError> {
+    match parse_row(v)?[..] {
+        [re] => Ok((re, 0.)),
+        [re, im] => Ok((re, im)),
+        _ => Err(ModuleError::MalformedInputSpec(v.to_string())),
+    }
+}
+
+/// Complex roots with their conjugates.
+fn with_conjugates(roots: &[Complex]) -> Vec<Complex> {
+    roots
+        .iter()
+        .flat_map(|&(re, im)| {
+            if im == 0. {
+                vec![(re, im)]
+            } else {
+                vec![(re, im), (re, -im)]
+            }
+        })
+        .collect()
+}
+
+/// Check that the matrices fit together and in the engine module.
+fn check_sizes(m: &StateSpaceMatrices) -> Result<(), ModuleError> {
+    let n = m.a.len();
+    let inputs = m.b.first().map_or(0, |row| row.len());
+    let rows_of = |rows: &[Vec<f64>], len: usize| rows.iter().all(|row| row.len() == len);
+    let fits = (1..=MAX_ORDER).contains(&n)
+        && inputs <= MAX_INPUTS
+        && m.c.len() <= MAX_OUTPUTS
+        && rows_of(&m.a, n)
+        && m.b.len() == n
+        && rows_of(&m.b, inputs)
+        && rows_of(&m.c, n)
+        && (m.d.is_empty() || (m.d.len() == m.c.len() && rows_of(&m.d, inputs)));
+
+    if fits {
+        Ok(())
+    } else {
+        Err(ModuleError::MalformedInputSpec(format!(
+            "{}x{} A, {}x{} B, {} rows of C and {} rows of D",
+            n,
+            m.a.first().map_or(0, |row| row.len()),
+            m.b.len(),
+            inputs,
+            m.c.len(),
+            m.d.len()
+        )))
+    }
+}
+
+impl StateSpaceModuleSpec {
+    pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
+        let mut name: String = MODULE_TYPE.to_string();
+        let mut signal_inputs: Vec<Expr> = Vec::new();
+        let mut cutoff: Option<Expr> = None;
+        let mut matrices = StateSpaceMatrices {
+            a: Vec::new(),
+            b: Vec::new(),
+            c: Vec::new(),
+            d: Vec::new(),
+        };
+        let mut poles: Vec<Complex> = Vec::new();
+        let mut zeros: Vec<Complex> = Vec::new();
+        let mut gain: f64 = 1.;
+        let mut design: Option<String> = None;
+        let mut order: usize = 2;
+        let mut ripple: f64 = DEFAULT_RIPPLE;
+        let mut highpass = false;
+
+        for (k, v) in props {
+            match k.as_str() {
+                MODULE_NAME => name = v.to_string(),
+                SIGNAL_INPUT => signal_inputs.push(Expr::parse(&v)?),
+                CUTOFF_CONTROL => cutoff = Some(Expr::parse(&v)?),
+                A_FIELD => matrices.a.push(parse_row(&v)?),
+                B_FIELD => matrices.b.push(parse_row(&v)?),
+                C_FIELD => matrices.c.push(parse_row(&v)?),
+                D_FIELD => matrices.d.push(parse_row(&v)?),
+                POLE_FIELD => poles.push(parse_root(&v)?),
+                ZERO_FIELD => zeros.push(parse_root(&v)?),
+                GAIN_FIELD => gain = v.trim().parse()?,
+                DESIGN_FIELD => design = Some(v.trim().to_string()),
+                ORDER_FIELD => order = v.trim().parse()?,
+                RIPPLE_FIELD => ripple = v.trim().parse()?,
+                RESPONSE_FIELD => {
+                    highpass = match v.trim() {
+                        "lowpass" => false,
+                        "highpass" => true,
+                        _ => return Err(ModuleError::MalformedInputSpec(v)),
+                    }
+                }
+                _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
+            }
+        }
+
+        // The system is given by its matrices, by its poles and zeros, or by
+        // the name of a design
+        let transfer_function = match (&design, poles.is_empty()) {
+            (None, true) if zeros.is_empty() => None,
+            (None, true) => {
+                return Err(ModuleError::MalformedInputSpec(format!(
+                    "{} zeros without poles",
+                    zeros.len()
+                )))
+            }
+            (None, false) => {
+                let (poles, zeros) = (with_conjugates(&poles), with_conjugates(&zeros));
+
+                if zeros.len() > poles.len() {
+                    return Err(ModuleError::MalformedInputSpec(format!(
+                        "{} zeros for {} poles",
+                        zeros.len(),
+                        poles.len()
+                    )));
+                }
+
+                Some(TransferFunction::from_poles_zeros(&poles, &zeros, gain))
+            }
+            (Some(design), true) if (1..=MAX_ORDER).contains(&order) => match design.as_str() {
+                "butterworth" => Some(TransferFunction::butterworth(order)),
+                "chebyshev" => Some(TransferFunction::chebyshev(order, ripple)),
+                "bessel" => Some(TransferFunction::bessel(order)),
+                _ => return Err(ModuleError::MalformedInputSpec(design.clone())),
+            },
+            (Some(design), _) => return Err(ModuleError::MalformedInputSpec(design.clone())),
+        };
+
+        if let Some(tf) = transfer_function {
+            if !matrices.a.is_empty() {
+                return Err(ModuleError::MalformedInputSpec(
+                    "both matrices and a transfer function".to_string(),
+                ));
+            }
+
+            let tf = match highpass {
+                true => tf.highpass().ok_or_else(|| {
+                    ModuleError::MalformedInputSpec("highpass with a pole at 0".to_string())
+                })?,
+                false => tf,
+            };
+
+            matrices = tf.state_space();
+        } else if highpass {
+            return Err(ModuleError::MalformedInputSpec(
+                "highpass without a transfer function".to_string(),
+            ));
+        }
+
+        check_sizes(&matrices)?;
+
+        let inputs = matrices.b[0].len();
+
+        if signal_inputs.len() > inputs {
+            return Err(ModuleError::MalformedInputSpec(format!(
+                "{} signal inputs for {} inputs of the system",
+                signal_inputs.len(),
+                inputs
+            )));
+        }
+
+        // Without a cutoff the system runs as it is written, for 1 rad/s
+        let frequency = match cutoff {
+            Some(cutoff) => Expr::BinOp(
+                BinaryOperator::Multiply,
+                Box::new(Expr::constant(2. * std::f64::consts::PI as Float)),
+                Box::new(cutoff),
+            ),
+            None => Expr::constant(1.),
+        };
+
+        Ok(Self {
+            name,
+            signal_inputs,
+            frequency,
+            state: vec![0; matrices.a.len() + matrices.c.len()],
+            matrices,
+        })
+    }
+
+    fn system(&self) -> LinearSystem {
+        let flatten = |rows: &[Vec<f64>]| -> Vec<Float> {
+            rows.iter().flatten().map(|x| *x as Float).collect()
+        };
+        let StateSpaceMatrices { a, b, c, d } = &self.matrices;
+
+        LinearSystem::new(&flatten(a), &flatten(b), &flatten(c), &flatten(d))
+    }
+
+    fn order(&self) -> usize {
+        self.matrices.a.len()
+    }
+}
+
+impl ModuleSpec for StateSpaceModuleSpec {
+    fn allocate_state(&mut self, alloc: &mut StateAllocator) {
+        alloc.allocate(&mut self.state);
+    }
+
+    fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
+        let mut signal_inputs = Vec::new();
+
+        for input in &self.signal_inputs {
+            signal_inputs.push(input.compile(synth_spec)?);
+        }
+
+        let (states, outputs) = self.state.split_at(self.order());
+        let module = StateSpace::new(
+            self.system(),
+            signal_inputs,
+            self.frequency.compile(synth_spec)?,
+            states,
+            outputs,
+        );
+
+        Ok(Box::new(module))
+    }
+
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
+        let flatten = |rows: &[Vec<f64>]| -> Vec<Float> {
+            rows.iter().flatten().map(|x| *x as Float).collect()
+        };
+        let StateSpaceMatrices { a, b, c, d } = &self.matrices;
+        let (a, b, c, d) = (flatten(a), flatten(b), flatten(c), flatten(d));
+        let signal_inputs = self
+            .signal_inputs
+            .iter()
+            .map(|input| gen_program(&input.compile(synth_spec).unwrap()));
+        let frequency = gen_program(&self.frequency.compile(synth_spec).unwrap());
+        let (states, outputs) = self.state.split_at(self.order());
+
+        Ok(quote! { SynthModule::StateSpace(StateSpace::new(
+            synth_engine::modules::state_space::LinearSystem::new(
+                &[#(#a),*],
+                &[#(#b),*],
+                &[#(#c),*],
+                &[#(#d),*],
+            ),
+            [#(#signal_inputs),*],
+            #frequency,
+            &[#(#states),*],
+            &[#(#outputs),*],
+        )) })
+    }
+
+    fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
+        match self.outputs().iter().position(|o| *o == state_field) {
+            Some(index) => Ok(self.state[index]),
+            None => Err(ModuleError::MissingStateName(
+                MODULE_TYPE.to_string(),
+                self.name.clone(),
+                state_field.to_string(),
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn state_size(&self) -> usize {
+        self.state.len()
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        self.signal_inputs
+            .iter()
+            .map(|expr| (SIGNAL_INPUT, expr))
+            .chain([(CUTOFF_CONTROL, &self.frequency)])
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        STATE_OUTPUTS[..self.order()]
+            .iter()
+            .chain(&SIGNAL_OUTPUTS[..self.matrices.c.len()])
+            .copied()
+            .collect()
+    }
+
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        vec![
+            SIGNAL_INPUT,
+            A_FIELD,
+            B_FIELD,
+            C_FIELD,
+            D_FIELD,
+            POLE_FIELD,
+            ZERO_FIELD,
+        ]
+    }
+
+    fn absolute_outputs(&self) -> Vec<&'static str> {
+        SIGNAL_OUTPUTS[..self.matrices.c.len()].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn system_sources() {
+        let spec = SynthSpec::from_ini_str(
+            "
+[state_space]
+name=matrices
+a=0.0, 1.0
+a=-1.0, -0.5
+b=0.0
+b=1.0
+c=1.0, 0.0
+c=0.0, 1.0
+
+[state_space]
+name=resonator
+pole=-0.1, 1.0
+zero=0.0
+
+[state_space]
+name=lowpass
+design=chebyshev
+order=3
+response=highpass
+",
+        )
+        .unwrap();
+        let outputs: Vec<Vec<&str>> = spec.module_specs().map(|m| m.outputs()).collect();
+
+        assert_eq!(
+            outputs,
+            vec![
+                vec!["state_0", "state_1", "output_0", "output_1"],
+                vec!["state_0", "state_1", "output_0"],
+                vec!["state_0", "state_1", "state_2", "output_0"],
+            ]
+        );
+
+        let mismatched = SynthSpec::from_ini_str("[state_space]\na=0.0, 1.0\nb=1.0\nc=1.0");
+        assert!(mismatched.is_err());
+        let both = SynthSpec::from_ini_str("[state_space]\ndesign=bessel\npole=-1.0");
+        assert!(both.is_err());
+        let zeros_only = SynthSpec::from_ini_str("[state_space]\nzero=0.0");
+        assert!(zeros_only.is_err());
+        let integrator =
+            SynthSpec::from_ini_str("[state_space]\npole=0.0\npole=-1.0\nresponse=highpass");
+        assert!(integrator.is_err());
+    }
+}
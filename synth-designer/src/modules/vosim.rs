@@ -1,15 +1,16 @@
 use crate::input_expr::*;
 use crate::modules::*;
-use crate::synth_spec::gen_stack_program;
-use crate::synth_spec::SynthSpec;
+use crate::synth_spec::gen_program;
+use crate::synth_spec::{codegen_table, SynthSpec};
+use crate::wavetable_gen::{precompute_tables, TableFields, TableSource, TABLE_FIELDS};
 use crate::DEFAULT_FREQUENCY_ZERO;
-use hound;
 use ini::Properties;
 use proc_macro2::TokenStream;
 use quote::quote;
 use synth_engine::modules::vosim::Vosim;
 use synth_engine::modules::wavetable::*;
 use synth_engine::simulator::module::Module;
+use synth_engine::Float;
 
 const MODULE_TYPE: &str = "wavetable_oscillator";
 const MODULE_NAME: &str = "name";
@@ -19,7 +20,6 @@ const LINEAR_CONTROL: &str = "linear_modulation";
 const GRAIN_FREQUENCY_CONTROL: &str = "grain_frequency_control";
 const GRAIN_LINEAR_CONTROL: &str = "grain_linear_modulation";
 const SCAN_CONTROL: &str = "scan_control";
-const WAVETABLE_FIELD: &str = "wavetable";
 const SIGNAL_OUTPUT: &str = "signal_output";
 const INPUT_SIZE: usize = 5;
 const STATE_SIZE: usize = 2;
@@ -28,32 +28,20 @@ pub struct VosimOscillatorModuleSpec {
     name: String,
     inputs: [Expr; INPUT_SIZE],
     state: [usize; STATE_SIZE],
-    f0: f32,
-    wavetables: Vec<Vec<f32>>,
-}
-
-fn load_wavetable(filename: &str) -> Result<Vec<f32>, ModuleError> {
-    let mut reader = hound::WavReader::open(filename)?;
-    let mut result: Vec<f32> = Vec::new();
-
-    for sample in reader.samples::<i16>() {
-        let sample = ((sample?) as f32) / (i16::MAX as f32);
-        result.push(sample);
-    }
-
-    Ok(result)
+    f0: Float,
+    wavetables: Vec<TableSource>,
 }
 
 impl VosimOscillatorModuleSpec {
     pub fn from_ini_properties(props: Properties) -> Result<Self, ModuleError> {
         let mut name: String = MODULE_TYPE.to_string();
-        let mut f0: f32 = DEFAULT_FREQUENCY_ZERO;
+        let mut f0: Float = DEFAULT_FREQUENCY_ZERO;
         let mut fc: Expr = Expr::zero();
         let mut gfc: Expr = Expr::zero();
         let mut lc: Expr = Expr::zero();
         let mut glc: Expr = Expr::zero();
         let mut sc: Expr = Expr::zero();
-        let mut wavetables: Vec<Vec<f32>> = Vec::new();
+        let mut tables = TableFields::default();
 
         for (k, v) in props {
             match k.as_str() {
@@ -63,59 +51,72 @@ impl VosimOscillatorModuleSpec {
                 LINEAR_CONTROL => lc = Expr::parse(&v)?,
                 GRAIN_LINEAR_CONTROL => glc = Expr::parse(&v)?,
                 SCAN_CONTROL => sc = Expr::parse(&v)?,
-                FREQUENCY_ZERO => f0 = v.parse::<f32>()?,
-                WAVETABLE_FIELD => wavetables.push(load_wavetable(&v)?),
+                FREQUENCY_ZERO => f0 = v.parse::<Float>()?,
+                _ if tables.parse_field(&k, &v)? => {}
                 _ => return Err(ModuleError::InvalidField(MODULE_TYPE.to_string(), k)),
             }
         }
 
-        if wavetables.len() == 0 {
-            Err(ModuleError::MissingField(
-                MODULE_TYPE.to_string(),
-                WAVETABLE_FIELD.to_string(),
-            ))
-        } else {
-            Ok(Self {
-                name,
-                inputs: [fc, lc, sc, gfc, glc],
-                state: [0; STATE_SIZE],
-                f0,
-                wavetables,
-            })
-        }
-    }
-}
-
-fn codegen_table_data(data: &WavetableData) -> TokenStream {
-    let samples = data.samples.clone();
-    let len = data.len_f32;
+        let wavetables = tables.build(MODULE_TYPE)?;
 
-    quote! {
-        VosimData {
-            samples: vec![ #(#samples),* ],
-            len_f32: #len,
-        }
+        Ok(Self {
+            name,
+            inputs: [fc, lc, sc, gfc, glc],
+            state: [0; STATE_SIZE],
+            f0,
+            wavetables,
+        })
     }
 }
 
-fn codegen_table_entries(entries: &Vec<WavetableEntry>) -> Vec<TokenStream> {
-    entries
-        .into_iter()
-        .map(|entry| {
-            let data: Vec<TokenStream> = (&entry.data)
-                .into_iter()
-                .map(|data| codegen_table_data(&data))
-                .collect();
+fn codegen_table_entries(
+    synth_spec: &SynthSpec,
+    module_name: &str,
+    entries: &[WavetableEntry],
+) -> Result<TokenStream, ModuleError> {
+    let mode = synth_spec.codegen_mode();
+    let entries = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let data = entry
+                .data
+                .iter()
+                .enumerate()
+                .map(|(level, data)| {
+                    let name = format!("{}_{}", i, level);
+                    let samples = synth_spec.codegen_samples(module_name, &name, &data.samples)?;
+                    let len = data.len_f32;
+
+                    Ok(quote! {
+                        synth_engine::modules::wavetable::WavetableData {
+                            samples: #samples,
+                            len_f32: #len,
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, ModuleError>>()?;
+            let data = codegen_table(
+                mode,
+                quote! { synth_engine::modules::wavetable::WavetableData },
+                &data,
+            );
             let len = entry.base_data_len;
 
-            quote! {
-                VosimEntry {
-                    data: vec![ #(#data),* ],
+            Ok(quote! {
+                synth_engine::modules::wavetable::WavetableEntry {
+                    data: #data,
                     base_data_len: #len,
                 }
-            }
+            })
         })
-        .collect()
+        .collect::<Result<Vec<_>, ModuleError>>()?;
+
+    Ok(codegen_table(
+        mode,
+        quote! { synth_engine::modules::wavetable::WavetableEntry },
+        &entries,
+    ))
 }
 
 impl ModuleSpec for VosimOscillatorModuleSpec {
@@ -124,7 +125,7 @@ impl ModuleSpec for VosimOscillatorModuleSpec {
     }
 
     fn create_module(&self, synth_spec: &SynthSpec) -> Result<Box<dyn Module>, ModuleError> {
-        let module = Vosim::new(
+        let module = Vosim::new_with_precompute(
             self.f0,
             self.state[0],
             self.state[1],
@@ -133,26 +134,29 @@ impl ModuleSpec for VosimOscillatorModuleSpec {
             self.inputs[3].compile(&synth_spec)?,
             self.inputs[4].compile(&synth_spec)?,
             self.inputs[2].compile(&synth_spec)?,
-            self.wavetables.clone(),
+            precompute_tables(&self.wavetables),
         );
 
         Ok(Box::new(module))
     }
 
-    fn codegen(&self, synth_spec: &SynthSpec) -> TokenStream {
+    fn codegen(&self, synth_spec: &SynthSpec) -> Result<TokenStream, ModuleError> {
         let f0 = self.f0;
         let s0 = self.state[0];
         let s1 = self.state[1];
-        let i0 = gen_stack_program(&self.inputs[0].compile(&synth_spec).unwrap());
-        let i1 = gen_stack_program(&self.inputs[1].compile(&synth_spec).unwrap());
-        let i2 = gen_stack_program(&self.inputs[2].compile(&synth_spec).unwrap());
-        let i3 = gen_stack_program(&self.inputs[3].compile(&synth_spec).unwrap());
-        let i4 = gen_stack_program(&self.inputs[4].compile(&synth_spec).unwrap());
-        let wavetables = codegen_table_entries(&Wavetable::precompute_wavetables(&self.wavetables));
-
-        quote! { SynthModule::Vosim(Vosim::new_with_precompute(
-            #f0, #s0, #s1, #i0, #i1, #i3, #i4, #i2, vec![#(#wavetables),*]
-        )) }
+        let i0 = gen_program(&self.inputs[0].compile(&synth_spec).unwrap());
+        let i1 = gen_program(&self.inputs[1].compile(&synth_spec).unwrap());
+        let i2 = gen_program(&self.inputs[2].compile(&synth_spec).unwrap());
+        let i3 = gen_program(&self.inputs[3].compile(&synth_spec).unwrap());
+        let i4 = gen_program(&self.inputs[4].compile(&synth_spec).unwrap());
+        let wavetables =
+            codegen_table_entries(synth_spec, &self.name, &precompute_tables(&self.wavetables))?;
+
+        Ok(
+            quote! { SynthModule::VosimOscillator(Vosim::new_with_precompute(
+                #f0, #s0, #s1, #i0, #i1, #i3, #i4, #i2, #wavetables
+            )) },
+        )
     }
 
     fn state_index(&self, state_field: &str) -> Result<usize, ModuleError> {
@@ -173,4 +177,22 @@ impl ModuleSpec for VosimOscillatorModuleSpec {
     fn state_size(&self) -> usize {
         self.state.len()
     }
+
+    fn inputs(&self) -> Vec<(&'static str, &Expr)> {
+        vec![
+            (FREQUENCY_CONTROL, &self.inputs[0]),
+            (LINEAR_CONTROL, &self.inputs[1]),
+            (SCAN_CONTROL, &self.inputs[2]),
+            (GRAIN_FREQUENCY_CONTROL, &self.inputs[3]),
+            (GRAIN_LINEAR_CONTROL, &self.inputs[4]),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<&'static str> {
+        vec![SIGNAL_OUTPUT]
+    }
+
+    fn repeated_fields(&self) -> Vec<&'static str> {
+        TABLE_FIELDS.to_vec()
+    }
 }
@@ -0,0 +1,600 @@
+//! Sub-patches: reusable groups of modules that are instantiated several times
+//! in a patch.
+//!
+//! A template is declared with a `[template]` section that names its inputs and
+//! outputs. Module sections with a `template=` key belong to the template body
+//! instead of the patch itself. Inside the body, `input.<name>` refers to a
+//! template input and module names refer to other modules of the body.
+//!
+//! ```ini
+//! [template]
+//! name=exciter
+//! input=gate
+//! input=brightness
+//! output=signal:filter.lowpass_output
+//!
+//! [noise]
+//! template=exciter
+//! name=noise
+//!
+//! [filter_12db]
+//! template=exciter
+//! name=filter
+//! signal_input=noise.signal_output * input.gate
+//! cutoff_frequency=input.brightness * 7.0
+//!
+//! [instance]
+//! template=exciter
+//! name=pluck
+//! gate=midi.gate
+//! brightness=cc.signal_output
+//! ```
+//!
+//! Each `[instance]` is expanded into ordinary module sections, named with the
+//! instance name as prefix (`pluck_noise`, `pluck_filter`). The rest of the
+//! patch refers to template outputs through the instance name (`pluck.signal`).
+//! Template inputs that an instance doesn't bind are zero.
+//! An instance with `poly_keys=<keys>` is expanded once per voice of that
+//! `[poly_keys]` module, named `<instance>_<voice>`. Template inputs `pitch`,
+//! `gate`, `velocity`, `aftertouch` and `timbre` that the instance doesn't bind
+//! are bound
+//! to the outputs of the voice, like `keys.gate_0`. The instance itself can't
+//! bind inputs to the outputs of a single voice. The instance outputs are the
+//! sums over all voices, and `level=<instance>.<output>` on the keys is
+//! expanded into one level per voice.
+//!
+//! Expansion happens on the INI level, so the expanded modules are parsed,
+//! allocated and code generated like any other module.
+
+use crate::input_expr::{BinaryOperator, Expr, ExprError};
+use crate::modules::poly_keys_module::{is_voice_output, voice_count, voice_output, VOICE_FIELDS};
+use ini::{Ini, Properties};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+const TEMPLATE_SECTION: &str = "template";
+const INSTANCE_SECTION: &str = "instance";
+const TEMPLATE_FIELD: &str = "template";
+const NAME_FIELD: &str = "name";
+const INPUT_FIELD: &str = "input";
+const OUTPUT_FIELD: &str = "output";
+const INPUT_MODULE: &str = "input";
+const POLY_KEYS_SECTION: &str = "poly_keys";
+const POLY_KEYS_FIELD: &str = "poly_keys";
+const LEVEL_FIELD: &str = "level";
+
+#[derive(Error, Debug)]
+pub enum SubPatchError {
+    #[error("Missing name on {0} section")]
+    MissingName(String),
+    #[error("Unknown template: {0}")]
+    UnknownTemplate(String),
+    #[error("Template with name {0} already defined")]
+    TemplateNameClash(String),
+    #[error("Malformed output spec on template {0}: {1}")]
+    MalformedOutput(String, String),
+    #[error("No input named {1} on template {0}")]
+    UnknownInput(String, String),
+    #[error("Invalid field, {1}, on template {0}")]
+    InvalidField(String, String),
+    #[error("Unknown poly keys: {0}")]
+    UnknownPolyKeys(String),
+    #[error("Invalid number of voices on poly keys {0}")]
+    InvalidVoices(String),
+    #[error("Input {1} of polyphonic instance {0} is bound to a single voice")]
+    VoiceBinding(String, String),
+    #[error("Error in input binding: {0}")]
+    ExprError(#[from] ExprError),
+}
+
+struct Template {
+    inputs: Vec<String>,
+    outputs: BTreeMap<String, (String, String)>,
+    modules: Vec<(String, Properties)>,
+}
+
+impl Template {
+    fn from_ini_properties(name: &str, props: &Properties) -> Result<Self, SubPatchError> {
+        let mut inputs = Vec::new();
+        let mut outputs = BTreeMap::new();
+
+        for (k, v) in props.iter() {
+            match k {
+                NAME_FIELD => {}
+                INPUT_FIELD => inputs.push(v.trim().to_string()),
+                OUTPUT_FIELD => {
+                    let malformed = || SubPatchError::MalformedOutput(name.to_string(), v.into());
+                    let (output, target) = v.split_once(':').ok_or_else(malformed)?;
+                    let (module, field) = target.trim().split_once('.').ok_or_else(malformed)?;
+
+                    outputs.insert(
+                        output.trim().to_string(),
+                        (module.to_string(), field.to_string()),
+                    );
+                }
+                _ => return Err(SubPatchError::InvalidField(name.to_string(), k.to_string())),
+            }
+        }
+
+        Ok(Self {
+            inputs,
+            outputs,
+            modules: Vec::new(),
+        })
+    }
+
+    fn module_names(&self) -> Vec<&str> {
+        self.modules
+            .iter()
+            .filter_map(|(_, props)| props.get(NAME_FIELD))
+            .collect()
+    }
+}
+
+fn prefixed(prefix: &str, name: &str) -> String {
+    format!("{}_{}", prefix, name)
+}
+
+/// Rewrite every value of `props` that parses as an expression with `f`. Values
+/// of the `verbatim` keys, values that don't parse (integers, file names) and
+/// values that `f` leaves untouched are kept as they are.
+fn rewrite_properties<F>(props: &Properties, verbatim: &[&str], f: &F) -> Properties
+where
+    F: Fn(&str, &str) -> Option<Expr>,
+{
+    let mut result = Properties::new();
+
+    for (k, v) in props.iter() {
+        let value = match Expr::parse(v) {
+            _ if verbatim.contains(&k) => v.to_string(),
+            Ok(expr) => {
+                let mapped = expr.map_outputs(f);
+
+                if mapped == expr {
+                    v.to_string()
+                } else {
+                    mapped.to_string()
+                }
+            }
+            Err(_) => v.to_string(),
+        };
+
+        result.append(k, value);
+    }
+
+    result
+}
+
+/// Add the modules of an instance of `template` to `expanded`, with template
+/// inputs replaced by `bindings`. Returns the template outputs of the instance.
+fn expand_instance(
+    instance_name: &str,
+    template: &Template,
+    bindings: &BTreeMap<String, Expr>,
+    expanded: &mut Vec<(String, Properties)>,
+) -> BTreeMap<String, Expr> {
+    let locals = template.module_names();
+    let rename = |m: &str, n: &str| {
+        if m == INPUT_MODULE {
+            Some(bindings.get(n).cloned().unwrap_or(Expr::zero()))
+        } else if locals.contains(&m) {
+            Some(Expr::OutputState(prefixed(instance_name, m), n.to_string()))
+        } else {
+            None
+        }
+    };
+
+    for (module_section, module_props) in &template.modules {
+        let mut module_props = rewrite_properties(module_props, &[NAME_FIELD], &rename);
+
+        module_props.remove(TEMPLATE_FIELD);
+
+        if let Some(name) = module_props.get(NAME_FIELD) {
+            let name = prefixed(instance_name, name);
+            module_props.insert(NAME_FIELD, name);
+        }
+
+        expanded.push((module_section.clone(), module_props));
+    }
+
+    template
+        .outputs
+        .iter()
+        .map(|(output, (m, n))| {
+            let target = Expr::OutputState(prefixed(instance_name, m), n.clone());
+            (output.clone(), target)
+        })
+        .collect()
+}
+
+/// Replace each `level` of a poly keys section that refers to an output of a
+/// polyphonic instance by one level per voice, referring to the same output of
+/// that voice.
+fn expand_levels(props: &Properties, poly_instances: &BTreeMap<String, usize>) -> Properties {
+    let mut result = Properties::new();
+
+    for (k, v) in props.iter() {
+        let instance = match Expr::parse(v) {
+            Ok(Expr::OutputState(m, n)) if k == LEVEL_FIELD => {
+                poly_instances.get(&m).map(|voices| (m, n, *voices))
+            }
+            _ => None,
+        };
+
+        match instance {
+            Some((m, n, voices)) => {
+                for voice in 0..voices {
+                    result.append(k, format!("{}.{}", voice_output(&m, voice), n));
+                }
+            }
+            None => result.append(k, v),
+        }
+    }
+
+    result
+}
+
+/// A patch with its template instances expanded.
+pub struct ExpandedPatch {
+    /// The module sections of the patch in file order, with instances replaced
+    /// by the modules they expand to
+    pub sections: Vec<(String, Properties)>,
+    /// The template inputs that instances don't bind, as instance and input
+    /// names. They are zero.
+    pub unbound_inputs: Vec<(String, String)>,
+}
+
+/// Expand all template instances in a patch. Returns the module sections of the
+/// patch in file order, with instances replaced by the modules they expand to.
+pub fn expand_sub_patches(ini: Ini) -> Result<Vec<(String, Properties)>, SubPatchError> {
+    expand_patch(ini).map(|patch| patch.sections)
+}
+
+/// Expand all template instances in a patch, keeping track of the template
+/// inputs the instances leave unbound.
+pub fn expand_patch(ini: Ini) -> Result<ExpandedPatch, SubPatchError> {
+    let mut templates: BTreeMap<String, Template> = BTreeMap::new();
+    let mut sections: Vec<(String, Properties)> = Vec::new();
+
+    for (section, props) in ini {
+        let Some(section) = section else { continue };
+        let section = section.to_lowercase();
+
+        if section == TEMPLATE_SECTION {
+            let name = props
+                .get(NAME_FIELD)
+                .ok_or_else(|| SubPatchError::MissingName(section.clone()))?
+                .to_string();
+
+            if templates.contains_key(&name) {
+                return Err(SubPatchError::TemplateNameClash(name));
+            }
+
+            let template = Template::from_ini_properties(&name, &props)?;
+            templates.insert(name, template);
+        } else {
+            sections.push((section, props));
+        }
+    }
+
+    let mut body: Vec<(String, Properties)> = Vec::new();
+
+    for (section, props) in sections {
+        match (section.as_str(), props.get(TEMPLATE_FIELD)) {
+            (INSTANCE_SECTION, _) => body.push((section, props)),
+            (_, Some(template_name)) => {
+                let template_name = template_name.to_string();
+                let template = templates
+                    .get_mut(&template_name)
+                    .ok_or(SubPatchError::UnknownTemplate(template_name))?;
+                template.modules.push((section, props));
+            }
+            (_, None) => body.push((section, props)),
+        }
+    }
+
+    let poly_keys_voices: BTreeMap<String, Option<usize>> = body
+        .iter()
+        .filter(|(section, _)| section == POLY_KEYS_SECTION)
+        .map(|(_, props)| {
+            let name = props.get(NAME_FIELD).unwrap_or(POLY_KEYS_SECTION);
+            (name.to_string(), voice_count(props).ok())
+        })
+        .collect();
+    let mut aliases: BTreeMap<String, BTreeMap<String, Expr>> = BTreeMap::new();
+    let mut poly_instances: BTreeMap<String, usize> = BTreeMap::new();
+    let mut expanded: Vec<(String, Properties)> = Vec::new();
+    let mut unbound_inputs: Vec<(String, String)> = Vec::new();
+
+    for (section, props) in body {
+        if section != INSTANCE_SECTION {
+            expanded.push((section, props));
+            continue;
+        }
+
+        let instance_name = props
+            .get(NAME_FIELD)
+            .ok_or_else(|| SubPatchError::MissingName(section.clone()))?
+            .to_string();
+        let template_name = props
+            .get(TEMPLATE_FIELD)
+            .ok_or_else(|| SubPatchError::UnknownTemplate(String::new()))?
+            .to_string();
+        let template = templates
+            .get(&template_name)
+            .ok_or_else(|| SubPatchError::UnknownTemplate(template_name.clone()))?;
+
+        let poly_keys = props
+            .get(POLY_KEYS_FIELD)
+            .map(|name| name.trim().to_string());
+        let mut bindings: BTreeMap<String, Expr> = BTreeMap::new();
+
+        for (k, v) in props.iter() {
+            match k {
+                NAME_FIELD | TEMPLATE_FIELD | POLY_KEYS_FIELD => {}
+                _ if template.inputs.iter().any(|i| i == k) => {
+                    bindings.insert(k.to_string(), Expr::parse(v)?);
+                }
+                _ => return Err(SubPatchError::UnknownInput(template_name, k.to_string())),
+            }
+        }
+
+        for input in &template.inputs {
+            let voice_input = poly_keys.is_some() && VOICE_FIELDS.contains(&input.as_str());
+
+            if !bindings.contains_key(input) && !voice_input {
+                unbound_inputs.push((instance_name.clone(), input.clone()));
+            }
+        }
+
+        let Some(keys) = poly_keys else {
+            let outputs = expand_instance(&instance_name, template, &bindings, &mut expanded);
+            aliases.insert(instance_name, outputs);
+            continue;
+        };
+
+        // Every voice would play the one voice the binding refers to
+        for (input, expr) in &bindings {
+            if expr
+                .output_refs()
+                .iter()
+                .any(|(m, n)| *m == keys && is_voice_output(n))
+            {
+                return Err(SubPatchError::VoiceBinding(instance_name, input.clone()));
+            }
+        }
+
+        let voices = poly_keys_voices
+            .get(&keys)
+            .copied()
+            .ok_or_else(|| SubPatchError::UnknownPolyKeys(keys.clone()))?
+            .ok_or_else(|| SubPatchError::InvalidVoices(keys.clone()))?;
+        let mut sums: BTreeMap<String, Expr> = BTreeMap::new();
+
+        for voice in 0..voices {
+            let mut voice_bindings = bindings.clone();
+
+            for field in VOICE_FIELDS {
+                if template.inputs.iter().any(|i| i == field) {
+                    voice_bindings.entry(field.to_string()).or_insert_with(|| {
+                        Expr::OutputState(keys.clone(), voice_output(field, voice))
+                    });
+                }
+            }
+
+            let voice_name = voice_output(&instance_name, voice);
+            let outputs = expand_instance(&voice_name, template, &voice_bindings, &mut expanded);
+
+            for (output, expr) in &outputs {
+                let sum = match sums.remove(output) {
+                    Some(sum) => {
+                        Expr::BinOp(BinaryOperator::Add, Box::new(sum), Box::new(expr.clone()))
+                    }
+                    None => expr.clone(),
+                };
+                sums.insert(output.clone(), sum);
+            }
+
+            aliases.insert(voice_name, outputs);
+        }
+
+        aliases.insert(instance_name.clone(), sums);
+        poly_instances.insert(instance_name, voices);
+    }
+
+    if aliases.is_empty() {
+        return Ok(ExpandedPatch {
+            sections: expanded,
+            unbound_inputs,
+        });
+    }
+
+    let resolve = |m: &str, n: &str| aliases.get(m).and_then(|outputs| outputs.get(n)).cloned();
+    let sections = expanded
+        .into_iter()
+        .map(|(section, props)| {
+            let props = if section == POLY_KEYS_SECTION {
+                expand_levels(&props, &poly_instances)
+            } else {
+                props
+            };
+
+            (section, rewrite_properties(&props, &[NAME_FIELD], &resolve))
+        })
+        .collect();
+
+    Ok(ExpandedPatch {
+        sections,
+        unbound_inputs,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATCH: &str = "
+[template]
+name=exciter
+input=gate
+output=signal:filter.lowpass_output
+
+[noise]
+template=exciter
+name=noise
+
+[filter_12db]
+template=exciter
+name=filter
+signal_input=noise.signal_output * input.gate
+resonance=1.0
+
+[instance]
+template=exciter
+name=left
+gate=midi.gate
+
+[instance]
+template=exciter
+name=right
+
+[mono_out]
+name=out
+output_index=0
+signal_input=left.signal + right.signal
+";
+
+    #[test]
+    fn expand_instances() {
+        let patch = expand_patch(Ini::load_from_str(PATCH).unwrap()).unwrap();
+        let sections = patch.sections;
+        let names: Vec<&str> = sections
+            .iter()
+            .map(|(_, props)| props.get(NAME_FIELD).unwrap())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "left_noise",
+                "left_filter",
+                "right_noise",
+                "right_filter",
+                "out"
+            ]
+        );
+
+        let left_input = Expr::parse(sections[1].1.get("signal_input").unwrap()).unwrap();
+        let right_input = Expr::parse(sections[3].1.get("signal_input").unwrap()).unwrap();
+        let output = Expr::parse(sections[4].1.get("signal_input").unwrap()).unwrap();
+
+        assert_eq!(
+            left_input,
+            Expr::parse("left_noise.signal_output * midi.gate").unwrap()
+        );
+        assert_eq!(
+            right_input,
+            Expr::parse("right_noise.signal_output * 0.").unwrap()
+        );
+        assert_eq!(
+            output,
+            Expr::parse("left_filter.lowpass_output + right_filter.lowpass_output").unwrap()
+        );
+        assert_eq!(sections[1].1.get("resonance"), Some("1.0"));
+        assert_eq!(sections[1].1.get(TEMPLATE_FIELD), None);
+
+        // The right instance doesn't bind the gate
+        assert_eq!(
+            patch.unbound_inputs,
+            vec![("right".to_string(), "gate".to_string())]
+        );
+    }
+
+    #[test]
+    fn expand_voices() {
+        let patch = "
+[template]
+name=voice
+input=gate
+input=pitch
+output=signal:amp.signal_output
+output=level:env.signal_output
+
+[contour]
+template=voice
+name=env
+signal_input=input.gate
+
+[amplifier]
+template=voice
+name=amp
+signal_input=input.pitch * env.signal_output
+
+[poly_keys]
+name=keys
+voices=2
+level=voice.level
+
+[instance]
+template=voice
+name=voice
+poly_keys=keys
+pitch=cc.signal_output
+
+[mono_out]
+name=out
+signal_input=voice.signal
+";
+        let sections = expand_sub_patches(Ini::load_from_str(patch).unwrap()).unwrap();
+        let names: Vec<&str> = sections
+            .iter()
+            .map(|(_, props)| props.get(NAME_FIELD).unwrap())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "keys",
+                "voice_0_env",
+                "voice_0_amp",
+                "voice_1_env",
+                "voice_1_amp",
+                "out"
+            ]
+        );
+
+        // Unbound voice inputs come from the keys, explicit bindings are kept
+        assert_eq!(sections[3].1.get("signal_input"), Some("keys.gate_1"));
+        assert_eq!(
+            Expr::parse(sections[4].1.get("signal_input").unwrap()).unwrap(),
+            Expr::parse("cc.signal_output * voice_1_env.signal_output").unwrap()
+        );
+
+        let levels: Vec<&str> = sections[0].1.get_all("level").collect();
+        assert_eq!(
+            levels,
+            vec!["voice_0_env.signal_output", "voice_1_env.signal_output"]
+        );
+        assert_eq!(
+            Expr::parse(sections[5].1.get("signal_input").unwrap()).unwrap(),
+            Expr::parse("voice_0_amp.signal_output + voice_1_amp.signal_output").unwrap()
+        );
+
+        // A binding to the outputs of one voice is refused
+        let patch = patch.replace("pitch=cc.signal_output", "pitch=keys.pitch_0 + 1.0");
+        assert!(matches!(
+            expand_sub_patches(Ini::load_from_str(&patch).unwrap()),
+            Err(SubPatchError::VoiceBinding(_, _))
+        ));
+    }
+
+    #[test]
+    fn unknown_template() {
+        let patch = "[instance]\ntemplate=nothing\nname=x\n";
+
+        assert!(matches!(
+            expand_sub_patches(Ini::load_from_str(patch).unwrap()),
+            Err(SubPatchError::UnknownTemplate(_))
+        ));
+    }
+}
@@ -1,31 +1,92 @@
+use crate::graph::DataflowGraph;
 use crate::modules::ModuleError;
 use crate::modules::ModuleSpec;
 use crate::modules::*;
 use crate::state_allocator::StateAllocator;
+use crate::sub_patch::expand_sub_patches;
+use crate::wavetable_file::{patch_dir, resolve_file_names};
 use crate::SynthError;
-use ini::Ini;
-use proc_macro2::TokenStream;
-use quote::quote;
+use ini::{Ini, Properties};
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{format_ident, quote};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use synth_engine::modules::poly_keys::MpeConfig;
+use synth_engine::simulator::loops::{
+    DelayFreeLoop, LoopSolver, DEFAULT_MAX_ITERATIONS, DEFAULT_TOLERANCE, MAX_NEWTON_STATES,
+};
 use synth_engine::simulator::module::Module;
+use synth_engine::simulator::state::State;
 use synth_engine::stack_program::Function;
 use synth_engine::stack_program::Instr;
 use synth_engine::stack_program::StackProgram;
+use synth_engine::Float;
 
-pub struct SynthSpec(BTreeMap<String, Box<dyn ModuleSpec>>);
+const EVALUATION_ORDER: &str = "evaluation_order";
+const LOOP_SOLVER: &str = "loop_solver";
+const LOOP_ITERATIONS: &str = "loop_iterations";
+const LOOP_TOLERANCE: &str = "loop_tolerance";
+const FIXED_POINT_SOLVER: &str = "fixed_point";
+const NEWTON_SOLVER: &str = "newton";
+
+/// Start values of the loop outputs when probing delay-free loops.
+const LOOP_PROBE_VALUES: [Float; 4] = [0.1, -0.5, 1.0, -4.0];
+const PROBE_STACK_SIZE: usize = 256;
+
+/// A delay-free loop as module indices, and the state indices of its outputs.
+type LoopStates = (Vec<usize>, Vec<usize>);
+
+/// How `SynthSpec::codegen` emits a synth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CodegenMode {
+    /// Delay lines and wavetables on the heap, for `synth-engine` with the
+    /// default `alloc` feature.
+    Alloc,
+    /// No heap allocation at all, for `synth-engine` without default features.
+    /// Wavetables are static data and delay line buffers are fields of a
+    /// `Buffers` struct, to be placed in static memory by the user.
+    Static,
+    /// Q16.16 fixed point for targets without a floating point unit, for
+    /// `synth-engine` with the `fixed-point` feature. Only modules that
+    /// implement `FixedModule` can be generated, and like the `Static` mode it
+    /// needs no heap.
+    Fixed,
+}
+
+/// The modules of a synth, in the order they are declared in the patch.
+pub struct SynthSpec {
+    modules: Vec<Box<dyn ModuleSpec>>,
+    names: BTreeMap<String, usize>,
+    evaluation_order: Vec<usize>,
+    loop_solver: LoopSolver,
+    loop_iterations: usize,
+    loop_tolerance: Float,
+    codegen_mode: CodegenMode,
+    data_dir: Option<PathBuf>,
+}
 
 impl SynthSpec {
     pub fn new() -> Self {
-        Self(BTreeMap::new())
+        Self {
+            modules: Vec::new(),
+            names: BTreeMap::new(),
+            evaluation_order: Vec::new(),
+            loop_solver: LoopSolver::FixedPoint,
+            loop_iterations: DEFAULT_MAX_ITERATIONS,
+            loop_tolerance: DEFAULT_TOLERANCE,
+            codegen_mode: CodegenMode::Alloc,
+            data_dir: None,
+        }
     }
 
     pub fn add_module(&mut self, module_spec: Box<dyn ModuleSpec>) -> Result<(), ModuleError> {
         let key = module_spec.get_name().to_string();
 
-        if self.0.contains_key(&key) {
+        if self.names.contains_key(&key) {
             Err(ModuleError::ModuleNameClash(key))
         } else {
-            self.0.insert(key, module_spec);
+            self.names.insert(key, self.modules.len());
+            self.modules.push(module_spec);
             Ok(())
         }
     }
@@ -36,21 +97,268 @@ impl SynthSpec {
         module_field: &str,
     ) -> Result<usize, ModuleError> {
         let module_spec = self
-            .0
+            .names
             .get(module_name)
+            .map(|i| &self.modules[*i])
             .ok_or(ModuleError::MissingModule(module_name.to_string()));
 
         module_spec?.state_index(module_field)
     }
 
+    /// The module specs in declaration order.
+    pub fn module_specs(&self) -> impl Iterator<Item = &dyn ModuleSpec> {
+        self.modules.iter().map(|v| v.as_ref())
+    }
+
+    /// The MPE zones of the keys modules of the synth.
+    pub fn mpe_zones(&self) -> Vec<MpeConfig> {
+        self.modules.iter().filter_map(|m| m.mpe()).collect()
+    }
+
     pub fn model_size(&self) -> usize {
-        self.0.len()
+        self.modules.len()
+    }
+
+    /// Evaluate the given modules first, in the given order. The remaining
+    /// modules follow in the order computed by `evaluation_order`.
+    pub fn set_evaluation_order(&mut self, module_names: &[&str]) -> Result<(), ModuleError> {
+        let mut order = Vec::new();
+
+        for name in module_names {
+            let index = self
+                .names
+                .get(*name)
+                .ok_or(ModuleError::MissingModule(name.to_string()))?;
+
+            if !order.contains(index) {
+                order.push(*index);
+            }
+        }
+
+        self.evaluation_order = order;
+
+        Ok(())
+    }
+
+    /// The order in which modules are simulated and finalized, as indices into
+    /// `module_specs`. A module comes after the modules whose absolute outputs
+    /// it reads, otherwise declaration order is kept, so the order doesn't
+    /// change when modules are renamed. Feedback loops are broken before the
+    /// first declared module of the loop.
+    pub fn evaluation_order(&self) -> Vec<usize> {
+        let graph = DataflowGraph::from_references(self);
+        let absolute: Vec<Vec<&str>> = self.modules.iter().map(|m| m.absolute_outputs()).collect();
+        let (order, _feedback) = graph.topological_order(|e| absolute[e.from].contains(&e.output));
+
+        let mut result = self.evaluation_order.clone();
+        result.extend(
+            order
+                .into_iter()
+                .filter(|m| !self.evaluation_order.contains(m)),
+        );
+
+        result
+    }
+
+    pub fn set_loop_solver(&mut self, solver: LoopSolver, iterations: usize, tolerance: Float) {
+        self.loop_solver = solver;
+        self.loop_iterations = iterations;
+        self.loop_tolerance = tolerance;
+    }
+
+    pub fn set_codegen_mode(&mut self, mode: CodegenMode) {
+        self.codegen_mode = mode;
+    }
+
+    pub fn codegen_mode(&self) -> CodegenMode {
+        self.codegen_mode
+    }
+
+    /// Generate an input program of a module as a native function, in fixed
+    /// point in the `Fixed` mode and in floating point otherwise.
+    pub fn codegen_program(&self, stack_program: &StackProgram) -> TokenStream {
+        match self.codegen_mode {
+            CodegenMode::Fixed => gen_fixed_program(stack_program),
+            CodegenMode::Alloc | CodegenMode::Static => gen_program(stack_program),
+        }
+    }
+
+    /// Write large data of generated code, like wavetable samples, as binary
+    /// files into `dir` instead of inlining it. Build scripts should use their
+    /// `OUT_DIR`.
+    pub fn set_data_dir(&mut self, dir: &Path) {
+        self.data_dir = Some(dir.to_path_buf());
+    }
+
+    /// Generate the samples of a wavetable of a module, as stored by
+    /// `WavetableData`. With a data directory the samples are written to a
+    /// file named by `name`, and included with `include_bytes!`.
+    pub fn codegen_samples(
+        &self,
+        module_name: &str,
+        name: &str,
+        samples: &[Float],
+    ) -> Result<TokenStream, ModuleError> {
+        let Some(data_dir) = &self.data_dir else {
+            let samples: Vec<TokenStream> = samples.iter().map(|v| quote! { #v }).collect();
+
+            return Ok(codegen_table(self.codegen_mode, float_type(), &samples));
+        };
+
+        let path = data_dir.join(format!("{}_{}.bin", rust_name(module_name), name));
+        let bytes: Vec<u8> = samples.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        std::fs::write(&path, bytes)
+            .map_err(|err| ModuleError::DataFile(path.display().to_string(), err.to_string()))?;
+
+        let path = path.to_string_lossy();
+        let blob = quote! {
+            static SAMPLES: &synth_engine::modules::wavetable::SampleBlob<[u8]> =
+                &synth_engine::modules::wavetable::SampleBlob(*include_bytes!(#path));
+        };
+
+        Ok(match self.codegen_mode {
+            CodegenMode::Alloc => quote! {{ #blob SAMPLES.samples().to_vec() }},
+            CodegenMode::Static | CodegenMode::Fixed => quote! {{ #blob SAMPLES.samples() }},
+        })
+    }
+
+    /// The delay-free loops of the synth: cycles of absolute outputs that are
+    /// read by other modules within the same stage. Each loop is given as
+    /// indices into `module_specs`.
+    pub fn delay_free_loops(&self) -> Vec<Vec<usize>> {
+        let graph = DataflowGraph::from_references(self);
+
+        graph.cycles(|e| self.is_delay_free(e.from, e.output, e.to, e.input))
+    }
+
+    fn is_delay_free(&self, from: usize, output: &str, to: usize, input: &str) -> bool {
+        self.modules[from].absolute_outputs().contains(&output)
+            && !self.modules[to].delayed_inputs().contains(&input)
+    }
+
+    /// Create the solvers for the delay-free loops of the synth, with module
+    /// indices that match `make_modules`. The state of the synth must be
+    /// allocated.
+    pub fn make_loops(&self) -> Result<Vec<DelayFreeLoop>, ModuleError> {
+        let order = self.evaluation_order();
+        let mut loops = Vec::new();
+
+        for (cycle, states) in self.loop_states()? {
+            let modules = cycle
+                .iter()
+                .map(|m| order.iter().position(|o| o == m).unwrap())
+                .collect();
+
+            loops.push(DelayFreeLoop::new(
+                modules,
+                states,
+                self.loop_solver,
+                self.loop_iterations,
+                self.loop_tolerance,
+            ));
+        }
+
+        Ok(loops)
+    }
+
+    /// The delay-free loops with the state indices of their outputs.
+    fn loop_states(&self) -> Result<Vec<LoopStates>, ModuleError> {
+        let graph = DataflowGraph::from_references(self);
+        let mut loops = Vec::new();
+
+        for cycle in self.delay_free_loops() {
+            let mut states = Vec::new();
+
+            for edge in &graph.edges {
+                if cycle.contains(&edge.from)
+                    && cycle.contains(&edge.to)
+                    && self.is_delay_free(edge.from, edge.output, edge.to, edge.input)
+                {
+                    let index = self.modules[edge.from].state_index(edge.output)?;
+
+                    if !states.contains(&index) {
+                        states.push(index);
+                    }
+                }
+            }
+
+            if self.loop_solver == LoopSolver::Newton && states.len() > MAX_NEWTON_STATES {
+                return Err(ModuleError::LargeLoop(self.module_names(&cycle)));
+            }
+
+            loops.push((cycle, states));
+        }
+
+        Ok(loops)
+    }
+
+    fn module_names(&self, modules: &[usize]) -> Vec<String> {
+        modules
+            .iter()
+            .map(|m| self.modules[*m].get_name().to_string())
+            .collect()
+    }
+
+    /// Probe the delay-free loops of the synth from a few start values of the
+    /// loop outputs, with every other state at zero. Returns the module names
+    /// and the largest residual of every loop the solver doesn't resolve from
+    /// some start value. The state of the synth must be allocated.
+    pub fn probe_loops(&self) -> Result<Vec<(Vec<String>, Float)>, ModuleError> {
+        let mut unresolved = Vec::new();
+
+        for (cycle, states) in self.loop_states()? {
+            let mut probe_modules = Vec::new();
+
+            for m in &cycle {
+                probe_modules.push(self.modules[*m].create_module(self)?);
+            }
+
+            let probe_loop = DelayFreeLoop::new(
+                (0..cycle.len()).collect(),
+                states.clone(),
+                self.loop_solver,
+                self.loop_iterations,
+                self.loop_tolerance,
+            );
+            let simulate = |state: &State, update: &mut _, stack: &mut [Float]| {
+                for module in &probe_modules {
+                    module.simulate(state, update, stack);
+                }
+            };
+            let mut worst: Option<Float> = None;
+
+            for value in LOOP_PROBE_VALUES {
+                let mut state = State::new(self.state_size());
+                let mut stack = [0.0; PROBE_STACK_SIZE];
+
+                for s in &states {
+                    state.set(*s, value);
+                }
+
+                let mut update = state.update_data(0., 0.);
+
+                simulate(&state, &mut update, &mut stack);
+
+                if let Err(residual) =
+                    probe_loop.solve(&mut state, &mut update, &mut stack, simulate)
+                {
+                    worst = Some(worst.map_or(residual, |w| w.max(residual)));
+                }
+            }
+
+            if let Some(residual) = worst {
+                unresolved.push((self.module_names(&cycle), residual));
+            }
+        }
+
+        Ok(unresolved)
     }
 
     fn state_size(&self) -> usize {
         let mut state_size: usize = 0;
 
-        for (_k, v) in self.0.iter() {
+        for v in self.modules.iter() {
             state_size = state_size + v.state_size();
         }
 
@@ -62,7 +370,7 @@ impl SynthSpec {
 
         let mut state_allocator = StateAllocator::new(size);
 
-        for (_k, v) in self.0.iter_mut() {
+        for v in self.modules.iter_mut() {
             v.allocate_state(&mut state_allocator)
         }
 
@@ -70,137 +378,930 @@ impl SynthSpec {
     }
 
     pub fn make_modules(&self, modules: &mut Vec<Box<dyn Module>>) -> Result<(), ModuleError> {
-        for (_k, v) in self.0.iter() {
-            modules.push(v.create_module(self)?);
+        for i in self.evaluation_order() {
+            modules.push(self.modules[i].create_module(self)?);
         }
 
         Ok(())
     }
 
-    pub fn codegen(&self) -> TokenStream {
+    /// Generate Rust code for the synth: a `Synth` struct with `step`,
+    /// `process_event` and `get_stereo_output` methods, like `RungeKutta`.
+    /// Input expressions become native functions and modules are held in an
+    /// array of `SynthModule`, so there is neither dynamic dispatch nor
+    /// interpretation when the synth runs. The code is meant to be included in
+    /// a module of its own. In the `Alloc` mode it expects the `alloc` crate to
+    /// be available, in the `Static` and `Fixed` modes it uses no heap at all,
+    /// see `CodegenMode`. The state of the synth must be allocated.
+    pub fn codegen(&self) -> Result<TokenStream, ModuleError> {
+        if self.codegen_mode == CodegenMode::Fixed {
+            if let Some(module_spec) = self.modules.iter().find(|m| !m.fixed_point()) {
+                return Err(ModuleError::NotFixedPoint(
+                    module_spec.get_name().to_string(),
+                ));
+            }
+        }
+
         let mut module_code: Vec<TokenStream> = Vec::new();
 
-        for (_k, v) in self.0.iter() {
-            module_code.push(v.codegen(self));
+        for i in self.evaluation_order() {
+            module_code.push(self.modules[i].codegen(self)?);
+        }
+
+        match self.codegen_mode {
+            CodegenMode::Alloc => self.codegen_alloc(module_code),
+            CodegenMode::Static => self.codegen_static(module_code),
+            CodegenMode::Fixed => self.codegen_fixed(module_code),
+        }
+    }
+
+    fn codegen_alloc(&self, module_code: Vec<TokenStream>) -> Result<TokenStream, ModuleError> {
+        let float = float_type();
+        let mut loop_code: Vec<TokenStream> = Vec::new();
+
+        for delay_free_loop in self.make_loops()? {
+            let modules = delay_free_loop.modules;
+            let states = delay_free_loop.states;
+            let solver = match delay_free_loop.solver {
+                LoopSolver::FixedPoint => quote! { LoopSolver::FixedPoint },
+                LoopSolver::Newton => quote! { LoopSolver::Newton },
+            };
+            let max_iterations = delay_free_loop.max_iterations;
+            let tolerance = delay_free_loop.tolerance;
+
+            loop_code.push(quote! {
+                DelayFreeLoop::new(
+                    vec![#(#modules),*], vec![#(#states),*], #solver, #max_iterations, #tolerance
+                )
+            });
         }
 
         let synth_state_size = self.state_size();
+        let module_count = self.modules.len();
+        let loop_imports = if loop_code.is_empty() {
+            quote! {}
+        } else {
+            quote! { use synth_engine::simulator::loops::{DelayFreeLoop, LoopSolver}; }
+        };
+
+        Ok(quote! {
+            use alloc::vec;
+            use synth_engine::event::ControllerEvent;
+            use synth_engine::modules::*;
+            #loop_imports
+            use synth_engine::simulator::rungekutta::RungeKutta;
+            use synth_engine::simulator::state::State;
+            use synth_engine::stack_program::NativeProgram;
+
+            pub const STATE_SIZE: usize = #synth_state_size;
+            pub const MODULE_COUNT: usize = #module_count;
 
-        quote! { ([#(#module_code),*], #synth_state_size) }
+            const _: () = assert!(
+                core::mem::size_of::<synth_engine::Float>() == core::mem::size_of::<#float>(),
+                "The synth was generated for another float precision than synth-engine uses"
+            );
+
+            pub struct Synth {
+                simulator: RungeKutta<[SynthModule<NativeProgram>; MODULE_COUNT]>,
+            }
+
+            impl Synth {
+                #[allow(unused_parens)]
+                pub fn new() -> Self {
+                    let modules: [SynthModule<NativeProgram>; MODULE_COUNT] = [#(#module_code),*];
+                    let loops = vec![#(#loop_code),*];
+
+                    Self {
+                        simulator: RungeKutta::rk4(STATE_SIZE)
+                            .with_module_set(modules)
+                            .with_loops(loops),
+                    }
+                }
+
+                pub fn step(&mut self, dt: #float) {
+                    self.simulator.step(dt);
+                }
+
+                pub fn process_event(&mut self, event: ControllerEvent) {
+                    self.simulator.process_event(event);
+                }
+
+                pub fn get_stereo_output(&self) -> (#float, #float) {
+                    self.simulator.get_stereo_output()
+                }
+            }
+
+            impl Default for Synth {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        })
     }
 
-    pub fn from_ini_file(filename: &str) -> Result<Self, SynthError> {
-        let spec_file = Ini::load_from_file(filename).map_err(|e| SynthError::FileError(e))?;
-        let mut synth_spec = SynthSpec::new();
+    fn codegen_static(&self, module_code: Vec<TokenStream>) -> Result<TokenStream, ModuleError> {
+        let float = float_type();
+        if let Some(cycle) = self.delay_free_loops().first() {
+            let names = cycle
+                .iter()
+                .map(|m| self.modules[*m].get_name().to_string())
+                .collect();
+
+            return Err(ModuleError::StaticLoop(names));
+        }
+
+        let mut buffer_names: Vec<Ident> = Vec::new();
+        let mut buffer_sizes: Vec<usize> = Vec::new();
+
+        for module_spec in &self.modules {
+            if let Some(size) = module_spec.buffer_size() {
+                buffer_names.push(buffer_ident(module_spec.get_name()));
+                buffer_sizes.push(size);
+            }
+        }
+
+        let synth_state_size = self.state_size();
+        let module_count = self.modules.len();
 
-        for (section, props) in spec_file {
-            if let Some(section) = section {
-                match &section.to_lowercase()[..] {
-                    "amplifier" => {
-                        let module_spec = AmpModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+        Ok(quote! {
+            use synth_engine::event::ControllerEvent;
+            use synth_engine::modules::*;
+            use synth_engine::simulator::rungekutta::RungeKutta;
+            use synth_engine::simulator::state::{State, MAX_STATE_SIZE};
+            use synth_engine::stack_program::NativeProgram;
+
+            pub const STATE_SIZE: usize = #synth_state_size;
+            pub const MODULE_COUNT: usize = #module_count;
+
+            const _: () = assert!(
+                core::mem::size_of::<synth_engine::Float>() == core::mem::size_of::<#float>(),
+                "The synth was generated for another float precision than synth-engine uses"
+            );
+
+            const _: () = assert!(STATE_SIZE <= MAX_STATE_SIZE, "Synth state is too large");
+
+            /// The delay line buffers of the synth. Large, so best placed in a
+            /// `static`, which `new` allows.
+            pub struct Buffers {
+                #(pub #buffer_names: [#float; #buffer_sizes],)*
+            }
+
+            impl Buffers {
+                pub const fn new() -> Self {
+                    Self {
+                        #(#buffer_names: [0.0; #buffer_sizes],)*
                     }
-                    "contour" => {
-                        let module_spec = ContourModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                }
+            }
+
+            impl Default for Buffers {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            pub struct Synth<'a> {
+                simulator: RungeKutta<[SynthModule<NativeProgram, &'a mut [#float]>; MODULE_COUNT]>,
+            }
+
+            impl<'a> Synth<'a> {
+                #[allow(unused_parens)]
+                pub fn new(buffers: &'a mut Buffers) -> Self {
+                    let Buffers { #(#buffer_names),* } = buffers;
+                    let modules: [SynthModule<NativeProgram, &'a mut [#float]>; MODULE_COUNT] =
+                        [#(#module_code),*];
+
+                    Self {
+                        simulator: RungeKutta::rk4(STATE_SIZE).with_module_set(modules),
                     }
-                    "filter_24db" => {
-                        let module_spec = Filter24dbModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                }
+
+                pub fn step(&mut self, dt: #float) {
+                    self.simulator.step(dt);
+                }
+
+                pub fn process_event(&mut self, event: ControllerEvent) {
+                    self.simulator.process_event(event);
+                }
+
+                pub fn get_stereo_output(&self) -> (#float, #float) {
+                    self.simulator.get_stereo_output()
+                }
+            }
+        })
+    }
+
+    fn codegen_fixed(&self, module_code: Vec<TokenStream>) -> Result<TokenStream, ModuleError> {
+        if let Some(cycle) = self.delay_free_loops().first() {
+            let names = cycle
+                .iter()
+                .map(|m| self.modules[*m].get_name().to_string())
+                .collect();
+
+            return Err(ModuleError::FixedPointLoop(names));
+        }
+
+        let synth_state_size = self.state_size();
+        let module_count = self.modules.len();
+
+        Ok(quote! {
+            use synth_engine::event::ControllerEvent;
+            use synth_engine::fixed::module::FixedSynthModule as SynthModule;
+            use synth_engine::fixed::program::NativeFixedProgram;
+            use synth_engine::fixed::rungekutta::FixedRungeKutta;
+            use synth_engine::fixed::state::FixedState;
+            use synth_engine::fixed::{Fixed, TimeStep};
+            use synth_engine::modules::*;
+
+            pub const STATE_SIZE: usize = #synth_state_size;
+            pub const MODULE_COUNT: usize = #module_count;
+
+            pub struct Synth {
+                simulator: FixedRungeKutta<[SynthModule<NativeFixedProgram>; MODULE_COUNT]>,
+            }
+
+            impl Synth {
+                #[allow(unused_parens)]
+                pub fn new() -> Self {
+                    let modules: [SynthModule<NativeFixedProgram>; MODULE_COUNT] =
+                        [#(#module_code),*];
+
+                    Self {
+                        simulator: FixedRungeKutta::rk4(STATE_SIZE, modules),
                     }
-                    "filter_12db" => {
-                        let module_spec = Filter12dbModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                }
+
+                pub fn step(&mut self, dt: TimeStep) {
+                    self.simulator.step(dt);
+                }
+
+                pub fn process_event(&mut self, event: ControllerEvent) {
+                    self.simulator.process_event(event);
+                }
+
+                pub fn get_stereo_output(&self) -> (Fixed, Fixed) {
+                    self.simulator.get_stereo_output()
+                }
+            }
+
+            impl Default for Synth {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+        })
+    }
+
+    /// Load a synth from a patch file. File names in the patch are relative to
+    /// the directory of the patch.
+    pub fn from_ini_file(filename: &str) -> Result<Self, SynthError> {
+        let mut spec_file = Ini::load_from_file(filename).map_err(|e| SynthError::FileError(e))?;
+
+        resolve_file_names(&mut spec_file, patch_dir(filename));
+
+        Self::from_ini(spec_file)
+    }
+
+    pub fn from_ini_str(spec: &str) -> Result<Self, SynthError> {
+        let spec_file = Ini::load_from_str(spec).map_err(SynthError::ParseError)?;
+
+        Self::from_ini(spec_file)
+    }
+
+    /// Load a synth from a patch. The state of the synth is allocated, and a
+    /// delay-free loop that the loop solver doesn't resolve from the probe
+    /// values of `probe_loops` is an error.
+    pub fn from_ini(spec_file: Ini) -> Result<Self, SynthError> {
+        let settings = spec_file
+            .section(None::<String>)
+            .cloned()
+            .unwrap_or_default();
+        let mut synth_spec = Self::from_sections(expand_sub_patches(spec_file)?)?;
+
+        synth_spec.apply_settings(&settings)?;
+        synth_spec.allocate_state();
+
+        if let Some((modules, residual)) = synth_spec.probe_loops()?.into_iter().next() {
+            return Err(ModuleError::UnresolvedLoop(modules, residual).into());
+        }
+
+        Ok(synth_spec)
+    }
+
+    /// Apply the general section of a patch: the evaluation order and the
+    /// settings of the loop solver.
+    pub fn apply_settings(&mut self, props: &Properties) -> Result<(), SynthError> {
+        let mut evaluation_order: Vec<&str> = Vec::new();
+        let mut loop_solver = LoopSolver::FixedPoint;
+        let mut loop_iterations = DEFAULT_MAX_ITERATIONS;
+        let mut loop_tolerance = DEFAULT_TOLERANCE;
+
+        for (k, v) in props.iter() {
+            let invalid = || SynthError::InvalidSetting(k.to_string(), v.to_string());
+
+            match k {
+                EVALUATION_ORDER => {
+                    evaluation_order = v
+                        .split(',')
+                        .map(|m| m.trim())
+                        .filter(|m| !m.is_empty())
+                        .collect();
+                }
+                LOOP_SOLVER => {
+                    loop_solver = match v.trim() {
+                        FIXED_POINT_SOLVER => LoopSolver::FixedPoint,
+                        NEWTON_SOLVER => LoopSolver::Newton,
+                        _ => return Err(invalid()),
                     }
-                    "filter_6db" => {
-                        let module_spec = Filter6dbModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                }
+                LOOP_ITERATIONS => loop_iterations = v.trim().parse().map_err(|_| invalid())?,
+                LOOP_TOLERANCE => loop_tolerance = v.trim().parse().map_err(|_| invalid())?,
+                _ => {}
+            }
+        }
+
+        self.set_evaluation_order(&evaluation_order)?;
+        self.set_loop_solver(loop_solver, loop_iterations, loop_tolerance);
+
+        Ok(())
+    }
+
+    /// Build a synth from module sections, as returned by `expand_sub_patches`.
+    pub fn from_sections(sections: Vec<(String, Properties)>) -> Result<Self, SynthError> {
+        let mut synth_spec = SynthSpec::new();
+
+        for (section, props) in sections {
+            match &section[..] {
+                "amplifier" => {
+                    let module_spec = AmpModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "contour" => {
+                    let module_spec = ContourModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "adsr" => {
+                    let module_spec = AdsrModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "lfo" => {
+                    let module_spec = LfoModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "filter_24db" => {
+                    let module_spec = Filter24dbModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "filter_12db" => {
+                    let module_spec = Filter12dbModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "filter_6db" => {
+                    let module_spec = Filter6dbModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "diode_ladder" => {
+                    let module_spec = DiodeLadderModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "sallen_key" => {
+                    let module_spec = SallenKeyModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "sem_filter" => {
+                    let module_spec = SemFilterModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "control" => {
+                    let module_spec = ControlModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "mono_keys" => {
+                    let module_spec = MonoKeysModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "poly_keys" => {
+                    let module_spec = PolyKeysModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "mono_out" => {
+                    let module_spec = MonoOutputModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "bowed_oscillator" => {
+                    let module_spec = BowedOscillatorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "wavetable" => {
+                    let module_spec = WavetableOscillatorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "vosim" => {
+                    let module_spec = VosimOscillatorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "quadrature_oscillator" => {
+                    let module_spec = QuadOscillatorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "delay_line" => {
+                    let module_spec = DelayLineModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "sample_hold" => {
+                    let module_spec = SampleHoldModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "slew" => {
+                    let module_spec = SlewModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "quantizer" => {
+                    let module_spec = QuantizerModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "mixer" => {
+                    let module_spec = MixerModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "integrator" => {
+                    let module_spec = IntegratorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "differentiator" => {
+                    let module_spec = DifferentiatorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "ode" => {
+                    let module_spec = OdeModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "state_space" => {
+                    let module_spec = StateSpaceModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                "noise" => {
+                    let module_spec = NoiseGeneratorModuleSpec::from_ini_properties(props)?;
+                    synth_spec.add_module(Box::new(module_spec))?;
+                }
+                x => return Err(SynthError::UnknownModule(x.to_string())),
+            }
+        }
+
+        Ok(synth_spec)
+    }
+}
+
+/// Generate a table of items of type `item_type`, as stored by wavetable
+/// modules: a vector in the `Alloc` mode, a static array in the `Static` mode.
+pub fn codegen_table(
+    mode: CodegenMode,
+    item_type: TokenStream,
+    items: &[TokenStream],
+) -> TokenStream {
+    let len = items.len();
+
+    match mode {
+        CodegenMode::Alloc => quote! { vec![#(#items),*] },
+        CodegenMode::Static | CodegenMode::Fixed => quote! {{
+            static TABLE: [#item_type; #len] = [#(#items),*];
+            &TABLE
+        }},
+    }
+}
+
+/// The float type of generated code, `f32` or `f64` as `Float` of the engine
+/// the designer is built with.
+pub fn float_type() -> TokenStream {
+    if std::mem::size_of::<Float>() == std::mem::size_of::<f64>() {
+        quote! { f64 }
+    } else {
+        quote! { f32 }
+    }
+}
+
+/// A module name made fit for Rust identifiers and file names.
+fn rust_name(module_name: &str) -> String {
+    module_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The name of the buffer of a module in the `Buffers` struct emitted by the
+/// static code generation mode.
+pub fn buffer_ident(module_name: &str) -> Ident {
+    format_ident!("{}_buffer", rust_name(module_name))
+}
+
+/// Generate a native function for an input expression, wrapped as a
+/// `NativeProgram`. The stack program is evaluated symbolically, so the
+/// generated code does the same operations in the same order as the
+/// interpreter.
+pub fn gen_program(stack_program: &StackProgram) -> TokenStream {
+    let float = float_type();
+    let mut stack: Vec<TokenStream> = Vec::new();
+    let mut reads_state = false;
+
+    for instr in &stack_program.code {
+        use Instr::*;
+
+        let mut pop = || stack.pop().expect("stack underflow in input program");
+
+        let value = match instr {
+            Add => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a + #b) }
+            }
+            Subtract => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a - #b) }
+            }
+            Multiply => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a * #b) }
+            }
+            Divide => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a / #b) }
+            }
+            Negate => {
+                let a = pop();
+                quote! { (#a * -1.0) }
+            }
+            Const(v) => quote! { #v },
+            State(s) => {
+                reads_state = true;
+                quote! { state.get(#s) }
+            }
+            Call(f) => {
+                use Function::*;
+
+                match f {
+                    Sin => {
+                        let a = pop();
+                        quote! { synth_engine::math::sin(#a) }
                     }
-                    "control" => {
-                        let module_spec = ControlModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Cos => {
+                        let a = pop();
+                        quote! { synth_engine::math::cos(#a) }
                     }
-                    "mono_keys" => {
-                        let module_spec = MonoKeysModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Tan => {
+                        let a = pop();
+                        quote! { synth_engine::math::tan(#a) }
                     }
-                    "mono_out" => {
-                        let module_spec = MonoOutputModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Tanh => {
+                        let a = pop();
+                        quote! { synth_engine::math::tanh(#a) }
                     }
-                    "bowed_oscillator" => {
-                        let module_spec = BowedOscillatorModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Ln => {
+                        let a = pop();
+                        quote! { synth_engine::math::ln(#a) }
                     }
-                    "wavetable" => {
-                        let module_spec =
-                            WavetableOscillatorModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Exp => {
+                        let a = pop();
+                        quote! { synth_engine::math::exp(#a) }
                     }
-                    "vosim" => {
-                        let module_spec = VosimOscillatorModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Abs => {
+                        let a = pop();
+                        quote! { #float::abs(#a) }
                     }
-                    "quadrature_oscillator" => {
-                        let module_spec = QuadOscillatorModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Min => {
+                        let (a, b) = (pop(), pop());
+                        quote! { #float::min(#a, #b) }
                     }
-                    "delay_line" => {
-                        let module_spec = DelayLineModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Max => {
+                        let (a, b) = (pop(), pop());
+                        quote! { #float::max(#a, #b) }
                     }
-                    "noise" => {
-                        let module_spec = NoiseGeneratorModuleSpec::from_ini_properties(props)?;
-                        synth_spec.add_module(Box::new(module_spec))?;
+                    Logistic => {
+                        let (x0, k, l, x) = (pop(), pop(), pop(), pop());
+                        quote! { (#l / (1.0 + synth_engine::math::exp(-#k * (#x - #x0)))) }
+                    }
+                    Lerp => {
+                        let (hi, lo, x) = (pop(), pop(), pop());
+                        quote! {{
+                            let x = #float::clamp(#x, 0.0, 1.0);
+                            #lo * (1.0 - x) + #hi * x
+                        }}
                     }
-                    x => return Err(SynthError::UnknownModule(x.to_string())),
-                }
-            } else {
-                for (k, v) in props.iter() {
-                    println!("{}: {}", k, v);
                 }
             }
-        }
+        };
 
-        Ok(synth_spec)
+        stack.push(value);
+    }
+
+    let value = stack.pop().expect("empty input program");
+
+    if reads_state {
+        quote! { NativeProgram(|state: &State| -> #float { #value }) }
+    } else {
+        quote! { NativeProgram(|_state: &State| -> #float { #value }) }
     }
 }
 
-pub fn gen_stack_program(stack_program: &StackProgram) -> TokenStream {
-    let mut prg: Vec<TokenStream> = Vec::new();
+/// Generate a stack program as a native function in fixed point, with the
+/// arithmetic of `FixedStackProgram`.
+pub fn gen_fixed_program(stack_program: &StackProgram) -> TokenStream {
+    let mut stack: Vec<TokenStream> = Vec::new();
+    let mut reads_state = false;
 
     for instr in &stack_program.code {
         use Instr::*;
 
-        match instr {
-            Add => prg.push(quote! { Add }),
-            Subtract => prg.push(quote! { Subtract }),
-            Multiply => prg.push(quote! { Multiply }),
-            Divide => prg.push(quote! { Divide }),
-            Negate => prg.push(quote! { Negate }),
-            Const(v) => prg.push(quote! { Const(#v) }),
-            State(s) => prg.push(quote! { State(#s) }),
+        let mut pop = || stack.pop().expect("stack underflow in input program");
+
+        let value = match instr {
+            Add => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a + #b) }
+            }
+            Subtract => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a - #b) }
+            }
+            Multiply => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a * #b) }
+            }
+            Divide => {
+                let (a, b) = (pop(), pop());
+                quote! { (#a / #b) }
+            }
+            Negate => {
+                let a = pop();
+                quote! { (-#a) }
+            }
+            Const(v) => {
+                // Unsuffixed, so that the engine may use either float precision
+                let v = Literal::f64_unsuffixed(*v as f64);
+
+                quote! {{
+                    const C: Fixed = Fixed::from_float(#v);
+                    C
+                }}
+            }
+            State(s) => {
+                reads_state = true;
+                quote! { state.get(#s) }
+            }
             Call(f) => {
                 use Function::*;
 
                 match f {
-                    Sin => prg.push(quote! { Call(Sin) }),
-                    Cos => prg.push(quote! { Call(Cos) }),
-                    Tan => prg.push(quote! { Call(Tan) }),
-                    Tanh => prg.push(quote! { Call(Tanh) }),
-                    Ln => prg.push(quote! { Call(Ln) }),
-                    Exp => prg.push(quote! { Call(Exp) }),
-                    Logistic => prg.push(quote! { Call(Logistic) }),
-                    Abs => prg.push(quote! { Call(Abs) }),
-                    Min => prg.push(quote! { Call(Min) }),
-                    Max => prg.push(quote! { Call(Max) }),
-                    Lerp => prg.push(quote! { Call(Lerp) }),
+                    Sin => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::sin(#a) }
+                    }
+                    Cos => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::cos(#a) }
+                    }
+                    Tan => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::tan(#a) }
+                    }
+                    Tanh => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::tanh(#a) }
+                    }
+                    Ln => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::ln(#a) }
+                    }
+                    Exp => {
+                        let a = pop();
+                        quote! { synth_engine::fixed::math::exp(#a) }
+                    }
+                    Abs => {
+                        let a = pop();
+                        quote! { Fixed::abs(#a) }
+                    }
+                    Min => {
+                        let (a, b) = (pop(), pop());
+                        quote! { Fixed::min(#a, #b) }
+                    }
+                    Max => {
+                        let (a, b) = (pop(), pop());
+                        quote! { Fixed::max(#a, #b) }
+                    }
+                    Logistic => {
+                        let (x0, k, l, x) = (pop(), pop(), pop(), pop());
+                        quote! {
+                            (#l / (Fixed::ONE + synth_engine::fixed::math::exp(-#k * (#x - #x0))))
+                        }
+                    }
+                    Lerp => {
+                        let (hi, lo, x) = (pop(), pop(), pop());
+                        quote! {{
+                            let x = Fixed::clamp(#x, Fixed::ZERO, Fixed::ONE);
+                            #lo * (Fixed::ONE - x) + #hi * x
+                        }}
+                    }
                 }
             }
-        }
+        };
+
+        stack.push(value);
     }
 
-    let stack_size = stack_program.stack_size;
+    let value = stack.pop().expect("empty input program");
 
-    quote! { StackProgram::new(vec![#(#prg),*], #stack_size) }
+    if reads_state {
+        quote! { NativeFixedProgram(|state: &FixedState| -> Fixed { #value }) }
+    } else {
+        quote! { NativeFixedProgram(|_state: &FixedState| -> Fixed { #value }) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn patch(source: &str, delay: &str) -> String {
+        format!(
+            "
+[mono_out]
+name=out
+signal_input={delay}.signal_output
+
+[delay_line]
+name={delay}
+signal_input=amp.signal_output
+
+[amplifier]
+name=amp
+signal_input={source}.signal_output + {delay}.signal_output
+
+[noise]
+name={source}
+"
+        )
+    }
+
+    fn order_of(synth_spec: &SynthSpec) -> Vec<String> {
+        let names: Vec<&str> = synth_spec.module_specs().map(|m| m.get_name()).collect();
+
+        synth_spec
+            .evaluation_order()
+            .into_iter()
+            .map(|i| names[i].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn evaluation_order() {
+        let synth_spec = SynthSpec::from_ini_str(&patch("noise", "delay")).unwrap();
+        assert_eq!(order_of(&synth_spec), vec!["noise", "delay", "out", "amp"]);
+
+        let renamed = SynthSpec::from_ini_str(&patch("a", "z")).unwrap();
+        assert_eq!(order_of(&renamed), vec!["a", "z", "out", "amp"]);
+    }
+
+    fn amp_loop(gain: &str, solver: &str) -> Result<SynthSpec, SynthError> {
+        let source = format!(
+            "
+loop_solver={solver}
+
+[amplifier]
+name=a
+signal_input=b.signal_output + 1.
+linear_control={gain}
+
+[amplifier]
+name=b
+signal_input=a.signal_output
+linear_control={gain}
+
+[delay_line]
+name=delay
+signal_input=a.signal_output
+pitch_control=b.signal_output
+
+[amplifier]
+name=c
+signal_input=delay.signal_output
+"
+        );
+        SynthSpec::from_ini_str(&source)
+    }
+
+    #[test]
+    fn delay_free_loops() {
+        let synth_spec = amp_loop("0.5", FIXED_POINT_SOLVER).unwrap();
+        assert_eq!(synth_spec.delay_free_loops(), vec![vec![0, 1]]);
+        assert_eq!(synth_spec.make_loops().unwrap().len(), 1);
+        assert!(synth_spec.probe_loops().unwrap().is_empty());
+
+        assert!(matches!(
+            amp_loop("2.", FIXED_POINT_SOLVER),
+            Err(SynthError::ModuleError(ModuleError::UnresolvedLoop(_, _)))
+        ));
+
+        let synth_spec = amp_loop("2.", NEWTON_SOLVER).unwrap();
+        assert!(synth_spec.probe_loops().unwrap().is_empty());
+    }
+
+    #[test]
+    fn evaluation_order_override() {
+        let source = format!("evaluation_order=amp, out\n{}", patch("noise", "delay"));
+        let synth_spec = SynthSpec::from_ini_str(&source).unwrap();
+        assert_eq!(order_of(&synth_spec), vec!["amp", "out", "noise", "delay"]);
+
+        let source = format!("evaluation_order=nothing\n{}", patch("noise", "delay"));
+        assert!(SynthSpec::from_ini_str(&source).is_err());
+    }
+
+    #[test]
+    fn fixed_point_codegen() {
+        let mut synth_spec = SynthSpec::from_ini_str(&patch("noise", "delay")).unwrap();
+        synth_spec.set_codegen_mode(CodegenMode::Fixed);
+        assert!(matches!(
+            synth_spec.codegen(),
+            Err(ModuleError::NotFixedPoint(name)) if name == "delay"
+        ));
+
+        let source = "
+[amplifier]
+name=a
+signal_input=b.signal_output + 1.
+linear_control=0.5
+
+[amplifier]
+name=b
+signal_input=a.signal_output
+linear_control=0.5
+";
+        let mut synth_spec = SynthSpec::from_ini_str(source).unwrap();
+        synth_spec.set_codegen_mode(CodegenMode::Fixed);
+        assert!(matches!(
+            synth_spec.codegen(),
+            Err(ModuleError::FixedPointLoop(_))
+        ));
+
+        let source = "[amplifier]\nname=a\nsignal_input=tanh(1.5) * 0.5\n";
+        let mut synth_spec = SynthSpec::from_ini_str(source).unwrap();
+        synth_spec.set_codegen_mode(CodegenMode::Fixed);
+        let code = synth_spec.codegen().unwrap().to_string();
+        assert!(code.contains("FixedRungeKutta"));
+        assert!(code.contains("synth_engine :: fixed :: math :: tanh"));
+    }
+
+    #[test]
+    fn samples_write_error() {
+        let source = "[wavetable]\nname=osc\nharmonics=1.0\ntable_size=16";
+        let mut synth_spec = SynthSpec::from_ini_str(source).unwrap();
+        assert!(synth_spec.codegen().is_ok());
+
+        synth_spec.set_data_dir(Path::new("/nonexistent/data"));
+        assert!(matches!(
+            synth_spec.codegen(),
+            Err(ModuleError::DataFile(_, _))
+        ));
+    }
+
+    #[test]
+    fn table_fields_from_file() {
+        let dir = std::env::temp_dir().join(format!("synth_spec_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(dir.join("ramp.wav"), spec).unwrap();
+        for i in 0..64 {
+            writer.write_sample(i * 1000 - 32000).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // The file names are resolved, the order of the table fields is kept
+        let patch = |wavetable: &str| {
+            format!(
+                "[wavetable]\nname=osc\ntable_size=64\nshape=saw\nwavetable={wavetable}\n\
+                 harmonics=1., 0.5\nwavetable={wavetable}\nexpression=x\n"
+            )
+        };
+        let filename = dir.join("patch.ini");
+        std::fs::write(&filename, patch("ramp.wav")).unwrap();
+
+        let from_file = SynthSpec::from_ini_file(filename.to_str().unwrap()).unwrap();
+        let from_str =
+            SynthSpec::from_ini_str(&patch(dir.join("ramp.wav").to_str().unwrap())).unwrap();
+        assert_eq!(
+            from_file.codegen().unwrap().to_string(),
+            from_str.codegen().unwrap().to_string()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
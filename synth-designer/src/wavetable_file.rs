@@ -0,0 +1,262 @@
+//! Loading of wavetables from WAV files.
+//!
+//! All sample formats `hound` reads are supported: 8 to 32 bit integers and 32
+//! bit floats. Files with several channels are mixed down, unless a channel is
+//! selected.
+//!
+//! A file can hold several single cycle frames back to back, each becoming a
+//! scan position of the oscillator. The frame size is given explicitly, or
+//! read from a `clm ` chunk as written by Serum and WaveEdit, whose text
+//! starts with `<!>` and the frame size, e.g. `<!>2048 01000000 wavetable`.
+//! Without either, the whole file is one frame.
+
+use crate::modules::ModuleError;
+use ini::Ini;
+use std::io::Cursor;
+use std::path::Path;
+use synth_engine::Float;
+
+/// Keys of module sections that name a file.
+const FILE_FIELDS: &[&str] = &["wavetable", "scale"];
+
+const CLM_CHUNK: &[u8; 4] = b"clm ";
+const CLM_PREFIX: &[u8] = b"<!>";
+
+/// How to read the frames of a wavetable file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WavetableOptions {
+    /// The channel to read, or all channels mixed down if `None`.
+    pub channel: Option<usize>,
+    /// The number of samples of a frame, overriding the `clm ` chunk.
+    pub frame_size: Option<usize>,
+}
+
+fn file_error(filename: &Path, reason: impl Into<String>) -> ModuleError {
+    ModuleError::WavetableFile(filename.display().to_string(), reason.into())
+}
+
+/// The frame size in the `clm ` chunk of a RIFF file, if there is one.
+fn clm_frame_size(bytes: &[u8]) -> Option<usize> {
+    let mut chunks = bytes.get(12..)?;
+
+    while chunks.len() >= 8 {
+        let size = u32::from_le_bytes(chunks[4..8].try_into().ok()?) as usize;
+        let data = chunks.get(8..8 + size)?;
+
+        if &chunks[..4] == CLM_CHUNK {
+            let text = data.strip_prefix(CLM_PREFIX)?;
+            let digits = text.iter().take_while(|c| c.is_ascii_digit()).count();
+
+            return std::str::from_utf8(&text[..digits]).ok()?.parse().ok();
+        }
+
+        // Chunks are padded to an even size
+        chunks = chunks.get(8 + size + (size & 1)..)?;
+    }
+
+    None
+}
+
+fn read_samples(bytes: &[u8], filename: &Path) -> Result<(Vec<Float>, usize), ModuleError> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+
+    let samples: Vec<Float> = match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Float, 32) => reader
+            .samples::<f32>()
+            .map(|sample| sample.map(|v| v as Float))
+            .collect::<Result<_, _>>()?,
+        (hound::SampleFormat::Int, bits @ 1..=32) => {
+            let max = ((1_i64 << (bits - 1)) - 1).max(1) as Float;
+
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|v| v as Float / max))
+                .collect::<Result<_, _>>()?
+        }
+        (format, bits) => {
+            return Err(file_error(
+                filename,
+                format!("unsupported format {:?} with {} bits", format, bits),
+            ))
+        }
+    };
+
+    Ok((samples, spec.channels as usize))
+}
+
+/// Load the frames of a wavetable file.
+pub fn load_wavetable(
+    filename: &Path,
+    options: WavetableOptions,
+) -> Result<Vec<Vec<Float>>, ModuleError> {
+    let bytes = std::fs::read(filename).map_err(|err| file_error(filename, err.to_string()))?;
+    let (samples, channels) = read_samples(&bytes, filename)?;
+
+    let samples: Vec<Float> = match options.channel {
+        Some(channel) if channel >= channels => {
+            return Err(file_error(
+                filename,
+                format!(
+                    "no channel {} in a file with {} channels",
+                    channel, channels
+                ),
+            ))
+        }
+        Some(channel) => samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect(),
+        None => samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<Float>() / channels as Float)
+            .collect(),
+    };
+
+    let frame_size = options
+        .frame_size
+        .or_else(|| clm_frame_size(&bytes))
+        .unwrap_or(samples.len());
+
+    if samples.is_empty() || frame_size == 0 || samples.len() % frame_size != 0 {
+        return Err(file_error(
+            filename,
+            format!(
+                "{} samples don't make frames of {} samples",
+                samples.len(),
+                frame_size
+            ),
+        ));
+    }
+
+    Ok(samples
+        .chunks(frame_size)
+        .map(|frame| frame.to_vec())
+        .collect())
+}
+
+/// The directory that file names in the patch file `filename` are relative to.
+pub fn patch_dir(filename: &str) -> &Path {
+    Path::new(filename).parent().unwrap_or(Path::new(""))
+}
+
+/// Make the file names of a patch relative to `base_dir`, the directory of the
+/// patch file. Absolute file names are kept as they are. The values are
+/// rewritten in place, as the order of the table fields is the scan order.
+pub fn resolve_file_names(spec_file: &mut Ini, base_dir: &Path) {
+    for (_, props) in spec_file.iter_mut() {
+        for (key, value) in props.iter_mut() {
+            if FILE_FIELDS.contains(&key) {
+                *value = base_dir.join(value.trim()).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_wav(
+        name: &str,
+        spec: hound::WavSpec,
+        write: impl Fn(&mut hound::WavWriter<std::io::BufWriter<std::fs::File>>),
+    ) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "synth_designer_{}_{}.wav",
+            std::process::id(),
+            name
+        ));
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+
+        write(&mut writer);
+        writer.finalize().unwrap();
+        path
+    }
+
+    #[test]
+    fn sample_formats_and_frames() {
+        // 24 bit stereo, the right channel is minus half the left one
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let path = write_wav("stereo", spec, |writer| {
+            for v in [0x400000, 0x200000, -0x400000] {
+                writer.write_sample(v).unwrap();
+                writer.write_sample(-v / 2).unwrap();
+            }
+        });
+
+        let mixed = load_wavetable(&path, WavetableOptions::default()).unwrap();
+        assert_eq!(mixed.len(), 1);
+        assert!((mixed[0][0] - 0.125).abs() < 1e-6);
+
+        let options = WavetableOptions {
+            channel: Some(1),
+            ..Default::default()
+        };
+        let right = load_wavetable(&path, options).unwrap();
+        assert!((right[0][2] - 0.25).abs() < 1e-6);
+
+        let options = WavetableOptions {
+            channel: Some(2),
+            ..Default::default()
+        };
+        assert!(load_wavetable(&path, options).is_err());
+        std::fs::remove_file(path).unwrap();
+
+        // Float samples, two frames of four from a clm chunk
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let path = write_wav("frames", spec, |writer| {
+            for i in 0..8 {
+                writer.write_sample(i as f32 / 8.).unwrap();
+            }
+        });
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let clm = b"<!>4    00000000 wavetable";
+        bytes.extend_from_slice(CLM_CHUNK);
+        bytes.extend_from_slice(&(clm.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(clm);
+        let riff_size = (bytes.len() - 8) as u32;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let frames = load_wavetable(&path, WavetableOptions::default()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1], vec![0.5, 0.625, 0.75, 0.875]);
+
+        let options = WavetableOptions {
+            frame_size: Some(3),
+            ..Default::default()
+        };
+        assert!(load_wavetable(&path, options).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn file_names_relative_to_patch() {
+        let mut spec_file =
+            Ini::load_from_str("[wavetable]\nwavetable=a.wav\nwavetable=/tmp/b.wav\nname=osc\n")
+                .unwrap();
+
+        resolve_file_names(&mut spec_file, Path::new("synths"));
+
+        let props = spec_file.section(Some("wavetable")).unwrap();
+        let filenames: Vec<&str> = props.get_all("wavetable").collect();
+        assert_eq!(filenames, vec!["synths/a.wav", "/tmp/b.wav"]);
+        assert_eq!(props.get("name"), Some("osc"));
+    }
+}
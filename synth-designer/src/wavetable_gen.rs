@@ -0,0 +1,265 @@
+//! The wavetables of the wavetable and vosim oscillators, loaded from files or
+//! generated from their definition in the module section.
+//!
+//! Every table field adds scan positions, in the order of the fields:
+//!
+//! - `wavetable=<file>` loads the frames of a WAV file, see `wavetable_file`.
+//! - `harmonics=1., 0.5:0.25, 0.333` lists the amplitudes of the harmonics,
+//!   with an optional phase in cycles after a colon.
+//! - `expression=sin(6.283185 * x)` evaluates an expression over the phase `x`,
+//!   from 0 to 1.
+//! - `shape=saw` is one of `sine`, `saw`, `square`, `triangle` or `pulse:<w>`
+//!   with a pulse width `w` between 0 and 1. The pulse has no DC offset.
+//!
+//! Harmonics and shapes are band limited at every mip level, expressions are
+//! sampled and then downsampled like files. Generated tables have
+//! `table_size` samples, 2048 by default.
+
+use crate::input_expr::Expr;
+use crate::modules::ModuleError;
+use crate::wavetable_file::{load_wavetable, WavetableOptions};
+use std::path::Path;
+use synth_engine::math::consts::PI;
+use synth_engine::modules::wavetable::WavetableEntry;
+use synth_engine::simulator::state::State;
+use synth_engine::Float;
+
+const WAVETABLE_FIELD: &str = "wavetable";
+const CHANNEL_FIELD: &str = "channel";
+const FRAME_SIZE_FIELD: &str = "frame_size";
+const HARMONICS_FIELD: &str = "harmonics";
+const EXPRESSION_FIELD: &str = "expression";
+const SHAPE_FIELD: &str = "shape";
+const TABLE_SIZE_FIELD: &str = "table_size";
+
+/// The fields that each add tables, and so may be given more than once.
+pub const TABLE_FIELDS: [&str; 4] = [
+    WAVETABLE_FIELD,
+    HARMONICS_FIELD,
+    EXPRESSION_FIELD,
+    SHAPE_FIELD,
+];
+const PHASE_VARIABLE: &str = "x";
+const DEFAULT_TABLE_SIZE: usize = 2048;
+
+/// The samples of a wavetable, or its harmonics as amplitude and phase.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TableSource {
+    Samples(Vec<Float>),
+    Harmonics(Vec<(Float, Float)>, usize),
+}
+
+impl TableSource {
+    /// The table with all its mip levels.
+    pub fn entry(&self) -> WavetableEntry {
+        match self {
+            TableSource::Samples(samples) => WavetableEntry::from_slice(samples),
+            TableSource::Harmonics(harmonics, size) => {
+                WavetableEntry::from_harmonics(harmonics, *size)
+            }
+        }
+    }
+}
+
+/// The tables of a list of sources.
+pub fn precompute_tables(tables: &[TableSource]) -> Vec<WavetableEntry> {
+    tables.iter().map(|table| table.entry()).collect()
+}
+
+enum TableField {
+    File(String),
+    Harmonics(String),
+    Expression(String),
+    Shape(String),
+}
+
+/// Collects the table fields of a module section, to build the tables when
+/// all fields are read.
+#[derive(Default)]
+pub struct TableFields {
+    fields: Vec<TableField>,
+    options: WavetableOptions,
+    table_size: Option<usize>,
+}
+
+fn invalid(reason: String) -> ModuleError {
+    ModuleError::InvalidWavetable(reason)
+}
+
+fn parse_harmonics(v: &str) -> Result<Vec<(Float, Float)>, ModuleError> {
+    v.split(',')
+        .map(|harmonic| {
+            let (amplitude, phase) = harmonic.split_once(':').unwrap_or((harmonic, "0"));
+
+            Ok((
+                amplitude.trim().parse::<Float>()?,
+                phase.trim().parse::<Float>()?,
+            ))
+        })
+        .collect()
+}
+
+/// The harmonics of a classic waveform, below the Nyquist frequency of a
+/// table of `size` samples.
+fn shape_harmonics(shape: &str, size: usize) -> Result<Vec<(Float, Float)>, ModuleError> {
+    let count = (size.max(1) - 1) / 2;
+    let (name, width) = shape.split_once(':').unwrap_or((shape, "0.5"));
+    let width = width.trim().parse::<Float>()?;
+
+    let harmonic = |k: usize| -> (Float, Float) {
+        let k_f = k as Float;
+        let odd = k % 2 == 1;
+
+        match name.trim() {
+            "sine" if k == 1 => (1., 0.),
+            "saw" => (-2. / (PI * k_f), 0.),
+            "square" if odd => (4. / (PI * k_f), 0.),
+            "triangle" if odd => {
+                let sign = if k % 4 == 1 { 1. } else { -1. };
+
+                (sign * 8. / (PI * PI * k_f * k_f), 0.)
+            }
+            "pulse" => {
+                // As sine and cosine parts
+                let a = 2. / (PI * k_f) * (1. - (2. * PI * k_f * width).cos());
+                let b = 2. / (PI * k_f) * (2. * PI * k_f * width).sin();
+
+                (a.hypot(b), b.atan2(a) / (2. * PI))
+            }
+            _ => (0., 0.),
+        }
+    };
+
+    match name.trim() {
+        "sine" | "saw" | "square" | "triangle" => Ok((1..=count).map(harmonic).collect()),
+        "pulse" if (0. ..=1.).contains(&width) => Ok((1..=count).map(harmonic).collect()),
+        _ => Err(invalid(format!("unknown shape {}", shape))),
+    }
+}
+
+fn expression_samples(v: &str, size: usize) -> Result<Vec<Float>, ModuleError> {
+    let program = Expr::parse(v)?.compile_with_variables(&[PHASE_VARIABLE])?;
+    let mut stack = vec![0.; program.stack_size];
+
+    (0..size)
+        .map(|i| {
+            let state = State::new_with_values(&[i as Float / size as Float]);
+
+            program
+                .run(&state, &mut stack)
+                .map_err(|err| invalid(format!("error evaluating {}: {:?}", v, err)))
+        })
+        .collect()
+}
+
+impl TableFields {
+    /// Take a field of a module section if it is a table field. Returns
+    /// whether it was.
+    pub fn parse_field(&mut self, k: &str, v: &str) -> Result<bool, ModuleError> {
+        match k {
+            WAVETABLE_FIELD => self.fields.push(TableField::File(v.to_string())),
+            HARMONICS_FIELD => self.fields.push(TableField::Harmonics(v.to_string())),
+            EXPRESSION_FIELD => self.fields.push(TableField::Expression(v.to_string())),
+            SHAPE_FIELD => self.fields.push(TableField::Shape(v.to_string())),
+            CHANNEL_FIELD => self.options.channel = Some(v.parse::<usize>()?),
+            FRAME_SIZE_FIELD => self.options.frame_size = Some(v.parse::<usize>()?),
+            TABLE_SIZE_FIELD => self.table_size = Some(v.parse::<usize>()?),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Load and generate the tables. A module needs at least one.
+    pub fn build(self, module_type: &str) -> Result<Vec<TableSource>, ModuleError> {
+        let size = self.table_size.unwrap_or(DEFAULT_TABLE_SIZE);
+        let mut tables = Vec::new();
+
+        if size < 4 {
+            return Err(invalid(format!("table size {} is below 4", size)));
+        }
+
+        for field in self.fields {
+            match field {
+                TableField::File(filename) => tables.extend(
+                    load_wavetable(Path::new(&filename), self.options)?
+                        .into_iter()
+                        .map(TableSource::Samples),
+                ),
+                TableField::Harmonics(v) => {
+                    tables.push(TableSource::Harmonics(parse_harmonics(&v)?, size))
+                }
+                TableField::Expression(v) => {
+                    tables.push(TableSource::Samples(expression_samples(&v, size)?))
+                }
+                TableField::Shape(v) => {
+                    tables.push(TableSource::Harmonics(shape_harmonics(&v, size)?, size))
+                }
+            }
+        }
+
+        if tables.is_empty() {
+            Err(ModuleError::MissingField(
+                module_type.to_string(),
+                WAVETABLE_FIELD.to_string(),
+            ))
+        } else {
+            Ok(tables)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_tables(fields: &[(&str, &str)]) -> Result<Vec<TableSource>, ModuleError> {
+        let mut table_fields = TableFields::default();
+
+        for (k, v) in fields {
+            assert!(table_fields.parse_field(k, v).unwrap());
+        }
+
+        table_fields.build("wavetable")
+    }
+
+    #[test]
+    fn generated_tables() {
+        let tables = build_tables(&[
+            ("table_size", "64"),
+            ("expression", "2. * x - 1."),
+            ("shape", "saw"),
+            ("harmonics", "1., 0.5:0.25"),
+        ])
+        .unwrap();
+
+        // The expression and the band limited saw agree away from the jump
+        let TableSource::Samples(ramp) = &tables[0] else {
+            panic!("expression should give samples");
+        };
+        let saw = &tables[1].entry().data[0].samples;
+        assert_eq!(ramp.len(), 64);
+        assert!((ramp[16] + 0.5).abs() < 1e-6);
+        assert!((saw[16] - ramp[16]).abs() < 0.05);
+        assert!((saw[40] - ramp[40]).abs() < 0.05);
+
+        assert_eq!(
+            tables[2],
+            TableSource::Harmonics(vec![(1., 0.), (0.5, 0.25)], 64)
+        );
+
+        assert!(build_tables(&[("shape", "pulse:0.25"), ("shape", "noise")]).is_err());
+        assert!(build_tables(&[("table_size", "64")]).is_err());
+    }
+
+    #[test]
+    fn pulse_width() {
+        let pulse = shape_harmonics("pulse:0.25", 256).unwrap();
+        let entry = TableSource::Harmonics(pulse, 256).entry();
+        let samples = &entry.data[0].samples;
+
+        // High for a quarter of the cycle, around -0.5 and 1.5 without DC
+        assert!((samples[32] - 1.5).abs() < 0.05);
+        assert!((samples[160] + 0.5).abs() < 0.05);
+    }
+}
@@ -1,4 +1,7 @@
-fn smoothstep(x: f32) -> f32 {
+use crate::math;
+use crate::Float;
+
+fn smoothstep(x: Float) -> Float {
     let x = x.clamp(0., 1.);
     let x2 = x * x;
     let x3 = x * x2;
@@ -7,28 +10,29 @@ fn smoothstep(x: f32) -> f32 {
 }
 
 #[allow(non_snake_case)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DistortionType {
     Tanh,
     Smoothstep,
     Diodelike {
-        a: f32,
-        b: f32,
-        c: f32,
-        ln_R: f32,
-        R: f32,
+        a: Float,
+        b: Float,
+        c: Float,
+        ln_R: Float,
+        R: Float,
     },
-    Logistic(f32),
+    Logistic(Float),
 }
 
 #[allow(non_snake_case)]
-pub fn make_diodelike(R: f32, n: f32) -> DistortionType {
+pub fn make_diodelike(R: Float, n: Float) -> DistortionType {
     let V_T = 0.026;
     let I_S = 10e-12;
 
-    let a = (I_S * R / (n * V_T)).ln();
+    let a = math::ln(I_S * R / (n * V_T));
     let b = 1. / (n * V_T);
     let c = n * V_T / R;
-    let ln_R = R.ln();
+    let ln_R = math::ln(R);
 
     DistortionType::Diodelike { a, b, c, ln_R, R }
 }
@@ -37,13 +41,13 @@ pub trait Distort {
     fn distort(&self, tpe: &Option<DistortionType>) -> Self;
 }
 
-impl Distort for f32 {
+impl Distort for Float {
     fn distort(&self, tpe: &Option<DistortionType>) -> Self {
         use DistortionType::*;
 
         match tpe {
             None => *self,
-            Some(Tanh) => self.tanh(),
+            Some(Tanh) => math::tanh(*self),
             Some(Smoothstep) => smoothstep(0.5 + self / 2.) * 2. - 1.,
             Some(Diodelike { a, b, c, ln_R, R }) => {
                 let x = ln_R + a + b * self;
@@ -51,11 +55,11 @@ impl Distort for f32 {
                     *self
                 } else {
                     #[allow(non_snake_case)]
-                    let I = c * (x - x.ln());
+                    let I = c * (x - math::ln(x));
                     *self - I * R
                 }
             }
-            Some(Logistic(amount)) => 2. / (1. + (-amount * self).exp()) - 1.,
+            Some(Logistic(amount)) => 2. / (1. + math::exp(-amount * self)) - 1.,
         }
     }
 }
@@ -1,29 +1,39 @@
 //! Types for events that the synth engine can receive for real time control.
 
+use crate::Float;
+
+/// Controller events, with the MIDI channel they came on, from 0 to 15. Most
+/// modules ignore the channel, MPE keys use it to tell notes apart.
 #[derive(Debug, Clone, Copy)]
 pub enum ControllerEvent {
     NoteOn {
+        channel: u8,
         pitch: u8,
-        velocity: f32,
-        pitch_value: f32,
+        velocity: Float,
+        pitch_value: Float,
     },
     NoteOff {
+        channel: u8,
         pitch: u8,
-        velocity: f32,
+        velocity: Float,
     },
     Aftertouch {
-        amount: f32,
+        channel: u8,
+        amount: Float,
     },
     PolyAftertouch {
+        channel: u8,
         pitch: u8,
-        amount: f32,
+        amount: Float,
     },
     ContinuousControl {
+        channel: u8,
         control: usize,
-        value: f32,
+        value: Float,
     },
     PitchWheel {
-        amount: f32,
+        channel: u8,
+        amount: Float,
     },
 }
 
@@ -36,13 +46,13 @@ const MIDI_PROGRAM_CHANGE: u8 = 0xC0;
 const MIDI_PITCH_WHEEL: u8 = 0xE0;
 const PITCH_WHEEL_MID: u16 = 0x2000;
 
-fn u7_to_f32(v: u8) -> f32 {
-    ((v & 0x7F) as f32) / (0x7F as f32)
+fn u7_to_f32(v: u8) -> Float {
+    ((v & 0x7F) as Float) / (0x7F as Float)
 }
 
-fn pitchwheel(msb: u8, lsb: u8) -> f32 {
+fn pitchwheel(msb: u8, lsb: u8) -> Float {
     let x: u16 = (((msb & 0x7F) as u16) << 7) | ((lsb & 0x7F) as u16);
-    ((x as f32) - (PITCH_WHEEL_MID as f32)) / (PITCH_WHEEL_MID as f32)
+    ((x as Float) - (PITCH_WHEEL_MID as Float)) / (PITCH_WHEEL_MID as Float)
 }
 
 pub fn decode_midi_bytes(bytes: &[u8], channel: Option<u8>) -> Option<ControllerEvent> {
@@ -56,29 +66,34 @@ pub fn decode_midi_bytes(bytes: &[u8], channel: Option<u8>) -> Option<Controller
 
             match (bytes[0] & 0xF0, bytes) {
                 (MIDI_NOTE_OFF, [_, pitch, v]) => Some(NoteOff {
+                    channel: chn,
                     pitch: *pitch,
                     velocity: u7_to_f32(*v),
                 }),
                 (MIDI_NOTE_ON, [_, pitch, v]) => {
                     if *v == 0 {
-                        Some(NoteOff { pitch: *pitch, velocity: 0. })
+                        Some(NoteOff { channel: chn, pitch: *pitch, velocity: 0. })
                     } else {
-                        Some(NoteOn { pitch: *pitch, velocity: u7_to_f32(*v), pitch_value: 0. })
+                        Some(NoteOn { channel: chn, pitch: *pitch, velocity: u7_to_f32(*v), pitch_value: 0. })
                     }
                 }
                 (MIDI_POLY_AFTERTOUCH, [_, pitch, v]) => Some(PolyAftertouch {
+                    channel: chn,
                     pitch: *pitch,
                     amount: u7_to_f32(*v),
                 }),
                 (MIDI_CC, [_, c, v]) => Some(ContinuousControl {
+                    channel: chn,
                     control: *c as usize,
                     value: u7_to_f32(*v),
                 }),
                 (MIDI_CHANNEL_AFTERTOUCH, [_, v, ..]) => Some(Aftertouch {
+                    channel: chn,
                     amount: u7_to_f32(*v),
                 }),
                 (MIDI_PROGRAM_CHANGE, [_, _amount, ..]) => None,
                 (MIDI_PITCH_WHEEL, [_, d1, d2]) => Some(PitchWheel {
+                    channel: chn,
                     amount: pitchwheel(*d2, *d1),
                 }),
                 _ => None,
@@ -0,0 +1,324 @@
+//! Math functions on `Fixed`, by linear interpolation in lookup tables. The
+//! tables are computed at compile time, so no floating point is done at
+//! runtime.
+
+use super::{saturate, Fixed, FRAC_BITS};
+
+const SIN_BITS: u32 = 10;
+const TANH_BITS: u32 = 10;
+const EXP2_BITS: u32 = 8;
+const LOG2_BITS: u32 = 8;
+
+/// tanh is 1 in Q16.16 beyond this.
+const TANH_LIMIT_BITS: u32 = 3;
+
+const PI: f64 = core::f64::consts::PI;
+const LN_2: f64 = core::f64::consts::LN_2;
+
+/// 2^32 / 2π, for radians to turns in Q0.32.
+const TURNS_PER_RADIAN: i64 = 683_565_276;
+
+const LOG2_E: Fixed = Fixed::from_float(core::f64::consts::LOG2_E as crate::Float);
+const LN_2_FIXED: Fixed = Fixed::from_float(LN_2 as crate::Float);
+
+const fn to_raw(v: f64, frac_bits: u32) -> i64 {
+    let v = v * (1_u64 << frac_bits) as f64;
+
+    if v < 0. {
+        (v - 0.5) as i64
+    } else {
+        (v + 0.5) as i64
+    }
+}
+
+/// sin(x) for x in [-π, π].
+const fn sin_f64(x: f64) -> f64 {
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 1;
+
+    while n < 20 {
+        term *= -x * x / ((2 * n) * (2 * n + 1)) as f64;
+        sum += term;
+        n += 1;
+    }
+
+    sum
+}
+
+/// exp(x) for x in [0, 16].
+const fn exp_f64(x: f64) -> f64 {
+    let x = x / 16.;
+    let mut term = 1.;
+    let mut sum = 1.;
+    let mut n = 1;
+
+    while n < 20 {
+        term *= x / n as f64;
+        sum += term;
+        n += 1;
+    }
+
+    let mut i = 0;
+
+    while i < 4 {
+        sum *= sum;
+        i += 1;
+    }
+
+    sum
+}
+
+/// ln(x) for x in [1, 2].
+const fn ln_f64(x: f64) -> f64 {
+    let z = (x - 1.) / (x + 1.);
+    let mut power = z;
+    let mut sum = 0.;
+    let mut n = 0;
+
+    while n < 30 {
+        sum += power / (2 * n + 1) as f64;
+        power *= z * z;
+        n += 1;
+    }
+
+    2. * sum
+}
+
+/// One turn of sin, in Q16.16.
+const SIN_TABLE: [i32; (1 << SIN_BITS) + 1] = {
+    let mut table = [0; (1 << SIN_BITS) + 1];
+    let mut i = 0;
+
+    while i < table.len() {
+        let x = 2. * PI * i as f64 / (1 << SIN_BITS) as f64;
+        let x = if x > PI { x - 2. * PI } else { x };
+
+        table[i] = to_raw(sin_f64(x), FRAC_BITS) as i32;
+        i += 1;
+    }
+
+    table
+};
+
+/// tanh on [0, 8], in Q16.16.
+const TANH_TABLE: [i32; (1 << TANH_BITS) + 1] = {
+    let mut table = [0; (1 << TANH_BITS) + 1];
+    let mut i = 0;
+
+    while i < table.len() {
+        let x = (i << TANH_LIMIT_BITS) as f64 / (1 << TANH_BITS) as f64;
+
+        table[i] = to_raw(1. - 2. / (exp_f64(2. * x) + 1.), FRAC_BITS) as i32;
+        i += 1;
+    }
+
+    table
+};
+
+/// 2^x on [0, 1], in Q2.30 for precision when scaled up.
+const EXP2_TABLE: [u32; (1 << EXP2_BITS) + 1] = {
+    let mut table = [0; (1 << EXP2_BITS) + 1];
+    let mut i = 0;
+
+    while i < table.len() {
+        let x = i as f64 / (1 << EXP2_BITS) as f64;
+
+        table[i] = to_raw(exp_f64(x * LN_2), 30) as u32;
+        i += 1;
+    }
+
+    table
+};
+
+/// log2(1 + x) on [0, 1], in Q16.16.
+const LOG2_TABLE: [i32; (1 << LOG2_BITS) + 1] = {
+    let mut table = [0; (1 << LOG2_BITS) + 1];
+    let mut i = 0;
+
+    while i < table.len() {
+        let x = i as f64 / (1 << LOG2_BITS) as f64;
+
+        table[i] = to_raw(ln_f64(1. + x) / LN_2, FRAC_BITS) as i32;
+        i += 1;
+    }
+
+    table
+};
+
+/// Interpolate between entries `i` and `i + 1`, with `r` the position
+/// between them in Q0.16.
+fn interpolate(a: i64, b: i64, r: i64) -> i64 {
+    a + (((b - a) * r) >> 16)
+}
+
+fn sin_turns(turns: u32) -> Fixed {
+    let i = (turns >> (32 - SIN_BITS)) as usize;
+    let r = ((turns >> (16 - SIN_BITS)) & 0xFFFF) as i64;
+
+    Fixed(interpolate(SIN_TABLE[i] as i64, SIN_TABLE[i + 1] as i64, r) as i32)
+}
+
+fn radians_to_turns(x: Fixed) -> u32 {
+    // Only the fraction of a turn matters, so the wrap around is intended
+    ((x.0 as i64 * TURNS_PER_RADIAN) >> FRAC_BITS) as u32
+}
+
+pub fn sin(x: Fixed) -> Fixed {
+    sin_turns(radians_to_turns(x))
+}
+
+pub fn cos(x: Fixed) -> Fixed {
+    sin_turns(radians_to_turns(x).wrapping_add(1 << 30))
+}
+
+pub fn tan(x: Fixed) -> Fixed {
+    sin(x) / cos(x)
+}
+
+pub fn tanh(x: Fixed) -> Fixed {
+    let a = x.abs().0 as i64;
+
+    if a >= 1 << (FRAC_BITS + TANH_LIMIT_BITS) {
+        return if x.0 < 0 { -Fixed::ONE } else { Fixed::ONE };
+    }
+
+    let shift = FRAC_BITS + TANH_LIMIT_BITS - TANH_BITS;
+    let i = (a >> shift) as usize;
+    let r = (a & ((1 << shift) - 1)) << (16 - shift);
+    let v = Fixed(interpolate(TANH_TABLE[i] as i64, TANH_TABLE[i + 1] as i64, r) as i32);
+
+    if x.0 < 0 {
+        -v
+    } else {
+        v
+    }
+}
+
+pub fn exp2(x: Fixed) -> Fixed {
+    let n = x.0 >> FRAC_BITS;
+    let f = (x.0 & 0xFFFF) as i64;
+    let i = (f >> (16 - EXP2_BITS)) as usize;
+    let r = (f & ((1 << (16 - EXP2_BITS)) - 1)) << EXP2_BITS;
+    let v = interpolate(EXP2_TABLE[i] as i64, EXP2_TABLE[i + 1] as i64, r);
+
+    // v is in Q2.30, shift it to Q16.16 and scale by 2^n
+    let shift = n - (30 - FRAC_BITS as i32);
+
+    if shift >= 32 {
+        Fixed::MAX
+    } else if shift >= 0 {
+        Fixed(saturate(v << shift))
+    } else if shift > -63 {
+        Fixed(saturate((v + (1 << (-shift - 1))) >> -shift))
+    } else {
+        Fixed::ZERO
+    }
+}
+
+pub fn exp(x: Fixed) -> Fixed {
+    exp2(x * LOG2_E)
+}
+
+/// log2 of positive numbers. Zero and negative numbers give `Fixed::MIN`.
+pub fn log2(x: Fixed) -> Fixed {
+    if x.0 <= 0 {
+        return Fixed::MIN;
+    }
+
+    let msb = 31 - x.0.leading_zeros() as i32;
+    let n = msb - FRAC_BITS as i32;
+
+    // The mantissa in Q1.31, without its leading one
+    let m = ((x.0 as u32) << (31 - msb)) & 0x7FFF_FFFF;
+    let i = (m >> (31 - LOG2_BITS)) as usize;
+    let r = ((m >> (31 - LOG2_BITS - 16)) & 0xFFFF) as i64;
+    let v = interpolate(LOG2_TABLE[i] as i64, LOG2_TABLE[i + 1] as i64, r);
+
+    Fixed(((n as i64) << FRAC_BITS) as i32 + v as i32)
+}
+
+pub fn ln(x: Fixed) -> Fixed {
+    if x.0 <= 0 {
+        return Fixed::MIN;
+    }
+
+    log2(x) * LN_2_FIXED
+}
+
+pub fn sqrt(x: Fixed) -> Fixed {
+    if x.0 <= 0 {
+        return Fixed::ZERO;
+    }
+
+    Fixed(isqrt((x.0 as u64) << FRAC_BITS) as i32)
+}
+
+// Integer square root, bit by bit, as u64::isqrt needs Rust 1.84
+fn isqrt(mut v: u64) -> u64 {
+    let mut root = 0;
+    let mut bit = 1 << 62;
+
+    while bit > v {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if v >= root + bit {
+            v -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+
+        bit >>= 2;
+    }
+
+    root
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math;
+    use crate::Float;
+
+    fn range(from: Float, to: Float) -> impl Iterator<Item = Float> {
+        (0..=2000).map(move |i| from + (to - from) * i as Float / 2000.)
+    }
+
+    fn max_error<F, G>(xs: impl Iterator<Item = Float>, fixed: F, float: G) -> Float
+    where
+        F: Fn(Fixed) -> Fixed,
+        G: Fn(Float) -> Float,
+    {
+        xs.map(|x| {
+            let x = Fixed::from_float(x);
+            (fixed(x).to_float() - float(x.to_float())).abs()
+        })
+        .fold(0., Float::max)
+    }
+
+    #[test]
+    fn lookup_tables() {
+        assert!(max_error(range(-50., 50.), sin, math::sin) < 3e-5);
+        assert!(max_error(range(-50., 50.), cos, math::cos) < 3e-5);
+        assert!(max_error(range(-1.5, 1.5), tan, math::tan) < 3e-3);
+        assert!(max_error(range(-10., 10.), tanh, math::tanh) < 3e-5);
+        assert!(max_error(range(-10., 0.), exp2, math::exp2) < 3e-5);
+        assert!(max_error(range(0.01, 100.), ln, math::ln) < 3e-5);
+        assert!(max_error(range(0., 1000.), sqrt, math::sqrt) < 3e-5);
+
+        // Relative error for large results
+        for x in range(0., 14.9) {
+            let expected = math::exp2(x);
+            let v = exp2(Fixed::from_float(x)).to_float();
+            assert!(((v - expected) / expected).abs() < 1e-4, "exp2({})", x);
+        }
+
+        assert_eq!(exp2(Fixed::from_int(20)), Fixed::MAX);
+        assert_eq!(exp2(Fixed::from_int(-40)), Fixed::ZERO);
+        assert_eq!(tanh(Fixed::from_int(100)), Fixed::ONE);
+        assert_eq!(ln(Fixed::ZERO), Fixed::MIN);
+    }
+}
@@ -0,0 +1,182 @@
+//! A fixed-point path through the engine, for targets without a floating
+//! point unit. Values are `Fixed`, in the Q16.16 format: a range of about
+//! ±32768 with a resolution of about 1.5e-5. All arithmetic saturates instead
+//! of wrapping around.
+//!
+//! The time step of a simulation is too small for Q16.16, so it is given as a
+//! `TimeStep` in the Q0.32 format, and the integration weights are applied
+//! with 64 bit intermediates.
+//!
+//! Modules run in fixed point by implementing `FixedModule`. Input expressions
+//! are compiled from stack programs to `FixedStackProgram`, or written as
+//! `NativeFixedProgram` functions. `FixedSynthModule` holds the modules with a
+//! fixed-point version, for synths generated in the `Fixed` code generation
+//! mode of the designer.
+//!
+//! Q16.16 limits what a patch can do: angular frequencies have to stay below
+//! 32768, so filter cutoffs below about 5 kHz, and small states that get
+//! multiplied by large factors lose most of their precision.
+
+pub mod math;
+pub mod module;
+pub mod program;
+pub mod rungekutta;
+pub mod state;
+
+use crate::Float;
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+const FRAC_BITS: u32 = 16;
+
+/// A signed Q16.16 fixed-point number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(pub i32);
+
+fn saturate(v: i64) -> i32 {
+    v.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+    pub const MAX: Fixed = Fixed(i32::MAX);
+    pub const MIN: Fixed = Fixed(i32::MIN);
+
+    /// The nearest fixed-point value, saturated to the range of `Fixed`.
+    pub const fn from_float(v: Float) -> Self {
+        let v = v * (1 << FRAC_BITS) as Float;
+
+        // Float to integer casts saturate, and NaN becomes zero
+        Fixed(if v < 0. { v - 0.5 } else { v + 0.5 } as i32)
+    }
+
+    pub fn from_int(v: i32) -> Self {
+        Fixed(saturate((v as i64) << FRAC_BITS))
+    }
+
+    pub fn to_float(self) -> Float {
+        self.0 as Float / (1 << FRAC_BITS) as Float
+    }
+
+    pub fn abs(self) -> Self {
+        Fixed(self.0.saturating_abs())
+    }
+
+    /// Multiply by a time step, with the full precision of both.
+    pub fn mul_time(self, dt: TimeStep) -> Self {
+        Fixed(saturate((self.0 as i64 * dt.0 as i64 + (1 << 31)) >> 32))
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: Fixed) -> Fixed {
+        let product = self.0 as i64 * rhs.0 as i64;
+
+        Fixed(saturate((product + (1 << (FRAC_BITS - 1))) >> FRAC_BITS))
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    /// Division by zero saturates, except that zero divided by zero is zero.
+    fn div(self, rhs: Fixed) -> Fixed {
+        if rhs.0 == 0 {
+            return match self.0 {
+                0 => Fixed::ZERO,
+                v if v > 0 => Fixed::MAX,
+                _ => Fixed::MIN,
+            };
+        }
+
+        Fixed(saturate(((self.0 as i64) << FRAC_BITS) / rhs.0 as i64))
+    }
+}
+
+/// A time step in seconds in the Q0.32 format, so always less than a second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeStep(pub u32);
+
+impl TimeStep {
+    /// The time step of one sample, computed without floating point.
+    pub fn from_sample_rate(sample_rate: u32) -> Self {
+        TimeStep(((1_u64 << 32) / sample_rate as u64) as u32)
+    }
+
+    /// A fraction of this time step.
+    pub fn scale(self, c: Fixed) -> Self {
+        TimeStep(((self.0 as i64 * c.0 as i64) >> FRAC_BITS).clamp(0, u32::MAX as i64) as u32)
+    }
+
+    /// The time step in seconds.
+    pub fn to_float(self) -> Float {
+        self.0 as Float / (1_u64 << 32) as Float
+    }
+
+    /// The time step as a fixed-point number, which loses most of its
+    /// precision.
+    pub fn to_fixed(self) -> Fixed {
+        Fixed((self.0 >> (32 - FRAC_BITS)) as i32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn saturating_arithmetic() {
+        let a = Fixed::from_float(1.5);
+        let b = Fixed::from_float(-0.25);
+
+        assert_eq!((a + b).to_float(), 1.25);
+        assert_eq!((a * b).to_float(), -0.375);
+        assert!(((b / a).to_float() + 1. / 6.).abs() < 2e-5);
+        assert_eq!(Fixed::from_int(30000) + Fixed::from_int(30000), Fixed::MAX);
+        assert_eq!(Fixed::from_int(-300) * Fixed::from_int(300), Fixed::MIN);
+        assert_eq!(a / Fixed::ZERO, Fixed::MAX);
+        assert_eq!(Fixed::from_float(1e9), Fixed::MAX);
+
+        let dt = TimeStep::from_sample_rate(44100);
+        let v = Fixed::from_int(10000).mul_time(dt);
+        assert!((v.to_float() - 10000. / 44100.).abs() < 2e-5);
+    }
+}
@@ -0,0 +1,117 @@
+use super::program::FixedProgram;
+use super::state::{FixedState, FixedStateUpdate};
+use super::{Fixed, TimeStep};
+use crate::event::ControllerEvent;
+use crate::modules::{
+    Amplifier, ContinuousControl, Envelope, Filter6db, MonoKeys, MonoOutput, QuadratureOscillator,
+};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+/// A module that runs in fixed point, like `Module`.
+pub trait FixedModule: Send {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]);
+
+    fn process_event(&mut self, event: &ControllerEvent);
+
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]);
+}
+
+/// The modules of a fixed-point synth, like `ModuleSet`.
+pub trait FixedModuleSet {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]);
+
+    fn process_event(&mut self, event: &ControllerEvent);
+
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]);
+}
+
+#[cfg(feature = "alloc")]
+impl FixedModuleSet for Vec<Box<dyn FixedModule>> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        for module in self {
+            module.simulate(state, update, stack);
+        }
+    }
+
+    fn process_event(&mut self, event: &ControllerEvent) {
+        for module in self {
+            module.process_event(event);
+        }
+    }
+
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]) {
+        for module in self {
+            module.finalize(state, time_step, stack);
+        }
+    }
+}
+
+impl<M: FixedModule, const N: usize> FixedModuleSet for [M; N] {
+    #[inline]
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        for module in self {
+            module.simulate(state, update, stack);
+        }
+    }
+
+    #[inline]
+    fn process_event(&mut self, event: &ControllerEvent) {
+        for module in self {
+            module.process_event(event);
+        }
+    }
+
+    #[inline]
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]) {
+        for module in self {
+            module.finalize(state, time_step, stack);
+        }
+    }
+}
+
+/// The modules that run in fixed point, like `SynthModule` and with the same
+/// variant names, so that generated code builds either.
+// Boxing the large variants would need the heap, like for `SynthModule`
+#[allow(clippy::large_enum_variant)]
+pub enum FixedSynthModule<P> {
+    Amp(Amplifier<P>),
+    Contour(Envelope<P>),
+    Filter1Pole(Filter6db<P>),
+    ContinuousControl(ContinuousControl),
+    MonoKeys(MonoKeys<P>),
+    Output(MonoOutput<P>),
+    QuadOscillator(QuadratureOscillator<P>),
+}
+
+// Static dispatch to the module in every variant
+macro_rules! dispatch {
+    ($self:expr, $m:ident => $e:expr) => {
+        match $self {
+            FixedSynthModule::Amp($m) => $e,
+            FixedSynthModule::Contour($m) => $e,
+            FixedSynthModule::Filter1Pole($m) => $e,
+            FixedSynthModule::ContinuousControl($m) => $e,
+            FixedSynthModule::MonoKeys($m) => $e,
+            FixedSynthModule::Output($m) => $e,
+            FixedSynthModule::QuadOscillator($m) => $e,
+        }
+    };
+}
+
+impl<P: FixedProgram> FixedModule for FixedSynthModule<P> {
+    #[inline]
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        dispatch!(self, m => m.simulate(state, update, stack))
+    }
+
+    #[inline]
+    fn process_event(&mut self, event: &ControllerEvent) {
+        dispatch!(self, m => m.process_event(event))
+    }
+
+    #[inline]
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]) {
+        dispatch!(self, m => m.finalize(state, time_step, stack))
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "alloc")]
+use super::math;
+use super::state::FixedState;
+use super::Fixed;
+use crate::stack_program::ExecError;
+#[cfg(feature = "alloc")]
+use crate::stack_program::{pop_stack, push_stack, Function, Instr, StackProgram};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// An input expression of a fixed-point module, like `Program`.
+pub trait FixedProgram: Send {
+    fn run(&self, state: &FixedState, stack: &mut [Fixed]) -> Result<Fixed, ExecError>;
+}
+
+/// An instruction of a `FixedStackProgram`, as `Instr` with fixed-point
+/// constants.
+#[cfg(feature = "alloc")]
+#[derive(PartialEq, Debug)]
+pub enum FixedInstr {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Negate,
+
+    Call(Function),
+    Const(Fixed),
+    State(usize),
+}
+
+/// A stack program with its constants converted to fixed point, so that it
+/// runs without floating point.
+#[cfg(feature = "alloc")]
+#[derive(PartialEq, Debug)]
+pub struct FixedStackProgram {
+    pub code: Vec<FixedInstr>,
+    pub stack_size: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl From<&StackProgram> for FixedStackProgram {
+    fn from(program: &StackProgram) -> Self {
+        let code = program
+            .code
+            .iter()
+            .map(|instr| match instr {
+                Instr::Add => FixedInstr::Add,
+                Instr::Subtract => FixedInstr::Subtract,
+                Instr::Multiply => FixedInstr::Multiply,
+                Instr::Divide => FixedInstr::Divide,
+                Instr::Negate => FixedInstr::Negate,
+                Instr::Call(f) => FixedInstr::Call(*f),
+                Instr::Const(v) => FixedInstr::Const(Fixed::from_float(*v)),
+                Instr::State(i) => FixedInstr::State(*i),
+            })
+            .collect();
+
+        Self {
+            code,
+            stack_size: program.stack_size,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FixedStackProgram {
+    pub fn constant(v: Fixed) -> Self {
+        Self {
+            code: alloc::vec![FixedInstr::Const(v)],
+            stack_size: 1,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        Self {
+            code: alloc::vec![FixedInstr::State(index)],
+            stack_size: 1,
+        }
+    }
+
+    pub fn run(&self, state: &FixedState, stack: &mut [Fixed]) -> Result<Fixed, ExecError> {
+        let mut stack_ptr: usize = 0;
+
+        for instr in &self.code {
+            use FixedInstr::*;
+
+            match instr {
+                Add => {
+                    let a = pop_stack(stack, &mut stack_ptr)?;
+                    let b = pop_stack(stack, &mut stack_ptr)?;
+                    push_stack(stack, &mut stack_ptr, a + b)?;
+                }
+                Subtract => {
+                    let a = pop_stack(stack, &mut stack_ptr)?;
+                    let b = pop_stack(stack, &mut stack_ptr)?;
+                    push_stack(stack, &mut stack_ptr, a - b)?;
+                }
+                Multiply => {
+                    let a = pop_stack(stack, &mut stack_ptr)?;
+                    let b = pop_stack(stack, &mut stack_ptr)?;
+                    push_stack(stack, &mut stack_ptr, a * b)?;
+                }
+                Divide => {
+                    let a = pop_stack(stack, &mut stack_ptr)?;
+                    let b = pop_stack(stack, &mut stack_ptr)?;
+                    push_stack(stack, &mut stack_ptr, a / b)?;
+                }
+                Negate => {
+                    let a = pop_stack(stack, &mut stack_ptr)?;
+                    push_stack(stack, &mut stack_ptr, -a)?;
+                }
+                Call(f) => {
+                    use Function::*;
+
+                    let v = match f {
+                        Sin => math::sin(pop_stack(stack, &mut stack_ptr)?),
+                        Cos => math::cos(pop_stack(stack, &mut stack_ptr)?),
+                        Tan => math::tan(pop_stack(stack, &mut stack_ptr)?),
+                        Tanh => math::tanh(pop_stack(stack, &mut stack_ptr)?),
+                        Abs => pop_stack(stack, &mut stack_ptr)?.abs(),
+                        Min => {
+                            let a = pop_stack(stack, &mut stack_ptr)?;
+                            let b = pop_stack(stack, &mut stack_ptr)?;
+                            a.min(b)
+                        }
+                        Max => {
+                            let a = pop_stack(stack, &mut stack_ptr)?;
+                            let b = pop_stack(stack, &mut stack_ptr)?;
+                            a.max(b)
+                        }
+                        Ln => math::ln(pop_stack(stack, &mut stack_ptr)?),
+                        Exp => math::exp(pop_stack(stack, &mut stack_ptr)?),
+                        Logistic => {
+                            let x0 = pop_stack(stack, &mut stack_ptr)?;
+                            let k = pop_stack(stack, &mut stack_ptr)?;
+                            let l = pop_stack(stack, &mut stack_ptr)?;
+                            let x = pop_stack(stack, &mut stack_ptr)?;
+                            l / (Fixed::ONE + math::exp(-k * (x - x0)))
+                        }
+                        Lerp => {
+                            let hi = pop_stack(stack, &mut stack_ptr)?;
+                            let lo = pop_stack(stack, &mut stack_ptr)?;
+                            let x =
+                                pop_stack(stack, &mut stack_ptr)?.clamp(Fixed::ZERO, Fixed::ONE);
+                            lo * (Fixed::ONE - x) + hi * x
+                        }
+                    };
+
+                    push_stack(stack, &mut stack_ptr, v)?;
+                }
+                Const(v) => {
+                    push_stack(stack, &mut stack_ptr, *v)?;
+                }
+                State(i) => {
+                    if *i >= state.len() {
+                        return Err(ExecError::StateOutOfBounds(*i));
+                    }
+
+                    push_stack(stack, &mut stack_ptr, state.get(*i))?;
+                }
+            }
+        }
+
+        pop_stack(stack, &mut stack_ptr)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FixedProgram for FixedStackProgram {
+    #[inline]
+    fn run(&self, state: &FixedState, stack: &mut [Fixed]) -> Result<Fixed, ExecError> {
+        FixedStackProgram::run(self, state, stack)
+    }
+}
+
+/// An input expression as a Rust function, like `NativeProgram`.
+#[derive(Clone, Copy)]
+pub struct NativeFixedProgram(pub fn(&FixedState) -> Fixed);
+
+impl FixedProgram for NativeFixedProgram {
+    #[inline]
+    fn run(&self, state: &FixedState, _stack: &mut [Fixed]) -> Result<Fixed, ExecError> {
+        Ok((self.0)(state))
+    }
+}
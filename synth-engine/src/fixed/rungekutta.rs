@@ -0,0 +1,377 @@
+use super::module::FixedModuleSet;
+use super::state::{FixedState, FixedStateUpdate};
+use super::{Fixed, TimeStep};
+use crate::event::ControllerEvent;
+
+const DEFAULT_STACK_SIZE: usize = 256;
+const MAX_STAGES: usize = 4;
+
+const fn fixed(v: crate::Float) -> Fixed {
+    Fixed::from_float(v)
+}
+
+const RK4_A: &[&[Fixed]] = &[
+    &[],
+    &[fixed(0.5)],
+    &[fixed(0.0), fixed(0.5)],
+    &[fixed(0.0), fixed(0.0), fixed(1.0)],
+];
+const RK4_B: &[Fixed] = &[
+    fixed(1.0 / 6.0),
+    fixed(1.0 / 3.0),
+    fixed(1.0 / 3.0),
+    fixed(1.0 / 6.0),
+];
+const RK4_C: &[Fixed] = &[fixed(0.0), fixed(0.5), fixed(0.5), fixed(1.0)];
+
+const EULER_A: &[&[Fixed]] = &[&[]];
+const EULER_B: &[Fixed] = &[fixed(1.0)];
+const EULER_C: &[Fixed] = &[fixed(0.0)];
+
+/// A Runge Kutta simulator in fixed point, like `RungeKutta`. Delay-free
+/// loops aren't supported.
+pub struct FixedRungeKutta<M> {
+    state: FixedState,
+    updates: [FixedStateUpdate; MAX_STAGES],
+    a: &'static [&'static [Fixed]],
+    b: &'static [Fixed],
+    c: &'static [Fixed],
+    stages: usize,
+    modules: M,
+    stack: [Fixed; DEFAULT_STACK_SIZE],
+}
+
+impl<M: FixedModuleSet> FixedRungeKutta<M> {
+    pub fn rk4(state_size: usize, modules: M) -> Self {
+        Self::with_tableau(state_size, modules, RK4_A, RK4_B, RK4_C)
+    }
+
+    pub fn euler(state_size: usize, modules: M) -> Self {
+        Self::with_tableau(state_size, modules, EULER_A, EULER_B, EULER_C)
+    }
+
+    fn with_tableau(
+        state_size: usize,
+        modules: M,
+        a: &'static [&'static [Fixed]],
+        b: &'static [Fixed],
+        c: &'static [Fixed],
+    ) -> Self {
+        debug_assert!(b.len() <= MAX_STAGES);
+
+        let state = FixedState::new(state_size);
+        let dt = TimeStep::default();
+
+        Self {
+            updates: core::array::from_fn(|_| state.update_data(dt, dt)),
+            state,
+            a,
+            b,
+            c,
+            stages: b.len(),
+            modules,
+            stack: [Fixed::ZERO; DEFAULT_STACK_SIZE],
+        }
+    }
+
+    pub fn step(&mut self, dt: TimeStep) {
+        for stage in 0..self.stages {
+            let (updates, current) = self.updates.split_at_mut(stage);
+            let update = &mut current[0];
+            let mut temp_state = self.state.clone();
+
+            update.reset(dt.scale(self.c[stage]), dt);
+            temp_state.apply_updates(updates, self.a[stage], self.c, dt);
+
+            self.modules.simulate(&temp_state, update, &mut self.stack);
+        }
+
+        self.state
+            .apply_updates(&self.updates[..self.stages], self.b, self.c, dt);
+
+        self.modules.finalize(&mut self.state, dt, &mut self.stack);
+    }
+
+    pub fn get_stereo_output(&self) -> (Fixed, Fixed) {
+        (self.state.get_output(0), self.state.get_output(1))
+    }
+
+    pub fn process_event(&mut self, event: ControllerEvent) {
+        self.modules.process_event(&event);
+    }
+
+    pub fn get_state(&mut self) -> &mut FixedState {
+        &mut self.state
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::fixed::module::FixedModule;
+    use crate::fixed::program::FixedStackProgram;
+    use crate::modules::*;
+    use crate::simulator::module::Module;
+    use crate::simulator::rungekutta::RungeKutta;
+    use crate::stack_program::StackProgram;
+    use crate::Float;
+    use alloc::{boxed::Box, vec, vec::Vec};
+
+    const SAMPLE_RATE: u32 = 44100;
+    const SAMPLES: usize = 4410;
+
+    /// The largest difference of the first output of the float and the
+    /// fixed-point simulation of the same modules, with events sent before
+    /// the given samples.
+    fn max_output_error(
+        state_size: usize,
+        modules: Vec<Box<dyn Module>>,
+        fixed_modules: Vec<Box<dyn FixedModule>>,
+        events: &[(usize, ControllerEvent)],
+    ) -> Float {
+        let mut simulator = RungeKutta::rk4(state_size).with_modules(modules);
+        let mut fixed_simulator = FixedRungeKutta::rk4(state_size, fixed_modules);
+        let dt = TimeStep::from_sample_rate(SAMPLE_RATE);
+        let mut error: Float = 0.;
+
+        for sample in 0..SAMPLES {
+            for (_, event) in events.iter().filter(|(at, _)| *at == sample) {
+                simulator.process_event(*event);
+                fixed_simulator.process_event(*event);
+            }
+
+            simulator.step(1. / SAMPLE_RATE as Float);
+            fixed_simulator.step(dt);
+
+            let expected = simulator.get_stereo_output().0;
+            let actual = fixed_simulator.get_stereo_output().0.to_float();
+
+            error = error.max((expected - actual).abs());
+        }
+
+        error
+    }
+
+    fn constant(v: Float) -> StackProgram {
+        StackProgram::constant(v)
+    }
+
+    fn fixed(program: StackProgram) -> FixedStackProgram {
+        FixedStackProgram::from(&program)
+    }
+
+    // Oscillator at 440 Hz, from pitch 5.75 over 8.18 Hz
+    fn oscillator<P>(program: impl Fn(StackProgram) -> P) -> QuadratureOscillator<P> {
+        QuadratureOscillator::new(8.18, 0, 1, program(constant(5.75)), program(constant(0.)))
+    }
+
+    fn amplifier<P>(program: impl Fn(StackProgram) -> P) -> Amplifier<P> {
+        Amplifier::new(
+            program(StackProgram::from_index(0)),
+            2,
+            program(constant(0.1)),
+            program(constant(0.7)),
+        )
+    }
+
+    // A step from 0 to 0.5 into a 6 dB filter at `f0`, or the oscillator
+    fn filter<P>(program: impl Fn(StackProgram) -> P, f0: Float, step: bool) -> Filter6db<P> {
+        let input = if step {
+            constant(0.5)
+        } else {
+            StackProgram::from_index(0)
+        };
+
+        Filter6db::new(
+            f0,
+            2,
+            3,
+            4,
+            program(constant(0.)),
+            program(constant(0.)),
+            program(input),
+        )
+    }
+
+    fn output<P>(program: impl Fn(StackProgram) -> P, index: usize) -> MonoOutput<P> {
+        MonoOutput::new(0, program(StackProgram::from_index(index)))
+    }
+
+    #[test]
+    fn modules_against_float_engine() {
+        // Quadrature oscillator, accumulates phase error over 440 cycles
+        let error = max_output_error(
+            2,
+            vec![Box::new(oscillator(|p| p)), Box::new(output(|p| p, 0))],
+            vec![Box::new(oscillator(fixed)), Box::new(output(fixed, 0))],
+            &[],
+        );
+        assert!(error < 5e-3, "quadrature oscillator error {}", error);
+
+        // Amplifier on the oscillator
+        let error = max_output_error(
+            3,
+            vec![
+                Box::new(oscillator(|p| p)),
+                Box::new(amplifier(|p| p)),
+                Box::new(output(|p| p, 2)),
+            ],
+            vec![
+                Box::new(oscillator(fixed)),
+                Box::new(amplifier(fixed)),
+                Box::new(output(fixed, 2)),
+            ],
+            &[],
+        );
+        assert!(error < 5e-3, "amplifier error {}", error);
+
+        // Lowpass step response at 100 Hz
+        let error = max_output_error(
+            5,
+            vec![
+                Box::new(filter(|p| p, 100., true)),
+                Box::new(output(|p| p, 3)),
+            ],
+            vec![
+                Box::new(filter(fixed, 100., true)),
+                Box::new(output(fixed, 3)),
+            ],
+            &[],
+        );
+        assert!(error < 1e-3, "lowpass filter error {}", error);
+
+        // Highpass of the oscillator at 1 kHz
+        let error = max_output_error(
+            5,
+            vec![
+                Box::new(oscillator(|p| p)),
+                Box::new(filter(|p| p, 1000., false)),
+                Box::new(output(|p| p, 4)),
+            ],
+            vec![
+                Box::new(oscillator(fixed)),
+                Box::new(filter(fixed, 1000., false)),
+                Box::new(output(fixed, 4)),
+            ],
+            &[],
+        );
+        assert!(error < 5e-3, "highpass filter error {}", error);
+
+        // Smoothed controller, the rounding of each step adds up over the
+        // transient
+        let event = ControllerEvent::ContinuousControl {
+            channel: 0,
+            control: 7,
+            value: 0.8,
+        };
+        let error = max_output_error(
+            1,
+            vec![
+                Box::new(ContinuousControl::new(0, 7, -1., 1.)),
+                Box::new(output(|p| p, 0)),
+            ],
+            vec![
+                Box::new(ContinuousControl::new(0, 7, -1., 1.)),
+                Box::new(output(fixed, 0)),
+            ],
+            &[(0, event)],
+        );
+        assert!(error < 2e-3, "control error {}", error);
+    }
+
+    const KEY_OUTPUTS: mono_keys::KeyOutputs = mono_keys::KeyOutputs {
+        pitch: 0,
+        gate: 1,
+        pressure: 2,
+        velocity: 3,
+        pitchwheel: 4,
+        trigger: 5,
+        sustain: 6,
+    };
+
+    fn keys<P>(program: impl Fn(StackProgram) -> P) -> MonoKeys<P> {
+        MonoKeys::new(KEY_OUTPUTS, program(constant(0.05)))
+    }
+
+    // Attack and decay on the gate of the keys, with a shape between the
+    // curves
+    fn envelope<P>(program: impl Fn(StackProgram) -> P) -> Envelope<P> {
+        Envelope::new(
+            program(StackProgram::from_index(KEY_OUTPUTS.gate)),
+            program(constant(0.02)),
+            program(constant(0.05)),
+            program(constant(0.3)),
+            7,
+            8,
+        )
+        .with_type(envelope::EnvType::AttackDecay)
+    }
+
+    #[test]
+    fn keys_and_envelope_against_float_engine() {
+        // A glide from the first note to the second, and pressure. Like the
+        // controller, the glide and the smoothed pressure add up the rounding
+        // of each step
+        let events = [
+            (
+                0,
+                ControllerEvent::NoteOn {
+                    channel: 0,
+                    pitch: 60,
+                    velocity: 0.8,
+                    pitch_value: 5.,
+                },
+            ),
+            (
+                0,
+                ControllerEvent::Aftertouch {
+                    channel: 0,
+                    amount: 0.6,
+                },
+            ),
+            (
+                SAMPLES / 4,
+                ControllerEvent::NoteOn {
+                    channel: 0,
+                    pitch: 72,
+                    velocity: 0.8,
+                    pitch_value: 6.,
+                },
+            ),
+        ];
+
+        let error = max_output_error(
+            7,
+            vec![Box::new(keys(|p| p)), Box::new(output(|p| p, 0))],
+            vec![Box::new(keys(fixed)), Box::new(output(fixed, 0))],
+            &events,
+        );
+        assert!(error < 5e-3, "keys pitch error {}", error);
+
+        let error = max_output_error(
+            7,
+            vec![Box::new(keys(|p| p)), Box::new(output(|p| p, 2))],
+            vec![Box::new(keys(fixed)), Box::new(output(fixed, 2))],
+            &events,
+        );
+        assert!(error < 5e-3, "keys pressure error {}", error);
+
+        // Rises and falls within the run. The cycle moves by about 74.3 units
+        // of Q16.16 a step, rounded to 74, so it lags the float engine.
+        let error = max_output_error(
+            9,
+            vec![
+                Box::new(keys(|p| p)),
+                Box::new(envelope(|p| p)),
+                Box::new(output(|p| p, 7)),
+            ],
+            vec![
+                Box::new(keys(fixed)),
+                Box::new(envelope(fixed)),
+                Box::new(output(fixed, 7)),
+            ],
+            &events,
+        );
+        assert!(error < 1e-2, "envelope error {}", error);
+    }
+}
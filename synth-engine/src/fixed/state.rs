@@ -0,0 +1,141 @@
+use super::{saturate, Fixed, TimeStep};
+use crate::simulator::state::{buffer, Buffer, UpdateType};
+
+/// The state of a fixed-point simulation, like `State`.
+#[derive(Debug, Clone)]
+pub struct FixedState {
+    values: Buffer<Fixed>,
+    outputs: [Fixed; 2],
+}
+
+pub struct FixedStateUpdate {
+    updates: Buffer<Fixed>,
+    update_types: Buffer<UpdateType>,
+    delta_time: TimeStep,
+    time_step: TimeStep,
+}
+
+impl FixedState {
+    pub fn new(size: usize) -> Self {
+        Self {
+            values: buffer(Fixed::ZERO, size),
+            outputs: [Fixed::ZERO; 2],
+        }
+    }
+
+    pub fn new_with_values(values: &[Fixed]) -> Self {
+        let mut state = Self::new(values.len());
+
+        state.values.copy_from_slice(values);
+        state
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn update_data(&self, delta_time: TimeStep, time_step: TimeStep) -> FixedStateUpdate {
+        FixedStateUpdate {
+            updates: buffer(Fixed::ZERO, self.len()),
+            update_types: buffer(UpdateType::Differentiable, self.len()),
+            delta_time,
+            time_step,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Fixed {
+        debug_assert!(index < self.values.len());
+
+        self.values[index]
+    }
+
+    pub fn set(&mut self, index: usize, v: Fixed) {
+        debug_assert!(index < self.values.len());
+
+        self.values[index] = v;
+    }
+
+    /// Apply the updates of the stages of a step, like `State::apply_updates`.
+    /// The weighted sum of the derivatives is kept in 64 bits and multiplied
+    /// by the time step with 128 bit intermediates, so that slow changes
+    /// aren't rounded away before they add up.
+    pub fn apply_updates(
+        &mut self,
+        updates: &[FixedStateUpdate],
+        weights: &[Fixed],
+        c: &[Fixed],
+        dt: TimeStep,
+    ) {
+        debug_assert!(updates.len() == weights.len());
+        debug_assert!(updates.len() <= c.len());
+
+        for i in 0..self.len() {
+            // Weighted derivatives in Q32.32
+            let mut derivative: i64 = 0;
+            let mut update = Fixed::ZERO;
+            let mut previous_value = self.values[i];
+
+            for (j, stage) in updates.iter().enumerate() {
+                match stage.update_types[i] {
+                    UpdateType::Absolute => {
+                        if j == 0 {
+                            previous_value = stage.updates[i];
+                        } else {
+                            update += (stage.updates[i] - previous_value) * weights[j] / c[j];
+                        }
+                    }
+                    UpdateType::Differentiable => {
+                        derivative += stage.updates[i].0 as i64 * weights[j].0 as i64;
+                    }
+                }
+            }
+
+            let change = (derivative as i128 * dt.0 as i128 + (1 << 47)) >> 48;
+
+            self.values[i] = previous_value + update + Fixed(saturate(change as i64));
+        }
+    }
+
+    pub fn set_output(&mut self, index: usize, v: Fixed) {
+        self.outputs[index] = v;
+    }
+
+    pub fn get_output(&self, index: usize) -> Fixed {
+        self.outputs[index]
+    }
+}
+
+impl FixedStateUpdate {
+    /// Clear all updates, for reuse in another simulation stage.
+    pub fn reset(&mut self, delta_time: TimeStep, time_step: TimeStep) {
+        self.updates.fill(Fixed::ZERO);
+        self.update_types.fill(UpdateType::Differentiable);
+        self.delta_time = delta_time;
+        self.time_step = time_step;
+    }
+
+    pub fn set(&mut self, index: usize, update: Fixed, update_type: UpdateType) {
+        debug_assert!(index < self.updates.len());
+
+        self.updates[index] = update;
+        self.update_types[index] = update_type;
+    }
+
+    pub fn get(&self, index: usize) -> Fixed {
+        debug_assert!(index < self.updates.len());
+
+        self.updates[index]
+    }
+
+    pub fn get_delta_time(&self) -> TimeStep {
+        self.delta_time
+    }
+
+    pub fn get_time_step(&self) -> TimeStep {
+        self.time_step
+    }
+}
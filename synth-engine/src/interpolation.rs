@@ -1,7 +1,9 @@
+use crate::math;
+use crate::Float;
 use core::ops::Deref;
 
 // see https://www.paulinternet.nl/?page=bicubic
-fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, x: f32) -> f32 {
+fn cubic(p0: Float, p1: Float, p2: Float, p3: Float, x: Float) -> Float {
     let x2 = x * x;
     let x3 = x2 * x;
 
@@ -11,20 +13,20 @@ fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, x: f32) -> f32 {
         + p1
 }
 
-const C1: [f32; 4] = [-0.1666667, 0.5, -0.5, 0.1666667];
-const C2: [f32; 4] = [0.5, -1., 0.5, 0.];
-const C3: [f32; 4] = [-0.33333333, -0.5, 1., -0.16666667];
-const C4: [f32; 4] = [0., 1., 0., 0.];
+const C1: [Float; 4] = [-1. / 6., 0.5, -0.5, 1. / 6.];
+const C2: [Float; 4] = [0.5, -1., 0.5, 0.];
+const C3: [Float; 4] = [-1. / 3., -0.5, 1., -1. / 6.];
+const C4: [Float; 4] = [0., 1., 0., 0.];
 
-fn lagrange_helper(d: &[f32; 4], i: usize) -> f32 {
+fn lagrange_helper(d: &[Float; 4], i: usize) -> Float {
     C1[i] * d[0] + C2[i] * d[1] + C3[i] * d[2] + C4[i] * d[3]
 }
 
 // see "Fractional Delay Farrow Filter" by Josef Hoffmann
 // (Note paper has error in C3 above)
-fn lagrange(p0: f32, p1: f32, p2: f32, p3: f32, delta: f32) -> f32 {
-    let d: [f32; 4] = [delta * delta * delta, delta * delta, delta, 1.];
-    let v: [f32; 4] = [
+fn lagrange(p0: Float, p1: Float, p2: Float, p3: Float, delta: Float) -> Float {
+    let d: [Float; 4] = [delta * delta * delta, delta * delta, delta, 1.];
+    let v: [Float; 4] = [
         lagrange_helper(&d, 0),
         lagrange_helper(&d, 1),
         lagrange_helper(&d, 2),
@@ -35,48 +37,48 @@ fn lagrange(p0: f32, p1: f32, p2: f32, p3: f32, delta: f32) -> f32 {
 }
 
 pub trait Interpolation {
-    fn cubic_interpolate(&self, x: f32) -> f32;
-    fn linear_interpolate(&self, x: f32) -> f32;
-    fn lagrange_interpolate(&self, x: f32) -> f32;
+    fn cubic_interpolate(&self, x: Float) -> Float;
+    fn linear_interpolate(&self, x: Float) -> Float;
+    fn lagrange_interpolate(&self, x: Float) -> Float;
 }
 
-impl<T: Deref<Target = [f32]>> Interpolation for T {
-    fn cubic_interpolate(&self, x: f32) -> f32 {
+impl<T: Deref<Target = [Float]>> Interpolation for T {
+    fn cubic_interpolate(&self, x: Float) -> Float {
         let len = self.len() as i32;
-        let index: i32 = x.floor() as i32;
+        let index: i32 = math::floor(x) as i32;
 
         let i0 = ((((index - 1) % len) + len) % len) as usize;
         let i1 = (((index % len) + len) % len) as usize;
         let i2 = ((((index + 1) % len) + len) % len) as usize;
         let i3 = ((((index + 2) % len) + len) % len) as usize;
 
-        let x = x - x.floor();
+        let x = x - math::floor(x);
 
         cubic(self[i0], self[i1], self[i2], self[i3], x)
     }
 
-    fn lagrange_interpolate(&self, x: f32) -> f32 {
+    fn lagrange_interpolate(&self, x: Float) -> Float {
         let len = self.len() as i32;
-        let index: i32 = x.floor() as i32;
+        let index: i32 = math::floor(x) as i32;
 
         let i0 = ((((index - 1) % len) + len) % len) as usize;
         let i1 = (((index % len) + len) % len) as usize;
         let i2 = ((((index + 1) % len) + len) % len) as usize;
         let i3 = ((((index + 2) % len) + len) % len) as usize;
 
-        let x = x - x.floor();
+        let x = x - math::floor(x);
 
         lagrange(self[i0], self[i1], self[i2], self[i3], x)
     }
 
-    fn linear_interpolate(&self, x: f32) -> f32 {
+    fn linear_interpolate(&self, x: Float) -> Float {
         let len = self.len();
-        let index: usize = x.floor() as usize;
+        let index: usize = math::floor(x) as usize;
 
         let i0 = ((index % len) + len) % len;
         let i1 = (((index + 1) % len) + len) % len;
 
-        let x = x - x.floor();
+        let x = x - math::floor(x);
 
         let p0 = self[i0];
         let p1 = self[i1];
@@ -85,7 +87,7 @@ impl<T: Deref<Target = [f32]>> Interpolation for T {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod test {
     use super::*;
     use alloc::vec;
@@ -93,7 +95,7 @@ mod test {
 
     #[test]
     fn cubic_interpolation_on_line() {
-        let ys: Vec<f32> = vec![0., 1., 2., 3., 4., 5.];
+        let ys: Vec<Float> = vec![0., 1., 2., 3., 4., 5.];
 
         assert_eq!(ys.cubic_interpolate(3.), 3.);
         assert_eq!(ys.cubic_interpolate(1.), 1.);
@@ -103,7 +105,7 @@ mod test {
 
     #[test]
     fn lagrange_interpolation_on_line() {
-        let ys: Vec<f32> = vec![0., 1., 2., 3., 4., 5., 6., 5., 8.];
+        let ys: Vec<Float> = vec![0., 1., 2., 3., 4., 5., 6., 5., 8.];
 
         assert_eq!(ys.lagrange_interpolate(3.), 3.);
         assert_eq!(ys.lagrange_interpolate(1.), 1.);
@@ -115,7 +117,7 @@ mod test {
 
     #[test]
     fn linear_interpolation_on_line() {
-        let ys: Vec<f32> = vec![0., 1., 2., 3., 4., 5.];
+        let ys: Vec<Float> = vec![0., 1., 2., 3., 4., 5.];
 
         assert_eq!(ys.linear_interpolate(0.), 0.);
         assert_eq!(ys.linear_interpolate(1.), 1.);
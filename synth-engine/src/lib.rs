@@ -5,15 +5,37 @@
 //! This library does _not_ provide an interface with audio or MIDI. This should
 //! be provided in the program using the synth simulator. See the `cli-synth` example
 //! program for suggestions.
+//!
+//! The `alloc` feature, enabled by default, is needed to build synths at
+//! runtime. Without it the engine doesn't use the heap at all and runs synths
+//! generated with the static code generation mode of `synth-designer`.
+//!
+//! All float math goes through the `math` module, backed by `libm`, so the
+//! engine doesn't need `std` in either case. The `fast-math` feature swaps in
+//! cheaper approximations of the exponential functions.
+//!
+//! Samples and state are `Float`, which is `f32` by default. Enable the `f64`
+//! feature for double precision, eg for offline rendering of long waveguides.
+//!
+//! The `fixed-point` feature adds the `fixed` module, a simulator with
+//! fixed-point state and arithmetic for targets without a floating point unit.
+//! Only some modules can run in it, see `FixedModule`.
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 pub mod distortion;
 pub mod event;
+#[cfg(feature = "fixed-point")]
+pub mod fixed;
 pub mod interpolation;
+pub mod math;
 pub mod modules;
 pub mod simulator;
+#[cfg(feature = "alloc")]
 pub mod sinc_filter;
 pub mod stack_program;
+
+pub use math::Float;
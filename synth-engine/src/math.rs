@@ -0,0 +1,142 @@
+//! Math functions for the engine. `core` has no transcendental functions for
+//! floats, so they come from `libm`. With the `fast-math` feature, `exp`,
+//! `exp2` and `tanh` are polynomial approximations instead, accurate to about
+//! single precision over the range used for audio and control signals. The
+//! approximations are only used when `Float` is `f32`.
+//!
+//! `Float` is the sample and state type of the engine, `f32` unless the `f64`
+//! feature is enabled.
+
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/// The mathematical constants of `Float`.
+pub mod consts {
+    #[cfg(not(feature = "f64"))]
+    pub use core::f32::consts::*;
+
+    #[cfg(feature = "f64")]
+    pub use core::f64::consts::*;
+}
+
+type Libm = libm::Libm<Float>;
+
+pub fn sin(x: Float) -> Float {
+    Libm::sin(x)
+}
+
+pub fn cos(x: Float) -> Float {
+    Libm::cos(x)
+}
+
+pub fn tan(x: Float) -> Float {
+    Libm::tan(x)
+}
+
+pub fn ln(x: Float) -> Float {
+    Libm::log(x)
+}
+
+pub fn sqrt(x: Float) -> Float {
+    Libm::sqrt(x)
+}
+
+pub fn powf(x: Float, y: Float) -> Float {
+    Libm::pow(x, y)
+}
+
+pub fn floor(x: Float) -> Float {
+    Libm::floor(x)
+}
+
+pub fn fract(x: Float) -> Float {
+    x - Libm::trunc(x)
+}
+
+#[cfg(any(not(feature = "fast-math"), feature = "f64"))]
+pub fn exp(x: Float) -> Float {
+    Libm::exp(x)
+}
+
+#[cfg(any(not(feature = "fast-math"), feature = "f64"))]
+pub fn exp2(x: Float) -> Float {
+    Libm::exp2(x)
+}
+
+#[cfg(any(not(feature = "fast-math"), feature = "f64"))]
+pub fn tanh(x: Float) -> Float {
+    Libm::tanh(x)
+}
+
+#[cfg(all(feature = "fast-math", not(feature = "f64")))]
+pub use fast::{exp, exp2, tanh};
+
+/// Polynomial approximations, always built so they can be tested against std.
+pub mod fast {
+    use core::f32::consts::{LN_2, LOG2_E};
+
+    // Taylor coefficients of 2^x, ln(2)^n / n!
+    const EXP2_COEFFICIENTS: [f32; 7] = [
+        1.0,
+        LN_2,
+        0.240_226_5,
+        0.055_504_11,
+        0.009_618_129,
+        0.001_333_355_8,
+        0.000_154_035_3,
+    ];
+
+    /// Beyond this tanh is 1 in single precision.
+    const TANH_LIMIT: f32 = 9.;
+
+    /// 2^x, with the fraction of x as a polynomial and the integer part put
+    /// directly into the exponent bits.
+    pub fn exp2(x: f32) -> f32 {
+        let x = x.clamp(-126., 127.);
+        let i = if x < 0. { x - 0.5 } else { x + 0.5 } as i32;
+        let f = x - i as f32;
+        let p = EXP2_COEFFICIENTS
+            .iter()
+            .rev()
+            .fold(0., |acc, c| acc * f + c);
+
+        p * f32::from_bits(((i + 127) as u32) << 23)
+    }
+
+    pub fn exp(x: f32) -> f32 {
+        exp2(x * LOG2_E)
+    }
+
+    pub fn tanh(x: f32) -> f32 {
+        if x.abs() >= TANH_LIMIT {
+            x.signum()
+        } else {
+            1. - 2. / (exp2(2. * LOG2_E * x) + 1.)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::fast;
+
+    fn range(from: f32, to: f32) -> impl Iterator<Item = f32> {
+        (0..=1000).map(move |i| from + (to - from) * i as f32 / 1000.)
+    }
+
+    #[test]
+    fn fast_approximations() {
+        for x in range(-20., 20.) {
+            let relative = |a: f32, b: f32| ((a - b) / b).abs();
+
+            assert!(relative(fast::exp2(x), x.exp2()) < 1e-5, "exp2({})", x);
+            assert!(relative(fast::exp(x), x.exp()) < 1e-5, "exp({})", x);
+            assert!((fast::tanh(x) - x.tanh()).abs() < 1e-6, "tanh({})", x);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! An ADSR envelope, with optional delay and hold stages.
+//!
+//! The position in a stage is a state of the simulation, rising from 0 to 1
+//! over the time of the stage. The output follows from it as an absolute value,
+//! bent by the curve of the stage.
+
+use crate::event::ControllerEvent;
+use crate::math;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+const MIN_TIME: Float = 0.001; // 1 ms
+const GATE_LEVEL: Float = 0.5;
+
+/// The input programs of an envelope. Times are in seconds, the sustain level
+/// and velocity from 0 to 1.
+pub struct AdsrInputs<P = DefaultProgram> {
+    pub gate: P,
+    pub delay: P,
+    pub attack: P,
+    pub hold: P,
+    pub decay: P,
+    pub sustain: P,
+    pub release: P,
+    pub velocity: P,
+}
+
+/// How the envelope moves between its levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AdsrShape {
+    /// The curve of the attack, 0 is linear. Negative curves start fast, like a
+    /// charging capacitor, positive curves start slow.
+    pub attack_curve: Float,
+    pub decay_curve: Float,
+    pub release_curve: Float,
+    /// How much the velocity scales the peak level, from 0 to 1
+    pub velocity_sensitivity: Float,
+    /// Restart from zero when the gate rises again, instead of from the
+    /// current level
+    pub retrigger_from_zero: bool,
+    /// Repeat attack and decay while the gate is up, instead of sustaining
+    pub looping: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+pub struct Adsr<P = DefaultProgram> {
+    inputs: AdsrInputs<P>,
+    output_index: usize,
+    phase_index: usize,
+    shape: AdsrShape,
+    stage: Stage,
+    /// The level the stage started from
+    start: Float,
+    /// The level at the end of the attack, scaled by the velocity
+    peak: Float,
+    gate: bool,
+}
+
+impl<P: Program> Adsr<P> {
+    pub fn new(inputs: AdsrInputs<P>, output_index: usize, phase_index: usize) -> Self {
+        Self {
+            inputs,
+            output_index,
+            phase_index,
+            shape: AdsrShape::default(),
+            stage: Stage::Idle,
+            start: 0.,
+            peak: 1.,
+            gate: false,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: AdsrShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    fn sustain_level(&self, state: &State, stack: &mut [Float]) -> Float {
+        self.peak
+            * self
+                .inputs
+                .sustain
+                .run(state, stack)
+                .unwrap_or(0.)
+                .clamp(0., 1.)
+    }
+
+    /// The time of the current stage, the level it goes to and its curve.
+    fn stage_params(&self, state: &State, stack: &mut [Float]) -> (Float, Float, Float) {
+        let time = |input: &P, stack: &mut [Float]| input.run(state, stack).unwrap_or(0.);
+
+        match self.stage {
+            Stage::Delay => (time(&self.inputs.delay, stack), self.start, 0.),
+            Stage::Attack => (
+                time(&self.inputs.attack, stack),
+                self.peak,
+                self.shape.attack_curve,
+            ),
+            Stage::Hold => (time(&self.inputs.hold, stack), self.peak, 0.),
+            Stage::Decay => (
+                time(&self.inputs.decay, stack),
+                self.sustain_level(state, stack),
+                self.shape.decay_curve,
+            ),
+            Stage::Release => (
+                time(&self.inputs.release, stack),
+                0.,
+                self.shape.release_curve,
+            ),
+            Stage::Sustain => (0., self.sustain_level(state, stack), 0.),
+            Stage::Idle => (0., 0., 0.),
+        }
+    }
+
+    /// Start a stage from the level in `start`. The delay and hold stages are
+    /// skipped when they take no time.
+    fn enter(&mut self, stage: Stage, state: &mut State, stack: &mut [Float]) {
+        let time = |input: &P, stack: &mut [Float]| input.run(state, stack).unwrap_or(0.);
+
+        self.stage = match stage {
+            Stage::Delay if time(&self.inputs.delay, stack) <= 0. => Stage::Attack,
+            Stage::Hold if time(&self.inputs.hold, stack) <= 0. => Stage::Decay,
+            stage => stage,
+        };
+
+        state.set(self.phase_index, 0.);
+        state.set(self.output_index, self.start);
+    }
+}
+
+/// Bend the position `x` in a stage, from 0 to 1, by the curve `k`.
+fn curve(x: Float, k: Float) -> Float {
+    let x = x.clamp(0., 1.);
+
+    if k.abs() < 1e-3 {
+        x
+    } else {
+        (math::exp(k * x) - 1.) / (math::exp(k) - 1.)
+    }
+}
+
+impl<P: Program> Module for Adsr<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let (time, target, k) = self.stage_params(state, stack);
+        let phase = state.get(self.phase_index);
+
+        let (rate, level) = match self.stage {
+            Stage::Idle | Stage::Sustain => (0., target),
+            _ => (
+                1. / time.max(MIN_TIME),
+                self.start + (target - self.start) * curve(phase, k),
+            ),
+        };
+
+        update.set(self.phase_index, rate, UpdateType::Differentiable);
+        update.set(self.output_index, level, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, _time_step: Float, stack: &mut [Float]) {
+        let gate = self.inputs.gate.run(state, stack).unwrap_or(0.) >= GATE_LEVEL;
+        let level = state.get(self.output_index);
+        let phase = state.get(self.phase_index);
+
+        if gate && !self.gate {
+            let velocity = self.inputs.velocity.run(state, stack).unwrap_or(0.);
+            let sensitivity = self.shape.velocity_sensitivity.clamp(0., 1.);
+
+            self.peak = 1. - sensitivity + sensitivity * velocity.clamp(0., 1.);
+            self.start = if self.shape.retrigger_from_zero {
+                0.
+            } else {
+                level
+            };
+            self.enter(Stage::Delay, state, stack);
+        } else if !gate && !matches!(self.stage, Stage::Idle | Stage::Release) {
+            self.start = level;
+            self.enter(Stage::Release, state, stack);
+        } else if phase >= 1. {
+            // Continue from the exact level the stage went to
+            self.start = self.stage_params(state, stack).1;
+
+            match self.stage {
+                Stage::Delay => self.enter(Stage::Attack, state, stack),
+                Stage::Attack => self.enter(Stage::Hold, state, stack),
+                Stage::Hold => self.enter(Stage::Decay, state, stack),
+                Stage::Decay if self.shape.looping => self.enter(Stage::Attack, state, stack),
+                Stage::Decay => self.enter(Stage::Sustain, state, stack),
+                Stage::Release => self.enter(Stage::Idle, state, stack),
+                Stage::Idle | Stage::Sustain => state.set(self.phase_index, 0.),
+            }
+        }
+
+        self.gate = gate;
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const GATE: usize = 2;
+    const VELOCITY: usize = 3;
+    const DT: Float = 0.001;
+
+    fn adsr(shape: AdsrShape) -> RungeKutta {
+        let inputs = AdsrInputs {
+            gate: StackProgram::new(vec![Instr::State(GATE)], 1),
+            delay: StackProgram::constant(0.),
+            attack: StackProgram::constant(0.01),
+            hold: StackProgram::constant(0.),
+            decay: StackProgram::constant(0.02),
+            sustain: StackProgram::constant(0.5),
+            release: StackProgram::constant(0.01),
+            velocity: StackProgram::new(vec![Instr::State(VELOCITY)], 1),
+        };
+        let module = Adsr::new(inputs, 0, 1).with_shape(shape);
+
+        RungeKutta::rk4(4).with_modules(vec![Box::new(module)])
+    }
+
+    /// Run for `time` seconds with the gate and velocity, returning the output.
+    fn run(simulator: &mut RungeKutta, gate: Float, velocity: Float, time: Float) -> Float {
+        simulator.get_state().set(GATE, gate);
+        simulator.get_state().set(VELOCITY, velocity);
+
+        for _ in 0..(time / DT).round() as usize {
+            simulator.step(DT);
+        }
+
+        simulator.get_state().get(0)
+    }
+
+    #[test]
+    fn stages() {
+        let mut env = adsr(AdsrShape::default());
+
+        assert!((run(&mut env, 1., 1., 0.005) - 0.5).abs() < 0.15);
+        assert!((run(&mut env, 1., 1., 0.1) - 0.5).abs() < 1e-3);
+        assert!(run(&mut env, 0., 1., 0.006) < 0.3);
+        assert_eq!(run(&mut env, 0., 1., 0.05), 0.);
+
+        // Velocity scales the peak and the sustain level
+        let mut env = adsr(AdsrShape {
+            velocity_sensitivity: 1.,
+            ..Default::default()
+        });
+        assert!((run(&mut env, 1., 0.5, 0.1) - 0.25).abs() < 1e-3);
+
+        // Retriggered from the current level, or from zero
+        assert!(run(&mut env, 0., 1., 0.002) > 0.1);
+        assert!(run(&mut env, 1., 1., 0.002) > 0.1);
+        let mut env = adsr(AdsrShape {
+            retrigger_from_zero: true,
+            ..Default::default()
+        });
+        run(&mut env, 1., 1., 0.1);
+        run(&mut env, 0., 1., 0.002);
+        assert!(run(&mut env, 1., 1., 0.002) < 0.3);
+    }
+
+    #[test]
+    fn curves_and_loop() {
+        // A fast starting attack is above the linear one halfway
+        let mut linear = adsr(AdsrShape::default());
+        let mut fast = adsr(AdsrShape {
+            attack_curve: -4.,
+            ..Default::default()
+        });
+        assert!(run(&mut fast, 1., 1., 0.005) > run(&mut linear, 1., 1., 0.005) + 0.2);
+
+        // Looping goes back to the attack instead of sustaining
+        let mut looping = adsr(AdsrShape {
+            looping: true,
+            ..Default::default()
+        });
+        let peaks = (0..100)
+            .map(|_| run(&mut looping, 1., 1., 0.001))
+            .filter(|level| *level > 0.95)
+            .count();
+        assert!(peaks >= 3);
+    }
+}
@@ -1,27 +1,28 @@
 use super::control_to_frequency;
 use crate::event::ControllerEvent;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
-pub struct AllpassFilter {
-    f0: f32,
+pub struct AllpassFilter<P = DefaultProgram> {
+    f0: Float,
     cap_state: usize,
     signal_output: usize,
-    freq_control_input: StackProgram,
-    linear_control: StackProgram,
-    signal_input: StackProgram,
+    freq_control_input: P,
+    linear_control: P,
+    signal_input: P,
 }
 
-impl AllpassFilter {
+impl<P: Program> AllpassFilter<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         cap_state: usize,
         signal_output: usize,
-        freq_control_input: StackProgram,
-        linear_control: StackProgram,
-        signal_input: StackProgram,
+        freq_control_input: P,
+        linear_control: P,
+        signal_input: P,
     ) -> Self {
         Self {
             f0,
@@ -34,8 +35,8 @@ impl AllpassFilter {
     }
 }
 
-impl Module for AllpassFilter {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for AllpassFilter<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let input = self.signal_input.run(state, stack).unwrap_or(0.);
         let f = control_to_frequency(
             self.f0,
@@ -60,7 +61,7 @@ impl Module for AllpassFilter {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         /* do nothing */
     }
 }
@@ -1,21 +1,31 @@
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    self,
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
+use crate::Float;
 
-pub struct Amplifier {
-    signal_input: StackProgram,
+pub struct Amplifier<P = DefaultProgram> {
+    signal_input: P,
     output_index: usize,
-    lin_control_input: StackProgram,
-    exp_control_input: StackProgram,
+    lin_control_input: P,
+    exp_control_input: P,
 }
 
-impl Amplifier {
+impl<P> Amplifier<P> {
     pub fn new(
-        signal_input: StackProgram,
+        signal_input: P,
         output_index: usize,
-        lin_control_input: StackProgram,
-        exp_control_input: StackProgram,
+        lin_control_input: P,
+        exp_control_input: P,
     ) -> Self {
         Self {
             signal_input,
@@ -26,19 +36,31 @@ impl Amplifier {
     }
 }
 
-fn amplifier_amount(lin_control: f32, exp_control: f32) -> f32 {
+fn amplifier_amount(lin_control: Float, exp_control: Float) -> Float {
     // TODO - these are constants - put them in the Amplifier struct
     // 2.0 and 5.0 should be arguments to `new`
-    let min: f32 = 2.0_f32.powf(-5.0);
-    let scale: f32 = 1. / (1. - min);
+    let min: Float = math::exp2(-5.0);
+    let scale: Float = 1. / (1. - min);
 
     let exp_control = exp_control.clamp(0., 1.);
-    let e = (2.0_f32.powf(5.0 * (exp_control - 1.)) - min) * scale;
+    let e = (math::exp2(5.0 * (exp_control - 1.)) - min) * scale;
     (e + lin_control).max(0.)
 }
 
-impl Module for Amplifier {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+#[cfg(feature = "fixed-point")]
+fn fixed_amplifier_amount(lin_control: Fixed, exp_control: Fixed) -> Fixed {
+    const FIVE: Fixed = Fixed::from_float(5.);
+
+    let min = fixed::math::exp2(-FIVE);
+    let scale = Fixed::ONE / (Fixed::ONE - min);
+
+    let exp_control = exp_control.clamp(Fixed::ZERO, Fixed::ONE);
+    let e = (fixed::math::exp2(FIVE * (exp_control - Fixed::ONE)) - min) * scale;
+    (e + lin_control).max(Fixed::ZERO)
+}
+
+impl<P: Program> Module for Amplifier<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let input = self.signal_input.run(state, stack).unwrap_or(0.0);
         let m = amplifier_amount(
             self.lin_control_input.run(state, stack).unwrap_or(0.0),
@@ -51,7 +73,31 @@ impl Module for Amplifier {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for Amplifier<P> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        let input = self.signal_input.run(state, stack).unwrap_or(Fixed::ZERO);
+        let m = fixed_amplifier_amount(
+            self.lin_control_input
+                .run(state, stack)
+                .unwrap_or(Fixed::ZERO),
+            self.exp_control_input
+                .run(state, stack)
+                .unwrap_or(Fixed::ZERO),
+        );
+        update.set(self.output_index, input * m, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut FixedState, _time_step: TimeStep, _stack: &mut [Fixed]) {
         /* do nothing */
     }
 }
@@ -1,9 +1,11 @@
 use super::control_to_frequency;
 use crate::event::ControllerEvent;
+use crate::math;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
 // NOTE the amplitude of this oscillator needs to be scaled up. It usually goes
 // in the range of 0.008 to 0.016 peak-to-peak.
@@ -20,27 +22,27 @@ use core::f32::consts::PI;
 
 // parameter `a` should be 100 to 1000 or thereabouts
 
-pub struct BowedOscillator {
-    f0: f32,
-    a: f32,
+pub struct BowedOscillator<P = DefaultProgram> {
+    f0: Float,
+    a: Float,
     state_u_index: usize,
     state_v_index: usize,
-    control_input: StackProgram,
-    linear_control: StackProgram,
-    pressure_input: StackProgram,
-    velocity_input: StackProgram,
+    control_input: P,
+    linear_control: P,
+    pressure_input: P,
+    velocity_input: P,
 }
 
-impl BowedOscillator {
+impl<P: Program> BowedOscillator<P> {
     pub fn new(
-        f0: f32,
-        a: f32,
+        f0: Float,
+        a: Float,
         state_u_index: usize,
         state_v_index: usize,
-        control_input: StackProgram,
-        linear_control: StackProgram,
-        pressure_input: StackProgram,
-        velocity_input: StackProgram,
+        control_input: P,
+        linear_control: P,
+        pressure_input: P,
+        velocity_input: P,
     ) -> Self {
         Self {
             f0,
@@ -55,17 +57,17 @@ impl BowedOscillator {
     }
 }
 
-fn friction(a: f32, x: f32) -> f32 {
-    (2. * a).sqrt() * x * (-2. * a * x * x + 0.5).exp()
+fn friction(a: Float, x: Float) -> Float {
+    math::sqrt(2. * a) * x * math::exp(-2. * a * x * x + 0.5)
 }
 
 #[allow(dead_code)]
-fn discontinuous_friction(a: f32, x: f32) -> f32 {
-    x.signum() * (-a * x.abs()).exp()
+fn discontinuous_friction(a: Float, x: Float) -> Float {
+    x.signum() * math::exp(-a * x.abs())
 }
 
-impl Module for BowedOscillator {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for BowedOscillator<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let linear_control = self.linear_control.run(state, stack).unwrap_or(0.);
 
         let omega = control_to_frequency(
@@ -100,7 +102,7 @@ impl Module for BowedOscillator {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         /* do nothing */
     }
 }
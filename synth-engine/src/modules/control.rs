@@ -1,19 +1,26 @@
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    module::FixedModule,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
-use core::f32::consts::PI;
+use crate::Float;
 
 pub struct ContinuousControl {
     output_index: usize,
     control: usize,
-    value: f32,
-    min_value: f32,
-    max_value: f32,
-    filter_freq: Option<f32>,
+    value: Float,
+    min_value: Float,
+    max_value: Float,
+    filter_freq: Option<Float>,
 }
 
 impl ContinuousControl {
-    pub fn new(output_index: usize, control: usize, min_value: f32, max_value: f32) -> Self {
+    pub fn new(output_index: usize, control: usize, min_value: Float, max_value: Float) -> Self {
         Self {
             output_index,
             control,
@@ -24,13 +31,13 @@ impl ContinuousControl {
         }
     }
 
-    fn compute_value(&self) -> f32 {
+    fn compute_value(&self) -> Float {
         (self.max_value - self.min_value) * self.value + self.min_value
     }
 }
 
 impl Module for ContinuousControl {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, _stack: &mut [f32]) {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, _stack: &mut [Float]) {
         if let Some(freq) = self.filter_freq {
             let k = 2. * PI * freq;
             let v = self.compute_value();
@@ -48,14 +55,40 @@ impl Module for ContinuousControl {
 
     fn process_event(&mut self, event: &ControllerEvent) {
         match event {
-            ControllerEvent::ContinuousControl { control, value } if *control == self.control => {
+            ControllerEvent::ContinuousControl { control, value, .. }
+                if *control == self.control =>
+            {
                 self.value = *value;
             }
             _ => { /* do nothing */ }
         }
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(feature = "fixed-point")]
+impl FixedModule for ContinuousControl {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, _stack: &mut [Fixed]) {
+        let v = Fixed::from_float(self.compute_value());
+
+        if let Some(freq) = self.filter_freq {
+            let k = Fixed::from_float(2. * PI * freq);
+            let d = v - state.get(self.output_index);
+
+            update.set(self.output_index, k * d, UpdateType::Differentiable);
+        } else {
+            update.set(self.output_index, v, UpdateType::Absolute);
+        }
+    }
+
+    fn process_event(&mut self, event: &ControllerEvent) {
+        Module::process_event(self, event);
+    }
+
+    fn finalize(&mut self, _state: &mut FixedState, _time_step: TimeStep, _stack: &mut [Fixed]) {
         /* do nothing */
     }
 }
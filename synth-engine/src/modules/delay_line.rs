@@ -4,27 +4,59 @@ use crate::interpolation::Interpolation;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use alloc::vec;
-use alloc::vec::Vec;
+use crate::Float;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 
-pub struct DelayLine {
-    data: Vec<f32>,
+/// The delay buffer of a delay line built at runtime.
+#[cfg(feature = "alloc")]
+pub type DefaultBuffer = Vec<Float>;
+
+/// Without `alloc` the delay buffer is borrowed, typically from a static array.
+#[cfg(not(feature = "alloc"))]
+pub type DefaultBuffer = &'static mut [Float];
+
+pub struct DelayLine<P = DefaultProgram, B = DefaultBuffer> {
+    data: B,
     current_index: usize,
     signal_output: usize,
-    f0: f32,
-    signal_input: StackProgram,
-    pitch_control: StackProgram,
-    linear_modulation: StackProgram,
+    f0: Float,
+    signal_input: P,
+    pitch_control: P,
+    linear_modulation: P,
 }
 
-impl DelayLine {
+#[cfg(feature = "alloc")]
+impl<P: Program> DelayLine<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         signal_output: usize,
-        signal_input: StackProgram,
-        pitch_control: StackProgram,
-        linear_modulation: StackProgram,
+        signal_input: P,
+        pitch_control: P,
+        linear_modulation: P,
         data_size: usize,
+    ) -> Self {
+        Self::with_buffer(
+            f0,
+            signal_output,
+            signal_input,
+            pitch_control,
+            linear_modulation,
+            vec![0.; data_size],
+        )
+    }
+}
+
+impl<P: Program, B: AsRef<[Float]> + AsMut<[Float]>> DelayLine<P, B> {
+    /// A delay line using `data` as its buffer. The length of the buffer sets
+    /// the longest delay.
+    pub fn with_buffer(
+        f0: Float,
+        signal_output: usize,
+        signal_input: P,
+        pitch_control: P,
+        linear_modulation: P,
+        data: B,
     ) -> Self {
         Self {
             f0,
@@ -33,12 +65,12 @@ impl DelayLine {
             pitch_control,
             linear_modulation,
             current_index: 0,
-            data: vec![0.; data_size],
+            data,
         }
     }
 
     fn index_modulo(&self, index: usize) -> usize {
-        let size = self.data.len();
+        let size = self.data.as_ref().len();
 
         ((index % size) + size) % size
     }
@@ -52,10 +84,11 @@ impl DelayLine {
     }
 }
 
-impl Module for DelayLine {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
-        let wi = self.write_index() as f32;
-        let l = self.data.len() as f32;
+impl<P: Program, B: AsRef<[Float]> + AsMut<[Float]> + Send> Module for DelayLine<P, B> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let wi = self.write_index() as Float;
+        let data = self.data.as_ref();
+        let l = data.len() as Float;
         let d = update.get_time_step();
         let s = update.get_delta_time();
 
@@ -70,7 +103,7 @@ impl Module for DelayLine {
 
         update.set(
             self.signal_output,
-            self.data.lagrange_interpolate(index),
+            data.lagrange_interpolate(index),
             UpdateType::Absolute,
         );
     }
@@ -79,10 +112,10 @@ impl Module for DelayLine {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, stack: &mut [Float]) {
         let write_index = self.write_index();
 
-        self.data[write_index] = self.signal_input.run(state, stack).unwrap_or(0.);
+        self.data.as_mut()[write_index] = self.signal_input.run(state, stack).unwrap_or(0.);
         self.increment_index();
     }
 }
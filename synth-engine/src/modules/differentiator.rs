@@ -0,0 +1,76 @@
+use crate::event::ControllerEvent;
+use crate::math::consts::PI;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The derivative of its input, band limited like an analog differentiator.
+/// A state follows the input through a one pole lowpass, and the output is
+/// the rate at which it moves. Below the bandwidth this is the derivative of
+/// the input.
+pub struct Differentiator<P = DefaultProgram> {
+    signal_input: P,
+    output_index: usize,
+    follower_index: usize,
+    /// The bandwidth as an angular frequency
+    omega: Float,
+}
+
+impl<P: Program> Differentiator<P> {
+    pub fn new(
+        signal_input: P,
+        bandwidth: Float,
+        output_index: usize,
+        follower_index: usize,
+    ) -> Self {
+        Self {
+            signal_input,
+            output_index,
+            follower_index,
+            omega: 2. * PI * bandwidth,
+        }
+    }
+}
+
+impl<P: Program> Module for Differentiator<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let x = self.signal_input.run(state, stack).unwrap_or(0.);
+        let rate = self.omega * (x - state.get(self.follower_index));
+
+        update.set(self.follower_index, rate, UpdateType::Differentiable);
+        update.set(self.output_index, rate, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::modules::Integrator;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    #[test]
+    fn ramp_slope() {
+        // The input is a ramp of slope 3, integrated in state 2
+        let ramp = Integrator::new(StackProgram::constant(3.), StackProgram::constant(0.), 2);
+        let differentiator =
+            Differentiator::new(StackProgram::new(vec![Instr::State(2)], 1), 1000., 0, 1);
+        let mut simulator =
+            RungeKutta::rk4(3).with_modules(vec![Box::new(ramp), Box::new(differentiator)]);
+
+        for _ in 0..100 {
+            simulator.step(1e-4);
+        }
+        assert!((simulator.get_state().get(0) - 3.).abs() < 1e-2);
+    }
+}
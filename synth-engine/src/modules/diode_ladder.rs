@@ -0,0 +1,116 @@
+//! A four pole diode ladder lowpass, like the filter of the TB-303.
+//!
+//! Unlike the transistor ladder, the stages of a diode ladder load each other:
+//! every capacitor is charged by the diode pair above it and discharged by the
+//! pair below it. The currents through the pairs are bent by the distortion.
+
+use super::FilterInputs;
+use crate::distortion::*;
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The feedback at which the linear ladder starts to self-oscillate
+const MAX_FEEDBACK: Float = 18.39;
+/// The frequency it oscillates at, relative to the rate of the stages
+const RESONANT_FREQUENCY: Float = 1.195;
+
+pub struct DiodeLadder<P = DefaultProgram> {
+    f0: Float,
+    inputs: FilterInputs<P>,
+    /// The four capacitor voltages, the last one is the output
+    state_indices: [usize; 4],
+    distortion: Option<DistortionType>,
+}
+
+impl<P: Program> DiodeLadder<P> {
+    pub fn new(f0: Float, inputs: FilterInputs<P>, state_indices: [usize; 4]) -> Self {
+        Self {
+            f0,
+            inputs,
+            state_indices,
+            distortion: Some(DistortionType::Tanh),
+        }
+    }
+
+    pub fn with_distortion(mut self, distortion: Option<DistortionType>) -> Self {
+        self.distortion = distortion;
+        self
+    }
+}
+
+impl<P: Program> Module for DiodeLadder<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        // The resonance peaks at the cutoff
+        let g = self.inputs.omega(self.f0, state, stack) / RESONANT_FREQUENCY;
+        let k = MAX_FEEDBACK * self.inputs.resonance(state, stack);
+        let input = self.inputs.signal.run(state, stack).unwrap_or(0.);
+        let y = self.state_indices.map(|i| state.get(i));
+        let current = |v: Float| v.distort(&self.distortion);
+
+        let i0 = current(input - k * y[3] - y[0]);
+        let i1 = current(y[0] - y[1]);
+        let i2 = current(y[1] - y[2]);
+        let i3 = current(y[2] - y[3]);
+        let dy = [i0 - i1, i1 - i2, i2 - i3, i3];
+
+        for (index, dy) in self.state_indices.iter().zip(dy) {
+            update.set(*index, g * dy, UpdateType::Differentiable);
+        }
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const DT: Float = 1. / 48000.;
+
+    /// The peak output between 0.1 and 0.2 s after a short pulse.
+    fn ringing(resonance: Float) -> Float {
+        let inputs = FilterInputs {
+            signal: StackProgram::new(vec![Instr::State(4)], 1),
+            cutoff: StackProgram::constant(0.),
+            linear: StackProgram::constant(0.),
+            resonance: StackProgram::constant(resonance),
+        };
+        let filter = DiodeLadder::new(500., inputs, [0, 1, 2, 3]);
+        let mut simulator = RungeKutta::rk4(5).with_modules(vec![Box::new(filter)]);
+        let mut peak: Float = 0.;
+
+        simulator.get_state().set(4, 1.);
+
+        for i in 0..9600 {
+            simulator.step(DT);
+
+            if i == 48 {
+                simulator.get_state().set(4, 0.);
+            }
+
+            if i >= 4800 {
+                peak = peak.max(simulator.get_state().get(3).abs());
+            }
+        }
+
+        peak
+    }
+
+    #[test]
+    fn self_oscillation() {
+        assert!(ringing(0.5) < 1e-3);
+        assert!(ringing(1.2) > 0.05);
+    }
+}
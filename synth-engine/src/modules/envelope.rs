@@ -1,30 +1,42 @@
+#[cfg(feature = "fixed-point")]
+use super::FIXED_TWO_PI;
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    self,
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
 #[allow(dead_code)]
-fn hamming(x: f32) -> f32 {
+fn hamming(x: Float) -> Float {
     let x = x.clamp(0., 1.);
 
-    0.54 - 0.46 * (2. * PI * x).cos()
+    0.54 - 0.46 * math::cos(2. * PI * x)
 }
 
 #[allow(dead_code)]
-fn blackman(x: f32) -> f32 {
+fn blackman(x: Float) -> Float {
     let x = x.clamp(0., 1.);
-    0.42 - 0.5 * (2. * PI * x).cos() + 0.08 * (4. * PI * x).cos()
+    0.42 - 0.5 * math::cos(2. * PI * x) + 0.08 * math::cos(4. * PI * x)
 }
 
 #[allow(dead_code)]
-fn triangle(x: f32) -> f32 {
+fn triangle(x: Float) -> Float {
     let x = x.clamp(0., 1.);
 
     1. - (2. * x - 1.).abs()
 }
 
-const MIN_TIME: f32 = 0.01_f32; // 10 ms
+const MIN_TIME: Float = 0.01; // 10 ms
 
 enum EnvState {
     Finished,
@@ -33,31 +45,38 @@ enum EnvState {
     Decay,
 }
 
-#[allow(dead_code)]
-#[derive(Eq, PartialEq)]
-enum EnvType {
+/// What the envelope does with its gate.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EnvType {
+    /// Rise and fall once when the gate goes up
     AttackDecay,
+    /// Rise when the gate goes up, hold while it is up and fall when it goes
+    /// down
+    #[default]
     AttackRelease,
+    /// Rise and fall over and over, ignoring the gate
     Cyclic,
 }
 
-pub struct Envelope {
-    signal_input: StackProgram,
-    attack_input: StackProgram,
-    decay_input: StackProgram,
-    shape_select: StackProgram,
+pub struct Envelope<P = DefaultProgram> {
+    signal_input: P,
+    attack_input: P,
+    decay_input: P,
+    shape_select: P,
     output_index: usize,
     cycle_state: usize,
     env_state: EnvState,
     env_type: EnvType,
+    /// The gate in the previous step, to find its rising edges
+    gate: bool,
 }
 
-impl Envelope {
+impl<P> Envelope<P> {
     pub fn new(
-        signal_input: StackProgram,
-        attack_input: StackProgram,
-        decay_input: StackProgram,
-        shape_select: StackProgram,
+        signal_input: P,
+        attack_input: P,
+        decay_input: P,
+        shape_select: P,
         output_index: usize,
         cycle_state: usize,
     ) -> Self {
@@ -70,24 +89,56 @@ impl Envelope {
             cycle_state,
             env_state: EnvState::Finished,
             env_type: EnvType::AttackRelease,
+            gate: false,
+        }
+    }
+
+    pub fn with_type(mut self, env_type: EnvType) -> Self {
+        self.env_type = env_type;
+        self
+    }
+
+    /// Move to the next stage at the end of a step, given whether the gate is
+    /// up and whether the cycle reached its top or its bottom.
+    fn next_state(&mut self, gate: bool, top: bool, bottom: bool) {
+        let rising = gate && !self.gate;
+
+        self.gate = gate;
+
+        use EnvState::*;
+        use EnvType::*;
+
+        match (&self.env_state, &self.env_type) {
+            (Attack, AttackRelease) if top => self.env_state = Hold,
+            (Attack, AttackDecay | Cyclic) if top => self.env_state = Decay,
+            (Hold, AttackRelease) if !gate => self.env_state = Decay,
+            (Hold, AttackDecay | Cyclic) => self.env_state = Decay,
+            (Decay, Cyclic) if bottom => self.env_state = Attack,
+            (Decay, AttackRelease) if gate => self.env_state = Attack,
+            (Decay, AttackDecay) if rising => self.env_state = Attack,
+            (Decay, AttackRelease | AttackDecay) if bottom => self.env_state = Finished,
+            (Finished, Cyclic) => self.env_state = Attack,
+            (Finished, AttackRelease) if gate => self.env_state = Attack,
+            (Finished, AttackDecay) if rising => self.env_state = Attack,
+            _ => { /* do nothing */ }
         }
     }
 }
 
-fn rise_decay(t: f32) -> f32 {
+fn rise_decay(t: Float) -> Float {
     let t = t.max(MIN_TIME);
 
     1. / t
 }
 
-fn output_value(cycle_index: f32, shape: f32) -> f32 {
+fn output_value(cycle_index: Float, shape: Float) -> Float {
     let cycle_index = (cycle_index * 0.5).clamp(0., 0.5);
     let shape = shape.clamp(0., 1.);
     blackman(cycle_index) * shape + triangle(cycle_index) * (1. - shape)
 }
 
-impl Module for Envelope {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for Envelope<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let attack = self.attack_input.run(state, stack).unwrap_or(0.);
         let decay = self.decay_input.run(state, stack).unwrap_or(0.);
 
@@ -124,28 +175,148 @@ impl Module for Envelope {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, stack: &mut [Float]) {
         let input_state = self.signal_input.run(state, stack).unwrap_or(0.0);
         let output_state = state.get(self.output_index);
         let cycle = state.get(self.cycle_state);
 
-        use EnvState::*;
-        use EnvType::*;
-
-        match (&self.env_state, &self.env_type) {
-            (Attack, AttackRelease) if cycle >= 1. => self.env_state = Hold,
-            (Attack, AttackDecay | Cyclic) if cycle >= 1. => self.env_state = Decay,
-            (Hold, AttackRelease) if input_state < 0.5 => self.env_state = Decay,
-            (Hold, AttackDecay | Cyclic) => self.env_state = Decay,
-            (Decay, Cyclic) if cycle <= 0. => self.env_state = Attack,
-            (Decay, AttackRelease | AttackDecay) if input_state >= 0.5 => self.env_state = Attack,
-            (Decay, AttackRelease | AttackDecay) if cycle <= 0. => self.env_state = Finished,
-            (Finished, Cyclic) => self.env_state = Attack,
-            (Finished, AttackRelease | AttackDecay) if input_state > 0.5 => self.env_state = Attack,
-            _ => { /* do nothing */ }
-        }
+        self.next_state(input_state >= 0.5, cycle >= 1., cycle <= 0.);
 
         state.set(self.output_index, output_state.clamp(0., 1.));
         state.set(self.cycle_state, cycle.clamp(0., 1.));
     }
 }
+
+#[cfg(feature = "fixed-point")]
+const FIXED_HALF: Fixed = Fixed::from_float(0.5);
+
+#[cfg(feature = "fixed-point")]
+fn fixed_rise_decay(t: Fixed) -> Fixed {
+    const FIXED_MIN_TIME: Fixed = Fixed::from_float(MIN_TIME);
+
+    Fixed::ONE / t.max(FIXED_MIN_TIME)
+}
+
+#[cfg(feature = "fixed-point")]
+fn fixed_output_value(cycle_index: Fixed, shape: Fixed) -> Fixed {
+    const A0: Fixed = Fixed::from_float(0.42);
+    const A2: Fixed = Fixed::from_float(0.08);
+    const TWO: Fixed = Fixed::from_float(2.);
+
+    let x = (cycle_index * FIXED_HALF).clamp(Fixed::ZERO, FIXED_HALF);
+    let shape = shape.clamp(Fixed::ZERO, Fixed::ONE);
+    let phase = FIXED_TWO_PI * x;
+    let blackman = A0 - FIXED_HALF * fixed::math::cos(phase) + A2 * fixed::math::cos(TWO * phase);
+    let triangle = Fixed::ONE - (TWO * x - Fixed::ONE).abs();
+
+    blackman * shape + triangle * (Fixed::ONE - shape)
+}
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for Envelope<P> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        let delta = match self.env_state {
+            EnvState::Attack => {
+                fixed_rise_decay(self.attack_input.run(state, stack).unwrap_or(Fixed::ZERO))
+            }
+            EnvState::Decay => {
+                -fixed_rise_decay(self.decay_input.run(state, stack).unwrap_or(Fixed::ZERO))
+            }
+            _ => return,
+        };
+        let shape = self.shape_select.run(state, stack).unwrap_or(Fixed::ZERO);
+
+        update.set(self.cycle_state, delta, UpdateType::Differentiable);
+        update.set(
+            self.output_index,
+            fixed_output_value(state.get(self.cycle_state), shape),
+            UpdateType::Absolute,
+        );
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut FixedState, _time_step: TimeStep, stack: &mut [Fixed]) {
+        let input_state = self.signal_input.run(state, stack).unwrap_or(Fixed::ZERO);
+        let output_state = state.get(self.output_index);
+        let cycle = state.get(self.cycle_state);
+
+        self.next_state(
+            input_state >= FIXED_HALF,
+            cycle >= Fixed::ONE,
+            cycle <= Fixed::ZERO,
+        );
+
+        state.set(
+            self.output_index,
+            output_state.clamp(Fixed::ZERO, Fixed::ONE),
+        );
+        state.set(self.cycle_state, cycle.clamp(Fixed::ZERO, Fixed::ONE));
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    const DT: Float = 1. / 1000.;
+    const GATE: usize = 2;
+
+    /// The output after each of `steps` steps of 1 ms, with the gate up for
+    /// the first `gate_steps`. Attack and decay take 0.1 s each.
+    fn run(env_type: EnvType, gate_steps: usize, steps: usize) -> Vec<Float> {
+        let envelope = Envelope::new(
+            StackProgram::new(vec![Instr::State(GATE)], 1),
+            StackProgram::constant(0.1),
+            StackProgram::constant(0.1),
+            StackProgram::constant(0.),
+            0,
+            1,
+        )
+        .with_type(env_type);
+        let mut simulator = RungeKutta::rk4(3).with_modules(vec![Box::new(envelope)]);
+
+        simulator.get_state().set(GATE, 1.);
+
+        (0..steps)
+            .map(|i| {
+                if i == gate_steps {
+                    simulator.get_state().set(GATE, 0.);
+                }
+
+                simulator.step(DT);
+                simulator.get_state().get(0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn attack_decay() {
+        // Rises and falls once while the gate is held
+        let output = run(EnvType::AttackDecay, 500, 500);
+        assert!(output[100] > 0.9);
+        assert!(output[250] < 0.01 && output[499] < 0.01);
+
+        // Holds while the gate is held
+        let output = run(EnvType::AttackRelease, 500, 500);
+        assert!(output[250] > 0.99 && output[499] > 0.99);
+    }
+
+    #[test]
+    fn cyclic() {
+        // Rises and falls over and over without a gate
+        let output = run(EnvType::Cyclic, 0, 500);
+        let peaks = output
+            .windows(2)
+            .filter(|w| w[0] > 0.9 && w[1] <= 0.9)
+            .count();
+        assert!(peaks >= 2);
+        assert!(output[300..].iter().any(|v| *v < 0.05));
+    }
+}
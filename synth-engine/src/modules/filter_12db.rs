@@ -1,35 +1,36 @@
 use super::control_to_frequency;
 use crate::distortion::*;
 use crate::event::ControllerEvent;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
-const CLAMP_VALUE: f32 = 2.5;
+const CLAMP_VALUE: Float = 2.5;
 
-pub struct Filter12db {
-    f0: f32,
+pub struct Filter12db<P = DefaultProgram> {
+    f0: Float,
     state_hp: usize,
     state_bp: usize,
     state_lp: usize,
-    freq_control_input: StackProgram,
-    linear_control_input: StackProgram,
-    res_control_input: StackProgram,
-    signal_input: StackProgram,
+    freq_control_input: P,
+    linear_control_input: P,
+    res_control_input: P,
+    signal_input: P,
     distortion: Option<DistortionType>,
 }
 
-impl Filter12db {
+impl<P: Program> Filter12db<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         state_hp: usize,
         state_bp: usize,
         state_lp: usize,
-        freq_control_input: StackProgram,
-        linear_control_input: StackProgram,
-        res_control_input: StackProgram,
-        signal_input: StackProgram,
+        freq_control_input: P,
+        linear_control_input: P,
+        res_control_input: P,
+        signal_input: P,
     ) -> Self {
         Self {
             f0,
@@ -40,13 +41,18 @@ impl Filter12db {
             linear_control_input,
             res_control_input,
             signal_input,
-            distortion: None, // Some(DistortionType::Smoothstep), //Some(make_diodelike(1000., 2.)),
+            distortion: None,
         }
     }
+
+    pub fn with_distortion(mut self, distortion: Option<DistortionType>) -> Self {
+        self.distortion = distortion;
+        self
+    }
 }
 
-impl Module for Filter12db {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for Filter12db<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let a = control_to_frequency(
             self.f0,
             self.freq_control_input.run(state, stack).unwrap_or(0.),
@@ -79,7 +85,7 @@ impl Module for Filter12db {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         let bp = state.get(self.state_bp);
         let lp = state.get(self.state_lp);
 
@@ -1,35 +1,36 @@
 use super::control_to_frequency;
 use crate::distortion::*;
 use crate::event::ControllerEvent;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
-pub struct Filter24db {
-    f0: f32,
+pub struct Filter24db<P = DefaultProgram> {
+    f0: Float,
     state0_index: usize,
     state1_index: usize,
     state2_index: usize,
     state3_index: usize,
-    freq_control_input: StackProgram,
-    linear_control_input: StackProgram,
-    res_control_input: StackProgram,
-    signal_input: StackProgram,
+    freq_control_input: P,
+    linear_control_input: P,
+    res_control_input: P,
+    signal_input: P,
     distortion: Option<DistortionType>,
 }
 
-impl Filter24db {
+impl<P: Program> Filter24db<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         state0_index: usize,
         state1_index: usize,
         state2_index: usize,
         state3_index: usize,
-        freq_control_input: StackProgram,
-        linear_control_input: StackProgram,
-        res_control_input: StackProgram,
-        signal_input: StackProgram,
+        freq_control_input: P,
+        linear_control_input: P,
+        res_control_input: P,
+        signal_input: P,
     ) -> Self {
         Self {
             f0,
@@ -44,17 +45,22 @@ impl Filter24db {
             distortion: Some(DistortionType::Smoothstep),
         }
     }
+
+    pub fn with_distortion(mut self, distortion: Option<DistortionType>) -> Self {
+        self.distortion = distortion;
+        self
+    }
 }
 
-impl Module for Filter24db {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
-        let f: f32 = control_to_frequency(
+impl<P: Program> Module for Filter24db<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let f: Float = control_to_frequency(
             self.f0,
             self.freq_control_input.run(state, stack).unwrap_or(0.),
             self.linear_control_input.run(state, stack).unwrap_or(0.),
         );
-        let g: f32 = f * 2. * PI;
-        let r: f32 = self
+        let g: Float = f * 2. * PI;
+        let r: Float = self
             .res_control_input
             .run(state, stack)
             .unwrap_or(0.)
@@ -92,7 +98,7 @@ impl Module for Filter24db {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         /* do nothing */
     }
 }
@@ -1,29 +1,39 @@
 use super::control_to_frequency;
+#[cfg(feature = "fixed-point")]
+use super::{fixed_control_to_frequency, FIXED_TWO_PI};
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
-pub struct Filter6db {
-    f0: f32,
+pub struct Filter6db<P = DefaultProgram> {
+    f0: Float,
     internal_state: usize,
     lowpass_output: usize,
     highpass_output: usize,
-    freq_control_input: StackProgram,
-    linear_control: StackProgram,
-    signal_input: StackProgram,
+    freq_control_input: P,
+    linear_control: P,
+    signal_input: P,
 }
 
-impl Filter6db {
+impl<P> Filter6db<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         internal_state: usize,
         lowpass_output: usize,
         highpass_output: usize,
-        freq_control_input: StackProgram,
-        linear_control: StackProgram,
-        signal_input: StackProgram,
+        freq_control_input: P,
+        linear_control: P,
+        signal_input: P,
     ) -> Self {
         Self {
             f0,
@@ -37,8 +47,8 @@ impl Filter6db {
     }
 }
 
-impl Module for Filter6db {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for Filter6db<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let input = self.signal_input.run(state, stack).unwrap_or(0.);
         let f = control_to_frequency(
             self.f0,
@@ -71,7 +81,52 @@ impl Module for Filter6db {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for Filter6db<P> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        let input = self.signal_input.run(state, stack).unwrap_or(Fixed::ZERO);
+        let f = fixed_control_to_frequency(
+            Fixed::from_float(self.f0),
+            self.freq_control_input
+                .run(state, stack)
+                .unwrap_or(Fixed::ZERO),
+            self.linear_control.run(state, stack).unwrap_or(Fixed::ZERO),
+        );
+
+        let a = FIXED_TWO_PI * f;
+
+        update.set(
+            self.lowpass_output,
+            a * (input - state.get(self.lowpass_output)),
+            UpdateType::Differentiable,
+        );
+
+        // The internal state is kept multiplied by `a`, as the integral alone
+        // is too small for fixed point at high cutoff frequencies. This
+        // ignores changes of `a` over time.
+        update.set(
+            self.highpass_output,
+            input - state.get(self.internal_state),
+            UpdateType::Absolute,
+        );
+
+        update.set(
+            self.internal_state,
+            a * state.get(self.highpass_output),
+            UpdateType::Differentiable,
+        );
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut FixedState, _time_step: TimeStep, _stack: &mut [Fixed]) {
         /* do nothing */
     }
 }
@@ -1,20 +1,18 @@
 use crate::event::ControllerEvent;
+use crate::math;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
+use crate::Float;
 
-pub struct Folder {
-    signal_input: StackProgram,
-    control_input: StackProgram,
+pub struct Folder<P = DefaultProgram> {
+    signal_input: P,
+    control_input: P,
     output_index: usize,
 }
 
-impl Folder {
-    pub fn new(
-        signal_input: StackProgram,
-        control_input: StackProgram,
-        output_index: usize,
-    ) -> Self {
+impl<P: Program> Folder<P> {
+    pub fn new(signal_input: P, control_input: P, output_index: usize) -> Self {
         Self {
             signal_input,
             control_input,
@@ -23,8 +21,8 @@ impl Folder {
     }
 }
 
-impl Module for Folder {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for Folder<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let i = self.signal_input.run(state, stack).unwrap_or(0.);
         let c = self
             .control_input
@@ -34,14 +32,14 @@ impl Module for Folder {
             .min(5.)
             + 1.;
 
-        update.set(self.output_index, (i * c).sin(), UpdateType::Absolute);
+        update.set(self.output_index, math::sin(i * c), UpdateType::Absolute);
     }
 
     fn process_event(&mut self, _event: &ControllerEvent) {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         /* do nothing */
     }
 }
@@ -0,0 +1,90 @@
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// Integrates its input: the derivative of the output is the input, minus the
+/// output times the leak. The output stays between its limits.
+pub struct Integrator<P = DefaultProgram> {
+    signal_input: P,
+    leak_input: P,
+    output_index: usize,
+    min: Float,
+    max: Float,
+}
+
+impl<P: Program> Integrator<P> {
+    pub fn new(signal_input: P, leak_input: P, output_index: usize) -> Self {
+        Self {
+            signal_input,
+            leak_input,
+            output_index,
+            min: -Float::MAX,
+            max: Float::MAX,
+        }
+    }
+
+    pub fn with_limits(mut self, min: Float, max: Float) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+}
+
+impl<P: Program> Module for Integrator<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let x = self.signal_input.run(state, stack).unwrap_or(0.);
+        let leak = self.leak_input.run(state, stack).unwrap_or(0.);
+        let y = state.get(self.output_index);
+        let dy = x - leak * y;
+
+        // Stop at the limits instead of integrating past them
+        let dy = if (y >= self.max && dy > 0.) || (y <= self.min && dy < 0.) {
+            0.
+        } else {
+            dy
+        };
+
+        update.set(self.output_index, dy, UpdateType::Differentiable);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        let y = state.get(self.output_index);
+
+        state.set(self.output_index, y.max(self.min).min(self.max));
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    #[test]
+    fn leak_and_limits() {
+        let integrator = Integrator::new(StackProgram::constant(1.), StackProgram::constant(0.), 0)
+            .with_limits(-1., 0.5);
+        let leaky = Integrator::new(StackProgram::constant(1.), StackProgram::constant(2.), 1);
+        let mut simulator =
+            RungeKutta::rk4(2).with_modules(vec![Box::new(integrator), Box::new(leaky)]);
+
+        for _ in 0..100 {
+            simulator.step(0.001);
+        }
+        assert!((simulator.get_state().get(0) - 0.1).abs() < 1e-4);
+
+        for _ in 0..4900 {
+            simulator.step(0.001);
+        }
+        assert_eq!(simulator.get_state().get(0), 0.5);
+        // Settles where the leak takes away the input
+        assert!((simulator.get_state().get(1) - 0.5).abs() < 1e-3);
+    }
+}
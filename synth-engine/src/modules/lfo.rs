@@ -0,0 +1,291 @@
+//! A low frequency oscillator for modulation.
+//!
+//! The phase is a state of the simulation, from 0 to 1 over a cycle, and the
+//! output is an absolute value computed from it. The rate is either given in
+//! Hz or follows the tempo of a clock, measured between its pulses.
+
+use super::noise::{A_PARAMETER_DEFAULT, B_PARAMETER_DEFAULT};
+use crate::event::ControllerEvent;
+use crate::math;
+use crate::math::consts::PI;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+const GATE_LEVEL: Float = 0.5;
+
+/// The input programs of an LFO.
+pub struct LfoInputs<P = DefaultProgram> {
+    /// The rate in Hz, also used until the clock has given two pulses
+    pub rate: P,
+    /// Restarts the cycle and the fade-in on a rising edge
+    pub trigger: P,
+    /// The fade-in time in seconds
+    pub fade: P,
+    /// Clock pulses to follow the tempo of
+    pub clock: P,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    /// A rising saw
+    Saw,
+    Square,
+    /// A new random value every cycle, with smooth steps between them
+    Random,
+}
+
+/// The length of a cycle in beats of a clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempoSync {
+    /// Beats per cycle, 1 for a quarter note
+    pub beats: Float,
+    /// Clock pulses per beat
+    pub pulses_per_beat: Float,
+}
+
+pub struct Lfo<P = DefaultProgram> {
+    inputs: LfoInputs<P>,
+    output_index: usize,
+    phase_index: usize,
+    shape: LfoShape,
+    unipolar: bool,
+    sync: Option<TempoSync>,
+    /// Time since the last trigger, for the fade-in
+    elapsed: Float,
+    trigger: bool,
+    clock: bool,
+    /// Time since the last clock pulse, and the time between the last two
+    since_pulse: Option<Float>,
+    pulse_period: Option<Float>,
+    random: u32,
+    /// The random values the random shape goes from and to
+    random_from: Float,
+    random_to: Float,
+}
+
+impl<P: Program> Lfo<P> {
+    pub fn new(inputs: LfoInputs<P>, output_index: usize, phase_index: usize) -> Self {
+        Self {
+            inputs,
+            output_index,
+            phase_index,
+            shape: LfoShape::Sine,
+            unipolar: false,
+            sync: None,
+            elapsed: 0.,
+            trigger: false,
+            clock: false,
+            since_pulse: None,
+            pulse_period: None,
+            random: 1,
+            random_from: 0.,
+            random_to: 0.,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: LfoShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Output from 0 to 1 instead of from -1 to 1.
+    pub fn with_unipolar(mut self, unipolar: bool) -> Self {
+        self.unipolar = unipolar;
+        self
+    }
+
+    /// Follow the tempo of the clock input.
+    pub fn with_sync(mut self, sync: TempoSync) -> Self {
+        self.sync = Some(sync);
+        self
+    }
+
+    /// Seed the random shape.
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.random = seed;
+        self.random_to = self.next_random();
+        self
+    }
+
+    fn next_random(&mut self) -> Float {
+        self.random = self
+            .random
+            .wrapping_mul(A_PARAMETER_DEFAULT)
+            .wrapping_add(B_PARAMETER_DEFAULT);
+
+        2. * (self.random as Float) / (u32::MAX as Float) - 1.
+    }
+
+    fn frequency(&self, state: &State, stack: &mut [Float]) -> Float {
+        match (self.sync, self.pulse_period) {
+            (Some(sync), Some(period)) => 1. / (period * sync.pulses_per_beat * sync.beats),
+            _ => self.inputs.rate.run(state, stack).unwrap_or(0.),
+        }
+    }
+
+    /// The bipolar waveform at the position `cycle` in the cycle.
+    fn wave(&self, cycle: Float) -> Float {
+        let phase = cycle - math::floor(cycle);
+
+        match self.shape {
+            LfoShape::Sine => math::sin(2. * PI * phase),
+            LfoShape::Triangle => {
+                let shifted = phase + 0.25;
+
+                1. - 4. * (shifted - math::floor(shifted) - 0.5).abs()
+            }
+            LfoShape::Saw => 2. * phase - 1.,
+            LfoShape::Square if phase < 0.5 => 1.,
+            LfoShape::Square => -1.,
+            LfoShape::Random => {
+                // Hold the new value until the phase wraps
+                let step = 0.5 - 0.5 * math::cos(PI * cycle.clamp(0., 1.));
+
+                self.random_from + (self.random_to - self.random_from) * step
+            }
+        }
+    }
+}
+
+impl<P: Program> Module for Lfo<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let fade = self.inputs.fade.run(state, stack).unwrap_or(0.);
+        let amount = if fade > 0. {
+            ((self.elapsed + update.get_delta_time()) / fade).min(1.)
+        } else {
+            1.
+        };
+        let value = self.wave(state.get(self.phase_index));
+        let value = if self.unipolar {
+            0.5 + 0.5 * value
+        } else {
+            value
+        };
+
+        update.set(
+            self.phase_index,
+            self.frequency(state, stack),
+            UpdateType::Differentiable,
+        );
+        update.set(self.output_index, amount * value, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        let trigger = self.inputs.trigger.run(state, stack).unwrap_or(0.) >= GATE_LEVEL;
+        let clock = self.inputs.clock.run(state, stack).unwrap_or(0.) >= GATE_LEVEL;
+        let phase = state.get(self.phase_index);
+
+        self.elapsed += time_step;
+        self.since_pulse = self.since_pulse.map(|t| t + time_step);
+
+        if clock && !self.clock {
+            if let Some(period) = self.since_pulse {
+                self.pulse_period = Some(period);
+            }
+
+            self.since_pulse = Some(0.);
+        }
+
+        if trigger && !self.trigger {
+            self.elapsed = 0.;
+            state.set(self.phase_index, 0.);
+        } else if !(0. ..1.).contains(&phase) {
+            state.set(self.phase_index, phase - math::floor(phase));
+        }
+
+        if phase >= 1. || (trigger && !self.trigger) {
+            self.random_from = self.random_to;
+            self.random_to = self.next_random();
+        }
+
+        self.trigger = trigger;
+        self.clock = clock;
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const TRIGGER: usize = 2;
+    const CLOCK: usize = 3;
+    const DT: Float = 0.001;
+
+    fn lfo(shape: LfoShape, fade: Float, sync: Option<TempoSync>) -> RungeKutta {
+        let inputs = LfoInputs {
+            rate: StackProgram::constant(2.),
+            trigger: StackProgram::new(vec![Instr::State(TRIGGER)], 1),
+            fade: StackProgram::constant(fade),
+            clock: StackProgram::new(vec![Instr::State(CLOCK)], 1),
+        };
+        let mut lfo = Lfo::new(inputs, 0, 1).with_shape(shape);
+
+        if let Some(sync) = sync {
+            lfo = lfo.with_sync(sync);
+        }
+
+        RungeKutta::rk4(4).with_modules(vec![Box::new(lfo)])
+    }
+
+    /// Run for `time` seconds, returning the output and the phase.
+    fn run(simulator: &mut RungeKutta, time: Float) -> (Float, Float) {
+        for _ in 0..(time / DT).round() as usize {
+            simulator.step(DT);
+        }
+
+        let state = simulator.get_state();
+        (state.get(0), state.get(1))
+    }
+
+    #[test]
+    fn shapes_and_fade() {
+        let mut triangle = lfo(LfoShape::Triangle, 0., None);
+        assert!((run(&mut triangle, 0.125).0 - 1.).abs() < 1e-2);
+        assert!((run(&mut triangle, 0.25).0 + 1.).abs() < 1e-2);
+
+        let mut saw = lfo(LfoShape::Saw, 0., None);
+        assert!((run(&mut saw, 0.6).1 - 0.2).abs() < 1e-3);
+
+        // The trigger restarts the cycle and the fade-in
+        let mut square = lfo(LfoShape::Square, 0.5, None);
+        assert!((run(&mut square, 1.1).0 - 1.).abs() < 1e-2);
+        square.get_state().set(TRIGGER, 1.);
+        let (value, phase) = run(&mut square, 0.101);
+        assert!((value - 0.2).abs() < 1e-2);
+        assert!((phase - 0.2).abs() < 1e-2);
+    }
+
+    #[test]
+    fn tempo_sync() {
+        // A pulse every 0.1 s, two pulses a beat, a cycle per beat
+        let sync = TempoSync {
+            beats: 1.,
+            pulses_per_beat: 2.,
+        };
+        let mut synced = lfo(LfoShape::Saw, 0., Some(sync));
+
+        for _ in 0..5 {
+            synced.get_state().set(CLOCK, 1.);
+            run(&mut synced, 0.05);
+            synced.get_state().set(CLOCK, 0.);
+            run(&mut synced, 0.05);
+        }
+
+        let (_, start) = run(&mut synced, 0.);
+        let (_, end) = run(&mut synced, 0.05);
+        let advance = end - start - math::floor(end - start);
+        assert!((advance - 0.25).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// Sums its inputs. The inputs, scaled by their gains, are added up into a
+/// single program, so any number of them costs one module.
+pub struct Mixer<P = DefaultProgram> {
+    signal_input: P,
+    output_index: usize,
+}
+
+impl<P: Program> Mixer<P> {
+    pub fn new(signal_input: P, output_index: usize) -> Self {
+        Self {
+            signal_input,
+            output_index,
+        }
+    }
+}
+
+impl<P: Program> Module for Mixer<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let v = self.signal_input.run(state, stack).unwrap_or(0.);
+
+        update.set(self.output_index, v, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
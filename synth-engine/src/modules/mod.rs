@@ -1,112 +1,211 @@
+pub mod adsr;
 pub mod allpass;
 pub mod amplifier;
 pub mod bowed;
 pub mod control;
 pub mod delay_line;
+pub mod differentiator;
+pub mod diode_ladder;
 pub mod envelope;
 pub mod filter_12db;
 pub mod filter_24db;
 pub mod filter_6db;
 pub mod folder;
+pub mod integrator;
+pub mod lfo;
+pub mod mixer;
 pub mod mono_keys;
 pub mod mono_output;
 pub mod noise;
+pub mod ode;
+pub mod pedals;
+pub mod poly_keys;
 pub mod quadrature;
+pub mod quantizer;
+pub mod sallen_key;
+pub mod sample_hold;
+pub mod sem_filter;
+pub mod slew;
+pub mod state_space;
 pub mod vosim;
 pub mod wavetable;
 
+pub use adsr::Adsr;
 pub use allpass::AllpassFilter;
 pub use amplifier::Amplifier;
 pub use bowed::BowedOscillator;
 pub use control::ContinuousControl;
 pub use delay_line::DelayLine;
+pub use differentiator::Differentiator;
+pub use diode_ladder::DiodeLadder;
 pub use envelope::Envelope;
 pub use filter_12db::Filter12db;
 pub use filter_24db::Filter24db;
 pub use filter_6db::Filter6db;
 pub use folder::Folder;
+pub use integrator::Integrator;
+pub use lfo::Lfo;
+pub use mixer::Mixer;
 pub use mono_keys::MonoKeys;
 pub use mono_output::MonoOutput;
 pub use noise::NoiseGenerator;
+pub use ode::Ode;
+pub use poly_keys::PolyKeys;
 pub use quadrature::QuadratureOscillator;
+pub use quantizer::Quantizer;
+pub use sallen_key::SallenKey;
+pub use sample_hold::SampleHold;
+pub use sem_filter::SemFilter;
+pub use slew::SlewLimiter;
+pub use state_space::StateSpace;
 pub use vosim::Vosim;
 pub use wavetable::Wavetable;
 
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{self, Fixed};
+#[cfg(feature = "fixed-point")]
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 
-pub fn control_to_frequency(f0: f32, exp_fc: f32, lin_fc: f32) -> f32 {
-    f0 * 2.0_f32.powf(exp_fc) + lin_fc
+pub fn control_to_frequency(f0: Float, exp_fc: Float, lin_fc: Float) -> Float {
+    f0 * math::exp2(exp_fc) + lin_fc
+}
+
+/// The input programs of the filter models.
+pub struct FilterInputs<P = DefaultProgram> {
+    pub signal: P,
+    /// The cutoff in octaves above the zero frequency of the filter
+    pub cutoff: P,
+    /// Added to the cutoff, in Hz
+    pub linear: P,
+    /// From 0, self-oscillating from 1
+    pub resonance: P,
+}
+
+impl<P: Program> FilterInputs<P> {
+    /// The cutoff as an angular frequency.
+    pub fn omega(&self, f0: Float, state: &State, stack: &mut [Float]) -> Float {
+        let f = control_to_frequency(
+            f0,
+            self.cutoff.run(state, stack).unwrap_or(0.),
+            self.linear.run(state, stack).unwrap_or(0.),
+        );
+
+        2. * math::consts::PI * f
+    }
+
+    pub fn resonance(&self, state: &State, stack: &mut [Float]) -> Float {
+        self.resonance.run(state, stack).unwrap_or(0.).max(0.)
+    }
+}
+
+/// 2π as a fixed-point number, for angular frequencies.
+#[cfg(feature = "fixed-point")]
+pub const FIXED_TWO_PI: Fixed = Fixed::from_float(2. * PI);
+
+#[cfg(feature = "fixed-point")]
+pub fn fixed_control_to_frequency(f0: Fixed, exp_fc: Fixed, lin_fc: Fixed) -> Fixed {
+    f0 * fixed::math::exp2(exp_fc) + lin_fc
 }
 
 ////// ALL BELOW IS FOR GETTING RID OF A BOX :-p //////
 
-#[allow(dead_code)]
-pub enum SynthModule {
-    Amp(Amplifier),
-    Contour(Envelope),
-    Filter1Pole(Filter6db),
-    Filter2Pole(Filter12db),
-    Filter4Pole(Filter24db),
+// Variants differ most in f64 builds, but boxing them would defeat the purpose
+#[allow(clippy::large_enum_variant)]
+pub enum SynthModule<P = DefaultProgram, B = DefaultBuffer> {
+    Amp(Amplifier<P>),
+    Contour(Envelope<P>),
+    Adsr(Adsr<P>),
+    Lfo(Lfo<P>),
+    SampleHold(SampleHold<P>),
+    Slew(SlewLimiter<P>),
+    Quantizer(Quantizer<P>),
+    Mixer(Mixer<P>),
+    Integrator(Integrator<P>),
+    Differentiator(Differentiator<P>),
+    Ode(Ode<P>),
+    StateSpace(StateSpace<P>),
+    Filter1Pole(Filter6db<P>),
+    Filter2Pole(Filter12db<P>),
+    Filter4Pole(Filter24db<P>),
+    DiodeLadder(DiodeLadder<P>),
+    SallenKey(SallenKey<P>),
+    SemFilter(SemFilter<P>),
     ContinuousControl(ContinuousControl),
-    MonoKeys(MonoKeys),
-    Output(MonoOutput),
+    MonoKeys(MonoKeys<P>),
+    PolyKeys(PolyKeys),
+    Output(MonoOutput<P>),
     Noise(NoiseGenerator),
-    QuadOscillator(QuadratureOscillator),
-    WavetableOscillator(Wavetable),
-    VosimOscillator(Vosim),
-    Delay(DelayLine),
-    Wavefolder(Folder),
-    Bowed(BowedOscillator),
+    QuadOscillator(QuadratureOscillator<P>),
+    WavetableOscillator(Wavetable<P>),
+    VosimOscillator(Vosim<P>),
+    Delay(DelayLine<P, B>),
+    Wavefolder(Folder<P>),
+    Bowed(BowedOscillator<P>),
 }
 
 use crate::event::ControllerEvent;
+use crate::math;
 use crate::simulator::state::{State, StateUpdate};
+use crate::stack_program::{DefaultProgram, Program};
+use crate::Float;
+use delay_line::DefaultBuffer;
 
-impl SynthModule {
-    #[allow(dead_code)]
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
-        use SynthModule::*;
-        match self {
-            Amp(a) => a.simulate(state, update, stack),
-            Contour(c) => c.simulate(state, update, stack),
-            Filter1Pole(f) => f.simulate(state, update, stack),
-            Filter2Pole(f) => f.simulate(state, update, stack),
-            Filter4Pole(f) => f.simulate(state, update, stack),
-            ContinuousControl(c) => c.simulate(state, update, stack),
-            MonoKeys(mk) => mk.simulate(state, update, stack),
-            Noise(n) => n.simulate(state, update, stack),
-            QuadOscillator(q) => q.simulate(state, update, stack),
-            WavetableOscillator(w) => w.simulate(state, update, stack),
-            VosimOscillator(v) => v.simulate(state, update, stack),
-            Delay(d) => d.simulate(state, update, stack),
-            Wavefolder(f) => f.simulate(state, update, stack),
-            Bowed(b) => b.simulate(state, update, stack),
-            _ => { /* do nothing */ }
+// Static dispatch to the module in every variant
+macro_rules! dispatch {
+    ($self:expr, $m:ident => $e:expr) => {
+        match $self {
+            SynthModule::Amp($m) => $e,
+            SynthModule::Contour($m) => $e,
+            SynthModule::Adsr($m) => $e,
+            SynthModule::Lfo($m) => $e,
+            SynthModule::SampleHold($m) => $e,
+            SynthModule::Slew($m) => $e,
+            SynthModule::Quantizer($m) => $e,
+            SynthModule::Mixer($m) => $e,
+            SynthModule::Integrator($m) => $e,
+            SynthModule::Differentiator($m) => $e,
+            SynthModule::Ode($m) => $e,
+            SynthModule::StateSpace($m) => $e,
+            SynthModule::Filter1Pole($m) => $e,
+            SynthModule::Filter2Pole($m) => $e,
+            SynthModule::Filter4Pole($m) => $e,
+            SynthModule::DiodeLadder($m) => $e,
+            SynthModule::SallenKey($m) => $e,
+            SynthModule::SemFilter($m) => $e,
+            SynthModule::ContinuousControl($m) => $e,
+            SynthModule::MonoKeys($m) => $e,
+            SynthModule::PolyKeys($m) => $e,
+            SynthModule::Output($m) => $e,
+            SynthModule::Noise($m) => $e,
+            SynthModule::QuadOscillator($m) => $e,
+            SynthModule::WavetableOscillator($m) => $e,
+            SynthModule::VosimOscillator($m) => $e,
+            SynthModule::Delay($m) => $e,
+            SynthModule::Wavefolder($m) => $e,
+            SynthModule::Bowed($m) => $e,
         }
+    };
+}
+
+impl<P: Program, B: AsRef<[Float]> + AsMut<[Float]> + Send> Module for SynthModule<P, B> {
+    #[inline]
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        dispatch!(self, m => m.simulate(state, update, stack))
     }
 
-    #[allow(dead_code)]
+    #[inline]
     fn process_event(&mut self, event: &ControllerEvent) {
-        use SynthModule::*;
-        match self {
-            ContinuousControl(c) => c.process_event(event),
-            MonoKeys(m) => m.process_event(event),
-            _ => { /* do nothing */ }
-        }
+        dispatch!(self, m => m.process_event(event))
     }
 
-    #[allow(dead_code)]
-    fn finalize(&mut self, state: &mut State, time_step: f32, stack: &mut [f32]) {
-        use SynthModule::*;
-
-        match self {
-            Noise(n) => n.finalize(state, time_step, stack),
-            Output(m) => m.finalize(state, time_step, stack),
-            Delay(d) => d.finalize(state, time_step, stack),
-            Contour(c) => c.finalize(state, time_step, stack),
-            QuadOscillator(q) => q.finalize(state, time_step, stack),
-            WavetableOscillator(w) => w.finalize(state, time_step, stack),
-            _ => {}
-        }
+    #[inline]
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        dispatch!(self, m => m.finalize(state, time_step, stack))
+    }
+
+    #[inline]
+    fn initialize(&mut self, state: &mut State, time_step: Float) {
+        dispatch!(self, m => m.initialize(state, time_step))
     }
 }
@@ -1,150 +1,691 @@
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math::consts::PI;
+use crate::modules::pedals::{PedalChange, Pedals};
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
-use alloc::collections::BTreeSet;
-use core::cmp::Ord;
-use core::cmp::Ordering;
-use core::f32::consts::PI;
+use crate::stack_program::*;
+use crate::Float;
 
-const PRESSURE_FILTER_CONSTANT: f32 = 2. * PI * 20.;
-const PITCHWHEEL_FILTER_CONSTANT: f32 = 2. * PI * 20.;
+const PRESSURE_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const PITCHWHEEL_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const SUSTAIN_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const MAX_ACTIVE_NOTES: usize = 16;
 
-struct ActiveNote {
-    pitch_code: u8,
-    pitch_value: f32,
-}
-
-impl PartialEq for ActiveNote {
-    fn eq(&self, other: &Self) -> bool {
-        self.pitch_code == other.pitch_code
-    }
+/// Glide times below this play the new pitch right away.
+const MIN_GLIDE_TIME: Float = 1e-3;
+/// An exponential glide gets within 1% of the new pitch in its glide time,
+/// about 5 time constants.
+const EXPONENTIAL_TIME_CONSTANTS: Float = 5.;
+/// An exponential glide ends this close to the new pitch, in octaves.
+const GLIDE_END_DISTANCE: Float = 1e-4;
 
-    fn ne(&self, other: &Self) -> bool {
-        self.pitch_code != other.pitch_code
-    }
+/// Which of the held notes plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotePriority {
+    /// The note pressed last
+    Last,
+    /// The lowest note
+    Low,
+    /// The highest note
+    High,
 }
 
-impl Eq for ActiveNote {}
+/// How the pitch moves from one note to the next. The glide time is in
+/// seconds, or in seconds per octave for `constant_rate`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlideMode {
+    /// The glide time is per octave, instead of the same for every interval
+    pub constant_rate: bool,
+    /// Exponential glides slow down towards the new pitch, linear glides keep
+    /// their speed
+    pub linear: bool,
+    /// Only glide between notes played legato, not from a released note
+    pub legato_only: bool,
+}
 
-impl PartialOrd for ActiveNote {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+/// The state indices of the outputs of the keys.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeyOutputs {
+    pub pitch: usize,
+    pub gate: usize,
+    pub pressure: usize,
+    pub velocity: usize,
+    pub pitchwheel: usize,
+    /// 1 for one step when a note starts
+    pub trigger: usize,
+    /// The position of the sustain pedal
+    pub sustain: usize,
 }
 
-impl Ord for ActiveNote {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.pitch_code.cmp(&other.pitch_code)
-    }
+#[derive(Clone, Copy)]
+struct ActiveNote {
+    pitch_code: u8,
+    pitch_value: Float,
+    velocity: Float,
+    /// Released notes are kept while a pedal holds them
+    key_down: bool,
 }
 
-pub struct MonoKeys {
-    pitch_output_index: usize,
-    gate_output_index: usize,
-    pressure_output_index: usize,
-    velocity_output_index: usize,
-    pitchwheel_output_index: usize,
-    active_notes: BTreeSet<ActiveNote>,
-    current_pressure: f32,
-    current_velocity: f32,
-    current_pitch_value: f32,
-    current_gate: f32,
-    pitch_wheel: f32,
+pub struct MonoKeys<P = DefaultProgram> {
+    outputs: KeyOutputs,
+    glide_time_input: P,
+    glide_mode: GlideMode,
+    priority: NotePriority,
+    legato: bool,
+    /// Held notes in the order they were pressed, without duplicates
+    active_notes: [ActiveNote; MAX_ACTIVE_NOTES],
+    active_note_count: usize,
+    /// The held note that plays
+    current_note: Option<ActiveNote>,
+    current_pressure: Float,
+    current_velocity: Float,
+    current_pitch_value: Float,
+    /// A new note started since the last step. Without legato the gate is
+    /// held low for the step, so that envelopes restart.
+    triggered: bool,
+    retrigger: bool,
+    /// The glide time read at the end of the last step
+    glide_time: Float,
+    /// The pitch without pitch wheel at the end of the last step
+    glide_pitch: Float,
+    /// Speed of a linear glide, or time constant of an exponential glide.
+    /// `None` without a glide going on.
+    glide: Option<Float>,
+    played: bool,
+    pedals: Pedals,
+    pitch_wheel: Float,
 }
 
-impl MonoKeys {
-    pub fn new(
-        pitch_output_index: usize,
-        gate_output_index: usize,
-        pressure_output_index: usize,
-        velocity_output_index: usize,
-        pitchwheel_output_index: usize,
-    ) -> Self {
+impl<P> MonoKeys<P> {
+    pub fn new(outputs: KeyOutputs, glide_time_input: P) -> Self {
         Self {
-            pitch_output_index,
-            gate_output_index,
-            pressure_output_index,
-            velocity_output_index,
-            pitchwheel_output_index,
-            active_notes: BTreeSet::new(),
+            outputs,
+            glide_time_input,
+            glide_mode: GlideMode::default(),
+            priority: NotePriority::Last,
+            legato: true,
+            active_notes: [ActiveNote {
+                pitch_code: 0,
+                pitch_value: 0.,
+                velocity: 0.,
+                key_down: false,
+            }; MAX_ACTIVE_NOTES],
+            active_note_count: 0,
+            current_note: None,
             current_pressure: 0.,
             current_velocity: 0.,
             current_pitch_value: 0.,
-            current_gate: 0.,
+            triggered: false,
+            retrigger: false,
+            glide_time: 0.,
+            glide_pitch: 0.,
+            glide: None,
+            played: false,
+            pedals: Pedals::default(),
             pitch_wheel: 0.,
         }
     }
+
+    pub fn with_glide(mut self, glide_mode: GlideMode) -> Self {
+        self.glide_mode = glide_mode;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: NotePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// With legato, a new note played while another is held changes the pitch
+    /// but keeps the gate high. Without, the gate drops for a step.
+    pub fn with_legato(mut self, legato: bool) -> Self {
+        self.legato = legato;
+        self
+    }
+
+    /// Add a note to the held notes, as the last pressed. When too many notes
+    /// are held, the first pressed is forgotten.
+    fn insert_note(&mut self, note: ActiveNote) {
+        self.remove_note(note.pitch_code);
+
+        if self.active_note_count == MAX_ACTIVE_NOTES {
+            self.active_notes.copy_within(1.., 0);
+            self.active_note_count -= 1;
+        }
+
+        self.active_notes[self.active_note_count] = note;
+        self.active_note_count += 1;
+    }
+
+    fn remove_note(&mut self, pitch_code: u8) {
+        let notes = &self.active_notes[..self.active_note_count];
+
+        if let Some(position) = notes.iter().position(|n| n.pitch_code == pitch_code) {
+            self.active_notes
+                .copy_within(position + 1..self.active_note_count, position);
+            self.active_note_count -= 1;
+        }
+    }
+
+    /// Start a glide from the current pitch to the pitch of the new note.
+    fn start_glide(&mut self, legato: bool) {
+        let distance = (self.current_pitch_value - self.glide_pitch).abs();
+        let time = self.glide_time;
+        let mode = self.glide_mode;
+
+        self.glide = if time < MIN_GLIDE_TIME || !self.played || (mode.legato_only && !legato) {
+            None
+        } else {
+            match (mode.linear, mode.constant_rate) {
+                (true, false) => Some(distance / time),
+                (true, true) => Some(1. / time),
+                (false, false) => Some(time / EXPONENTIAL_TIME_CONSTANTS),
+                (false, true) => Some(time * distance / EXPONENTIAL_TIME_CONSTANTS),
+            }
+        };
+    }
+
+    /// A key went up, the note ends unless a pedal holds it.
+    fn release_note(&mut self, pitch_code: u8) {
+        if !self.pedals.holds(pitch_code) {
+            self.remove_note(pitch_code);
+        } else if let Some(note) = self.active_notes[..self.active_note_count]
+            .iter_mut()
+            .find(|n| n.pitch_code == pitch_code)
+        {
+            note.key_down = false;
+        }
+    }
+
+    /// End the notes with released keys that the pedals don't hold anymore.
+    fn end_released_notes(&mut self) {
+        let mut i = 0;
+
+        while i < self.active_note_count {
+            let note = self.active_notes[i];
+
+            if note.key_down || self.pedals.holds(note.pitch_code) {
+                i += 1;
+            } else {
+                self.remove_note(note.pitch_code);
+            }
+        }
+    }
+
+    /// The held note that plays, by priority.
+    fn priority_note(&self) -> Option<ActiveNote> {
+        let notes = self.active_notes[..self.active_note_count].iter();
+
+        match self.priority {
+            NotePriority::Last => notes.last(),
+            NotePriority::Low => notes.min_by_key(|n| n.pitch_code),
+            NotePriority::High => notes.max_by_key(|n| n.pitch_code),
+        }
+        .copied()
+    }
+
+    /// Track the held notes, the pedals and the expression, shared by the
+    /// float and fixed-point versions.
+    fn handle_event(&mut self, event: &ControllerEvent) {
+        use ControllerEvent::*;
+
+        match event {
+            NoteOn {
+                pitch,
+                velocity,
+                pitch_value,
+                ..
+            } => self.insert_note(ActiveNote {
+                pitch_code: *pitch,
+                pitch_value: *pitch_value,
+                velocity: *velocity,
+                key_down: true,
+            }),
+            NoteOff { pitch, .. } => self.release_note(*pitch),
+            Aftertouch { amount, .. } => self.current_pressure = *amount,
+            PolyAftertouch { pitch, amount, .. } => {
+                if self
+                    .current_note
+                    .is_some_and(|note| note.pitch_code == *pitch)
+                {
+                    self.current_pressure = *amount;
+                }
+            }
+            PitchWheel { amount, .. } => self.pitch_wheel = *amount,
+            _ => match self.pedals.process_event(event) {
+                Some(PedalChange::SostenutoDown) => {
+                    for note in &self.active_notes[..self.active_note_count] {
+                        if note.key_down {
+                            self.pedals.catch(note.pitch_code);
+                        }
+                    }
+                }
+                Some(PedalChange::Released) => self.end_released_notes(),
+                _ => return,
+            },
+        }
+
+        let previous = self.current_note;
+        let note = self.priority_note();
+
+        self.current_note = note;
+
+        if let Some(note) = note {
+            if previous.map_or(true, |p| p.pitch_code != note.pitch_code) {
+                self.current_pitch_value = note.pitch_value;
+                self.start_glide(previous.is_some());
+                self.played = true;
+            }
+
+            self.current_velocity = note.velocity;
+
+            // Only a note that was just pressed triggers, not a held note that
+            // plays again on release
+            let is_new = matches!(event, NoteOn { pitch, .. } if *pitch == note.pitch_code);
+            let changed = previous.map_or(true, |p| p.pitch_code != note.pitch_code);
+
+            if is_new && changed {
+                self.triggered = true;
+                self.retrigger |= previous.is_some() && !self.legato;
+            }
+        }
+    }
+
+    /// End a step, with the glide time and the pitch without pitch wheel
+    /// read from the state.
+    fn end_step(&mut self, glide_time: Float, glide_pitch: Float, time_step: Float) {
+        self.triggered = false;
+        self.retrigger = false;
+        self.glide_time = glide_time;
+        self.glide_pitch = glide_pitch;
+
+        // End the glide at the new pitch
+        if let Some(glide) = self.glide {
+            let distance = (self.current_pitch_value - self.glide_pitch).abs();
+            let end_distance = if self.glide_mode.linear {
+                glide * time_step
+            } else {
+                GLIDE_END_DISTANCE
+            };
+
+            if distance <= end_distance {
+                self.glide = None;
+                self.glide_pitch = self.current_pitch_value;
+            }
+        }
+    }
 }
 
-impl Module for MonoKeys {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, _stack: &mut [f32]) {
+impl<P: Program> Module for MonoKeys<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, _stack: &mut [Float]) {
+        let gate = self.current_note.is_some() && !self.retrigger;
+        let trigger = if self.triggered { 1. } else { 0. };
+
         update.set(
-            self.gate_output_index,
-            self.current_gate,
+            self.outputs.gate,
+            if gate { 1. } else { 0. },
             UpdateType::Absolute,
         );
+        update.set(self.outputs.trigger, trigger, UpdateType::Absolute);
         update.set(
-            self.pitchwheel_output_index,
-            PITCHWHEEL_FILTER_CONSTANT
-                * (self.pitch_wheel - state.get(self.pitchwheel_output_index)),
+            self.outputs.pitchwheel,
+            PITCHWHEEL_FILTER_CONSTANT * (self.pitch_wheel - state.get(self.outputs.pitchwheel)),
             UpdateType::Differentiable,
         );
         update.set(
-            self.pitch_output_index,
-            self.current_pitch_value + state.get(self.pitchwheel_output_index),
-            UpdateType::Absolute,
+            self.outputs.sustain,
+            SUSTAIN_FILTER_CONSTANT * (self.pedals.sustain - state.get(self.outputs.sustain)),
+            UpdateType::Differentiable,
         );
+
+        let pitch_wheel = state.get(self.outputs.pitchwheel);
+
+        match self.glide {
+            // The pitch output moves with the glide and the pitch wheel
+            Some(glide) => {
+                let distance =
+                    self.current_pitch_value - (state.get(self.outputs.pitch) - pitch_wheel);
+                let speed = if self.glide_mode.linear {
+                    glide.copysign(distance)
+                } else {
+                    distance / glide.max(MIN_GLIDE_TIME)
+                };
+
+                update.set(
+                    self.outputs.pitch,
+                    speed + PITCHWHEEL_FILTER_CONSTANT * (self.pitch_wheel - pitch_wheel),
+                    UpdateType::Differentiable,
+                );
+            }
+            None => update.set(
+                self.outputs.pitch,
+                self.current_pitch_value + pitch_wheel,
+                UpdateType::Absolute,
+            ),
+        }
         update.set(
-            self.pressure_output_index,
-            PRESSURE_FILTER_CONSTANT
-                * (self.current_pressure - state.get(self.pressure_output_index)),
+            self.outputs.pressure,
+            PRESSURE_FILTER_CONSTANT * (self.current_pressure - state.get(self.outputs.pressure)),
             UpdateType::Differentiable,
         );
         update.set(
-            self.velocity_output_index,
+            self.outputs.velocity,
             self.current_velocity,
             UpdateType::Absolute,
         );
     }
 
     fn process_event(&mut self, event: &ControllerEvent) {
-        use ControllerEvent::*;
+        self.handle_event(event);
+    }
 
-        match event {
-            NoteOn {
-                pitch,
-                velocity,
-                pitch_value,
-            } => {
-                self.current_velocity = *velocity;
-                self.active_notes.insert(ActiveNote {
-                    pitch_code: *pitch,
-                    pitch_value: *pitch_value,
-                });
-            }
-            NoteOff { pitch, .. } => {
-                self.active_notes.remove(&ActiveNote {
-                    pitch_code: *pitch,
-                    pitch_value: 0.,
-                });
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        let glide_time = self.glide_time_input.run(state, stack).unwrap_or(0.);
+        let glide_pitch = state.get(self.outputs.pitch) - state.get(self.outputs.pitchwheel);
+
+        self.end_step(glide_time, glide_pitch, time_step);
+    }
+}
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for MonoKeys<P> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, _stack: &mut [Fixed]) {
+        const PRESSURE_CONSTANT: Fixed = Fixed::from_float(PRESSURE_FILTER_CONSTANT);
+        const PITCHWHEEL_CONSTANT: Fixed = Fixed::from_float(PITCHWHEEL_FILTER_CONSTANT);
+        const SUSTAIN_CONSTANT: Fixed = Fixed::from_float(SUSTAIN_FILTER_CONSTANT);
+
+        let gate = self.current_note.is_some() && !self.retrigger;
+        let trigger = if self.triggered {
+            Fixed::ONE
+        } else {
+            Fixed::ZERO
+        };
+        let target_pitch_wheel = Fixed::from_float(self.pitch_wheel);
+        let sustain = Fixed::from_float(self.pedals.sustain);
+
+        update.set(
+            self.outputs.gate,
+            if gate { Fixed::ONE } else { Fixed::ZERO },
+            UpdateType::Absolute,
+        );
+        update.set(self.outputs.trigger, trigger, UpdateType::Absolute);
+        update.set(
+            self.outputs.pitchwheel,
+            PITCHWHEEL_CONSTANT * (target_pitch_wheel - state.get(self.outputs.pitchwheel)),
+            UpdateType::Differentiable,
+        );
+        update.set(
+            self.outputs.sustain,
+            SUSTAIN_CONSTANT * (sustain - state.get(self.outputs.sustain)),
+            UpdateType::Differentiable,
+        );
+
+        let pitch_wheel = state.get(self.outputs.pitchwheel);
+        let pitch_value = Fixed::from_float(self.current_pitch_value);
+
+        match self.glide {
+            Some(glide) => {
+                let distance = pitch_value - (state.get(self.outputs.pitch) - pitch_wheel);
+                let speed = match (self.glide_mode.linear, distance < Fixed::ZERO) {
+                    (true, true) => -Fixed::from_float(glide),
+                    (true, false) => Fixed::from_float(glide),
+                    (false, _) => distance / Fixed::from_float(glide.max(MIN_GLIDE_TIME)),
+                };
+
+                update.set(
+                    self.outputs.pitch,
+                    speed + PITCHWHEEL_CONSTANT * (target_pitch_wheel - pitch_wheel),
+                    UpdateType::Differentiable,
+                );
             }
-            Aftertouch { amount } => self.current_pressure = *amount,
-            PitchWheel { amount } => self.pitch_wheel = *amount,
-            _ => {} // do nothing
+            None => update.set(
+                self.outputs.pitch,
+                pitch_value + pitch_wheel,
+                UpdateType::Absolute,
+            ),
         }
+        update.set(
+            self.outputs.pressure,
+            PRESSURE_CONSTANT
+                * (Fixed::from_float(self.current_pressure) - state.get(self.outputs.pressure)),
+            UpdateType::Differentiable,
+        );
+        update.set(
+            self.outputs.velocity,
+            Fixed::from_float(self.current_velocity),
+            UpdateType::Absolute,
+        );
+    }
 
-        match self.active_notes.first() {
-            Some(ActiveNote { pitch_value, .. }) => {
-                self.current_gate = 1.;
-                self.current_pitch_value = *pitch_value;
-            }
-            None => {
-                self.current_gate = 0.;
-            }
+    fn process_event(&mut self, event: &ControllerEvent) {
+        self.handle_event(event);
+    }
+
+    fn finalize(&mut self, state: &mut FixedState, time_step: TimeStep, stack: &mut [Fixed]) {
+        let glide_time = self
+            .glide_time_input
+            .run(state, stack)
+            .unwrap_or(Fixed::ZERO);
+        let glide_pitch = state.get(self.outputs.pitch) - state.get(self.outputs.pitchwheel);
+
+        self.end_step(
+            glide_time.to_float(),
+            glide_pitch.to_float(),
+            time_step.to_float(),
+        );
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+
+    const OUTPUTS: KeyOutputs = KeyOutputs {
+        pitch: 0,
+        gate: 1,
+        pressure: 2,
+        velocity: 3,
+        pitchwheel: 4,
+        trigger: 5,
+        sustain: 6,
+    };
+
+    fn note_on(keys: &mut MonoKeys, pitch: u8) {
+        keys.process_event(&ControllerEvent::NoteOn {
+            channel: 0,
+            pitch,
+            velocity: pitch as Float / 100.,
+            pitch_value: pitch as Float,
+        });
+    }
+
+    fn note_off(keys: &mut MonoKeys, pitch: u8) {
+        keys.process_event(&ControllerEvent::NoteOff {
+            channel: 0,
+            pitch,
+            velocity: 0.,
+        });
+    }
+
+    /// The playing pitch, and whether the note triggered and the gate dropped
+    /// for the step.
+    fn step(keys: &mut MonoKeys) -> (Float, bool, bool) {
+        let result = (keys.current_pitch_value, keys.triggered, keys.retrigger);
+        let mut state = State::new(7);
+
+        keys.finalize(&mut state, 1., &mut []);
+        result
+    }
+
+    #[test]
+    fn note_priority() {
+        let mut last = MonoKeys::new(OUTPUTS, StackProgram::constant(0.));
+        note_on(&mut last, 60);
+        assert_eq!(step(&mut last), (60., true, false));
+        note_on(&mut last, 64);
+        note_on(&mut last, 62);
+        assert_eq!(step(&mut last), (62., true, false));
+
+        // Releasing goes back to the previous held note, without a trigger
+        note_off(&mut last, 62);
+        assert_eq!(step(&mut last), (64., false, false));
+        assert_eq!(last.current_velocity, 0.64);
+        note_off(&mut last, 60);
+        note_off(&mut last, 64);
+        assert!(last.current_note.is_none());
+
+        let mut low = MonoKeys::new(OUTPUTS, StackProgram::constant(0.))
+            .with_priority(NotePriority::Low)
+            .with_legato(false);
+        note_on(&mut low, 64);
+        step(&mut low);
+        note_on(&mut low, 67);
+        assert_eq!(step(&mut low), (64., false, false));
+        note_on(&mut low, 60);
+        assert_eq!(step(&mut low), (60., true, true));
+        note_off(&mut low, 60);
+        assert_eq!(step(&mut low), (64., false, false));
+
+        let mut high =
+            MonoKeys::new(OUTPUTS, StackProgram::constant(0.)).with_priority(NotePriority::High);
+        note_on(&mut high, 64);
+        note_on(&mut high, 60);
+        assert_eq!(step(&mut high), (64., true, false));
+    }
+
+    fn control(keys: &mut MonoKeys, control: usize, value: Float) {
+        keys.process_event(&ControllerEvent::ContinuousControl {
+            channel: 0,
+            control,
+            value,
+        });
+    }
+
+    #[test]
+    fn pedals() {
+        let mut keys = MonoKeys::new(OUTPUTS, StackProgram::constant(0.));
+
+        // Sustained notes keep playing after their keys go up
+        note_on(&mut keys, 60);
+        step(&mut keys);
+        control(&mut keys, 64, 1.);
+        note_off(&mut keys, 60);
+        assert_eq!(step(&mut keys), (60., false, false));
+        assert!(keys.current_note.is_some());
+        control(&mut keys, 64, 0.);
+        assert!(keys.current_note.is_none());
+
+        // Sostenuto only holds the notes down when it was pressed
+        note_on(&mut keys, 60);
+        control(&mut keys, 66, 1.);
+        note_on(&mut keys, 64);
+        step(&mut keys);
+        note_off(&mut keys, 64);
+        note_off(&mut keys, 60);
+        assert_eq!(step(&mut keys), (60., false, false));
+        control(&mut keys, 66, 0.);
+        assert!(keys.current_note.is_none());
+    }
+
+    /// The pitch output after playing note 0 and then note 12 for `seconds`.
+    fn glide(mode: GlideMode, legato: bool, seconds: Float) -> Float {
+        use crate::simulator::rungekutta::RungeKutta;
+        use alloc::boxed::Box;
+        use alloc::vec;
+
+        let dt = 1e-3;
+        let keys = MonoKeys::new(OUTPUTS, StackProgram::constant(0.1)).with_glide(mode);
+        let mut simulator = RungeKutta::rk4(7).with_modules(vec![Box::new(keys)]);
+
+        simulator.process_event(ControllerEvent::NoteOn {
+            channel: 0,
+            pitch: 0,
+            velocity: 1.,
+            pitch_value: 0.,
+        });
+        simulator.step(dt);
+
+        if !legato {
+            simulator.process_event(ControllerEvent::NoteOff {
+                channel: 0,
+                pitch: 0,
+                velocity: 0.,
+            });
+        }
+
+        simulator.process_event(ControllerEvent::NoteOn {
+            channel: 0,
+            pitch: 12,
+            velocity: 1.,
+            pitch_value: 1.,
+        });
+
+        for _ in 0..(seconds / dt).round() as usize {
+            simulator.step(dt);
         }
+
+        simulator.get_state().get(0)
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
-        /* do nothing */
+    #[test]
+    fn glide_modes() {
+        let linear = GlideMode {
+            linear: true,
+            ..Default::default()
+        };
+        assert!((glide(linear, true, 0.05) - 0.5).abs() < 0.02);
+        assert_eq!(glide(linear, true, 0.11), 1.);
+
+        // An octave per 0.1 s, whatever the interval
+        let rate = GlideMode {
+            linear: true,
+            constant_rate: true,
+            ..Default::default()
+        };
+        assert!((glide(rate, true, 0.05) - 0.5).abs() < 0.02);
+
+        // Within 1% after the glide time
+        let exponential = GlideMode::default();
+        let pitch = glide(exponential, true, 0.1);
+        assert!(pitch > 0.99 && pitch < 1., "exponential glide to {}", pitch);
+
+        let legato_only = GlideMode {
+            legato_only: true,
+            ..Default::default()
+        };
+        assert!(glide(legato_only, true, 0.01) < 0.5);
+        assert_eq!(glide(legato_only, false, 0.001), 1.);
+    }
+
+    #[test]
+    fn channel_and_note_pressure() {
+        let mut keys = MonoKeys::new(OUTPUTS, StackProgram::constant(0.));
+        let poly_aftertouch = |keys: &mut MonoKeys, pitch, amount| {
+            keys.process_event(&ControllerEvent::PolyAftertouch {
+                channel: 0,
+                pitch,
+                amount,
+            })
+        };
+
+        note_on(&mut keys, 60);
+        keys.process_event(&ControllerEvent::Aftertouch {
+            channel: 0,
+            amount: 0.3,
+        });
+        assert_eq!(keys.current_pressure, 0.3);
+
+        // Only the pressure of the playing note counts
+        poly_aftertouch(&mut keys, 60, 0.7);
+        assert_eq!(keys.current_pressure, 0.7);
+        poly_aftertouch(&mut keys, 64, 0.2);
+        assert_eq!(keys.current_pressure, 0.7);
     }
 }
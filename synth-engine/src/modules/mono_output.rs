@@ -1,15 +1,23 @@
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate};
 use crate::stack_program::*;
+use crate::Float;
 
-pub struct MonoOutput {
+pub struct MonoOutput<P = DefaultProgram> {
     output_index: usize,
-    signal_input: StackProgram,
+    signal_input: P,
 }
 
-impl MonoOutput {
-    pub fn new(output_index: usize, signal_input: StackProgram) -> Self {
+impl<P> MonoOutput<P> {
+    pub fn new(output_index: usize, signal_input: P) -> Self {
         Self {
             output_index,
             signal_input,
@@ -17,8 +25,8 @@ impl MonoOutput {
     }
 }
 
-impl Module for MonoOutput {
-    fn simulate(&self, _state: &State, _update: &mut StateUpdate, _stack: &mut [f32]) {
+impl<P: Program> Module for MonoOutput<P> {
+    fn simulate(&self, _state: &State, _update: &mut StateUpdate, _stack: &mut [Float]) {
         /* do nothing */
     }
 
@@ -26,8 +34,24 @@ impl Module for MonoOutput {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, stack: &mut [Float]) {
         let v = self.signal_input.run(state, stack).unwrap_or(0.);
         state.set_output(self.output_index, v);
     }
 }
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for MonoOutput<P> {
+    fn simulate(&self, _state: &FixedState, _update: &mut FixedStateUpdate, _stack: &mut [Fixed]) {
+        /* do nothing */
+    }
+
+    fn process_event(&mut self, _even: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut FixedState, _time_step: TimeStep, stack: &mut [Fixed]) {
+        let v = self.signal_input.run(state, stack).unwrap_or(Fixed::ZERO);
+        state.set_output(self.output_index, v);
+    }
+}
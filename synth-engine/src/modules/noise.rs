@@ -2,6 +2,7 @@ use crate::event::ControllerEvent;
 use crate::interpolation::Interpolation;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::Float;
 use core::u32::MAX;
 
 // From the book, "Musical applications of Microprocessors", Chamberlin
@@ -11,7 +12,7 @@ pub const B_PARAMETER_DEFAULT: u32 = 907633515;
 pub struct NoiseGenerator {
     a: u32,
     b: u32,
-    data: [f32; 4],
+    data: [Float; 4],
     m: u32,
     output_index: usize,
 }
@@ -38,7 +39,7 @@ impl NoiseGenerator {
 }
 
 impl Module for NoiseGenerator {
-    fn simulate(&self, _state: &State, update: &mut StateUpdate, _stack: &mut [f32]) {
+    fn simulate(&self, _state: &State, update: &mut StateUpdate, _stack: &mut [Float]) {
         update.set(
             self.output_index,
             self.data
@@ -52,9 +53,9 @@ impl Module for NoiseGenerator {
         /* do nothing */
     }
 
-    fn finalize(&mut self, _state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         self.data.copy_within(0..3, 1);
         self.m = self.next(self.m);
-        self.data[0] = 2. * (self.m as f32) / (MAX as f32) - 1.;
+        self.data[0] = 2. * (self.m as Float) / (MAX as Float) - 1.;
     }
 }
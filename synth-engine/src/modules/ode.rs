@@ -0,0 +1,125 @@
+//! A system of ordinary differential equations given by its derivatives.
+//!
+//! Every equation integrates one state of the simulation by its derivative
+//! program, which may read the states of all equations. Two more states hold
+//! the time since the start and the time step, so derivatives can depend on
+//! them.
+
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The most equations of a system.
+pub const MAX_EQUATIONS: usize = 16;
+
+/// One state of a system and its derivative.
+pub struct Equation<P = DefaultProgram> {
+    pub state_index: usize,
+    pub derivative: P,
+    /// The value the state starts from
+    pub initial: Float,
+}
+
+pub struct Ode<P = DefaultProgram> {
+    equations: [Option<Equation<P>>; MAX_EQUATIONS],
+    time_index: usize,
+    time_step_index: usize,
+}
+
+impl<P: Program> Ode<P> {
+    /// A system of at most `MAX_EQUATIONS` equations, the others are ignored.
+    pub fn new(
+        equations: impl IntoIterator<Item = Equation<P>>,
+        time_index: usize,
+        time_step_index: usize,
+    ) -> Self {
+        let mut slots: [Option<Equation<P>>; MAX_EQUATIONS] = core::array::from_fn(|_| None);
+
+        for (slot, equation) in slots.iter_mut().zip(equations) {
+            *slot = Some(equation);
+        }
+
+        Self {
+            equations: slots,
+            time_index,
+            time_step_index,
+        }
+    }
+}
+
+impl<P: Program> Module for Ode<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        for equation in self.equations.iter().flatten() {
+            let derivative = equation.derivative.run(state, stack).unwrap_or(0.);
+
+            update.set(equation.state_index, derivative, UpdateType::Differentiable);
+        }
+
+        update.set(self.time_index, 1., UpdateType::Differentiable);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, time_step: Float, _stack: &mut [Float]) {
+        state.set(self.time_step_index, time_step);
+    }
+
+    fn initialize(&mut self, state: &mut State, time_step: Float) {
+        for equation in self.equations.iter().flatten() {
+            state.set(equation.state_index, equation.initial);
+        }
+
+        state.set(self.time_step_index, time_step);
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::math;
+    use crate::math::consts::PI;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const DT: Float = 0.001;
+
+    #[test]
+    fn harmonic_oscillator() {
+        use Instr::*;
+
+        // x' = y, y' = -(2π)² x, a cosine of 1 Hz
+        let omega2 = 4. * PI * PI;
+        let equations = [
+            Equation {
+                state_index: 0,
+                derivative: StackProgram::new(vec![State(1)], 1),
+                initial: 1.,
+            },
+            Equation {
+                state_index: 1,
+                derivative: StackProgram::new(vec![State(0), Const(-omega2), Multiply], 2),
+                initial: 0.,
+            },
+        ];
+        let ode = Ode::new(equations, 2, 3);
+        let mut simulator = RungeKutta::rk4(4).with_modules(vec![Box::new(ode)]);
+
+        // The first step starts from the initial values, with the time step
+        simulator.step(DT);
+        assert!((simulator.get_state().get(0) - math::cos(2. * PI * DT)).abs() < 1e-5);
+
+        for _ in 0..1000 {
+            simulator.step(DT);
+        }
+
+        let state = simulator.get_state();
+        assert!((state.get(0) - 1.).abs() < 1e-2);
+        assert!((state.get(2) - 1001. * DT).abs() < 1e-4);
+        assert_eq!(state.get(3), DT);
+    }
+}
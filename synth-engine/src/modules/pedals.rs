@@ -0,0 +1,116 @@
+//! Sustain and sostenuto pedals, shared by the keyboard modules.
+
+use crate::event::ControllerEvent;
+use crate::Float;
+
+const SUSTAIN_CONTROL: usize = 64;
+const SOSTENUTO_CONTROL: usize = 66;
+
+/// Pedal values from this up count as down, MIDI 64 and up.
+const PEDAL_DOWN: Float = 0.5;
+
+/// The state of the pedals. The sustain pedal holds every note released while
+/// it is down, the sostenuto pedal only the notes whose keys were down when it
+/// was pressed.
+#[derive(Clone, Copy, Default)]
+pub struct Pedals {
+    /// The sustain pedal position, from 0 to 1, for half-pedal effects
+    pub sustain: Float,
+    sostenuto: bool,
+    /// Bit set of the pitches caught by the sostenuto pedal
+    sostenuto_notes: u128,
+}
+
+/// What a pedal event changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PedalChange {
+    /// The sostenuto pedal went down, and should catch the held keys
+    SostenutoDown,
+    /// A pedal went up, notes whose keys are released may end
+    Released,
+    None,
+}
+
+impl Pedals {
+    /// Take a pedal event, returns `None` for other events.
+    pub fn process_event(&mut self, event: &ControllerEvent) -> Option<PedalChange> {
+        let ControllerEvent::ContinuousControl { control, value, .. } = event else {
+            return None;
+        };
+
+        match *control {
+            SUSTAIN_CONTROL => {
+                let was_down = self.sustain_down();
+
+                self.sustain = *value;
+
+                Some(if was_down && !self.sustain_down() {
+                    PedalChange::Released
+                } else {
+                    PedalChange::None
+                })
+            }
+            SOSTENUTO_CONTROL => {
+                let down = *value >= PEDAL_DOWN;
+                let change = match (self.sostenuto, down) {
+                    (false, true) => PedalChange::SostenutoDown,
+                    (true, false) => {
+                        self.sostenuto_notes = 0;
+                        PedalChange::Released
+                    }
+                    _ => PedalChange::None,
+                };
+
+                self.sostenuto = down;
+                Some(change)
+            }
+            _ => None,
+        }
+    }
+
+    fn sustain_down(&self) -> bool {
+        self.sustain >= PEDAL_DOWN
+    }
+
+    /// Catch a held key with the sostenuto pedal that just went down.
+    pub fn catch(&mut self, pitch: u8) {
+        self.sostenuto_notes |= 1 << (pitch & 0x7F);
+    }
+
+    /// Whether a note with its key released keeps playing.
+    pub fn holds(&self, pitch: u8) -> bool {
+        self.sustain_down() || self.sostenuto_notes & (1 << (pitch & 0x7F)) != 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pedal(pedals: &mut Pedals, control: usize, value: Float) -> Option<PedalChange> {
+        pedals.process_event(&ControllerEvent::ContinuousControl {
+            channel: 0,
+            control,
+            value,
+        })
+    }
+
+    #[test]
+    fn sustain_and_sostenuto() {
+        let mut pedals = Pedals::default();
+
+        assert_eq!(pedal(&mut pedals, 7, 1.), None);
+        assert_eq!(pedal(&mut pedals, 64, 0.3), Some(PedalChange::None));
+        assert!(!pedals.holds(60));
+        assert_eq!(pedal(&mut pedals, 64, 0.8), Some(PedalChange::None));
+        assert!(pedals.holds(60));
+        assert_eq!(pedal(&mut pedals, 64, 0.), Some(PedalChange::Released));
+
+        assert_eq!(pedal(&mut pedals, 66, 1.), Some(PedalChange::SostenutoDown));
+        pedals.catch(60);
+        assert!(pedals.holds(60));
+        assert!(!pedals.holds(62));
+        assert_eq!(pedal(&mut pedals, 66, 0.), Some(PedalChange::Released));
+        assert!(!pedals.holds(60));
+    }
+}
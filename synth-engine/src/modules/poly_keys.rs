@@ -0,0 +1,659 @@
+use crate::event::ControllerEvent;
+use crate::math::consts::PI;
+use crate::modules::pedals::{PedalChange, Pedals};
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::Float;
+
+const PRESSURE_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const PITCHWHEEL_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const TIMBRE_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const SUSTAIN_FILTER_CONSTANT: Float = 2. * PI * 20.;
+const TIMBRE_CONTROL: usize = 74;
+const MIDI_CHANNELS: usize = 16;
+pub const MAX_VOICES: usize = 16;
+
+/// The level below which a released voice counts as silent, and is free to
+/// play a new note.
+const SILENCE_LEVEL: Float = 1e-3;
+
+/// Which voice a new note takes when no voice is free. Voices that are
+/// released but still sounding are taken before held ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// The voice whose note started first.
+    Oldest,
+    /// The voice with the lowest level, or the oldest without levels.
+    Quietest,
+    /// A voice that plays the same note, even if other voices are free.
+    /// Otherwise as `Oldest`.
+    SameNote,
+}
+
+/// Which end of the MIDI channels an MPE zone takes. The lower zone has its
+/// master channel on channel 0 and member channels above it, the upper zone
+/// on channel 15 with member channels below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MpeZone {
+    Lower,
+    Upper,
+}
+
+/// MIDI Polyphonic Expression: every note is played on its own member channel,
+/// so pitch bend, pressure and timbre (CC 74) on that channel belong to the
+/// note. The same on the master channel apply to all notes, but a note with
+/// pressure of its own keeps it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MpeConfig {
+    pub zone: MpeZone,
+    /// The number of member channels, 1 to 15
+    pub member_channels: u8,
+    /// The pitch of a full per-note bend, in octaves
+    pub bend_range: Float,
+}
+
+impl MpeConfig {
+    fn master_channel(&self) -> u8 {
+        match self.zone {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
+
+    /// Whether the channel is a member channel of the zone.
+    pub fn is_member(&self, channel: u8) -> bool {
+        let members = self.member_channels.clamp(1, 15);
+
+        match self.zone {
+            MpeZone::Lower => (1..=members).contains(&channel),
+            MpeZone::Upper => (15 - members..15).contains(&channel),
+        }
+    }
+}
+
+/// Per-note expression of a voice, or the last values sent on an MPE member
+/// channel.
+#[derive(Clone, Copy, Default)]
+struct Expression {
+    bend: Float,
+    /// Per-note pressure, from poly aftertouch or the member channel. A note
+    /// without any follows the channel pressure.
+    pressure: Option<Float>,
+    timbre: Float,
+}
+
+/// The state indices of the outputs of a voice. With a `level`, usually the
+/// output of the envelope of the voice, a released voice is kept until its
+/// release tail has faded out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VoiceOutputs {
+    pub pitch: usize,
+    pub gate: usize,
+    pub velocity: usize,
+    pub pressure: usize,
+    pub timbre: usize,
+    pub level: Option<usize>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Voice {
+    pitch_code: u8,
+    pitch_value: Float,
+    velocity: Float,
+    channel: u8,
+    expression: Expression,
+    gate: bool,
+    /// A released key whose note a pedal holds has its gate up, but no key down
+    key_down: bool,
+    /// The gate is held low for one step, so that envelopes restart
+    retrigger: bool,
+    /// Times of the last note on and note off, counted in events
+    started: u64,
+    released: u64,
+    level: Float,
+}
+
+/// Allocates notes to a fixed set of voices, for polyphonic patches.
+pub struct PolyKeys {
+    outputs: [VoiceOutputs; MAX_VOICES],
+    voices: [Voice; MAX_VOICES],
+    voice_count: usize,
+    pitchwheel_output_index: usize,
+    sustain_output_index: usize,
+    stealing: VoiceStealing,
+    mpe: Option<MpeConfig>,
+    /// The expression last sent on each channel, for MPE
+    channels: [Expression; MIDI_CHANNELS],
+    pedals: Pedals,
+    pitch_wheel: Float,
+    /// Aftertouch on the MPE master channel, or on any channel without MPE
+    channel_pressure: Float,
+    clock: u64,
+}
+
+impl PolyKeys {
+    /// Keys for the voices with the given outputs, at most `MAX_VOICES`.
+    pub fn new(
+        voices: &[VoiceOutputs],
+        pitchwheel_output_index: usize,
+        sustain_output_index: usize,
+        stealing: VoiceStealing,
+    ) -> Self {
+        let voice_count = voices.len().min(MAX_VOICES);
+        let mut outputs = [VoiceOutputs::default(); MAX_VOICES];
+
+        outputs[..voice_count].copy_from_slice(&voices[..voice_count]);
+
+        Self {
+            outputs,
+            voices: [Voice::default(); MAX_VOICES],
+            voice_count,
+            pitchwheel_output_index,
+            sustain_output_index,
+            stealing,
+            mpe: None,
+            channels: [Expression::default(); MIDI_CHANNELS],
+            pedals: Pedals::default(),
+            pitch_wheel: 0.,
+            channel_pressure: 0.,
+            clock: 0,
+        }
+    }
+
+    /// Take notes and their expression from an MPE zone. Events on channels
+    /// outside the zone are ignored.
+    pub fn with_mpe(mut self, mpe: MpeConfig) -> Self {
+        self.mpe = Some(mpe);
+        self
+    }
+
+    /// Whether the event channel is an MPE member channel. Without MPE, or on
+    /// the master channel, the event applies to all notes.
+    fn is_member(&self, channel: u8) -> bool {
+        self.mpe.is_some_and(|mpe| mpe.is_member(channel))
+    }
+
+    fn in_zone(&self, channel: u8) -> bool {
+        match self.mpe {
+            Some(mpe) => channel == mpe.master_channel() || mpe.is_member(channel),
+            None => true,
+        }
+    }
+
+    /// Set the expression of the channel, and of the notes playing on it, or
+    /// of all notes if it isn't a member channel.
+    fn set_expression(&mut self, channel: u8, set: impl Fn(&mut Expression)) {
+        let member = self.is_member(channel);
+
+        if member {
+            set(&mut self.channels[channel as usize & 0xF]);
+        }
+
+        for voice in &mut self.voices[..self.voice_count] {
+            if !member || voice.channel == channel {
+                set(&mut voice.expression);
+            }
+        }
+    }
+
+    /// The per-note pressure of the voice once it has received any, else the
+    /// channel pressure.
+    fn pressure(&self, voice: &Voice) -> Float {
+        voice.expression.pressure.unwrap_or(self.channel_pressure)
+    }
+
+    fn is_sounding(&self, index: usize) -> bool {
+        let voice = &self.voices[index];
+
+        voice.gate || (self.outputs[index].level.is_some() && voice.level > SILENCE_LEVEL)
+    }
+
+    /// The voice to play a new note on.
+    fn choose_voice(&self, pitch: u8) -> usize {
+        let voices = &self.voices[..self.voice_count];
+        let indices = 0..self.voice_count;
+
+        if self.stealing == VoiceStealing::SameNote {
+            let same_note = indices
+                .clone()
+                .find(|i| voices[*i].pitch_code == pitch && self.is_sounding(*i));
+
+            if let Some(index) = same_note {
+                return index;
+            }
+        }
+
+        // The free voice that was released first, so release tails of other
+        // voices last as long as possible
+        let free = indices
+            .clone()
+            .filter(|i| !self.is_sounding(*i))
+            .min_by_key(|i| voices[*i].released);
+
+        if let Some(index) = free {
+            return index;
+        }
+
+        // Voices whose keys are up, released or held by a pedal, go first
+        let any_released = voices.iter().any(|voice| !voice.key_down);
+        let candidates = indices.filter(|i| !any_released || !voices[*i].key_down);
+        let has_levels = self.outputs[..self.voice_count]
+            .iter()
+            .all(|outputs| outputs.level.is_some());
+
+        match self.stealing {
+            VoiceStealing::Quietest if has_levels => candidates
+                .min_by(|a, b| voices[*a].level.total_cmp(&voices[*b].level))
+                .unwrap_or(0),
+            _ => candidates.min_by_key(|i| voices[*i].started).unwrap_or(0),
+        }
+    }
+
+    fn note_on(&mut self, channel: u8, pitch: u8, velocity: Float, pitch_value: Float) {
+        if self.voice_count == 0 {
+            return;
+        }
+
+        let index = self.choose_voice(pitch);
+        let member = self.is_member(channel);
+        let voice = &mut self.voices[index];
+
+        // A note on a member channel starts with the expression sent before it
+        if member {
+            voice.expression = self.channels[channel as usize & 0xF];
+        } else {
+            voice.expression.pressure = None;
+        }
+
+        self.clock += 1;
+        voice.retrigger = voice.gate;
+        voice.gate = true;
+        voice.key_down = true;
+        voice.channel = channel;
+        voice.pitch_code = pitch;
+        voice.pitch_value = pitch_value;
+        voice.velocity = velocity;
+        voice.started = self.clock;
+    }
+
+    fn note_off(&mut self, channel: u8, pitch: u8) {
+        let any_channel = self.mpe.is_none();
+        let held = self.pedals.holds(pitch);
+
+        self.clock += 1;
+
+        for voice in &mut self.voices[..self.voice_count] {
+            if voice.key_down
+                && voice.pitch_code == pitch
+                && (any_channel || voice.channel == channel)
+            {
+                voice.key_down = false;
+
+                if !held {
+                    voice.gate = false;
+                    voice.released = self.clock;
+                }
+            }
+        }
+    }
+
+    fn pedal_change(&mut self, change: PedalChange) {
+        match change {
+            PedalChange::SostenutoDown => {
+                for voice in &self.voices[..self.voice_count] {
+                    if voice.key_down {
+                        self.pedals.catch(voice.pitch_code);
+                    }
+                }
+            }
+            PedalChange::Released => {
+                self.clock += 1;
+
+                for voice in &mut self.voices[..self.voice_count] {
+                    if voice.gate && !voice.key_down && !self.pedals.holds(voice.pitch_code) {
+                        voice.gate = false;
+                        voice.released = self.clock;
+                    }
+                }
+            }
+            PedalChange::None => {}
+        }
+    }
+}
+
+impl Module for PolyKeys {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, _stack: &mut [Float]) {
+        let pitch_wheel = state.get(self.pitchwheel_output_index);
+
+        update.set(
+            self.pitchwheel_output_index,
+            PITCHWHEEL_FILTER_CONSTANT * (self.pitch_wheel - pitch_wheel),
+            UpdateType::Differentiable,
+        );
+        update.set(
+            self.sustain_output_index,
+            SUSTAIN_FILTER_CONSTANT * (self.pedals.sustain - state.get(self.sustain_output_index)),
+            UpdateType::Differentiable,
+        );
+
+        for (voice, outputs) in self.voices[..self.voice_count].iter().zip(&self.outputs) {
+            let gate = if voice.gate && !voice.retrigger {
+                1.
+            } else {
+                0.
+            };
+
+            update.set(outputs.gate, gate, UpdateType::Absolute);
+            update.set(
+                outputs.pitch,
+                voice.pitch_value + voice.expression.bend + pitch_wheel,
+                UpdateType::Absolute,
+            );
+            update.set(outputs.velocity, voice.velocity, UpdateType::Absolute);
+            update.set(
+                outputs.pressure,
+                PRESSURE_FILTER_CONSTANT * (self.pressure(voice) - state.get(outputs.pressure)),
+                UpdateType::Differentiable,
+            );
+            update.set(
+                outputs.timbre,
+                TIMBRE_FILTER_CONSTANT * (voice.expression.timbre - state.get(outputs.timbre)),
+                UpdateType::Differentiable,
+            );
+        }
+    }
+
+    fn process_event(&mut self, event: &ControllerEvent) {
+        use ControllerEvent::*;
+
+        match event {
+            NoteOn { channel, .. }
+            | NoteOff { channel, .. }
+            | Aftertouch { channel, .. }
+            | PolyAftertouch { channel, .. }
+            | ContinuousControl { channel, .. }
+            | PitchWheel { channel, .. }
+                if !self.in_zone(*channel) => {}
+            NoteOn {
+                channel,
+                pitch,
+                velocity,
+                pitch_value,
+            } => self.note_on(*channel, *pitch, *velocity, *pitch_value),
+            NoteOff { channel, pitch, .. } => self.note_off(*channel, *pitch),
+            Aftertouch { channel, amount } if self.is_member(*channel) => {
+                self.set_expression(*channel, |expression| expression.pressure = Some(*amount))
+            }
+            Aftertouch { amount, .. } => self.channel_pressure = *amount,
+            PolyAftertouch { pitch, amount, .. } => {
+                for voice in &mut self.voices[..self.voice_count] {
+                    if voice.gate && voice.pitch_code == *pitch {
+                        voice.expression.pressure = Some(*amount);
+                    }
+                }
+            }
+            ContinuousControl {
+                channel,
+                control: TIMBRE_CONTROL,
+                value,
+            } => self.set_expression(*channel, |expression| expression.timbre = *value),
+            PitchWheel { channel, amount } => match self.mpe {
+                Some(mpe) if mpe.is_member(*channel) => {
+                    let bend = *amount * mpe.bend_range;
+
+                    self.set_expression(*channel, |expression| expression.bend = bend)
+                }
+                _ => self.pitch_wheel = *amount,
+            },
+            _ => {
+                if let Some(change) = self.pedals.process_event(event) {
+                    self.pedal_change(change);
+                }
+            }
+        }
+    }
+
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        for (voice, outputs) in self.voices[..self.voice_count]
+            .iter_mut()
+            .zip(&self.outputs)
+        {
+            voice.retrigger = false;
+
+            if let Some(level) = outputs.level {
+                voice.level = state.get(level).abs();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(voice_count: usize, stealing: VoiceStealing, levels: bool) -> PolyKeys {
+        let voices: [VoiceOutputs; MAX_VOICES] = core::array::from_fn(|i| VoiceOutputs {
+            pitch: 6 * i,
+            gate: 6 * i + 1,
+            velocity: 6 * i + 2,
+            pressure: 6 * i + 3,
+            timbre: 6 * i + 4,
+            level: levels.then_some(6 * i + 5),
+        });
+
+        PolyKeys::new(
+            &voices[..voice_count],
+            6 * voice_count,
+            6 * voice_count + 1,
+            stealing,
+        )
+    }
+
+    fn note_on(keys: &mut PolyKeys, pitch: u8) {
+        keys.process_event(&ControllerEvent::NoteOn {
+            channel: 0,
+            pitch,
+            velocity: 1.,
+            pitch_value: pitch as Float,
+        });
+    }
+
+    fn note_off(keys: &mut PolyKeys, pitch: u8) {
+        keys.process_event(&ControllerEvent::NoteOff {
+            channel: 0,
+            pitch,
+            velocity: 0.,
+        });
+    }
+
+    fn pitches(keys: &PolyKeys) -> [u8; 2] {
+        [keys.voices[0].pitch_code, keys.voices[1].pitch_code]
+    }
+
+    #[test]
+    fn voice_allocation() {
+        // Stealing the oldest note, the retriggered voice has its gate low
+        let mut oldest = keys(2, VoiceStealing::Oldest, false);
+        note_on(&mut oldest, 60);
+        note_on(&mut oldest, 64);
+        note_on(&mut oldest, 67);
+        assert_eq!(pitches(&oldest), [67, 64]);
+        assert!(oldest.voices[0].retrigger);
+
+        // Released voices are reused first, the longest released one before
+        // the others
+        note_off(&mut oldest, 64);
+        note_off(&mut oldest, 67);
+        note_on(&mut oldest, 72);
+        assert_eq!(pitches(&oldest), [67, 72]);
+
+        // A released voice keeps sounding while its level is up
+        let mut quietest = keys(2, VoiceStealing::Quietest, true);
+        note_on(&mut quietest, 60);
+        note_on(&mut quietest, 64);
+        quietest.voices[0].level = 0.8;
+        quietest.voices[1].level = 0.2;
+        note_on(&mut quietest, 67);
+        assert_eq!(pitches(&quietest), [60, 67]);
+
+        note_off(&mut quietest, 60);
+        note_on(&mut quietest, 72);
+        assert_eq!(pitches(&quietest), [72, 67]);
+
+        // The same note takes its old voice, even with another voice free
+        let mut same_note = keys(2, VoiceStealing::SameNote, false);
+        note_on(&mut same_note, 60);
+        note_on(&mut same_note, 60);
+        assert_eq!(pitches(&same_note), [60, 0]);
+    }
+
+    #[test]
+    fn sustain_pedal() {
+        let mut keys = keys(2, VoiceStealing::Oldest, false);
+        let sustain = |keys: &mut PolyKeys, value| {
+            keys.process_event(&ControllerEvent::ContinuousControl {
+                channel: 0,
+                control: 64,
+                value,
+            })
+        };
+
+        note_on(&mut keys, 60);
+        sustain(&mut keys, 1.);
+        note_off(&mut keys, 60);
+        note_on(&mut keys, 64);
+        assert!(keys.voices[0].gate);
+
+        // The sustained voice is stolen before the held one
+        note_on(&mut keys, 67);
+        assert_eq!(pitches(&keys), [67, 64]);
+
+        note_off(&mut keys, 64);
+        sustain(&mut keys, 0.);
+        assert!(keys.voices[0].gate);
+        assert!(!keys.voices[1].gate);
+    }
+
+    #[test]
+    fn mpe_expression() {
+        let mpe = MpeConfig {
+            zone: MpeZone::Lower,
+            member_channels: 3,
+            bend_range: 4.,
+        };
+        let mut keys = keys(2, VoiceStealing::Oldest, false).with_mpe(mpe);
+
+        // Bend sent before the note on its member channel belongs to the note
+        let events = [
+            ControllerEvent::PitchWheel {
+                channel: 1,
+                amount: 0.5,
+            },
+            ControllerEvent::NoteOn {
+                channel: 1,
+                pitch: 60,
+                velocity: 1.,
+                pitch_value: 5.,
+            },
+            ControllerEvent::NoteOn {
+                channel: 2,
+                pitch: 60,
+                velocity: 1.,
+                pitch_value: 5.,
+            },
+            ControllerEvent::ContinuousControl {
+                channel: 2,
+                control: TIMBRE_CONTROL,
+                value: 0.7,
+            },
+            ControllerEvent::Aftertouch {
+                channel: 0,
+                amount: 0.3,
+            },
+            // Outside the zone
+            ControllerEvent::NoteOn {
+                channel: 9,
+                pitch: 40,
+                velocity: 1.,
+                pitch_value: 3.,
+            },
+            ControllerEvent::NoteOff {
+                channel: 1,
+                pitch: 60,
+                velocity: 0.,
+            },
+        ];
+
+        for event in &events {
+            keys.process_event(event);
+        }
+
+        let [first, second] = [keys.voices[0], keys.voices[1]];
+        assert_eq!(first.expression.bend, 2.);
+        assert_eq!(second.expression.bend, 0.);
+        assert_eq!(first.expression.timbre, 0.);
+        assert_eq!(second.expression.timbre, 0.7);
+        assert_eq!(
+            [first.expression.pressure, second.expression.pressure],
+            [None, None]
+        );
+        assert_eq!(keys.channel_pressure, 0.3);
+
+        // The note off only releases the note on its own channel
+        assert!(!first.gate);
+        assert!(second.gate);
+        assert_eq!(second.pitch_code, 60);
+    }
+
+    #[test]
+    fn channel_and_note_pressure() {
+        let aftertouch = |keys: &mut PolyKeys, channel, amount| {
+            keys.process_event(&ControllerEvent::Aftertouch { channel, amount })
+        };
+        let poly_aftertouch = |keys: &mut PolyKeys, channel, pitch, amount| {
+            keys.process_event(&ControllerEvent::PolyAftertouch {
+                channel,
+                pitch,
+                amount,
+            })
+        };
+        let pressures = |keys: &PolyKeys| [0, 1].map(|i| keys.pressure(&keys.voices[i]));
+
+        // Poly aftertouch overrides channel aftertouch for its note only
+        let mut plain = keys(2, VoiceStealing::Oldest, false);
+        note_on(&mut plain, 60);
+        note_on(&mut plain, 64);
+        aftertouch(&mut plain, 0, 0.3);
+        poly_aftertouch(&mut plain, 0, 64, 0.8);
+        aftertouch(&mut plain, 0, 0.4);
+        assert_eq!(pressures(&plain), [0.4, 0.8]);
+
+        // A new note on the voice follows the channel again
+        note_off(&mut plain, 64);
+        note_on(&mut plain, 67);
+        assert_eq!(pressures(&plain), [0.4, 0.4]);
+
+        // With MPE the master channel doesn't overwrite member channel pressure
+        let mpe = MpeConfig {
+            zone: MpeZone::Lower,
+            member_channels: 3,
+            bend_range: 2.,
+        };
+        let mut mpe_keys = keys(2, VoiceStealing::Oldest, false).with_mpe(mpe);
+        for channel in [1, 2] {
+            mpe_keys.process_event(&ControllerEvent::NoteOn {
+                channel,
+                pitch: 60 + channel,
+                velocity: 1.,
+                pitch_value: 5.,
+            });
+        }
+        aftertouch(&mut mpe_keys, 2, 0.6);
+        aftertouch(&mut mpe_keys, 0, 0.2);
+        assert_eq!(pressures(&mpe_keys), [0.2, 0.6]);
+
+        poly_aftertouch(&mut mpe_keys, 1, 61, 0.5);
+        aftertouch(&mut mpe_keys, 0, 0.1);
+        assert_eq!(pressures(&mpe_keys), [0.5, 0.6]);
+    }
+}
@@ -1,26 +1,38 @@
 use super::control_to_frequency;
+#[cfg(feature = "fixed-point")]
+use super::{fixed_control_to_frequency, FIXED_TWO_PI};
 use crate::event::ControllerEvent;
+#[cfg(feature = "fixed-point")]
+use crate::fixed::{
+    self,
+    module::FixedModule,
+    program::FixedProgram,
+    state::{FixedState, FixedStateUpdate},
+    Fixed, TimeStep,
+};
+use crate::math;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
-use core::f32::consts::PI;
+use crate::Float;
 
 // TODO add to synth designer
-pub struct QuadratureOscillator {
-    f0: f32,
+pub struct QuadratureOscillator<P = DefaultProgram> {
+    f0: Float,
     state_x_index: usize,
     state_y_index: usize,
-    control_input: StackProgram,
-    linear_control: StackProgram,
+    control_input: P,
+    linear_control: P,
 }
 
-impl QuadratureOscillator {
+impl<P> QuadratureOscillator<P> {
     pub fn new(
-        f0: f32,
+        f0: Float,
         state_x_index: usize,
         state_y_index: usize,
-        control_input: StackProgram,
-        linear_control: StackProgram,
+        control_input: P,
+        linear_control: P,
     ) -> Self {
         Self {
             f0,
@@ -32,8 +44,8 @@ impl QuadratureOscillator {
     }
 }
 
-impl Module for QuadratureOscillator {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for QuadratureOscillator<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let omega = 2.
             * PI
             * control_to_frequency(
@@ -53,12 +65,12 @@ impl Module for QuadratureOscillator {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         let x = state.get(self.state_x_index);
         let y = state.get(self.state_y_index);
-        let s = (x * x + y * y).sqrt();
+        let s = math::sqrt(x * x + y * y);
 
-        if s < f32::EPSILON {
+        if s < Float::EPSILON {
             state.set(self.state_x_index, 0.);
             state.set(self.state_y_index, 1.);
         } else {
@@ -67,3 +79,39 @@ impl Module for QuadratureOscillator {
         }
     }
 }
+
+#[cfg(feature = "fixed-point")]
+impl<P: FixedProgram> FixedModule for QuadratureOscillator<P> {
+    fn simulate(&self, state: &FixedState, update: &mut FixedStateUpdate, stack: &mut [Fixed]) {
+        let omega = FIXED_TWO_PI
+            * fixed_control_to_frequency(
+                Fixed::from_float(self.f0),
+                self.control_input.run(state, stack).unwrap_or(Fixed::ZERO),
+                self.linear_control.run(state, stack).unwrap_or(Fixed::ZERO),
+            );
+
+        let x = state.get(self.state_x_index);
+        let y = state.get(self.state_y_index);
+
+        update.set(self.state_x_index, omega * y, UpdateType::Differentiable);
+        update.set(self.state_y_index, -omega * x, UpdateType::Differentiable);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut FixedState, _time_step: TimeStep, _stack: &mut [Fixed]) {
+        let x = state.get(self.state_x_index);
+        let y = state.get(self.state_y_index);
+        let s = fixed::math::sqrt(x * x + y * y);
+
+        if s == Fixed::ZERO {
+            state.set(self.state_x_index, Fixed::ZERO);
+            state.set(self.state_y_index, Fixed::ONE);
+        } else {
+            state.set(self.state_x_index, x / s);
+            state.set(self.state_y_index, y / s);
+        }
+    }
+}
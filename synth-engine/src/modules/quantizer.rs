@@ -0,0 +1,84 @@
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The most pitches of a scale, one per MIDI note.
+pub const MAX_PITCHES: usize = 128;
+
+/// Snaps its input to the nearest pitch of a scale, in octaves like the pitch
+/// outputs of the keyboard modules.
+pub struct Quantizer<P = DefaultProgram> {
+    signal_input: P,
+    output_index: usize,
+    /// The pitches of the scale in rising order
+    pitches: [Float; MAX_PITCHES],
+    len: usize,
+}
+
+impl<P: Program> Quantizer<P> {
+    /// A quantizer to the given pitches, at most `MAX_PITCHES` of them in
+    /// rising order.
+    pub fn new(signal_input: P, output_index: usize, pitches: &[Float]) -> Self {
+        let len = pitches.len().min(MAX_PITCHES);
+        let mut table = [0.; MAX_PITCHES];
+
+        table[..len].copy_from_slice(&pitches[..len]);
+
+        Self {
+            signal_input,
+            output_index,
+            pitches: table,
+            len,
+        }
+    }
+
+    fn quantize(&self, v: Float) -> Float {
+        let pitches = &self.pitches[..self.len];
+        let above = pitches.partition_point(|p| *p < v);
+
+        match (above.checked_sub(1).map(|i| pitches[i]), pitches.get(above)) {
+            (Some(below), Some(above)) if v - below < above - v => below,
+            (_, Some(above)) => *above,
+            (Some(below), None) => below,
+            (None, None) => v,
+        }
+    }
+}
+
+impl<P: Program> Module for Quantizer<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let v = self.signal_input.run(state, stack).unwrap_or(0.);
+
+        update.set(self.output_index, self.quantize(v), UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nearest_pitch() {
+        let quantizer = Quantizer::new(StackProgram::constant(0.), 0, &[0., 0.25, 0.5, 1.]);
+
+        assert_eq!(quantizer.quantize(-1.), 0.);
+        assert_eq!(quantizer.quantize(0.1), 0.);
+        assert_eq!(quantizer.quantize(0.2), 0.25);
+        assert_eq!(quantizer.quantize(0.8), 1.);
+        assert_eq!(quantizer.quantize(2.), 1.);
+        assert_eq!(
+            Quantizer::new(StackProgram::constant(0.), 0, &[]).quantize(0.3),
+            0.3
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! A two pole Sallen-Key lowpass, like the Korg 35 filter of the MS-20.
+//!
+//! Two one pole stages with the difference between them fed back positively,
+//! clipped by the diodes of the resonance path. Without the distortion the
+//! response is `1 / (s² + (2 - k) s + 1)`, self-oscillating from a feedback `k`
+//! of 2.
+
+use super::FilterInputs;
+use crate::distortion::*;
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+const MAX_FEEDBACK: Float = 2.;
+
+pub struct SallenKey<P = DefaultProgram> {
+    f0: Float,
+    inputs: FilterInputs<P>,
+    /// The first stage, then the second stage which is the output
+    state_indices: [usize; 2],
+    distortion: Option<DistortionType>,
+}
+
+impl<P: Program> SallenKey<P> {
+    pub fn new(f0: Float, inputs: FilterInputs<P>, state_indices: [usize; 2]) -> Self {
+        Self {
+            f0,
+            inputs,
+            state_indices,
+            distortion: Some(DistortionType::Tanh),
+        }
+    }
+
+    pub fn with_distortion(mut self, distortion: Option<DistortionType>) -> Self {
+        self.distortion = distortion;
+        self
+    }
+}
+
+impl<P: Program> Module for SallenKey<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let omega = self.inputs.omega(self.f0, state, stack);
+        let k = MAX_FEEDBACK * self.inputs.resonance(state, stack);
+        let input = self.inputs.signal.run(state, stack).unwrap_or(0.);
+        let [y1, y2] = self.state_indices.map(|i| state.get(i));
+        let feedback = (k * (y1 - y2)).distort(&self.distortion);
+
+        update.set(
+            self.state_indices[0],
+            omega * (input - y1 + feedback),
+            UpdateType::Differentiable,
+        );
+        update.set(
+            self.state_indices[1],
+            omega * (y1 - y2),
+            UpdateType::Differentiable,
+        );
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const DT: Float = 1. / 48000.;
+
+    /// The peak output and the number of zero crossings between 0.1 and 0.2 s
+    /// after a short pulse, with the cutoff at 500 Hz.
+    fn ringing(resonance: Float, distortion: Option<DistortionType>) -> (Float, usize) {
+        let inputs = FilterInputs {
+            signal: StackProgram::new(vec![Instr::State(2)], 1),
+            cutoff: StackProgram::constant(0.),
+            linear: StackProgram::constant(0.),
+            resonance: StackProgram::constant(resonance),
+        };
+        let filter = SallenKey::new(500., inputs, [0, 1]).with_distortion(distortion);
+        let mut simulator = RungeKutta::rk4(3).with_modules(vec![Box::new(filter)]);
+        let mut peak: Float = 0.;
+        let mut crossings = 0;
+        let mut previous: Float = 0.;
+
+        simulator.get_state().set(2, 1.);
+
+        for i in 0..9600 {
+            simulator.step(DT);
+
+            if i == 48 {
+                simulator.get_state().set(2, 0.);
+            }
+
+            let output = simulator.get_state().get(1);
+
+            if i >= 4800 {
+                peak = peak.max(output.abs());
+
+                if (output < 0.) != (previous < 0.) {
+                    crossings += 1;
+                }
+            }
+
+            previous = output;
+        }
+
+        (peak, crossings)
+    }
+
+    #[test]
+    fn self_oscillation() {
+        // Damped below a feedback of 2, 1 / (s² + s + 1) at half resonance
+        assert!(ringing(0.5, None).0 < 1e-3);
+        assert!(ringing(0.9, Some(DistortionType::Tanh)).0 < 1e-3);
+
+        // Undamped at full resonance, ringing at the cutoff
+        let (peak, crossings) = ringing(1., None);
+        assert!(peak > 0.01);
+        assert!((98..=102).contains(&crossings));
+
+        // The distortion holds the oscillation at a steady level above it
+        let (peak, crossings) = ringing(1.2, Some(DistortionType::Tanh));
+        assert!(peak > 0.1);
+        assert!((90..=110).contains(&crossings));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+const TRIGGER_LEVEL: Float = 0.5;
+
+/// Samples its input when the trigger rises above 0.5, and holds it until the
+/// next time.
+pub struct SampleHold<P = DefaultProgram> {
+    signal_input: P,
+    trigger_input: P,
+    output_index: usize,
+    value: Float,
+    trigger: bool,
+}
+
+impl<P: Program> SampleHold<P> {
+    pub fn new(signal_input: P, trigger_input: P, output_index: usize) -> Self {
+        Self {
+            signal_input,
+            trigger_input,
+            output_index,
+            value: 0.,
+            trigger: false,
+        }
+    }
+}
+
+impl<P: Program> Module for SampleHold<P> {
+    fn simulate(&self, _state: &State, update: &mut StateUpdate, _stack: &mut [Float]) {
+        update.set(self.output_index, self.value, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, _time_step: Float, stack: &mut [Float]) {
+        let trigger = self.trigger_input.run(state, stack).unwrap_or(0.) >= TRIGGER_LEVEL;
+
+        if trigger && !self.trigger {
+            self.value = self.signal_input.run(state, stack).unwrap_or(0.);
+            state.set(self.output_index, self.value);
+        }
+
+        self.trigger = trigger;
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn sample_on_rising_edge() {
+        let (input, trigger) = (1, 2);
+        let mut sample_hold = SampleHold::new(
+            StackProgram::new(vec![Instr::State(input)], 1),
+            StackProgram::new(vec![Instr::State(trigger)], 1),
+            0,
+        );
+        let mut state = State::new(3);
+        let mut step = |state: &mut State, value: Float, trigger_value: Float| {
+            state.set(input, value);
+            state.set(trigger, trigger_value);
+            sample_hold.finalize(state, 0.001, &mut [0.; 1]);
+            state.get(0)
+        };
+
+        assert_eq!(step(&mut state, 0.3, 0.), 0.);
+        assert_eq!(step(&mut state, 0.4, 1.), 0.4);
+        // Holds while the trigger stays up and after it falls
+        assert_eq!(step(&mut state, 0.5, 1.), 0.4);
+        assert_eq!(step(&mut state, 0.6, 0.), 0.4);
+        assert_eq!(step(&mut state, 0.7, 0.), 0.4);
+        // Samples again on the next rising edge only
+        assert_eq!(step(&mut state, 0.8, 0.7), 0.8);
+        assert_eq!(step(&mut state, 0.9, 0.7), 0.8);
+    }
+}
@@ -0,0 +1,136 @@
+//! A two pole state variable filter, like the filter of the Oberheim SEM.
+//!
+//! Its integrators are driven through the distortion, like the OTAs of the
+//! original. Besides the lowpass, bandpass and highpass outputs it has a notch
+//! output, the sum of the lowpass and the highpass.
+
+use super::FilterInputs;
+use crate::distortion::*;
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The least damping, at full resonance
+const MIN_DAMPING: Float = 0.01;
+
+/// The state indices of the outputs of the filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SemOutputs {
+    pub lowpass: usize,
+    pub bandpass: usize,
+    pub highpass: usize,
+    pub notch: usize,
+}
+
+pub struct SemFilter<P = DefaultProgram> {
+    f0: Float,
+    inputs: FilterInputs<P>,
+    outputs: SemOutputs,
+    distortion: Option<DistortionType>,
+}
+
+impl<P: Program> SemFilter<P> {
+    pub fn new(f0: Float, inputs: FilterInputs<P>, outputs: SemOutputs) -> Self {
+        Self {
+            f0,
+            inputs,
+            outputs,
+            distortion: Some(DistortionType::Tanh),
+        }
+    }
+
+    pub fn with_distortion(mut self, distortion: Option<DistortionType>) -> Self {
+        self.distortion = distortion;
+        self
+    }
+}
+
+impl<P: Program> Module for SemFilter<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let omega = self.inputs.omega(self.f0, state, stack);
+        let damping = (1. - self.inputs.resonance(state, stack)).max(MIN_DAMPING);
+        let input = self.inputs.signal.run(state, stack).unwrap_or(0.);
+        let bp = state.get(self.outputs.bandpass);
+        let lp = state.get(self.outputs.lowpass);
+
+        let notch = input - 2. * damping * bp;
+        let hp = notch - lp;
+
+        update.set(self.outputs.highpass, hp, UpdateType::Absolute);
+        update.set(self.outputs.notch, notch, UpdateType::Absolute);
+        update.set(
+            self.outputs.bandpass,
+            omega * hp.distort(&self.distortion),
+            UpdateType::Differentiable,
+        );
+        update.set(
+            self.outputs.lowpass,
+            omega * bp.distort(&self.distortion),
+            UpdateType::Differentiable,
+        );
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::math;
+    use crate::math::consts::PI;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const DT: Float = 1. / 48000.;
+
+    /// The amplitude of the notch output for a sine at `frequency`, with the
+    /// notch at 1 kHz.
+    fn notch_level(frequency: Float) -> Float {
+        let inputs = FilterInputs {
+            signal: StackProgram::new(vec![Instr::State(4)], 1),
+            cutoff: StackProgram::constant(0.),
+            linear: StackProgram::constant(0.),
+            resonance: StackProgram::constant(0.5),
+        };
+        let outputs = SemOutputs {
+            lowpass: 0,
+            bandpass: 1,
+            highpass: 2,
+            notch: 3,
+        };
+        let filter = SemFilter::new(1000., inputs, outputs).with_distortion(None);
+        let mut simulator = RungeKutta::rk4(5).with_modules(vec![Box::new(filter)]);
+        let mut peak: Float = 0.;
+
+        for i in 0..9600 {
+            let t = i as Float * DT;
+
+            simulator
+                .get_state()
+                .set(4, math::sin(2. * PI * frequency * t));
+            simulator.step(DT);
+
+            if i >= 4800 {
+                peak = peak.max(simulator.get_state().get(3).abs());
+            }
+        }
+
+        peak
+    }
+
+    #[test]
+    fn notch() {
+        assert!(notch_level(1000.) < 0.05);
+        assert!(notch_level(100.) > 0.9);
+        assert!(notch_level(10000.) > 0.9);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// Follows its input at a limited rate. The rise and fall controls are the
+/// time in seconds to move by 1, zero follows the input right away.
+pub struct SlewLimiter<P = DefaultProgram> {
+    signal_input: P,
+    rise_input: P,
+    fall_input: P,
+    output_index: usize,
+    /// The output at the start of the time step
+    value: Float,
+}
+
+impl<P: Program> SlewLimiter<P> {
+    pub fn new(signal_input: P, rise_input: P, fall_input: P, output_index: usize) -> Self {
+        Self {
+            signal_input,
+            rise_input,
+            fall_input,
+            output_index,
+            value: 0.,
+        }
+    }
+
+    /// The output `dt` into the time step.
+    fn slew(&self, state: &State, stack: &mut [Float], dt: Float) -> Float {
+        let target = self.signal_input.run(state, stack).unwrap_or(0.);
+        let rise = self.rise_input.run(state, stack).unwrap_or(0.);
+        let fall = self.fall_input.run(state, stack).unwrap_or(0.);
+        let step = target - self.value;
+
+        let limited = if step > 0. && rise > 0. {
+            step.min(dt / rise)
+        } else if step < 0. && fall > 0. {
+            step.max(-dt / fall)
+        } else {
+            step
+        };
+
+        self.value + limited
+    }
+}
+
+impl<P: Program> Module for SlewLimiter<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let value = self.slew(state, stack, update.get_delta_time());
+
+        update.set(self.output_index, value, UpdateType::Absolute);
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        self.value = self.slew(state, stack, time_step);
+        state.set(self.output_index, self.value);
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn rise_and_fall() {
+        let input = 1;
+        let mut slew = SlewLimiter::new(
+            StackProgram::new(vec![Instr::State(input)], 1),
+            StackProgram::constant(0.5),
+            StackProgram::constant(0.),
+            0,
+        );
+        let mut state = State::new(2);
+
+        state.set(input, 1.);
+        slew.finalize(&mut state, 0.1, &mut [0.; 1]);
+        assert!((state.get(0) - 0.2).abs() < 1e-6);
+
+        for _ in 0..10 {
+            slew.finalize(&mut state, 0.1, &mut [0.; 1]);
+        }
+        assert_eq!(state.get(0), 1.);
+
+        // No fall time, so it follows a falling input right away
+        state.set(input, -1.);
+        slew.finalize(&mut state, 0.1, &mut [0.; 1]);
+        assert_eq!(state.get(0), -1.);
+    }
+}
@@ -0,0 +1,215 @@
+//! A linear system given by its state-space matrices.
+//!
+//! The states follow `x' = ω (A x + B u)` and the outputs are `y = C x + D u`,
+//! where `u` are the inputs. The frequency `ω` scales the time of the system,
+//! so a filter designed for a cutoff of 1 rad/s has its cutoff at `ω`.
+
+use crate::event::ControllerEvent;
+use crate::simulator::module::Module;
+use crate::simulator::state::{State, StateUpdate, UpdateType};
+use crate::stack_program::*;
+use crate::Float;
+
+/// The most states of a system.
+pub const MAX_ORDER: usize = 8;
+/// The most inputs and outputs of a system.
+pub const MAX_INPUTS: usize = 4;
+pub const MAX_OUTPUTS: usize = 4;
+
+/// The matrices of a linear system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearSystem {
+    a: [[Float; MAX_ORDER]; MAX_ORDER],
+    b: [[Float; MAX_INPUTS]; MAX_ORDER],
+    c: [[Float; MAX_ORDER]; MAX_OUTPUTS],
+    d: [[Float; MAX_INPUTS]; MAX_OUTPUTS],
+    order: usize,
+    inputs: usize,
+    outputs: usize,
+}
+
+impl LinearSystem {
+    /// A system from its matrices, row by row. The order, the number of inputs
+    /// and the number of outputs follow from the sizes of `a`, `b` and `c`,
+    /// entries beyond the most the system can have are ignored.
+    pub fn new(a: &[Float], b: &[Float], c: &[Float], d: &[Float]) -> Self {
+        let order = (0..=MAX_ORDER)
+            .rev()
+            .find(|n| n * n <= a.len())
+            .unwrap_or(0);
+        let inputs = b.len().checked_div(order).unwrap_or(0);
+        let outputs = c.len().checked_div(order).unwrap_or(0);
+        let mut system = Self {
+            a: [[0.; MAX_ORDER]; MAX_ORDER],
+            b: [[0.; MAX_INPUTS]; MAX_ORDER],
+            c: [[0.; MAX_ORDER]; MAX_OUTPUTS],
+            d: [[0.; MAX_INPUTS]; MAX_OUTPUTS],
+            order,
+            inputs: inputs.min(MAX_INPUTS),
+            outputs: outputs.min(MAX_OUTPUTS),
+        };
+
+        for i in 0..system.order {
+            system.a[i][..order].copy_from_slice(&a[i * order..(i + 1) * order]);
+            system.b[i][..system.inputs]
+                .copy_from_slice(&b[i * inputs..i * inputs + system.inputs]);
+        }
+
+        for i in 0..system.outputs {
+            system.c[i][..order].copy_from_slice(&c[i * order..(i + 1) * order]);
+
+            for j in 0..system.inputs {
+                system.d[i][j] = d.get(i * inputs + j).copied().unwrap_or(0.);
+            }
+        }
+
+        system
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+}
+
+pub struct StateSpace<P = DefaultProgram> {
+    system: LinearSystem,
+    signal_inputs: [Option<P>; MAX_INPUTS],
+    frequency: P,
+    state_indices: [usize; MAX_ORDER],
+    output_indices: [usize; MAX_OUTPUTS],
+}
+
+impl<P: Program> StateSpace<P> {
+    /// A system with the given input programs, and the states and outputs at
+    /// the given indices. Missing inputs are zero.
+    pub fn new(
+        system: LinearSystem,
+        signal_inputs: impl IntoIterator<Item = P>,
+        frequency: P,
+        state_indices: &[usize],
+        output_indices: &[usize],
+    ) -> Self {
+        let mut inputs: [Option<P>; MAX_INPUTS] = core::array::from_fn(|_| None);
+        let mut states = [0; MAX_ORDER];
+        let mut outputs = [0; MAX_OUTPUTS];
+
+        for (slot, input) in inputs.iter_mut().zip(signal_inputs) {
+            *slot = Some(input);
+        }
+
+        states[..system.order].copy_from_slice(&state_indices[..system.order]);
+        outputs[..system.outputs].copy_from_slice(&output_indices[..system.outputs]);
+
+        Self {
+            system,
+            signal_inputs: inputs,
+            frequency,
+            state_indices: states,
+            output_indices: outputs,
+        }
+    }
+}
+
+impl<P: Program> Module for StateSpace<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        let LinearSystem {
+            a,
+            b,
+            c,
+            d,
+            order,
+            inputs,
+            outputs,
+        } = &self.system;
+        let omega = self.frequency.run(state, stack).unwrap_or(0.);
+        let mut u = [0.; MAX_INPUTS];
+        let mut x = [0.; MAX_ORDER];
+
+        for (u, input) in u.iter_mut().zip(&self.signal_inputs) {
+            if let Some(input) = input {
+                *u = input.run(state, stack).unwrap_or(0.);
+            }
+        }
+
+        for (x, index) in x.iter_mut().zip(&self.state_indices[..*order]) {
+            *x = state.get(*index);
+        }
+
+        let dot =
+            |row: &[Float], v: &[Float]| -> Float { row.iter().zip(v).map(|(r, v)| r * v).sum() };
+
+        for i in 0..*order {
+            let dx = dot(&a[i][..*order], &x) + dot(&b[i][..*inputs], &u);
+
+            update.set(
+                self.state_indices[i],
+                omega * dx,
+                UpdateType::Differentiable,
+            );
+        }
+
+        for i in 0..*outputs {
+            let y = dot(&c[i][..*order], &x) + dot(&d[i][..*inputs], &u);
+
+            update.set(self.output_indices[i], y, UpdateType::Absolute);
+        }
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {
+        /* do nothing */
+    }
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {
+        /* do nothing */
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::math::consts::PI;
+    use crate::simulator::rungekutta::RungeKutta;
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    const DT: Float = 1. / 48000.;
+
+    #[test]
+    fn lowpass() {
+        // A second order Butterworth lowpass at 100 Hz, fed a step
+        let sqrt2 = crate::math::sqrt(2.);
+        let system = LinearSystem::new(&[0., 1., -1., -sqrt2], &[0., 1.], &[1., 0.], &[0.]);
+        let filter = StateSpace::new(
+            system,
+            [StackProgram::constant(1.)],
+            StackProgram::constant(2. * PI * 100.),
+            &[0, 1],
+            &[2],
+        );
+        let mut simulator = RungeKutta::rk4(3).with_modules(vec![Box::new(filter)]);
+
+        assert_eq!(
+            (system.order(), system.inputs(), system.outputs()),
+            (2, 1, 1)
+        );
+
+        // The step response overshoots by about 4% and settles
+        let mut peak: Float = 0.;
+
+        for _ in 0..4800 {
+            simulator.step(DT);
+            peak = peak.max(simulator.get_state().get(2));
+        }
+
+        assert!((peak - 1.043).abs() < 1e-2);
+        assert!((simulator.get_state().get(2) - 1.).abs() < 1e-3);
+    }
+}
@@ -1,36 +1,40 @@
 use super::control_to_frequency;
 use super::wavetable::*;
 use crate::event::ControllerEvent;
+use crate::math;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
 use crate::stack_program::*;
+use crate::Float;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use core::f32::consts::PI;
 
-pub struct Vosim {
-    f0: f32,
+pub struct Vosim<P = DefaultProgram> {
+    f0: Float,
     position_state: usize,
     signal_output: usize,
-    pitch_control: StackProgram,
-    linear_modulation: StackProgram,
-    grain_pitch_control: StackProgram,
-    grain_linear_modulation: StackProgram,
-    wavetable_select: StackProgram,
-    wavetables: Vec<WavetableEntry>,
-    amp: f32,
+    pitch_control: P,
+    linear_modulation: P,
+    grain_pitch_control: P,
+    grain_linear_modulation: P,
+    wavetable_select: P,
+    wavetables: Table<WavetableEntry>,
+    amp: Float,
 }
 
-impl Vosim {
+impl<P: Program> Vosim<P> {
+    #[cfg(feature = "alloc")]
     pub fn new(
-        f0: f32,
+        f0: Float,
         position_state: usize,
         signal_output: usize,
-        pitch_control: StackProgram,
-        linear_modulation: StackProgram,
-        grain_pitch_control: StackProgram,
-        grain_linear_modulation: StackProgram,
-        wavetable_select: StackProgram,
-        wavetables: Vec<Vec<f32>>,
+        pitch_control: P,
+        linear_modulation: P,
+        grain_pitch_control: P,
+        grain_linear_modulation: P,
+        wavetable_select: P,
+        wavetables: Vec<Vec<Float>>,
     ) -> Self {
         Self {
             f0,
@@ -50,15 +54,15 @@ impl Vosim {
     }
 
     pub fn new_with_precompute(
-        f0: f32,
+        f0: Float,
         position_state: usize,
         signal_output: usize,
-        pitch_control: StackProgram,
-        linear_modulation: StackProgram,
-        grain_pitch_control: StackProgram,
-        grain_linear_modulation: StackProgram,
-        wavetable_select: StackProgram,
-        wavetables: Vec<WavetableEntry>,
+        pitch_control: P,
+        linear_modulation: P,
+        grain_pitch_control: P,
+        grain_linear_modulation: P,
+        wavetable_select: P,
+        wavetables: Table<WavetableEntry>,
     ) -> Self {
         Self {
             f0,
@@ -75,8 +79,8 @@ impl Vosim {
     }
 }
 
-impl Module for Vosim {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+impl<P: Program> Module for Vosim<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let velocity = control_to_frequency(
             self.f0,
             self.pitch_control.run(state, stack).unwrap_or(0.),
@@ -93,7 +97,7 @@ impl Module for Vosim {
         let grain_distance = update.get_time_step() * velocity;
 
         let position = ((position % 1.) + 1.) % 1.;
-        let grain_ratio = if grain_velocity > f32::EPSILON {
+        let grain_ratio = if grain_velocity > Float::EPSILON {
             velocity.abs() / grain_velocity.abs()
         } else {
             0.
@@ -112,9 +116,9 @@ impl Module for Vosim {
                     .run(state, stack)
                     .unwrap_or(0.)
                     .clamp(0., 1.);
-                let scan_select = scan * ((self.wavetables.len() - 1) as f32);
-                let index = scan_select.floor() as usize;
-                let x = scan_select.fract();
+                let scan_select = scan * ((self.wavetables.len() - 1) as Float);
+                let index = math::floor(scan_select) as usize;
+                let x = math::fract(scan_select);
                 let index0 = index.min(self.wavetables.len() - 1);
                 let index1 = (index + 1).min(self.wavetables.len() - 1);
 
@@ -139,7 +143,7 @@ impl Module for Vosim {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         let p = ((state.get(self.position_state) % 1.) + 1.) % 1.;
 
         state.set(self.position_state, p);
@@ -1,24 +1,63 @@
 use super::control_to_frequency;
 use crate::event::ControllerEvent;
 use crate::interpolation::Interpolation;
+use crate::math;
+use crate::math::consts::PI;
 use crate::simulator::module::Module;
 use crate::simulator::state::{State, StateUpdate, UpdateType};
+#[cfg(feature = "alloc")]
 use crate::sinc_filter::downsample_half;
 use crate::stack_program::*;
+use crate::Float;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use core::f32::consts::PI;
 
-pub const FREQUENCY_LIMIT: f32 = 18_000.0;
+pub const FREQUENCY_LIMIT: Float = 18_000.0;
+
+/// Storage of wavetables loaded and precomputed at runtime.
+#[cfg(feature = "alloc")]
+pub type Table<T> = Vec<T>;
+
+/// Without `alloc` wavetables are static data, precomputed by code generation.
+#[cfg(not(feature = "alloc"))]
+pub type Table<T> = &'static [T];
+
+/// Sample data embedded in a program with `include_bytes!`, as emitted by code
+/// generation. The alignment allows a view of the bytes as samples without
+/// copying them, in either precision.
+#[repr(C, align(8))]
+pub struct SampleBlob<B: ?Sized>(pub B);
+
+impl SampleBlob<[u8]> {
+    /// The samples of the blob, stored as little endian `Float`.
+    pub const fn samples(&'static self) -> &'static [Float] {
+        if cfg!(target_endian = "big") {
+            panic!("Sample blobs are little endian");
+        }
+
+        let size = core::mem::size_of::<Float>();
+
+        assert!(
+            self.0.len() % size == 0,
+            "Sample blob size must be a multiple of the sample size"
+        );
+
+        // Safety: the bytes are aligned for Float by the representation of the
+        // blob, and any bit pattern is a valid Float.
+        unsafe { core::slice::from_raw_parts(self.0.as_ptr() as *const Float, self.0.len() / size) }
+    }
+}
 
 pub struct WavetableData {
-    pub samples: Vec<f32>,
-    pub len_f32: f32,
+    pub samples: Table<Float>,
+    pub len_f32: Float,
 }
 
 impl WavetableData {
-    fn from_slice(samples: &[f32]) -> Self {
-        let mut samples_vec: Vec<f32> = Vec::new();
-        let len_f32 = samples.len() as f32;
+    #[cfg(feature = "alloc")]
+    fn from_slice(samples: &[Float]) -> Self {
+        let mut samples_vec: Vec<Float> = Vec::new();
+        let len_f32 = samples.len() as Float;
 
         samples_vec.extend_from_slice(samples);
 
@@ -28,6 +67,7 @@ impl WavetableData {
         }
     }
 
+    #[cfg(feature = "alloc")]
     fn downsample(&self) -> Option<Self> {
         if self.samples.len() < 4 {
             None
@@ -38,19 +78,20 @@ impl WavetableData {
         }
     }
 
-    pub fn eval(&self, x: f32) -> f32 {
+    pub fn eval(&self, x: Float) -> Float {
         let x = x * self.len_f32;
         self.samples.cubic_interpolate(x)
     }
 }
 
 pub struct WavetableEntry {
-    pub data: Vec<WavetableData>,
+    pub data: Table<WavetableData>,
     pub base_data_len: usize,
 }
 
 impl WavetableEntry {
-    pub fn from_slice(samples: &[f32]) -> Self {
+    #[cfg(feature = "alloc")]
+    pub fn from_slice(samples: &[Float]) -> Self {
         let mut data = Vec::new();
 
         let mut current_wavetable_data = WavetableData::from_slice(samples);
@@ -66,8 +107,43 @@ impl WavetableEntry {
         }
     }
 
-    fn get_data_by_frequency(&self, cycles_per_step: f32) -> Option<&WavetableData> {
-        let mut samples_per_step = (self.base_data_len as f32) * cycles_per_step;
+    /// A wavetable of `size` samples from the amplitudes and phases, in
+    /// cycles, of its harmonics, starting with the fundamental. Every level
+    /// only has the harmonics below its Nyquist frequency, so the levels are
+    /// band limited without filtering.
+    #[cfg(feature = "alloc")]
+    pub fn from_harmonics(harmonics: &[(Float, Float)], size: usize) -> Self {
+        let mut data = Vec::new();
+        let mut len = size;
+
+        while len >= 4 {
+            let samples: Vec<Float> = (0..len)
+                .map(|i| {
+                    let x = i as Float / len as Float;
+
+                    harmonics
+                        .iter()
+                        .take((len - 1) / 2)
+                        .enumerate()
+                        .map(|(k, (amplitude, phase))| {
+                            amplitude * math::sin(2. * PI * ((k + 1) as Float * x + phase))
+                        })
+                        .sum()
+                })
+                .collect();
+
+            data.push(WavetableData::from_slice(&samples));
+            len /= 2;
+        }
+
+        Self {
+            data,
+            base_data_len: size,
+        }
+    }
+
+    fn get_data_by_frequency(&self, cycles_per_step: Float) -> Option<&WavetableData> {
+        let mut samples_per_step = (self.base_data_len as Float) * cycles_per_step;
 
         for i in 0..self.data.len() {
             if samples_per_step <= 1. {
@@ -80,7 +156,7 @@ impl WavetableEntry {
         None
     }
 
-    pub fn eval(&self, cycles_per_step: f32, x: f32) -> f32 {
+    pub fn eval(&self, cycles_per_step: Float, x: Float) -> Float {
         if let Some(data) = self.get_data_by_frequency(cycles_per_step) {
             data.eval(x)
         } else {
@@ -89,26 +165,27 @@ impl WavetableEntry {
     }
 }
 
-pub struct Wavetable {
-    f0: f32,
+pub struct Wavetable<P = DefaultProgram> {
+    f0: Float,
     position_state: usize,
     signal_output: usize,
-    pitch_control: StackProgram,
-    linear_modulation: StackProgram,
-    wavetable_select: StackProgram,
-    wavetables: Vec<WavetableEntry>,
-    amp: f32,
+    pitch_control: P,
+    linear_modulation: P,
+    wavetable_select: P,
+    wavetables: Table<WavetableEntry>,
+    amp: Float,
 }
 
-impl Wavetable {
+impl<P: Program> Wavetable<P> {
+    #[cfg(feature = "alloc")]
     pub fn new(
-        f0: f32,
+        f0: Float,
         position_state: usize,
         signal_output: usize,
-        pitch_control: StackProgram,
-        linear_modulation: StackProgram,
-        wavetable_select: StackProgram,
-        wavetables: Vec<Vec<f32>>,
+        pitch_control: P,
+        linear_modulation: P,
+        wavetable_select: P,
+        wavetables: Vec<Vec<Float>>,
     ) -> Self {
         Self {
             f0,
@@ -125,21 +202,14 @@ impl Wavetable {
         }
     }
 
-    pub fn precompute_wavetables(table_data: &Vec<Vec<f32>>) -> Vec<WavetableEntry> {
-        table_data
-            .into_iter()
-            .map(|samples| WavetableEntry::from_slice(&samples))
-            .collect()
-    }
-
     pub fn new_with_precompute(
-        f0: f32,
+        f0: Float,
         position_state: usize,
         signal_output: usize,
-        pitch_control: StackProgram,
-        linear_modulation: StackProgram,
-        wavetable_select: StackProgram,
-        wavetables: Vec<WavetableEntry>,
+        pitch_control: P,
+        linear_modulation: P,
+        wavetable_select: P,
+        wavetables: Table<WavetableEntry>,
     ) -> Self {
         Self {
             f0,
@@ -154,8 +224,18 @@ impl Wavetable {
     }
 }
 
-impl Module for Wavetable {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]) {
+#[cfg(feature = "alloc")]
+impl Wavetable {
+    pub fn precompute_wavetables(table_data: &Vec<Vec<Float>>) -> Vec<WavetableEntry> {
+        table_data
+            .into_iter()
+            .map(|samples| WavetableEntry::from_slice(&samples))
+            .collect()
+    }
+}
+
+impl<P: Program> Module for Wavetable<P> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
         let velocity = control_to_frequency(
             self.f0,
             self.pitch_control.run(state, stack).unwrap_or(0.),
@@ -172,9 +252,9 @@ impl Module for Wavetable {
                 .run(state, stack)
                 .unwrap_or(0.)
                 .clamp(0., 1.);
-            let scan_select = scan * ((self.wavetables.len() - 1) as f32);
-            let index = scan_select.floor() as usize;
-            let x = scan_select.fract();
+            let scan_select = scan * ((self.wavetables.len() - 1) as Float);
+            let index = math::floor(scan_select) as usize;
+            let x = math::fract(scan_select);
             let index0 = index.min(self.wavetables.len() - 1);
             let index1 = (index + 1).min(self.wavetables.len() - 1);
 
@@ -198,9 +278,43 @@ impl Module for Wavetable {
         /* do nothing */
     }
 
-    fn finalize(&mut self, state: &mut State, _time_step: f32, _stack: &mut [f32]) {
+    fn finalize(&mut self, state: &mut State, _time_step: Float, _stack: &mut [Float]) {
         let p = ((state.get(self.position_state) % 1.) + 1.) % 1.;
 
         state.set(self.position_state, p);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(not(feature = "f64"))]
+    static BLOB: &SampleBlob<[u8]> = &SampleBlob([0, 0, 128, 63, 0, 0, 0, 191]);
+    #[cfg(feature = "f64")]
+    static BLOB: &SampleBlob<[u8]> =
+        &SampleBlob([0, 0, 0, 0, 0, 0, 240, 63, 0, 0, 0, 0, 0, 0, 224, 191]);
+    static SAMPLES: &[Float] = BLOB.samples();
+
+    #[test]
+    fn sample_blob() {
+        assert_eq!(SAMPLES, &[1.0, -0.5]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn band_limited_levels() {
+        // Fundamental and fourth harmonic, a quarter cycle shifted
+        let entry =
+            WavetableEntry::from_harmonics(&[(1., 0.), (0., 0.), (0., 0.), (0.5, 0.25)], 16);
+        let lengths: Vec<usize> = entry.data.iter().map(|data| data.samples.len()).collect();
+
+        assert_eq!(lengths, [16, 8, 4]);
+        assert!((entry.data[0].samples[0] - 0.5).abs() < 1e-6);
+        assert!((entry.data[0].samples[4] - 1.5).abs() < 1e-6);
+
+        // The fourth harmonic is at the Nyquist frequency of the second level
+        assert!(entry.data[1].samples[0].abs() < 1e-6);
+        assert!((entry.data[1].samples[2] - 1.).abs() < 1e-6);
+    }
+}
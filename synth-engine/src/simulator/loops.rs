@@ -0,0 +1,262 @@
+//! Resolution of delay-free loops.
+//!
+//! Modules with absolute outputs compute them directly from their inputs. When
+//! such outputs feed each other in a cycle, the loop has no delay to break it
+//! and the outputs of a stage depend on themselves. The loop is then solved as
+//! an implicit equation `x = g(x)`, where `x` are the loop outputs and `g` is
+//! one simulation of the loop modules, either by fixed-point iteration or by
+//! Newton's method with a finite difference Jacobian.
+//!
+//! See D. Chowdhury, "A Comparison of Virtual Analog Modelling Techniques for
+//! Desktop and Embedded Implementations", section on delay-free loops.
+
+use crate::simulator::state::{State, StateUpdate};
+use crate::Float;
+use alloc::vec::Vec;
+
+pub const DEFAULT_MAX_ITERATIONS: usize = 32;
+pub const DEFAULT_TOLERANCE: Float = 1e-5;
+/// The most loop outputs Newton's method solves, to keep its Jacobian on the
+/// stack
+pub const MAX_NEWTON_STATES: usize = 8;
+
+const JACOBIAN_STEP: Float = 1e-3;
+const MIN_PIVOT: Float = 1e-9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopSolver {
+    FixedPoint,
+    Newton,
+}
+
+#[derive(Clone, Debug)]
+pub struct DelayFreeLoop {
+    pub modules: Vec<usize>,
+    pub states: Vec<usize>,
+    pub solver: LoopSolver,
+    pub max_iterations: usize,
+    pub tolerance: Float,
+}
+
+impl DelayFreeLoop {
+    /// A loop through the given modules, as indices into the module list of the
+    /// simulator, with `states` the state indices of the loop outputs. Panics if
+    /// Newton's method is asked to solve more than `MAX_NEWTON_STATES` outputs.
+    pub fn new(
+        modules: Vec<usize>,
+        states: Vec<usize>,
+        solver: LoopSolver,
+        max_iterations: usize,
+        tolerance: Float,
+    ) -> Self {
+        assert!(
+            solver != LoopSolver::Newton || states.len() <= MAX_NEWTON_STATES,
+            "too many outputs in delay-free loop for Newton's method"
+        );
+
+        Self {
+            modules,
+            states,
+            solver,
+            max_iterations,
+            tolerance,
+        }
+    }
+
+    /// Solve the loop. On entry `update` must hold the result of simulating the
+    /// loop modules on `state`. The function `simulate` runs the loop modules
+    /// and is called repeatedly with the loop outputs written to `state`. On
+    /// return `update` holds the solution. Returns the number of iterations, or
+    /// the remaining residual if the solver didn't converge.
+    pub fn solve<F>(
+        &self,
+        state: &mut State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+        mut simulate: F,
+    ) -> Result<usize, Float>
+    where
+        F: FnMut(&State, &mut StateUpdate, &mut [Float]),
+    {
+        match self.solver {
+            LoopSolver::FixedPoint => self.fixed_point(state, update, stack, &mut simulate),
+            LoopSolver::Newton => self.newton(state, update, stack, &mut simulate),
+        }
+    }
+
+    fn residual(&self, state: &State, update: &StateUpdate) -> Float {
+        self.states
+            .iter()
+            .map(|s| (update.get(*s) - state.get(*s)).abs())
+            .fold(0., Float::max)
+    }
+
+    fn fixed_point<F>(
+        &self,
+        state: &mut State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+        simulate: &mut F,
+    ) -> Result<usize, Float>
+    where
+        F: FnMut(&State, &mut StateUpdate, &mut [Float]),
+    {
+        for iteration in 0..self.max_iterations {
+            let residual = self.residual(state, update);
+
+            if residual <= self.tolerance {
+                return Ok(iteration);
+            }
+
+            for s in &self.states {
+                state.set(*s, update.get(*s));
+            }
+
+            simulate(state, update, stack);
+        }
+
+        Err(self.residual(state, update))
+    }
+
+    fn newton<F>(
+        &self,
+        state: &mut State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+        simulate: &mut F,
+    ) -> Result<usize, Float>
+    where
+        F: FnMut(&State, &mut StateUpdate, &mut [Float]),
+    {
+        let n = self.states.len();
+        let mut x = [0.0 as Float; MAX_NEWTON_STATES];
+        let mut g = [0.0 as Float; MAX_NEWTON_STATES];
+        let mut jacobian = [[0.0 as Float; MAX_NEWTON_STATES + 1]; MAX_NEWTON_STATES];
+
+        for iteration in 0..self.max_iterations {
+            if self.residual(state, update) <= self.tolerance {
+                return Ok(iteration);
+            }
+
+            for (i, s) in self.states.iter().enumerate() {
+                x[i] = state.get(*s);
+                g[i] = update.get(*s);
+            }
+
+            // Columns of d(g(x) - x)/dx, with -(g(x) - x) as the last column
+            for (j, s) in self.states.iter().enumerate() {
+                state.set(*s, x[j] + JACOBIAN_STEP);
+                simulate(state, update, stack);
+                state.set(*s, x[j]);
+
+                for (i, row) in jacobian[..n].iter_mut().enumerate() {
+                    row[j] = (update.get(self.states[i]) - g[i]) / JACOBIAN_STEP;
+                }
+
+                jacobian[j][j] -= 1.;
+            }
+
+            for (i, row) in jacobian[..n].iter_mut().enumerate() {
+                row[n] = x[i] - g[i];
+            }
+
+            // Fall back to a fixed-point step when the Jacobian is singular
+            if solve_linear(&mut jacobian[..n]) {
+                for (i, s) in self.states.iter().enumerate() {
+                    state.set(*s, x[i] + jacobian[i][n]);
+                }
+            } else {
+                for (i, s) in self.states.iter().enumerate() {
+                    state.set(*s, g[i]);
+                }
+            }
+
+            simulate(state, update, stack);
+        }
+
+        Err(self.residual(state, update))
+    }
+}
+
+/// Solve a linear system given as an augmented matrix of `n` rows, by Gaussian
+/// elimination with partial pivoting. The solution is left in column `n`.
+/// Returns false if the system is singular.
+fn solve_linear(m: &mut [[Float; MAX_NEWTON_STATES + 1]]) -> bool {
+    let n = m.len();
+
+    for col in 0..n {
+        let Some(pivot) = (col..n).max_by(|a, b| m[*a][col].abs().total_cmp(&m[*b][col].abs()))
+        else {
+            return false;
+        };
+
+        if m[pivot][col].abs() < MIN_PIVOT {
+            return false;
+        }
+
+        m.swap(col, pivot);
+
+        let (upper, lower) = m.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+
+        for row in lower {
+            let f = row[col] / pivot_row[col];
+
+            for (a, b) in row[col..=n].iter_mut().zip(&pivot_row[col..=n]) {
+                *a -= f * b;
+            }
+        }
+    }
+
+    for row in (0..n).rev() {
+        let sum: Float = (row + 1..n).map(|k| m[row][k] * m[k][n]).sum();
+        m[row][n] = (m[row][n] - sum) / m[row][row];
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::simulator::state::UpdateType;
+    use alloc::vec;
+
+    // x0 = k * x1 + 1, x1 = k * x0
+    fn linear_loop(k: Float) -> impl FnMut(&State, &mut StateUpdate, &mut [Float]) {
+        move |state: &State, update: &mut StateUpdate, _stack: &mut [Float]| {
+            update.set(0, k * state.get(1) + 1., UpdateType::Absolute);
+            update.set(1, k * state.get(0), UpdateType::Absolute);
+        }
+    }
+
+    fn solve(solver: LoopSolver, k: Float) -> (Result<usize, Float>, Float, Float) {
+        let delay_free_loop = DelayFreeLoop::new(vec![0, 1], vec![0, 1], solver, 32, 1e-5);
+        let mut state = State::new(2);
+        let mut update = state.update_data(0., 0.);
+        let mut stack = [0.0 as Float; 4];
+        let mut simulate = linear_loop(k);
+
+        simulate(&state, &mut update, &mut stack);
+
+        let result = delay_free_loop.solve(&mut state, &mut update, &mut stack, simulate);
+
+        (result, update.get(0), update.get(1))
+    }
+
+    #[test]
+    fn fixed_point_and_newton() {
+        // Solution x0 = 1 / (1 - k^2), x1 = k * x0
+        let (result, x0, x1) = solve(LoopSolver::FixedPoint, 0.5);
+        assert!(result.is_ok());
+        assert!((x0 - 4. / 3.).abs() < 1e-4 && (x1 - 2. / 3.).abs() < 1e-4);
+
+        // Fixed-point iteration diverges for a loop gain above one
+        let (result, _, _) = solve(LoopSolver::FixedPoint, 2.);
+        assert!(result.is_err());
+
+        let (result, x0, x1) = solve(LoopSolver::Newton, 2.);
+        assert!(result.is_ok());
+        assert!((x0 + 1. / 3.).abs() < 1e-4 && (x1 + 2. / 3.).abs() < 1e-4);
+    }
+}
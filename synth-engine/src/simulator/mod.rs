@@ -1,6 +1,8 @@
 //! The workhorse module for running the synth engine.
 //!
 
+#[cfg(feature = "alloc")]
+pub mod loops;
 pub mod module;
 pub mod rungekutta;
 pub mod state;
@@ -1,10 +1,142 @@
 use crate::event::ControllerEvent;
 use crate::simulator::state::{State, StateUpdate};
+use crate::Float;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
 
 pub trait Module: Send {
-    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [f32]);
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]);
 
     fn process_event(&mut self, event: &ControllerEvent);
 
-    fn finalize(&mut self, state: &mut State, time_step: f32, stack: &mut [f32]);
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]);
+
+    /// Set the states the module starts from, before the first step of the
+    /// simulation.
+    fn initialize(&mut self, _state: &mut State, _time_step: Float) {}
+}
+
+/// The modules of a synth, simulated in order. Implemented for a vector of
+/// boxed modules, as built at runtime from a patch, and for arrays of a single
+/// module type, as emitted by code generation.
+pub trait ModuleSet {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]);
+
+    fn simulate_module(
+        &self,
+        index: usize,
+        state: &State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+    );
+
+    fn process_event(&mut self, event: &ControllerEvent);
+
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]);
+
+    fn initialize(&mut self, state: &mut State, time_step: Float);
+}
+
+/// The module set of a simulator that is yet to be given its modules.
+#[cfg(feature = "alloc")]
+pub type DefaultModuleSet = Vec<Box<dyn Module>>;
+
+#[cfg(not(feature = "alloc"))]
+pub type DefaultModuleSet = ();
+
+#[cfg(feature = "alloc")]
+impl ModuleSet for Vec<Box<dyn Module>> {
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        for module in self {
+            module.simulate(state, update, stack);
+        }
+    }
+
+    fn simulate_module(
+        &self,
+        index: usize,
+        state: &State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+    ) {
+        self[index].simulate(state, update, stack);
+    }
+
+    fn process_event(&mut self, event: &ControllerEvent) {
+        for module in self {
+            module.process_event(event);
+        }
+    }
+
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        for module in self {
+            module.finalize(state, time_step, stack);
+        }
+    }
+
+    fn initialize(&mut self, state: &mut State, time_step: Float) {
+        for module in self {
+            module.initialize(state, time_step);
+        }
+    }
+}
+
+impl<M: Module, const N: usize> ModuleSet for [M; N] {
+    #[inline]
+    fn simulate(&self, state: &State, update: &mut StateUpdate, stack: &mut [Float]) {
+        for module in self {
+            module.simulate(state, update, stack);
+        }
+    }
+
+    #[inline]
+    fn simulate_module(
+        &self,
+        index: usize,
+        state: &State,
+        update: &mut StateUpdate,
+        stack: &mut [Float],
+    ) {
+        self[index].simulate(state, update, stack);
+    }
+
+    #[inline]
+    fn process_event(&mut self, event: &ControllerEvent) {
+        for module in self {
+            module.process_event(event);
+        }
+    }
+
+    #[inline]
+    fn finalize(&mut self, state: &mut State, time_step: Float, stack: &mut [Float]) {
+        for module in self {
+            module.finalize(state, time_step, stack);
+        }
+    }
+
+    fn initialize(&mut self, state: &mut State, time_step: Float) {
+        for module in self {
+            module.initialize(state, time_step);
+        }
+    }
+}
+
+/// No modules at all.
+impl ModuleSet for () {
+    fn simulate(&self, _state: &State, _update: &mut StateUpdate, _stack: &mut [Float]) {}
+
+    fn simulate_module(
+        &self,
+        _index: usize,
+        _state: &State,
+        _update: &mut StateUpdate,
+        _stack: &mut [Float],
+    ) {
+    }
+
+    fn process_event(&mut self, _event: &ControllerEvent) {}
+
+    fn finalize(&mut self, _state: &mut State, _time_step: Float, _stack: &mut [Float]) {}
+
+    fn initialize(&mut self, _state: &mut State, _time_step: Float) {}
 }
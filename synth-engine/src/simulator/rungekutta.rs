@@ -1,9 +1,13 @@
 use crate::event::ControllerEvent;
+#[cfg(feature = "alloc")]
+use crate::simulator::loops::DelayFreeLoop;
+#[cfg(feature = "alloc")]
 use crate::simulator::module::Module;
-use crate::simulator::state::State;
-use alloc::boxed::Box;
-use alloc::vec;
-use alloc::vec::Vec;
+use crate::simulator::module::{DefaultModuleSet, ModuleSet};
+use crate::simulator::state::{State, StateUpdate};
+use crate::Float;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
 
 // TODO detect when we are dealing with _stiff equations_ as described
 // [here](https://en.wikipedia.org/wiki/Stiff_equation). This is eg when
@@ -11,124 +15,158 @@ use alloc::vec::Vec;
 // won't be able to give a good approximation.
 
 const DEFAULT_STACK_SIZE: usize = 256;
+const MAX_STAGES: usize = 4;
 
-pub struct RungeKutta {
+const RK4_A: &[&[Float]] = &[&[], &[0.5], &[0.0, 0.5], &[0.0, 0.0, 1.0]];
+const RK4_B: &[Float] = &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0];
+const RK4_C: &[Float] = &[0.0, 0.5, 0.5, 1.0];
+
+const RK38_A: &[&[Float]] = &[&[], &[1.0 / 3.0], &[-1.0 / 3.0, 1.0], &[1.0, -1.0, 1.0]];
+const RK38_B: &[Float] = &[1.0 / 8.0, 3.0 / 8.0, 3.0 / 8.0, 1.0 / 8.0];
+const RK38_C: &[Float] = &[0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0];
+
+const EULER_A: &[&[Float]] = &[&[]];
+const EULER_B: &[Float] = &[1.0];
+const EULER_C: &[Float] = &[0.0];
+
+pub struct RungeKutta<M = DefaultModuleSet> {
     state: State,
-    a: Vec<Vec<f32>>,
-    b: Vec<f32>,
-    c: Vec<f32>,
+    updates: [StateUpdate; MAX_STAGES],
+    a: &'static [&'static [Float]],
+    b: &'static [Float],
+    c: &'static [Float],
     stages: usize,
-    modules: Vec<Box<dyn Module>>,
-    stack: Vec<f32>,
+    modules: M,
+    #[cfg(feature = "alloc")]
+    loops: Vec<DelayFreeLoop>,
+    stack: [Float; DEFAULT_STACK_SIZE],
+    /// Whether the modules have set their initial states
+    initialized: bool,
 }
 
 impl RungeKutta {
     pub fn rk4(state_size: usize) -> Self {
-        let a = vec![vec![], vec![0.5], vec![0.0, 0.5], vec![0.0, 0.0, 1.0]];
-
-        let b = vec![1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0];
-
-        let c = vec![0.0, 0.5, 0.5, 1.0];
-
-        Self {
-            state: State::new(state_size),
-            a,
-            b,
-            c,
-            stages: 4,
-            modules: Vec::new(),
-            stack: vec![0.0_f32; DEFAULT_STACK_SIZE],
-        }
+        Self::with_tableau(state_size, RK4_A, RK4_B, RK4_C)
     }
 
     pub fn rk38(state_size: usize) -> Self {
-        let a = vec![
-            vec![],
-            vec![1.0 / 3.0],
-            vec![-1.0 / 3.0, 1.0],
-            vec![1.0, -1.0, 1.0],
-        ];
+        Self::with_tableau(state_size, RK38_A, RK38_B, RK38_C)
+    }
 
-        let b = vec![1.0 / 8.0, 3.0 / 8.0, 3.0 / 8.0, 1.0 / 8.0];
+    pub fn euler(state_size: usize) -> Self {
+        Self::with_tableau(state_size, EULER_A, EULER_B, EULER_C)
+    }
 
-        let c = vec![0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0];
+    pub fn second_order(_alpha: Float, _state_size: usize) -> Self {
+        todo!("Second order Runge Kutta method")
+    }
 
-        Self {
-            state: State::new(state_size),
-            a,
-            b,
-            c,
-            stages: 4,
-            modules: Vec::new(),
-            stack: vec![0.0_f32; DEFAULT_STACK_SIZE],
-        }
+    #[cfg(feature = "alloc")]
+    pub fn with_modules(&mut self, modules: Vec<Box<dyn Module>>) -> Self {
+        self.with_module_set(modules)
     }
 
-    pub fn euler(state_size: usize) -> Self {
-        let a = vec![vec![]];
-        let b = vec![1.0];
-        let c = vec![0.0];
+    fn with_tableau(
+        state_size: usize,
+        a: &'static [&'static [Float]],
+        b: &'static [Float],
+        c: &'static [Float],
+    ) -> Self {
+        debug_assert!(b.len() <= MAX_STAGES);
+
+        let state = State::new(state_size);
 
         Self {
-            state: State::new(state_size),
+            updates: core::array::from_fn(|_| state.update_data(0., 0.)),
+            state,
             a,
             b,
             c,
-            stages: 1,
-            modules: Vec::new(),
-            stack: vec![0.0_f32; DEFAULT_STACK_SIZE],
+            stages: b.len(),
+            modules: DefaultModuleSet::default(),
+            #[cfg(feature = "alloc")]
+            loops: Vec::new(),
+            stack: [0.; DEFAULT_STACK_SIZE],
+            initialized: false,
         }
     }
+}
 
-    pub fn second_order(_alpha: f32, _state_size: usize) -> Self {
-        todo!("Second order Runge Kutta method")
-    }
-
-    pub fn with_modules(&mut self, modules: Vec<Box<dyn Module>>) -> Self {
-        let state_size = self.state.len();
-
-        Self {
-            state: State::new(state_size),
-            a: self.a.clone(),
-            b: self.b.clone(),
-            c: self.c.clone(),
+impl<M: ModuleSet> RungeKutta<M> {
+    /// A simulator with the same method and state size, running the given
+    /// modules.
+    pub fn with_module_set<N: ModuleSet>(&self, modules: N) -> RungeKutta<N> {
+        let state = State::new(self.state.len());
+
+        RungeKutta {
+            updates: core::array::from_fn(|_| state.update_data(0., 0.)),
+            state,
+            a: self.a,
+            b: self.b,
+            c: self.c,
             stages: self.stages,
             modules,
-            stack: self.stack.clone(),
+            #[cfg(feature = "alloc")]
+            loops: Vec::new(),
+            stack: self.stack,
+            initialized: false,
         }
     }
 
-    pub fn step(&mut self, dt: f32) {
-        let mut updates = vec![];
+    /// Solve the given delay-free loops in every stage, after the modules have
+    /// been simulated. Module indices refer to the modules of the simulator.
+    #[cfg(feature = "alloc")]
+    pub fn with_loops(mut self, loops: Vec<DelayFreeLoop>) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    pub fn step(&mut self, dt: Float) {
+        if !self.initialized {
+            self.modules.initialize(&mut self.state, dt);
+            self.initialized = true;
+        }
 
         for stage in 0..self.stages {
-            let mut update = self.state.update_data(dt * self.c[stage], dt);
+            let (updates, current) = self.updates.split_at_mut(stage);
+            let update = &mut current[0];
             let mut temp_state = self.state.clone();
 
-            temp_state.apply_updates(&updates, &self.a[stage], &self.c, dt);
-
-            for module in &self.modules {
-                module.simulate(&temp_state, &mut update, &mut self.stack);
+            update.reset(dt * self.c[stage], dt);
+            temp_state.apply_updates(updates, self.a[stage], self.c, dt);
+
+            self.modules.simulate(&temp_state, update, &mut self.stack);
+
+            #[cfg(feature = "alloc")]
+            for delay_free_loop in &self.loops {
+                let modules = &self.modules;
+
+                // Use the last iterate when the loop doesn't converge
+                let _ = delay_free_loop.solve(
+                    &mut temp_state,
+                    update,
+                    &mut self.stack,
+                    |state, update, stack| {
+                        for m in &delay_free_loop.modules {
+                            modules.simulate_module(*m, state, update, stack);
+                        }
+                    },
+                );
             }
-
-            updates.push(update);
         }
 
-        self.state.apply_updates(&updates, &self.b, &self.c, dt);
+        self.state
+            .apply_updates(&self.updates[..self.stages], self.b, self.c, dt);
 
-        for module in &mut self.modules {
-            module.finalize(&mut self.state, dt, &mut self.stack);
-        }
+        self.modules.finalize(&mut self.state, dt, &mut self.stack);
     }
 
-    pub fn get_stereo_output(&self) -> (f32, f32) {
+    pub fn get_stereo_output(&self) -> (Float, Float) {
         (self.state.get_output(0), self.state.get_output(1))
     }
 
     pub fn process_event(&mut self, event: ControllerEvent) {
-        for module in &mut self.modules {
-            module.process_event(&event);
-        }
+        self.modules.process_event(&event);
     }
 
     pub fn get_state(&mut self) -> &mut State {
@@ -1,5 +1,13 @@
-use alloc::vec;
-use alloc::vec::Vec;
+use crate::Float;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(not(feature = "alloc"))]
+use core::ops::{Deref, DerefMut};
+
+/// The largest state a synth can have when the engine is built without
+/// `alloc`, as the state is then kept in arrays of this size.
+#[cfg(not(feature = "alloc"))]
+pub const MAX_STATE_SIZE: usize = 256;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UpdateType {
@@ -7,65 +15,116 @@ pub enum UpdateType {
     Absolute,
 }
 
+#[cfg(feature = "alloc")]
+pub(crate) type Buffer<T> = Vec<T>;
+
+#[cfg(feature = "alloc")]
+pub(crate) fn buffer<T: Copy>(value: T, len: usize) -> Buffer<T> {
+    vec![value; len]
+}
+
+#[cfg(not(feature = "alloc"))]
+pub(crate) type Buffer<T> = FixedBuffer<T>;
+
+#[cfg(not(feature = "alloc"))]
+pub(crate) fn buffer<T: Copy>(value: T, len: usize) -> Buffer<T> {
+    assert!(len <= MAX_STATE_SIZE, "State size {} is too large", len);
+
+    FixedBuffer {
+        values: [value; MAX_STATE_SIZE],
+        len,
+    }
+}
+
+/// The first `len` elements of an array, for storage without the heap.
+#[cfg(not(feature = "alloc"))]
+#[derive(Debug, Clone)]
+pub(crate) struct FixedBuffer<T> {
+    values: [T; MAX_STATE_SIZE],
+    len: usize,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<T> Deref for FixedBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.values[..self.len]
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl<T> DerefMut for FixedBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.values[..self.len]
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
-    values: Vec<f32>,
-    outputs: Vec<f32>,
+    values: Buffer<Float>,
+    outputs: [Float; 2],
 }
 
 pub struct StateUpdate {
-    updates: Vec<f32>,
-    update_types: Vec<UpdateType>,
-    delta_time: f32,
-    time_step: f32,
+    updates: Buffer<Float>,
+    update_types: Buffer<UpdateType>,
+    delta_time: Float,
+    time_step: Float,
 }
 
 impl State {
     pub fn new(size: usize) -> Self {
         Self {
-            values: vec![0.0_f32; size],
-            outputs: vec![0.0_f32; 2],
+            values: buffer(0., size),
+            outputs: [0.; 2],
         }
     }
 
-    pub fn new_with_values(values: &[f32]) -> Self {
-        Self {
-            values: values.to_vec(),
-            outputs: vec![0.; 2],
-        }
+    pub fn new_with_values(values: &[Float]) -> Self {
+        let mut state = Self::new(values.len());
+
+        state.values.copy_from_slice(values);
+        state
     }
 
     pub fn len(&self) -> usize {
         self.values.len()
     }
 
-    pub fn update_data(&self, delta_time: f32, time_step: f32) -> StateUpdate {
+    pub fn update_data(&self, delta_time: Float, time_step: Float) -> StateUpdate {
         StateUpdate {
-            updates: vec![0.0_f32; self.len()],
-            update_types: vec![UpdateType::Differentiable; self.len()],
+            updates: buffer(0., self.len()),
+            update_types: buffer(UpdateType::Differentiable, self.len()),
             delta_time,
             time_step,
         }
     }
 
-    pub fn get(&self, index: usize) -> f32 {
+    pub fn get(&self, index: usize) -> Float {
         debug_assert!(index < self.values.len());
 
         self.values[index]
     }
 
-    pub fn set(&mut self, index: usize, v: f32) {
+    pub fn set(&mut self, index: usize, v: Float) {
         debug_assert!(index < self.values.len());
 
         self.values[index] = v;
     }
 
-    pub fn apply_updates(&mut self, updates: &[StateUpdate], weights: &[f32], c: &[f32], dt: f32) {
+    pub fn apply_updates(
+        &mut self,
+        updates: &[StateUpdate],
+        weights: &[Float],
+        c: &[Float],
+        dt: Float,
+    ) {
         debug_assert!(updates.len() == weights.len());
         debug_assert!(updates.len() <= c.len());
 
         for i in 0..self.len() {
-            let mut update = 0.0_f32;
+            let mut update = 0.0 as Float;
             let mut previous_value = self.values[i];
 
             for j in 0..updates.len() {
@@ -87,28 +146,42 @@ impl State {
         }
     }
 
-    pub fn set_output(&mut self, index: usize, v: f32) {
+    pub fn set_output(&mut self, index: usize, v: Float) {
         self.outputs[index] = v;
     }
 
-    pub fn get_output(&self, index: usize) -> f32 {
+    pub fn get_output(&self, index: usize) -> Float {
         self.outputs[index]
     }
 }
 
 impl StateUpdate {
-    pub fn set(&mut self, index: usize, update: f32, update_type: UpdateType) {
+    /// Clear all updates, for reuse in another simulation stage.
+    pub fn reset(&mut self, delta_time: Float, time_step: Float) {
+        self.updates.fill(0.);
+        self.update_types.fill(UpdateType::Differentiable);
+        self.delta_time = delta_time;
+        self.time_step = time_step;
+    }
+
+    pub fn set(&mut self, index: usize, update: Float, update_type: UpdateType) {
         debug_assert!(index < self.updates.len());
 
         self.updates[index] = update;
         self.update_types[index] = update_type;
     }
 
-    pub fn get_delta_time(&self) -> f32 {
+    pub fn get(&self, index: usize) -> Float {
+        debug_assert!(index < self.updates.len());
+
+        self.updates[index]
+    }
+
+    pub fn get_delta_time(&self) -> Float {
         self.delta_time
     }
 
-    pub fn get_time_step(&self) -> f32 {
+    pub fn get_time_step(&self) -> Float {
         self.time_step
     }
 }
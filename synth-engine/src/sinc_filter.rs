@@ -1,37 +1,39 @@
+use crate::math;
+use crate::math::consts::PI;
+use crate::Float;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::f32::consts::PI;
 
-fn sinc(fc: f32, x: isize) -> f32 {
-    let x = x as f32;
+fn sinc(fc: Float, x: isize) -> Float {
+    let x = x as Float;
 
-    if x.abs() < f32::EPSILON {
+    if x.abs() < Float::EPSILON {
         1.
     } else {
-        (2. * PI * fc * x).sin() / x
+        math::sin(2. * PI * fc * x) / x
     }
 }
 
 #[allow(dead_code)]
-fn hamming_window(i: isize, m: usize) -> f32 {
-    let m = m as f32;
-    let i = i as f32;
+fn hamming_window(i: isize, m: usize) -> Float {
+    let m = m as Float;
+    let i = i as Float;
 
-    0.54 - 0.46 * (2. * PI * i / m).cos()
+    0.54 - 0.46 * math::cos(2. * PI * i / m)
 }
 
 #[allow(dead_code)]
-fn blackman_window(i: isize, m: usize) -> f32 {
-    let m = m as f32;
-    let i = i as f32;
+fn blackman_window(i: isize, m: usize) -> Float {
+    let m = m as Float;
+    let i = i as Float;
 
-    0.42 - 0.5 * (2. * PI * i / m).cos() + 0.08 * (4. * PI * i / m).cos()
+    0.42 - 0.5 * math::cos(2. * PI * i / m) + 0.08 * math::cos(4. * PI * i / m)
 }
 
-pub fn sinc_kernel(fc: f32, m: usize) -> Vec<f32> {
+pub fn sinc_kernel(fc: Float, m: usize) -> Vec<Float> {
     let m2 = (m as isize) / 2;
     let mut result = Vec::with_capacity((m2 * 2 + 1) as usize);
-    let mut k: f32 = 0.;
+    let mut k: Float = 0.;
 
     for i in -m2..=m2 {
         let window = blackman_window(i + m2, m);
@@ -51,8 +53,8 @@ pub fn sinc_kernel(fc: f32, m: usize) -> Vec<f32> {
     result
 }
 
-pub fn convolve(kernel: &[f32], samples: &[f32], index: usize) -> f32 {
-    let mut acc = 0.0_f32;
+pub fn convolve(kernel: &[Float], samples: &[Float], index: usize) -> Float {
+    let mut acc = 0.0 as Float;
 
     for kernel_index in 0..kernel.len() {
         let samples_index = (index + kernel_index) % samples.len();
@@ -62,7 +64,7 @@ pub fn convolve(kernel: &[f32], samples: &[f32], index: usize) -> f32 {
     acc
 }
 
-pub fn downsample_half(m: usize, samples: &[f32]) -> Vec<f32> {
+pub fn downsample_half(m: usize, samples: &[Float]) -> Vec<Float> {
     let kernel = sinc_kernel(0.25, m);
 
     let mut result = vec![0.; samples.len() / 2];
@@ -1,6 +1,9 @@
+#[cfg(feature = "alloc")]
+use crate::math;
 use crate::simulator::state::State;
-use alloc::vec;
-use alloc::vec::Vec;
+use crate::Float;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 use thiserror::Error;
 
 #[derive(PartialEq, Debug)]
@@ -12,11 +15,11 @@ pub enum Instr {
     Negate,
 
     Call(Function),
-    Const(f32),
+    Const(Float),
     State(usize),
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Function {
     Sin,
     Cos,
@@ -43,12 +46,14 @@ pub enum ExecError {
     StateOutOfBounds(usize),
 }
 
+#[cfg(feature = "alloc")]
 #[derive(PartialEq, Debug)]
 pub struct StackProgram {
     pub code: Vec<Instr>,
     pub stack_size: usize,
 }
 
+#[cfg(feature = "alloc")]
 pub fn compute_stack_size(code: &Vec<Instr>) -> usize {
     let mut stack_size: usize = 0;
     let mut stack_max_size: usize = 0;
@@ -76,6 +81,35 @@ pub fn compute_stack_size(code: &Vec<Instr>) -> usize {
     stack_max_size
 }
 
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn pop_stack<T: Copy>(stack: &[T], stack_ptr: &mut usize) -> Result<T, ExecError> {
+    if stack_ptr < &mut 1 {
+        Err(ExecError::StackUnderflow)
+    } else {
+        let v = stack[*stack_ptr - 1];
+        *stack_ptr -= 1;
+        Ok(v)
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn push_stack<T>(
+    stack: &mut [T],
+    stack_ptr: &mut usize,
+    value: T,
+) -> Result<(), ExecError> {
+    if stack_ptr >= &mut stack.len() {
+        Err(ExecError::StackOverflow)
+    } else {
+        stack[*stack_ptr] = value;
+        *stack_ptr += 1;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl StackProgram {
     pub fn new(code: Vec<Instr>, stack_size: usize) -> Self {
         Self { code, stack_size }
@@ -88,7 +122,7 @@ impl StackProgram {
         }
     }
 
-    pub fn constant(v: f32) -> Self {
+    pub fn constant(v: Float) -> Self {
         Self {
             code: vec![Instr::Const(v)],
             stack_size: 1,
@@ -102,33 +136,7 @@ impl StackProgram {
         }
     }
 
-    pub fn run(&self, state: &State, stack: &mut [f32]) -> Result<f32, ExecError> {
-        #[inline]
-        fn pop_stack(stack: &[f32], stack_ptr: &mut usize) -> Result<f32, ExecError> {
-            if stack_ptr < &mut 1 {
-                Err(ExecError::StackUnderflow)
-            } else {
-                let v = stack[*stack_ptr - 1];
-                *stack_ptr -= 1;
-                Ok(v)
-            }
-        }
-
-        #[inline]
-        fn push_stack(
-            stack: &mut [f32],
-            stack_ptr: &mut usize,
-            value: f32,
-        ) -> Result<(), ExecError> {
-            if stack_ptr >= &mut stack.len() {
-                Err(ExecError::StackOverflow)
-            } else {
-                stack[*stack_ptr] = value;
-                *stack_ptr += 1;
-                Ok(())
-            }
-        }
-
+    pub fn run(&self, state: &State, stack: &mut [Float]) -> Result<Float, ExecError> {
         let mut stack_ptr: usize = 0;
 
         for instr in &self.code {
@@ -166,19 +174,19 @@ impl StackProgram {
                     match f {
                         Sin => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.sin())?;
+                            push_stack(stack, &mut stack_ptr, math::sin(a))?;
                         }
                         Cos => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.cos())?;
+                            push_stack(stack, &mut stack_ptr, math::cos(a))?;
                         }
                         Tan => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.tan())?;
+                            push_stack(stack, &mut stack_ptr, math::tan(a))?;
                         }
                         Tanh => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.tanh())?;
+                            push_stack(stack, &mut stack_ptr, math::tanh(a))?;
                         }
                         Abs => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
@@ -196,18 +204,18 @@ impl StackProgram {
                         }
                         Ln => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.ln())?;
+                            push_stack(stack, &mut stack_ptr, math::ln(a))?;
                         }
                         Exp => {
                             let a = pop_stack(stack, &mut stack_ptr)?;
-                            push_stack(stack, &mut stack_ptr, a.exp())?;
+                            push_stack(stack, &mut stack_ptr, math::exp(a))?;
                         }
                         Logistic => {
                             let x0 = pop_stack(stack, &mut stack_ptr)?;
                             let k = pop_stack(stack, &mut stack_ptr)?;
                             let l = pop_stack(stack, &mut stack_ptr)?;
                             let x = pop_stack(stack, &mut stack_ptr)?;
-                            let v = l / (1. + (-k * (x - x0)).exp());
+                            let v = l / (1. + math::exp(-k * (x - x0)));
                             push_stack(stack, &mut stack_ptr, v)?;
                         }
                         Lerp => {
@@ -237,7 +245,40 @@ impl StackProgram {
     }
 }
 
-#[cfg(test)]
+/// An input expression of a module, evaluated on the current state.
+pub trait Program: Send {
+    fn run(&self, state: &State, stack: &mut [Float]) -> Result<Float, ExecError>;
+}
+
+#[cfg(feature = "alloc")]
+impl Program for StackProgram {
+    #[inline]
+    fn run(&self, state: &State, stack: &mut [Float]) -> Result<Float, ExecError> {
+        StackProgram::run(self, state, stack)
+    }
+}
+
+/// An input expression compiled to Rust code, as emitted by the code
+/// generation in `synth-designer`. It needs no stack and can't fail.
+#[derive(Clone, Copy)]
+pub struct NativeProgram(pub fn(&State) -> Float);
+
+impl Program for NativeProgram {
+    #[inline]
+    fn run(&self, state: &State, _stack: &mut [Float]) -> Result<Float, ExecError> {
+        Ok((self.0)(state))
+    }
+}
+
+/// The program type of modules built at runtime, from expressions in a patch.
+#[cfg(feature = "alloc")]
+pub type DefaultProgram = StackProgram;
+
+/// Without `alloc` input expressions can't be interpreted, only generated.
+#[cfg(not(feature = "alloc"))]
+pub type DefaultProgram = NativeProgram;
+
+#[cfg(all(test, feature = "alloc"))]
 mod test {
     use super::*;
     use crate::simulator::state::State as SimulatorState;